@@ -0,0 +1,10 @@
+echo "loop:"
+cargo run -- build -f ./benchmark/loop_pipeline.fix && time ./a.out
+
+echo ""
+echo "Stream:"
+cargo run -- build -f ./benchmark/stream_pipeline.fix && time ./a.out
+
+echo ""
+echo "Iterator:"
+cargo run -- build -f ./benchmark/iterator_pipeline.fix && time ./a.out
@@ -209,9 +209,16 @@ def write_source_file(passes):
         f.write('\n')
 
 
-def run_benchmark(run_bench_iteration=RUN_BENCH_ITERATION, timeout=60):
+# Convert the name of a method of PassManager to the pass name accepted by `--llvm-passes` option.
+def to_pass_name(p):
+    return p.removeprefix('add_').replace('_pass', '')
+
+
+def run_benchmark(passes, run_bench_iteration=RUN_BENCH_ITERATION, timeout=60):
+    # Passes are given by `--llvm-passes` option, so that we do not need to rebuild the compiler for each trial.
     cp = subprocess.run(['cargo', 'run', '--', 'build', '-f',
-                        FIX_SOURCE_FILE + '.fix'], capture_output=True, text=True)
+                        FIX_SOURCE_FILE + '.fix', '--llvm-passes',
+                        ','.join(to_pass_name(p) for p in passes)], capture_output=True, text=True)
     if cp.returncode != 0:
         print('build failed.')
         print('stdout:')
@@ -254,8 +261,7 @@ def optimize():
     print('Initial passes:')
     print_passes(optimum_passes)
 
-    write_source_file(optimum_passes)
-    optimum_time = run_benchmark()
+    optimum_time = run_benchmark(optimum_passes)
     print('Time with initial passes: {}'.format(
         sum(optimum_time) / len(optimum_time)))
 
@@ -270,9 +276,8 @@ def optimize():
         passes = optimum_passes.copy()
         for p in added_passes:
             passes.append(p)
-        write_source_file(passes)
         timeout = int(ceil(np.average(optimum_time) * 2.0))
-        time = run_benchmark(timeout=timeout)
+        time = run_benchmark(passes, timeout=timeout)
         (t_val, p_val, free) = ttest_ind(
             time, optimum_time, alternative="smaller", usevar="unequal")
         if p_val <= SIGNIFICANCE_LEVEL:
@@ -294,9 +299,8 @@ def optimize():
                 removed_passes.append(p)
         print('Try removing passes:')
         print_passes(removed_passes)
-        write_source_file(passes)
         timeout = int(ceil(np.average(optimum_time) * 2.0))
-        time = run_benchmark(timeout=timeout)
+        time = run_benchmark(passes, timeout=timeout)
         (t_val, p_val, free) = ttest_ind(time * ALLOWED_DEGRATION_ON_MINIMIZE,
                                          optimum_time, alternative="smaller", usevar="unequal")
         if p_val <= SIGNIFICANCE_LEVEL:
@@ -312,7 +316,7 @@ def optimize():
         print_passes(optimum_passes)
         write_source_file(optimum_passes)
         timeout = int(ceil(np.average(optimum_time) * 2.0))
-        optimum_time = run_benchmark(timeout=timeout)
+        optimum_time = run_benchmark(optimum_passes, timeout=timeout)
         print('Current optimum time: {} ({} samples)'.format(
             np.average(optimum_time), optimum_time.size))
 
@@ -183,6 +183,13 @@ pub extern "C" fn report_release(address: *const i8, obj_id: i64) -> () {
     }
 }
 
+// Get the number of objects allocated so far.
+// Tests use this to check that a code does not allocate objects for each iteration.
+#[no_mangle]
+pub extern "C" fn get_allocation_count() -> i64 {
+    *(*OBJECT_ID).lock().unwrap()
+}
+
 // Check if all non-global objects had been released.
 #[no_mangle]
 pub extern "C" fn check_leak() -> () {
@@ -21,6 +21,9 @@ pub struct ExprNode {
     pub ty: Option<Arc<TypeNode>>,
     // When this expression is a function, this field contains indices of parameters which are released exactly once by calling this function (if known).
     pub released_params_indices: Option<Vec<usize>>,
+    // When this expression is a lambda or a boxed struct literal, this field indicates that the object does not escape from the current function and can be allocated on the stack.
+    #[serde(default)]
+    pub allocate_on_stack: bool,
 }
 
 impl ExprNode {
@@ -38,6 +41,21 @@ impl ExprNode {
         Arc::new(ret)
     }
 
+    // Set `allocate_on_stack`.
+    pub fn set_allocate_on_stack(&self, allocate_on_stack: bool) -> Arc<Self> {
+        let mut ret = self.clone();
+        ret.allocate_on_stack = allocate_on_stack;
+        Arc::new(ret)
+    }
+
+    // Is this expression a lambda or a struct literal which should be allocated on the stack?
+    pub fn is_allocate_on_stack(&self) -> bool {
+        match &*self.expr {
+            Expr::TyAnno(e, _) => e.is_allocate_on_stack(),
+            _ => self.allocate_on_stack,
+        }
+    }
+
     // Get free vars
     pub fn free_vars(self: &Self) -> &HashSet<FullName> {
         self.free_vars.as_ref().unwrap()
@@ -442,6 +460,28 @@ impl ExprNode {
         Arc::new(ret)
     }
 
+    pub fn set_ffi_call_tys(
+        &self,
+        ret_ty: Arc<TyCon>,
+        param_tys: Vec<Arc<TyCon>>,
+    ) -> Arc<ExprNode> {
+        let mut ret = self.clone();
+        match &*self.expr {
+            Expr::FFICall(fun_name, _, _, args) => {
+                ret.expr = Arc::new(Expr::FFICall(
+                    fun_name.clone(),
+                    ret_ty,
+                    param_tys,
+                    args.clone(),
+                ));
+            }
+            _ => {
+                panic!()
+            }
+        }
+        Arc::new(ret)
+    }
+
     pub fn set_ffi_call_args(&self, args: Vec<Arc<ExprNode>>) -> Arc<ExprNode> {
         let mut ret = self.clone();
         match &*self.expr {
@@ -562,8 +602,22 @@ impl ExprNode {
                 }
                 Ok(expr)
             }
-            Expr::FFICall(_, _, _, args) => {
-                let mut expr = self.clone();
+            Expr::FFICall(_, ret_ty, param_tys, args) => {
+                // Resolve names of struct types in the signature.
+                let resolve_tycon = |tc: &Arc<TyCon>| -> Result<Arc<TyCon>, Errors> {
+                    if is_ffi_builtin_tycon(tc) {
+                        return Ok(tc.clone());
+                    }
+                    let mut tc = tc.as_ref().clone();
+                    tc.resolve_namespace(ctx, &self.source)?;
+                    Ok(Arc::new(tc))
+                };
+                let ret_ty = resolve_tycon(ret_ty)?;
+                let param_tys = param_tys
+                    .iter()
+                    .map(|tc| resolve_tycon(tc))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let mut expr = self.set_ffi_call_tys(ret_ty, param_tys);
                 for (i, arg) in args.iter().enumerate() {
                     expr = expr.set_ffi_call_arg(arg.resolve_namespace(ctx)?, i);
                 }
@@ -784,6 +838,84 @@ impl ExprNode {
             }
         }
     }
+
+    // Find the type of the value bound to the local variable `name` in the chain of let-expressions.
+    pub fn find_let_bound_type(self: &Arc<ExprNode>, name: &str) -> Option<Arc<TypeNode>> {
+        match &*self.expr {
+            Expr::Let(pat, bound, val) => match &pat.pattern {
+                Pattern::Var(v, _) if v.name.is_local() && v.name.name == name => bound.ty.clone(),
+                _ => val.find_let_bound_type(name),
+            },
+            _ => None,
+        }
+    }
+
+    // Collect inferred types of variables introduced in this expression, i.e.,
+    // patterns of `let` (parameters of lambdas are also desugared to `let`) and results of monadic binds `*x`.
+    // Pushes pairs of the source code of the binder and its type, where the substitution `s` is applied.
+    // Patterns whose types are explicitly annotated are skipped.
+    pub fn collect_binder_types(
+        self: &Arc<ExprNode>,
+        s: &Substitution,
+        out: &mut Vec<(Span, Arc<TypeNode>)>,
+    ) {
+        match &*self.expr {
+            Expr::Var(_) => {}
+            Expr::LLVM(_) => {}
+            Expr::App(func, args) => {
+                // Monadic bind `*x` is desugared to `bind(|#monadic_value| ...)(x)`.
+                if let (Expr::App(_, lams), Some(monad_src)) = (&*func.expr, &args[0].source) {
+                    if let Expr::Lam(vars, _) = &*lams[0].expr {
+                        if vars.len() == 1 && vars[0].name.name.starts_with(MONADIC_VALUE_NAME) {
+                            if let Some(ty) = &lams[0].ty {
+                                let ty = s.substitute_type(ty);
+                                if ty.is_closure() {
+                                    out.push((monad_src.clone(), ty.get_lambda_srcs()[0].clone()));
+                                }
+                            }
+                        }
+                    }
+                }
+                func.collect_binder_types(s, out);
+                for arg in args {
+                    arg.collect_binder_types(s, out);
+                }
+            }
+            Expr::Lam(_, body) => body.collect_binder_types(s, out),
+            Expr::Let(pat, bound, val) => {
+                let annotated = match &pat.pattern {
+                    Pattern::Var(_, ty) => ty.is_some(),
+                    _ => false,
+                };
+                if let (false, Some(src), Some(ty)) = (annotated, &pat.info.source, &bound.ty) {
+                    out.push((src.clone(), s.substitute_type(ty)));
+                }
+                bound.collect_binder_types(s, out);
+                val.collect_binder_types(s, out);
+            }
+            Expr::If(cond, then_expr, else_expr) => {
+                cond.collect_binder_types(s, out);
+                then_expr.collect_binder_types(s, out);
+                else_expr.collect_binder_types(s, out);
+            }
+            Expr::TyAnno(e, _) => e.collect_binder_types(s, out),
+            Expr::MakeStruct(_, fields) => {
+                for (_, field_expr) in fields {
+                    field_expr.collect_binder_types(s, out);
+                }
+            }
+            Expr::ArrayLit(elems) => {
+                for elem in elems {
+                    elem.collect_binder_types(s, out);
+                }
+            }
+            Expr::FFICall(_, _, _, args) => {
+                for arg in args {
+                    arg.collect_binder_types(s, out);
+                }
+            }
+        }
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -802,7 +934,7 @@ pub enum Expr {
     // `MakeStruct x y` is compiled to a more performant code than function call (currently).
     MakeStruct(Arc<TyCon>, Vec<(Name, Arc<ExprNode>)>),
     FFICall(
-        Name,               /* function name */
+        Option<Name>,       /* function name (`None` for FFI_CALL_PTR) */
         Arc<TyCon>,         /* Return type */
         Vec<Arc<TyCon>>,    /* Parameter types */
         Vec<Arc<ExprNode>>, /* Arguments */
@@ -818,6 +950,7 @@ impl Expr {
             app_order: AppSourceCodeOrderType::FX,
             ty: None,
             released_params_indices: None,
+            allocate_on_stack: false,
         })
     }
     pub fn to_string(&self) -> String {
@@ -892,8 +1025,13 @@ impl Expr {
             }
             Expr::FFICall(fun_name, _, _, args) => {
                 format!(
-                    "FFI_CALL[{}{}]",
-                    fun_name,
+                    "{}[{}{}]",
+                    if fun_name.is_some() {
+                        "FFI_CALL"
+                    } else {
+                        "FFI_CALL_PTR"
+                    },
+                    fun_name.clone().unwrap_or_default(),
                     args.iter()
                         .map(|e| ", ".to_string() + &e.expr.to_string())
                         .collect::<Vec<_>>()
@@ -992,7 +1130,7 @@ pub fn expr_array_lit(elems: Vec<Arc<ExprNode>>, src: Option<Span>) -> Arc<ExprN
 }
 
 pub fn expr_ffi_call(
-    fun_name: Name,
+    fun_name: Option<Name>,
     ret_ty: Arc<TyCon>,
     param_tys: Vec<Arc<TyCon>>,
     args: Vec<Arc<ExprNode>>,
@@ -4,6 +4,32 @@ pub fn is_accessible(stmts: &[ImportStatement], name: &FullName) -> bool {
     stmts.iter().any(|stmt| stmt.is_accessible(name))
 }
 
+// Get the shortest name by which `name` is referred to unambiguously among `names` accessible by the import statements.
+// If `name` itself is not accessible, it is returned as it is.
+pub fn shortest_accessible_name(
+    stmts: &[ImportStatement],
+    name: &FullName,
+    names: &HashSet<FullName>,
+) -> FullName {
+    if !is_accessible(stmts, name) {
+        return name.clone();
+    }
+    let ns_len = name.namespace.names.len();
+    for i in (0..=ns_len).rev() {
+        let short = FullName::new(
+            &NameSpace::new(name.namespace.names[i..].to_vec()),
+            &name.name,
+        );
+        let ambiguous = names.iter().any(|other| {
+            other != name && short.is_suffix(other) && is_accessible(stmts, other)
+        });
+        if !ambiguous {
+            return short;
+        }
+    }
+    name.clone()
+}
+
 #[derive(Clone)]
 pub struct ImportStatement {
     pub importer: Name,
@@ -15,6 +41,28 @@ pub struct ImportStatement {
 }
 
 impl ImportStatement {
+    // Create an import statement which imports only the specified entity, e.g., `import Mod::{A::b};` for `Mod::A::b`.
+    pub fn single_entity(importer: &Name, name: &FullName) -> ImportStatement {
+        let mut names = name.namespace.names.clone();
+        let module = names.remove(0);
+        let mut item = if name.name.starts_with(|c: char| c.is_uppercase()) {
+            ImportTreeNode::TypeOrTrait(name.name.clone(), None)
+        } else {
+            ImportTreeNode::Symbol(name.name.clone(), None)
+        };
+        for ns in names.into_iter().rev() {
+            item = ImportTreeNode::NameSpace(ns, vec![item], None);
+        }
+        ImportStatement {
+            importer: importer.clone(),
+            module,
+            items: vec![item],
+            hiding: vec![],
+            source: None,
+            implicit: false,
+        }
+    }
+
     pub fn is_accessible(&self, name: &FullName) -> bool {
         if name.module() != self.module {
             return false;
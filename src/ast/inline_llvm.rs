@@ -69,6 +69,7 @@ pub enum LLVMGenerator {
     ),
     GetRetainFunctionOfBoxedValueFunctionBody(InlineLLVMGetRetainFunctionOfBoxedValueFunctionBody),
     GetBoxedDataPtrFunctionBody(InlineLLVMGetBoxedDataPtrFunctionBody),
+    MakeCCallbackFunctionBody(InlineLLVMMakeCCallbackFunctionBody),
 }
 
 impl LLVMGenerator {
@@ -147,6 +148,7 @@ impl LLVMGenerator {
                 x.generate(gc, ty, rvo, bvs)
             }
             LLVMGenerator::GetBoxedDataPtrFunctionBody(x) => x.generate(gc, ty, rvo, bvs),
+            LLVMGenerator::MakeCCallbackFunctionBody(x) => x.generate(gc, ty, rvo, bvs),
             LLVMGenerator::StructPunchBody(x) => x.generate(gc, ty, rvo, bvs),
             LLVMGenerator::StructPlugInBody(x) => x.generate(gc, ty, rvo, bvs),
         }
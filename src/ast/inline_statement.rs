@@ -0,0 +1,42 @@
+// Inline syntax: `INLINE[fix_value_name];` or `NOINLINE[fix_value_name];`
+
+use crate::ast::name::FullName;
+use crate::sourcefile::Span;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum InlineAttribute {
+    // Always inline the value if possible, regardless of its size.
+    Inline,
+    // Never inline the value.
+    NoInline,
+}
+
+impl InlineAttribute {
+    pub fn to_string(&self) -> &'static str {
+        match self {
+            InlineAttribute::Inline => "INLINE",
+            InlineAttribute::NoInline => "NOINLINE",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct InlineStatement {
+    pub fix_value_name: FullName,
+    pub attribute: InlineAttribute,
+    pub src: Option<Span>,
+}
+
+impl InlineStatement {
+    pub fn new(
+        fix_value_name: FullName,
+        attribute: InlineAttribute,
+        src: Option<Span>,
+    ) -> InlineStatement {
+        InlineStatement {
+            fix_value_name,
+            attribute,
+            src,
+        }
+    }
+}
@@ -1,6 +1,7 @@
 pub mod export_statement;
 pub mod expr;
 pub mod import;
+pub mod inline_statement;
 pub mod inline_llvm;
 pub mod name;
 pub mod pattern;
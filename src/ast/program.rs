@@ -1,5 +1,5 @@
 use crate::ast::export_statement::{ExportStatement, ExportedFunctionType};
-use crate::error::Errors;
+use crate::error::{ErrorCode, Errors};
 use build_time::build_time_utc;
 use serde::{Deserialize, Serialize};
 use std::{io::Write, sync::Arc, vec};
@@ -207,6 +207,18 @@ impl SymbolExpr {
             SymbolExpr::Method(ms) => ms.iter().filter_map(|m| m.find_node_at(file, pos)).next(),
         }
     }
+
+    // Collect inferred types of variables introduced in this expression.
+    pub fn collect_binder_types(&self, out: &mut Vec<(Span, Arc<TypeNode>)>) {
+        match self {
+            SymbolExpr::Simple(e) => e.collect_binder_types(out),
+            SymbolExpr::Method(ms) => {
+                for m in ms {
+                    m.expr.collect_binder_types(out);
+                }
+            }
+        }
+    }
 }
 
 // Pair of expression and type resolver for it.
@@ -238,6 +250,11 @@ impl TypedExpr {
             }
         })
     }
+
+    // Collect inferred types of variables introduced in this expression.
+    pub fn collect_binder_types(&self, out: &mut Vec<(Span, Arc<TypeNode>)>) {
+        self.expr.collect_binder_types(&self.substitution, out);
+    }
 }
 
 // Trait method implementation
@@ -341,7 +358,10 @@ impl<'a> NameResolutionContext {
                     short_name.to_string()
                 ),
                 &[span],
-            ))
+            )
+            .set_code(ErrorCode::UnknownTypeOrTrait {
+                name: short_name.to_string(),
+            }))
         } else if candidates.len() == 1 {
             Ok(candidates[0].clone())
         } else {
@@ -402,6 +422,8 @@ pub struct Program {
     pub trait_env: TraitEnv,
     // Export statements.
     pub export_statements: Vec<ExportStatement>,
+    // Inline statements.
+    pub inline_statements: Vec<InlineStatement>,
     // List of tuple sizes used in this program.
     pub used_tuple_sizes: Vec<u32>,
     // Import statements.
@@ -415,6 +437,8 @@ pub struct Program {
     pub instantiated_symbols: HashMap<FullName, InstantiatedSymbol>,
     // Deferred instantiation, which is a state variable for the instantiation process.
     pub deferred_instantiation: Vec<InstantiatedSymbol>,
+    // Names of foreign functions called by `FFI_CALL` in instantiated symbols.
+    pub called_ffi_functions: HashSet<Name>,
 
     /* Dependency information */
     pub modules: Vec<ModuleInfo>,
@@ -429,11 +453,13 @@ impl Program {
             global_values: Default::default(),
             instantiated_symbols: Default::default(),
             deferred_instantiation: Default::default(),
+            called_ffi_functions: Default::default(),
             trait_env: Default::default(),
             type_env: Default::default(),
             used_tuple_sizes: (0..=TUPLE_SIZE_BASE).filter(|i| *i != 1).collect(),
             modules: Default::default(),
             export_statements: vec![],
+            inline_statements: vec![],
         };
         fix_mod.add_import_statement_no_verify(ImportStatement::implicit_self_import(
             mod_info.name.clone(),
@@ -758,7 +784,10 @@ impl Program {
                         name.to_string()
                     ),
                     &[&gv.defn.unwrap().src.as_ref().map(|s| s.to_head_character())],
-                ));
+                )
+                .set_code(ErrorCode::MissingTypeSignature {
+                    name: name.to_string(),
+                }));
             } else {
                 let decl_src = gv.decl.as_ref().unwrap().src.clone();
                 errors.eat_err(self.add_global_value(
@@ -992,6 +1021,41 @@ impl Program {
         Ok(())
     }
 
+    // Infer the most general scheme of a global value from its expression, ignoring its type signature.
+    // This is used to suggest the type signature of a global value which lacks it.
+    pub fn infer_scheme_of_global_value(
+        &self,
+        tc: &TypeCheckContext,
+        name: &FullName,
+    ) -> Result<Arc<Scheme>, Errors> {
+        let gv = self.global_values.get(name).unwrap();
+        let expr = match &gv.expr {
+            SymbolExpr::Simple(te) => te.expr.clone(),
+            SymbolExpr::Method(_) => {
+                return Err(Errors::from_msg(format!(
+                    "`{}` is a trait method.",
+                    name.to_string()
+                )))
+            }
+        };
+
+        // Perform namespace inference and resolve type aliases.
+        let define_module = name.module();
+        let nrctx = NameResolutionContext::new(
+            &self.tycon_names_with_aliases(),
+            &self.trait_names_with_aliases(),
+            self.assoc_ty_to_arity(),
+            self.mod_to_import_stmts[&define_module].clone(),
+        );
+        let expr = expr.resolve_namespace(&nrctx)?;
+        let expr = expr.resolve_type_aliases(&tc.type_env)?;
+
+        // Perform type inference.
+        let mut tc = tc.clone();
+        tc.current_module = Some(define_module);
+        tc.infer_scheme(expr)
+    }
+
     // Instantiate symbol.
     fn instantiate_symbol(
         &mut self,
@@ -1218,7 +1282,10 @@ impl Program {
                 }
                 expr
             }
-            Expr::FFICall(_, _, _, args) => {
+            Expr::FFICall(fun_name, _, _, args) => {
+                if let Some(fun_name) = fun_name {
+                    self.called_ffi_functions.insert(fun_name.clone());
+                }
                 let mut expr = expr.clone();
                 for (i, e) in args.iter().enumerate() {
                     let e = self.instantiate_expr(e)?;
@@ -1326,6 +1393,52 @@ impl Program {
         errors.to_result()
     }
 
+    // Validate inline statements.
+    pub fn validate_inline_statements(&self) -> Result<(), Errors> {
+        let mut errors = Errors::empty();
+        let mut attributes: HashMap<FullName, (InlineAttribute, Option<Span>)> = HashMap::new();
+        for stmt in &self.inline_statements {
+            // Check that the value is defined.
+            if !self.global_values.contains_key(&stmt.fix_value_name) {
+                errors.append(Errors::from_msg_srcs(
+                    format!(
+                        "Unknown value `{}` in `{}` statement.",
+                        stmt.fix_value_name.to_string(),
+                        stmt.attribute.to_string()
+                    ),
+                    &[&stmt.src],
+                ));
+                continue;
+            }
+            // Check that `INLINE` and `NOINLINE` are not specified for the same value.
+            if let Some((attribute, span)) = attributes.get(&stmt.fix_value_name) {
+                if *attribute != stmt.attribute {
+                    errors.append(Errors::from_msg_srcs(
+                        format!(
+                            "Both `INLINE` and `NOINLINE` are specified for `{}`.",
+                            stmt.fix_value_name.to_string()
+                        ),
+                        &[&stmt.src, span],
+                    ));
+                }
+                continue;
+            }
+            attributes.insert(
+                stmt.fix_value_name.clone(),
+                (stmt.attribute, stmt.src.clone()),
+            );
+        }
+        errors.to_result()
+    }
+
+    // Get the inline attribute of each global value specified by inline statements.
+    pub fn inline_attributes(&self) -> HashMap<FullName, InlineAttribute> {
+        self.inline_statements
+            .iter()
+            .map(|stmt| (stmt.fix_value_name.clone(), stmt.attribute))
+            .collect()
+    }
+
     // Validate and update export statements.
     pub fn validate_export_statements(&self) -> Result<(), Errors> {
         let mut errors = Errors::empty();
@@ -1773,6 +1886,9 @@ impl Program {
         // Merge export statements.
         self.export_statements.append(&mut other.export_statements);
 
+        // Merge inline statements.
+        self.inline_statements.append(&mut other.inline_statements);
+
         // Merge used_tuple_sizes.
         self.used_tuple_sizes.append(&mut other.used_tuple_sizes);
 
@@ -1938,4 +2054,18 @@ impl Program {
         }
         None
     }
+
+    // Collect inferred types of variables introduced in the specified file, i.e., `let` patterns, lambda parameters and results of monadic binds.
+    // The result is sorted by source code positions.
+    pub fn collect_binder_types(&self, file: &Path) -> Vec<(Span, Arc<TypeNode>)> {
+        let mut binders = vec![];
+        for (_, gv) in &self.global_values {
+            gv.expr.collect_binder_types(&mut binders);
+        }
+        let file = to_absolute_path(file);
+        binders.retain(|(span, _)| to_absolute_path(&span.input.file_path) == file);
+        binders.sort_by_key(|(span, _)| (span.start, span.end));
+        binders.dedup_by_key(|(span, _)| (span.start, span.end));
+        binders
+    }
 }
@@ -3,7 +3,6 @@ use std::sync::Arc;
 
 use crate::error::error_exit_with_src;
 use crate::error::Errors;
-use inkwell::types::BasicType;
 use serde::{Deserialize, Serialize};
 
 use super::*;
@@ -151,55 +150,6 @@ impl TyCon {
         ty
     }
 
-    // Convert "()", "I8", "Ptr", etc to corresponding c_type.
-    // Returns none if it is VoidType.
-    pub fn get_c_type<'c>(self: &TyCon, ctx: &'c Context) -> Option<BasicTypeEnum<'c>> {
-        if self.name.namespace != NameSpace::new_str(&[STD_NAME]) {
-            panic!("call get_c_type for {}", self.to_string())
-        }
-        if self.name == make_tuple_name(0) {
-            return None;
-        }
-        if self.name.name == I8_NAME {
-            return Some(ctx.i8_type().as_basic_type_enum());
-        }
-        if self.name.name == U8_NAME {
-            return Some(ctx.i8_type().as_basic_type_enum());
-        }
-        if self.name.name == I16_NAME {
-            return Some(ctx.i16_type().as_basic_type_enum());
-        }
-        if self.name.name == U16_NAME {
-            return Some(ctx.i16_type().as_basic_type_enum());
-        }
-        if self.name.name == I32_NAME {
-            return Some(ctx.i32_type().as_basic_type_enum());
-        }
-        if self.name.name == U32_NAME {
-            return Some(ctx.i32_type().as_basic_type_enum());
-        }
-        if self.name.name == I64_NAME {
-            return Some(ctx.i64_type().as_basic_type_enum());
-        }
-        if self.name.name == U64_NAME {
-            return Some(ctx.i64_type().as_basic_type_enum());
-        }
-        if self.name.name == F32_NAME {
-            return Some(ctx.f32_type().as_basic_type_enum());
-        }
-        if self.name.name == F64_NAME {
-            return Some(ctx.f64_type().as_basic_type_enum());
-        }
-        if self.name.name == PTR_NAME {
-            return Some(
-                ctx.i8_type()
-                    .ptr_type(AddressSpace::from(0))
-                    .as_basic_type_enum(),
-            );
-        }
-        panic!("call get_c_type for {}", self.to_string())
-    }
-
     pub fn is_singned_intger(self: &TyCon) -> bool {
         if self.name.namespace != NameSpace::new_str(&[STD_NAME]) {
             panic!("call is_singned_intger for {}", self.to_string())
@@ -381,6 +331,30 @@ impl TypeNode {
         }
     }
 
+    // Rename type constructors in this type by the given function.
+    pub fn map_tycon_names(
+        self: &Arc<TypeNode>,
+        f: &impl Fn(&FullName) -> FullName,
+    ) -> Arc<TypeNode> {
+        match &self.ty {
+            Type::TyVar(_) => self.clone(),
+            Type::TyCon(tc) => self.set_tycon_tc(Arc::new(TyCon::new(f(&tc.name)))),
+            Type::TyApp(fun, arg) => self
+                .set_tyapp_fun(fun.map_tycon_names(f))
+                .set_tyapp_arg(arg.map_tycon_names(f)),
+            Type::FunTy(src, dst) => self
+                .set_funty_src(src.map_tycon_names(f))
+                .set_funty_dst(dst.map_tycon_names(f)),
+            Type::AssocTy(_, args) => {
+                let args = args
+                    .iter()
+                    .map(|arg| arg.map_tycon_names(f))
+                    .collect::<Vec<_>>();
+                self.set_assocty_args(args)
+            }
+        }
+    }
+
     // Is this type constructed from type constructor, not from associated types?
     pub fn is_assoc_ty_free(&self) -> bool {
         match &self.ty {
@@ -1676,8 +1650,8 @@ impl Scheme {
         constraints_str + &ty.to_string()
     }
 
-    pub fn to_string_normalize(&self) -> String {
-        // Change names of generalized type variables to a, b, ...
+    // Create a substitution which changes names of generalized type variables to a, b, ...
+    fn normalizing_substitution(&self) -> Substitution {
         let mut s = Substitution::default();
         let mut tyvar_num = -1;
         for tyvar in &self.gen_vars {
@@ -1688,9 +1662,37 @@ impl Scheme {
                 type_tyvar(&new_name, &tyvar.kind.clone()),
             ));
         }
+        s
+    }
+
+    pub fn to_string_normalize(&self) -> String {
+        let s = self.normalizing_substitution();
         self.to_string_substituted(&s)
     }
 
+    // Stringify as `to_string_normalize`, and also returns the byte ranges of the parameter types of the function type in the resulting string.
+    // For example, for `[a : ToString] a -> (a -> b) -> b`, the ranges of `a` and `(a -> b)` are returned.
+    pub fn to_string_normalize_with_param_ranges(&self) -> (String, Vec<(usize, usize)>) {
+        let s = self.normalizing_substitution();
+        let str = self.to_string_substituted(&s);
+        let ty = s.substitute_type(&self.ty);
+
+        // The type follows the constraints in the string.
+        let mut pos = str.len() - ty.to_string().len();
+        let mut ranges = vec![];
+        let mut ty = ty;
+        while let Type::FunTy(src, dst) = &ty.ty {
+            let mut len = src.to_string().len();
+            if let Type::FunTy(_, _) = src.ty {
+                len += 2; // For braces.
+            }
+            ranges.push((pos, pos + len));
+            pos += len + " -> ".len();
+            ty = dst.clone();
+        }
+        (str, ranges)
+    }
+
     pub fn to_string(&self) -> String {
         let s = Substitution::default();
         self.to_string_substituted(&s)
@@ -0,0 +1,1569 @@
+// Generate Fix bindings from a C header (`fix bindgen`).
+//
+// This is not a full C parser: the preprocessor is not run (`#include` and conditional directives are ignored), and only the following declarations are recognized:
+// - function prototypes, which are converted to functions returning `IO` that call the C function by `FFI_CALL`,
+// - typedefs of number types, pointers, structs and enums, which are converted to type aliases,
+// - struct definitions, which are converted to unboxed structs,
+// - enumerators and `#define`s of integer or floating point constants, which are converted to global values.
+// Other declarations (variadic functions, unions, bitfields, global variables, ...) are skipped and reported as warnings.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::error::Errors;
+
+// Keywords and literals of Fix which cannot be used as value names.
+const FIX_RESERVED_NAMES: [&str; 14] = [
+    "let", "eval", "in", "if", "else", "do", "type", "trait", "impl", "module", "import", "true",
+    "false", "nullptr",
+];
+
+// Words which may appear in a declaration but do not affect the type.
+const C_QUALIFIERS: [&str; 16] = [
+    "const",
+    "volatile",
+    "restrict",
+    "__restrict",
+    "__restrict__",
+    "__const",
+    "extern",
+    "static",
+    "inline",
+    "__inline",
+    "__inline__",
+    "register",
+    "__extension__",
+    "_Noreturn",
+    "_Nonnull",
+    "_Nullable",
+];
+
+// Words which build a C number type.
+const C_BASE_TYPE_WORDS: [&str; 11] = [
+    "void", "char", "short", "int", "long", "float", "double", "signed", "unsigned", "_Bool",
+    "bool",
+];
+
+// Words which are followed by a parenthesized list to be ignored.
+const C_EXTENSION_WORDS: [&str; 6] = [
+    "__attribute__",
+    "__attribute",
+    "__declspec",
+    "__asm__",
+    "__asm",
+    "asm",
+];
+
+// The result of `generate_bindings`.
+pub struct Bindings {
+    // The source code of the generated Fix module.
+    pub source: String,
+    // Messages about skipped declarations.
+    pub warnings: Vec<String>,
+}
+
+// Run `fix bindgen` subcommand.
+pub fn run_bindgen(
+    header_path: &Path,
+    module_name: &str,
+    output_path: Option<&Path>,
+) -> Result<(), Errors> {
+    let header = std::fs::read_to_string(header_path).map_err(|e| {
+        Errors::from_msg(format!(
+            "Failed to read file \"{}\": {:?}",
+            header_path.to_string_lossy(),
+            e
+        ))
+    })?;
+    let header_name = header_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or(header_path.to_string_lossy().to_string());
+    let bindings = generate_bindings(&header, &header_name, module_name)?;
+    for warning in &bindings.warnings {
+        eprintln!("Warning: {}", warning);
+    }
+
+    // By default, write the bindings into `{header stem}.fix` in the current directory.
+    let output_path = match output_path {
+        Some(path) => path.to_path_buf(),
+        None => {
+            let stem = header_path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or("bindings".to_string());
+            PathBuf::from(format!("{}.fix", stem))
+        }
+    };
+    std::fs::write(&output_path, bindings.source).map_err(|e| {
+        Errors::from_msg(format!(
+            "Failed to write file \"{}\": {:?}",
+            output_path.to_string_lossy(),
+            e
+        ))
+    })?;
+    println!(
+        "Saved bindings to \"{}\"",
+        output_path.to_string_lossy().to_string()
+    );
+    Ok(())
+}
+
+// Generate the source code of a Fix module `module_name` from the content of a C header.
+// - `header_name`: used in comments and warnings.
+pub fn generate_bindings(
+    header: &str,
+    header_name: &str,
+    module_name: &str,
+) -> Result<Bindings, Errors> {
+    validate_module_name(module_name)?;
+
+    let mut gen = BindingGenerator::new(header_name);
+    let (defines, toks) = tokenize_header(header);
+    for define in defines {
+        gen.process_define(define);
+    }
+    for decl in split_declarations(remove_extensions(toks)) {
+        gen.process_declaration(&decl);
+    }
+
+    let mut source = format!(
+        "// Bindings for \"{}\" generated by `fix bindgen`.\nmodule {};\n\n",
+        header_name, module_name
+    );
+    source += gen.source.trim_end();
+    source += "\n";
+    Ok(Bindings {
+        source,
+        warnings: gen.warnings,
+    })
+}
+
+fn validate_module_name(module_name: &str) -> Result<(), Errors> {
+    let is_valid = module_name.split('.').all(|part| {
+        part.starts_with(|c: char| c.is_ascii_uppercase())
+            && part.chars().all(|c| c.is_ascii_alphanumeric())
+    });
+    if !is_valid {
+        return Err(Errors::from_msg(format!(
+            "`{}` is not a valid module name. It should start with an uppercase letter and consist of letters and digits.",
+            module_name
+        )));
+    }
+    Ok(())
+}
+
+#[derive(Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(String),
+    Char(i128),
+    Str,
+    Punct(String),
+}
+
+#[derive(Clone)]
+struct Tok {
+    token: Token,
+    line: usize,
+}
+
+impl Tok {
+    fn ident(&self) -> Option<&str> {
+        match &self.token {
+            Token::Ident(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn is_ident(&self, s: &str) -> bool {
+        self.ident() == Some(s)
+    }
+
+    fn is_punct(&self, s: &str) -> bool {
+        match &self.token {
+            Token::Punct(p) => p == s,
+            _ => false,
+        }
+    }
+
+    fn to_c_string(&self) -> String {
+        match &self.token {
+            Token::Ident(s) | Token::Number(s) | Token::Punct(s) => s.clone(),
+            Token::Char(c) => format!("{}", c),
+            Token::Str => "\"...\"".to_string(),
+        }
+    }
+}
+
+fn is_punct_at(toks: &[Tok], i: usize, s: &str) -> bool {
+    toks.get(i).map_or(false, |t| t.is_punct(s))
+}
+
+// A `#define NAME body` directive.
+struct Define {
+    name: String,
+    is_function_like: bool,
+    body: Vec<Tok>,
+    line: usize,
+}
+
+// Split a header into `#define` directives and tokens of other lines.
+fn tokenize_header(header: &str) -> (Vec<Define>, Vec<Tok>) {
+    // Join lines continued by backslashes, remembering the line number where each logical line starts.
+    let header = strip_comments(header);
+    let mut lines: Vec<(usize, String)> = vec![];
+    let mut continued: Option<(usize, String)> = None;
+    for (i, line) in header.lines().enumerate() {
+        let (start, mut text) = continued.take().unwrap_or((i + 1, String::new()));
+        if let Some(stripped) = line.trim_end().strip_suffix('\\') {
+            text += stripped;
+            text += " ";
+            continued = Some((start, text));
+            continue;
+        }
+        text += line;
+        lines.push((start, text));
+    }
+    lines.extend(continued);
+
+    let mut defines = vec![];
+    let mut toks = vec![];
+    for (line_no, line) in lines {
+        let trimmed = line.trim_start();
+        if let Some(directive) = trimmed.strip_prefix('#') {
+            let directive = directive.trim_start();
+            if let Some(rest) = directive.strip_prefix("define") {
+                if !rest.starts_with(|c: char| c.is_whitespace()) {
+                    continue;
+                }
+                let rest = rest.trim_start();
+                let name_len = rest
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len());
+                let name = rest[..name_len].to_string();
+                let is_function_like = rest[name_len..].starts_with('(');
+                let mut body = vec![];
+                tokenize_line(&rest[name_len..], line_no, &mut body);
+                defines.push(Define {
+                    name,
+                    is_function_like,
+                    body,
+                    line: line_no,
+                });
+            }
+            // Other directives are ignored.
+            continue;
+        }
+        tokenize_line(&line, line_no, &mut toks);
+    }
+    (defines, toks)
+}
+
+// Replace comments with spaces, keeping newlines.
+fn strip_comments(src: &str) -> String {
+    let chars = src.chars().collect::<Vec<_>>();
+    let mut res = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '/' && chars.get(i + 1) == Some(&'/') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c == '/' && chars.get(i + 1) == Some(&'*') {
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                if chars[i] == '\n' {
+                    res.push('\n');
+                }
+                i += 1;
+            }
+            i += 2;
+            res.push(' ');
+        } else if c == '"' || c == '\'' {
+            // Copy a string or character literal as is.
+            res.push(c);
+            i += 1;
+            while i < chars.len() && chars[i] != c && chars[i] != '\n' {
+                if chars[i] == '\\' && i + 1 < chars.len() {
+                    res.push(chars[i]);
+                    i += 1;
+                }
+                res.push(chars[i]);
+                i += 1;
+            }
+            if i < chars.len() && chars[i] == c {
+                res.push(c);
+                i += 1;
+            }
+        } else {
+            res.push(c);
+            i += 1;
+        }
+    }
+    res
+}
+
+fn tokenize_line(line: &str, line_no: usize, toks: &mut Vec<Tok>) {
+    const PUNCTS: [&str; 13] = [
+        "...", "<<", ">>", "->", "==", "!=", "<=", ">=", "&&", "||", "##", "++", "--",
+    ];
+    let chars = line.chars().collect::<Vec<_>>();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        let token = if c.is_whitespace() {
+            i += 1;
+            continue;
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            Token::Ident(chars[start..i].iter().collect())
+        } else if c.is_ascii_digit()
+            || (c == '.' && chars.get(i + 1).map_or(false, |c| c.is_ascii_digit()))
+        {
+            let is_hex = c == '0' && matches!(chars.get(i + 1), Some('x') | Some('X'));
+            while i < chars.len() {
+                let d = chars[i];
+                let is_exponent_sign = (d == '+' || d == '-')
+                    && (if is_hex { "pP" } else { "eE" }).contains(chars[i - 1]);
+                if !(d.is_ascii_alphanumeric() || d == '.' || is_exponent_sign) {
+                    break;
+                }
+                i += 1;
+            }
+            Token::Number(chars[start..i].iter().collect())
+        } else if c == '"' {
+            i += 1;
+            while i < chars.len() && chars[i] != '"' {
+                i += if chars[i] == '\\' { 2 } else { 1 };
+            }
+            i += 1;
+            Token::Str
+        } else if c == '\'' {
+            i += 1;
+            let mut value = 0;
+            if i < chars.len() && chars[i] == '\\' {
+                i += 1;
+                value = match chars.get(i) {
+                    Some('n') => '\n' as i128,
+                    Some('t') => '\t' as i128,
+                    Some('r') => '\r' as i128,
+                    Some('0') => 0,
+                    Some(c) => *c as i128,
+                    None => 0,
+                };
+            } else if i < chars.len() {
+                value = chars[i] as i128;
+            }
+            while i < chars.len() && chars[i] != '\'' {
+                i += 1;
+            }
+            i += 1;
+            Token::Char(value)
+        } else {
+            let rest = chars[i..].iter().take(3).collect::<String>();
+            let punct = PUNCTS
+                .iter()
+                .find(|p| rest.starts_with(*p))
+                .map(|p| p.to_string())
+                .unwrap_or(c.to_string());
+            i += punct.chars().count();
+            Token::Punct(punct)
+        };
+        toks.push(Tok {
+            token,
+            line: line_no,
+        });
+    }
+}
+
+// Find the index of the bracket closing the one at `open`.
+fn find_closing(toks: &[Tok], open: usize) -> Result<usize, String> {
+    let mut depth = 0;
+    for (i, tok) in toks.iter().enumerate().skip(open) {
+        if tok.is_punct("(") || tok.is_punct("[") || tok.is_punct("{") {
+            depth += 1;
+        } else if tok.is_punct(")") || tok.is_punct("]") || tok.is_punct("}") {
+            depth -= 1;
+            if depth == 0 {
+                return Ok(i);
+            }
+        }
+    }
+    Err("unbalanced brackets".to_string())
+}
+
+// Split tokens by `sep` which is not enclosed by brackets.
+fn split_top_level<'a>(toks: &'a [Tok], sep: &str) -> Vec<&'a [Tok]> {
+    let mut parts = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, tok) in toks.iter().enumerate() {
+        if tok.is_punct("(") || tok.is_punct("[") || tok.is_punct("{") {
+            depth += 1;
+        } else if tok.is_punct(")") || tok.is_punct("]") || tok.is_punct("}") {
+            depth -= 1;
+        } else if depth == 0 && tok.is_punct(sep) {
+            parts.push(&toks[start..i]);
+            start = i + 1;
+        }
+    }
+    parts.push(&toks[start..]);
+    parts
+}
+
+fn find_top_level(toks: &[Tok], punct: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, tok) in toks.iter().enumerate() {
+        if depth == 0 && tok.is_punct(punct) {
+            return Some(i);
+        }
+        if tok.is_punct("(") || tok.is_punct("[") || tok.is_punct("{") {
+            depth += 1;
+        } else if tok.is_punct(")") || tok.is_punct("]") || tok.is_punct("}") {
+            depth -= 1;
+        }
+    }
+    None
+}
+
+// Remove compiler extensions such as `__attribute__((...))`, and `extern "C" { ... }` wrappers.
+fn remove_extensions(toks: Vec<Tok>) -> Vec<Tok> {
+    let mut removed = HashSet::new();
+    let mut i = 0;
+    while i < toks.len() {
+        let tok = &toks[i];
+        if C_EXTENSION_WORDS.iter().any(|w| tok.is_ident(w)) && is_punct_at(&toks, i + 1, "(") {
+            let end = find_closing(&toks, i + 1).unwrap_or(toks.len() - 1);
+            removed.extend(i..=end);
+            i = end + 1;
+            continue;
+        }
+        if tok.is_ident("extern") && toks.get(i + 1).map_or(false, |t| t.token == Token::Str) {
+            removed.insert(i);
+            removed.insert(i + 1);
+            if is_punct_at(&toks, i + 2, "{") {
+                removed.insert(i + 2);
+                if let Ok(end) = find_closing(&toks, i + 2) {
+                    removed.insert(end);
+                }
+            }
+            i += 2;
+            continue;
+        }
+        i += 1;
+    }
+    toks.into_iter()
+        .enumerate()
+        .filter(|(i, _)| !removed.contains(i))
+        .map(|(_, tok)| tok)
+        .collect()
+}
+
+// Split tokens into declarations, which are terminated by `;` or the body of a function definition.
+fn split_declarations(toks: Vec<Tok>) -> Vec<Vec<Tok>> {
+    let mut decls = vec![];
+    let mut decl: Vec<Tok> = vec![];
+    let mut depth = 0;
+    for tok in toks {
+        if depth == 0 && tok.is_punct(";") {
+            decls.push(std::mem::take(&mut decl));
+            continue;
+        }
+        if depth == 0 && decl.is_empty() && (tok.is_punct("}") || tok.is_punct(")")) {
+            // A stray closing bracket, e.g., of `extern "C" {` enclosed by `#ifdef`.
+            continue;
+        }
+        if tok.is_punct("(") || tok.is_punct("[") || tok.is_punct("{") {
+            depth += 1;
+        } else if tok.is_punct(")") || tok.is_punct("]") || tok.is_punct("}") {
+            depth -= 1;
+        }
+        let closes_body = depth == 0 && tok.is_punct("}");
+        decl.push(tok);
+        if closes_body {
+            // The end of a function definition `... f(...) { ... }`, which is not followed by `;`.
+            let open = find_top_level(&decl, "{").unwrap();
+            if open > 0 && decl[open - 1].is_punct(")") {
+                decls.push(std::mem::take(&mut decl));
+            }
+        }
+    }
+    if !decl.is_empty() {
+        decls.push(decl);
+    }
+    decls.into_iter().filter(|decl| !decl.is_empty()).collect()
+}
+
+// Convert tokens into C source code, used in comments.
+fn to_c_string(toks: &[Tok]) -> String {
+    let mut res = String::new();
+    let mut prev: Option<&Tok> = None;
+    for tok in toks {
+        if let Some(prev) = prev {
+            let no_space = tok.is_punct(",")
+                || tok.is_punct(")")
+                || tok.is_punct("[")
+                || tok.is_punct("]")
+                || prev.is_punct("(")
+                || prev.is_punct("[")
+                || prev.is_punct("*")
+                || (tok.is_punct("(")
+                    && (prev.is_punct(")")
+                        || prev
+                            .ident()
+                            .map_or(false, |s| !C_BASE_TYPE_WORDS.contains(&s))));
+            if !no_space {
+                res.push(' ');
+            }
+        }
+        res += &tok.to_c_string();
+        prev = Some(tok);
+    }
+    res
+}
+
+// Convert a C identifier into a Fix type name, e.g., `my_handle_t` into `MyHandleT`.
+fn to_type_name(c_name: &str) -> String {
+    let name = c_name
+        .split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            let head = chars.next().unwrap().to_ascii_uppercase();
+            head.to_string() + chars.as_str()
+        })
+        .collect::<String>();
+    if name.starts_with(|c: char| c.is_ascii_uppercase()) {
+        name
+    } else {
+        "C".to_string() + &name
+    }
+}
+
+// Convert a C identifier into a Fix value name, e.g., `FOO_MAX` into `foo_max` and `SDL_Init` into `sdl_init`.
+fn to_value_name(c_name: &str) -> String {
+    let mut name = if c_name.starts_with(|c: char| c.is_ascii_uppercase()) {
+        let chars = c_name.chars().collect::<Vec<_>>();
+        let mut name = String::new();
+        for (i, c) in chars.iter().enumerate() {
+            if c.is_ascii_uppercase()
+                && i > 0
+                && (chars[i - 1].is_ascii_lowercase() || chars[i - 1].is_ascii_digit())
+            {
+                name.push('_');
+            }
+            name.push(c.to_ascii_lowercase());
+        }
+        name
+    } else {
+        c_name.to_string()
+    };
+    if FIX_RESERVED_NAMES.contains(&name.as_str()) {
+        name.push('_');
+    }
+    name
+}
+
+// The base type of a declaration.
+#[derive(Clone)]
+enum CBaseType {
+    // Type specifiers other than `struct`, `union` or `enum`, e.g., `["unsigned", "int"]` or `["size_t"]`.
+    Words(Vec<String>),
+    // `struct`, `union` or `enum`, with optional tag name and body.
+    Tag {
+        kind: String,
+        name: Option<String>,
+        body: Option<Vec<Tok>>,
+    },
+}
+
+// A parsed declaration of a single name, such as `const char *s` or `int (*f)(int)`.
+struct Declarator {
+    base: CBaseType,
+    is_pointer: bool,
+    is_array: bool,
+    name: Option<String>,
+    // The number of tokens which form the base type.
+    spec_len: usize,
+    // Tokens which follow the declarator, e.g., `: 3` of a bitfield.
+    rest: Vec<Tok>,
+}
+
+fn skip_qualifiers(toks: &[Tok], i: &mut usize) {
+    while toks
+        .get(*i)
+        .and_then(|t| t.ident())
+        .map_or(false, |s| C_QUALIFIERS.contains(&s))
+    {
+        *i += 1;
+    }
+}
+
+// Parse a declaration of a single name.
+// - `expect_name`: whether the declaration may contain the declared name.
+fn parse_declarator(toks: &[Tok], expect_name: bool) -> Result<Declarator, String> {
+    let mut i = 0;
+    let mut name = None;
+    skip_qualifiers(toks, &mut i);
+    let tag_kind = toks
+        .get(i)
+        .and_then(|t| t.ident())
+        .filter(|s| ["struct", "union", "enum"].contains(s))
+        .map(|s| s.to_string());
+    let base = if let Some(kind) = tag_kind {
+        i += 1;
+        let tag_name = toks.get(i).and_then(|t| t.ident()).map(|s| s.to_string());
+        if tag_name.is_some() {
+            i += 1;
+        }
+        let body = if is_punct_at(toks, i, "{") {
+            let end = find_closing(toks, i)?;
+            let body = toks[i + 1..end].to_vec();
+            i = end + 1;
+            Some(body)
+        } else {
+            None
+        };
+        if tag_name.is_none() && body.is_none() {
+            return Err(format!("missing tag name of `{}`", kind));
+        }
+        skip_qualifiers(toks, &mut i);
+        CBaseType::Tag {
+            kind,
+            name: tag_name,
+            body,
+        }
+    } else {
+        let mut words = vec![];
+        while let Some(word) = toks.get(i).and_then(|t| t.ident()) {
+            if !C_QUALIFIERS.contains(&word) {
+                words.push((word.to_string(), i));
+            }
+            i += 1;
+        }
+        // If no pointer follows, the last word may be the declared name, as in `size_t n`.
+        let last_is_name = expect_name
+            && !is_punct_at(toks, i, "*")
+            && words.len() >= 2
+            && !C_BASE_TYPE_WORDS.contains(&words.last().unwrap().0.as_str());
+        if last_is_name {
+            let (word, idx) = words.pop().unwrap();
+            name = Some(word);
+            i = idx;
+        }
+        CBaseType::Words(words.into_iter().map(|(word, _)| word).collect())
+    };
+    let spec_len = i;
+    if name.is_some() {
+        i += 1;
+    }
+
+    let mut is_pointer = false;
+    while is_punct_at(toks, i, "*") {
+        is_pointer = true;
+        i += 1;
+        skip_qualifiers(toks, &mut i);
+    }
+    if name.is_none() && expect_name {
+        if is_punct_at(toks, i, "(") {
+            // A function pointer `(*name)(params)`.
+            let end = find_closing(toks, i)?;
+            let inner = &toks[i + 1..end];
+            if !inner.first().map_or(false, |t| t.is_punct("*")) {
+                return Err("unsupported declarator".to_string());
+            }
+            name = inner
+                .iter()
+                .filter_map(|t| t.ident())
+                .filter(|s| !C_QUALIFIERS.contains(s))
+                .last()
+                .map(|s| s.to_string());
+            i = end + 1;
+            if is_punct_at(toks, i, "(") {
+                i = find_closing(toks, i)? + 1;
+            }
+            is_pointer = true;
+        } else if let Some(word) = toks.get(i).and_then(|t| t.ident()) {
+            name = Some(word.to_string());
+            i += 1;
+        }
+    }
+    let mut is_array = false;
+    while is_punct_at(toks, i, "[") {
+        is_array = true;
+        i = find_closing(toks, i)? + 1;
+    }
+    Ok(Declarator {
+        base,
+        is_pointer,
+        is_array,
+        name,
+        spec_len,
+        rest: toks[i..].to_vec(),
+    })
+}
+
+// A Fix type corresponding to a C type.
+#[derive(Clone)]
+enum FixType {
+    // `void`, which is converted to `()`.
+    Unit,
+    Type {
+        // The name used in Fix type signatures, which may be a type alias.
+        name: String,
+        // The name used in `FFI_CALL`, which is not a type alias.
+        ffi: String,
+    },
+}
+
+impl FixType {
+    fn number(name: &str) -> FixType {
+        FixType::Type {
+            name: name.to_string(),
+            ffi: name.to_string(),
+        }
+    }
+
+    fn name(&self) -> String {
+        match self {
+            FixType::Unit => "()".to_string(),
+            FixType::Type { name, .. } => name.clone(),
+        }
+    }
+
+    fn ffi(&self) -> String {
+        match self {
+            FixType::Unit => "()".to_string(),
+            FixType::Type { ffi, .. } => ffi.clone(),
+        }
+    }
+}
+
+// Get the Fix type of a well-known typedef name of C standard headers.
+fn standard_typedef(name: &str) -> Option<FixType> {
+    let fix_name = match name {
+        "size_t" => "CSizeT",
+        "int8_t" => "I8",
+        "uint8_t" => "U8",
+        "int16_t" => "I16",
+        "uint16_t" => "U16",
+        "int32_t" => "I32",
+        "uint32_t" => "U32",
+        "int64_t" => "I64",
+        "uint64_t" => "U64",
+        "intptr_t" | "ssize_t" | "ptrdiff_t" => "I64",
+        "uintptr_t" => "U64",
+        _ => return None,
+    };
+    Some(FixType::number(fix_name))
+}
+
+#[derive(Clone, Copy)]
+enum ConstValue {
+    Int { value: i128, unsigned: bool },
+    Float(f64),
+}
+
+impl ConstValue {
+    fn parse_number(s: &str) -> Option<ConstValue> {
+        let lower = s.to_ascii_lowercase();
+        let is_hex = lower.starts_with("0x");
+        if !is_hex && (lower.contains('.') || lower.contains('e')) {
+            let body = lower.trim_end_matches(|c| c == 'f' || c == 'l');
+            return body.parse::<f64>().ok().map(ConstValue::Float);
+        }
+        let body = lower.trim_end_matches(|c| c == 'u' || c == 'l');
+        let unsigned = lower[body.len()..].contains('u');
+        let (digits, radix) = if is_hex {
+            (&body[2..], 16)
+        } else if let Some(bin) = body.strip_prefix("0b") {
+            (bin, 2)
+        } else if body.len() > 1 && body.starts_with('0') {
+            (&body[1..], 8)
+        } else {
+            (body, 10)
+        };
+        let value = i128::from_str_radix(digits, radix).ok()?;
+        Some(ConstValue::Int { value, unsigned })
+    }
+}
+
+// Evaluates a constant expression in a `#define` or an enumerator.
+struct ConstEvaluator<'a> {
+    toks: &'a [Tok],
+    pos: usize,
+    constants: &'a HashMap<String, ConstValue>,
+}
+
+impl<'a> ConstEvaluator<'a> {
+    fn eval(toks: &'a [Tok], constants: &'a HashMap<String, ConstValue>) -> Option<ConstValue> {
+        let mut evaluator = ConstEvaluator {
+            toks,
+            pos: 0,
+            constants,
+        };
+        let value = evaluator.binary(0)?;
+        if evaluator.pos != toks.len() {
+            return None;
+        }
+        Some(value)
+    }
+
+    fn peek_punct(&self) -> Option<&str> {
+        match self.toks.get(self.pos).map(|t| &t.token) {
+            Some(Token::Punct(p)) => Some(p),
+            _ => None,
+        }
+    }
+
+    // Parse binary operators whose precedence is at least `min_prec`.
+    fn binary(&mut self, min_prec: usize) -> Option<ConstValue> {
+        const OPS: [&[&str]; 6] = [
+            &["|"],
+            &["^"],
+            &["&"],
+            &["<<", ">>"],
+            &["+", "-"],
+            &["*", "/", "%"],
+        ];
+        if min_prec >= OPS.len() {
+            return self.unary();
+        }
+        let mut lhs = self.binary(min_prec + 1)?;
+        while let Some(op) = self.peek_punct() {
+            if !OPS[min_prec].contains(&op) {
+                break;
+            }
+            let op = op.to_string();
+            self.pos += 1;
+            let rhs = self.binary(min_prec + 1)?;
+            lhs = Self::apply(&op, lhs, rhs)?;
+        }
+        Some(lhs)
+    }
+
+    fn apply(op: &str, lhs: ConstValue, rhs: ConstValue) -> Option<ConstValue> {
+        match (lhs, rhs) {
+            (
+                ConstValue::Int {
+                    value: l,
+                    unsigned: lu,
+                },
+                ConstValue::Int {
+                    value: r,
+                    unsigned: ru,
+                },
+            ) => {
+                let value = match op {
+                    "|" => l | r,
+                    "^" => l ^ r,
+                    "&" => l & r,
+                    "<<" => l.checked_shl(u32::try_from(r).ok()?)?,
+                    ">>" => l.checked_shr(u32::try_from(r).ok()?)?,
+                    "+" => l.checked_add(r)?,
+                    "-" => l.checked_sub(r)?,
+                    "*" => l.checked_mul(r)?,
+                    "/" => l.checked_div(r)?,
+                    "%" => l.checked_rem(r)?,
+                    _ => return None,
+                };
+                Some(ConstValue::Int {
+                    value,
+                    unsigned: lu || ru,
+                })
+            }
+            (lhs, rhs) => {
+                let to_f64 = |v: ConstValue| match v {
+                    ConstValue::Int { value, .. } => value as f64,
+                    ConstValue::Float(f) => f,
+                };
+                let (l, r) = (to_f64(lhs), to_f64(rhs));
+                let value = match op {
+                    "+" => l + r,
+                    "-" => l - r,
+                    "*" => l * r,
+                    "/" => l / r,
+                    _ => return None,
+                };
+                Some(ConstValue::Float(value))
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Option<ConstValue> {
+        if let Some(op) = self.peek_punct() {
+            if ["-", "+", "~", "!"].contains(&op) {
+                let op = op.to_string();
+                self.pos += 1;
+                let value = self.unary()?;
+                return match (op.as_str(), value) {
+                    ("+", value) => Some(value),
+                    ("-", ConstValue::Int { value, unsigned }) => Some(ConstValue::Int {
+                        value: -value,
+                        unsigned,
+                    }),
+                    ("-", ConstValue::Float(f)) => Some(ConstValue::Float(-f)),
+                    ("~", ConstValue::Int { value, unsigned }) => Some(ConstValue::Int {
+                        value: !value,
+                        unsigned,
+                    }),
+                    ("!", ConstValue::Int { value, .. }) => Some(ConstValue::Int {
+                        value: (value == 0) as i128,
+                        unsigned: false,
+                    }),
+                    _ => None,
+                };
+            }
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Option<ConstValue> {
+        let tok = self.toks.get(self.pos)?;
+        self.pos += 1;
+        match &tok.token {
+            Token::Number(s) => ConstValue::parse_number(s),
+            Token::Char(c) => Some(ConstValue::Int {
+                value: *c,
+                unsigned: false,
+            }),
+            Token::Ident(name) => self.constants.get(name).copied(),
+            Token::Punct(p) if p == "(" => {
+                let value = self.binary(0)?;
+                if self.peek_punct() != Some(")") {
+                    return None;
+                }
+                self.pos += 1;
+                Some(value)
+            }
+            _ => None,
+        }
+    }
+}
+
+struct BindingGenerator {
+    header_name: String,
+    // The body of the generated module.
+    source: String,
+    warnings: Vec<String>,
+    // Values of macros and enumerators.
+    constants: HashMap<String, ConstValue>,
+    // Typedef names to Fix types, or the reasons why they are not supported.
+    typedefs: HashMap<String, Result<FixType, String>>,
+    // Tags (such as `struct foo`) to Fix types, or the reasons why they are not supported.
+    tags: HashMap<String, Result<FixType, String>>,
+    // Typedef names of structs which are declared before the struct is defined, as in `typedef struct foo foo_t;`.
+    pending_typedefs: HashMap<String, String>,
+    type_names: HashSet<String>,
+    value_names: HashSet<String>,
+}
+
+impl BindingGenerator {
+    fn new(header_name: &str) -> BindingGenerator {
+        BindingGenerator {
+            header_name: header_name.to_string(),
+            source: String::new(),
+            warnings: vec![],
+            constants: HashMap::new(),
+            typedefs: HashMap::new(),
+            tags: HashMap::new(),
+            pending_typedefs: HashMap::new(),
+            type_names: HashSet::new(),
+            value_names: HashSet::new(),
+        }
+    }
+
+    fn warn(&mut self, line: usize, msg: String) {
+        self.source += &format!("// Skipped: {}\n\n", msg);
+        self.warnings
+            .push(format!("{}:{}: {}", self.header_name, line, msg));
+    }
+
+    fn emit(&mut self, comment: &str, code: &str) {
+        self.source += &format!("// {}\n{}\n\n", comment, code);
+    }
+
+    fn add_type_name(&mut self, c_name: &str) -> Result<String, String> {
+        let name = to_type_name(c_name);
+        if !self.type_names.insert(name.clone()) {
+            return Err(format!(
+                "the Fix name `{}` conflicts with another type",
+                name
+            ));
+        }
+        Ok(name)
+    }
+
+    fn add_value_name(&mut self, c_name: &str) -> Result<String, String> {
+        let name = to_value_name(c_name);
+        if !self.value_names.insert(name.clone()) {
+            return Err(format!(
+                "the Fix name `{}` conflicts with another value",
+                name
+            ));
+        }
+        Ok(name)
+    }
+
+    fn process_define(&mut self, define: Define) {
+        if define.name.is_empty() || (define.body.is_empty() && !define.is_function_like) {
+            // Include guards, etc.
+            return;
+        }
+        if define.is_function_like {
+            self.warn(
+                define.line,
+                format!(
+                    "macro `{}`: function-like macros are not supported",
+                    define.name
+                ),
+            );
+            return;
+        }
+        match ConstEvaluator::eval(&define.body, &self.constants) {
+            Some(value) => {
+                let comment = format!("`#define {} {}`", define.name, to_c_string(&define.body));
+                if let Err(e) = self.emit_constant(&define.name, value, None, &comment) {
+                    self.warn(define.line, format!("macro `{}`: {}", define.name, e));
+                }
+            }
+            None => self.warn(
+                define.line,
+                format!(
+                    "macro `{}`: the value is not a number or a constant expression",
+                    define.name
+                ),
+            ),
+        }
+    }
+
+    // Emit a global value for a constant.
+    // - `ty`: the type of the constant. If `None`, `I64`, `U64` or `F64` is used.
+    fn emit_constant(
+        &mut self,
+        c_name: &str,
+        value: ConstValue,
+        ty: Option<&str>,
+        comment: &str,
+    ) -> Result<(), String> {
+        self.constants.insert(c_name.to_string(), value);
+        let (ty, expr) = match (value, ty) {
+            (ConstValue::Int { value, .. }, Some(ty)) => {
+                let lit = if value < 0 {
+                    format!("({})", value)
+                } else {
+                    value.to_string()
+                };
+                (ty.to_string(), format!("{}.to_{}", lit, ty))
+            }
+            (ConstValue::Int { value, unsigned }, None) => {
+                if unsigned || value > i64::MAX as i128 {
+                    let value = value.rem_euclid(1 << 64);
+                    ("U64".to_string(), format!("{}_U64", value))
+                } else if value >= i64::MIN as i128 {
+                    ("I64".to_string(), value.to_string())
+                } else {
+                    return Err("the value is too small".to_string());
+                }
+            }
+            (ConstValue::Float(f), _) => {
+                if !f.is_finite() {
+                    return Err("the value is not finite".to_string());
+                }
+                ("F64".to_string(), format!("{:?}", f))
+            }
+        };
+        let name = self.add_value_name(c_name)?;
+        self.emit(
+            comment,
+            &format!("{} : {};\n{} = {};", name, ty, name, expr),
+        );
+        Ok(())
+    }
+
+    fn process_declaration(&mut self, toks: &[Tok]) {
+        let line = toks[0].line;
+        if toks[0].is_ident("typedef") {
+            self.process_typedef(&toks[1..], line);
+            return;
+        }
+        if let Some(open) = find_top_level(toks, "(") {
+            self.process_function(toks, open, line);
+            return;
+        }
+        let decl = match parse_declarator(toks, true) {
+            Ok(decl) => decl,
+            Err(e) => {
+                self.warn(line, format!("`{}`: {}", to_c_string(toks), e));
+                return;
+            }
+        };
+        if let CBaseType::Tag {
+            kind,
+            name: tag_name,
+            body: Some(body),
+        } = &decl.base
+        {
+            if let Err(e) = self.define_tag(kind, tag_name, body, None) {
+                let tag = format!("{} {}", kind, tag_name.clone().unwrap_or_default());
+                self.warn(line, format!("`{}`: {}", tag.trim_end(), e));
+            }
+        }
+        // A declaration without a name, such as `struct foo;`, declares only the tag.
+        if let Some(name) = decl.name {
+            self.warn(
+                line,
+                format!("variable `{}`: global variables are not supported", name),
+            );
+        }
+    }
+
+    fn process_typedef(&mut self, toks: &[Tok], line: usize) {
+        let parts = split_top_level(toks, ",");
+        let mut decls = vec![];
+        for (i, part) in parts.iter().enumerate() {
+            // The second and later declarators share the base type with the first one.
+            let part = if i == 0 {
+                part.to_vec()
+            } else {
+                let spec_len = decls.first().map_or(0, |d: &Declarator| d.spec_len);
+                [&parts[0][..spec_len], part].concat()
+            };
+            match parse_declarator(&part, true) {
+                Ok(decl) => decls.push(decl),
+                Err(e) => {
+                    self.warn(line, format!("typedef `{}`: {}", to_c_string(&part), e));
+                    return;
+                }
+            }
+        }
+
+        // If the typedef defines a struct, union or enum, then define it first.
+        // A non-pointer typedef name is used as the Fix name of the struct.
+        let mut defined_tag = None;
+        if let CBaseType::Tag {
+            kind,
+            name: tag_name,
+            body: Some(body),
+        } = &decls[0].base
+        {
+            let typedef_name = decls
+                .iter()
+                .find(|d| !d.is_pointer && !d.is_array)
+                .and_then(|d| d.name.clone());
+            defined_tag = Some(self.define_tag(kind, tag_name, body, typedef_name));
+        }
+
+        for decl in decls {
+            let c_name = match &decl.name {
+                Some(name) => name.clone(),
+                None => {
+                    self.warn(line, "typedef without a name".to_string());
+                    continue;
+                }
+            };
+            // Typedefs of incomplete structs are common for opaque handles, and they are reported only when used by value.
+            let mut is_opaque = false;
+            let result = if decl.is_array {
+                Err("array types are not supported".to_string())
+            } else if !decl.rest.is_empty() {
+                Err("unsupported declarator".to_string())
+            } else if decl.is_pointer {
+                Ok(FixType::number("Ptr"))
+            } else {
+                match &decl.base {
+                    CBaseType::Words(words) => self.resolve_words(words),
+                    CBaseType::Tag { body: Some(_), .. } => defined_tag.clone().unwrap(),
+                    CBaseType::Tag {
+                        kind,
+                        name: Some(tag_name),
+                        body: None,
+                    } => {
+                        let key = format!("{} {}", kind, tag_name);
+                        if kind == "struct" && !self.tags.contains_key(&key) {
+                            // The struct may be defined later.
+                            self.pending_typedefs
+                                .entry(key.clone())
+                                .or_insert(c_name.clone());
+                            is_opaque = true;
+                        }
+                        self.resolve_tag(kind, tag_name)
+                    }
+                    CBaseType::Tag { .. } => Err("unsupported declarator".to_string()),
+                }
+            };
+            let result = match result {
+                Ok(FixType::Type { name, ffi }) if name != to_type_name(&c_name) => {
+                    // Define a type alias.
+                    match self.add_type_name(&c_name) {
+                        Ok(alias) => {
+                            let comment = format!("`typedef {}`", to_c_string(toks));
+                            self.emit(&comment, &format!("type {} = {};", alias, name));
+                            Ok(FixType::Type { name: alias, ffi })
+                        }
+                        Err(e) => Err(e),
+                    }
+                }
+                result => result,
+            };
+            if let Err(e) = &result {
+                if !is_opaque {
+                    self.warn(line, format!("typedef `{}`: {}", c_name, e));
+                }
+            }
+            self.typedefs.insert(c_name, result);
+        }
+    }
+
+    // Define a struct, union or enum with a body.
+    // - `typedef_name`: the name given by `typedef`, which is preferred to the tag name as the Fix name of the type.
+    fn define_tag(
+        &mut self,
+        kind: &str,
+        tag_name: &Option<String>,
+        body: &[Tok],
+        typedef_name: Option<String>,
+    ) -> Result<FixType, String> {
+        let key = tag_name.as_ref().map(|name| format!("{} {}", kind, name));
+        let result = match kind {
+            "enum" => {
+                self.define_enumerators(body);
+                Ok(FixType::number("CInt"))
+            }
+            "union" => Err("unions are not supported".to_string()),
+            _ => {
+                let pending_name = key.as_ref().and_then(|key| self.pending_typedefs.get(key));
+                let c_name = typedef_name.or(pending_name.cloned()).or(tag_name.clone());
+                match c_name {
+                    Some(c_name) => self.define_struct(&c_name, kind, tag_name, body),
+                    None => Err("anonymous structs are not supported".to_string()),
+                }
+            }
+        };
+        if let Some(key) = key {
+            if let Some(typedef_name) = self.pending_typedefs.remove(&key) {
+                self.typedefs.insert(typedef_name, result.clone());
+            }
+            self.tags.insert(key, result.clone());
+        }
+        result
+    }
+
+    fn define_struct(
+        &mut self,
+        c_name: &str,
+        kind: &str,
+        tag_name: &Option<String>,
+        body: &[Tok],
+    ) -> Result<FixType, String> {
+        let mut fields = vec![];
+        let mut field_names = HashSet::new();
+        for member in split_top_level(body, ";") {
+            if member.is_empty() {
+                continue;
+            }
+            let parts = split_top_level(member, ",");
+            let mut spec_len = 0;
+            for (i, part) in parts.iter().enumerate() {
+                let part = if i == 0 {
+                    part.to_vec()
+                } else {
+                    [&parts[0][..spec_len], part].concat()
+                };
+                let decl = parse_declarator(&part, true)?;
+                spec_len = decl.spec_len;
+                if decl.rest.first().map_or(false, |t| t.is_punct(":")) {
+                    return Err("bitfields are not supported".to_string());
+                }
+                if let CBaseType::Tag { body: Some(_), .. } = &decl.base {
+                    return Err(
+                        "nested struct, union or enum definitions are not supported".to_string()
+                    );
+                }
+                let field_c_name = decl
+                    .name
+                    .clone()
+                    .ok_or("unnamed fields are not supported".to_string())?;
+                if decl.is_array {
+                    return Err(format!(
+                        "the field `{}` is an array, which is not supported",
+                        field_c_name
+                    ));
+                }
+                if !decl.rest.is_empty() {
+                    return Err("unsupported declarator".to_string());
+                }
+                let ty = match self.resolve_declarator(&decl)? {
+                    FixType::Unit => {
+                        return Err(format!("the field `{}` has type `void`", field_c_name))
+                    }
+                    ty => ty,
+                };
+                let field_name = to_value_name(&field_c_name);
+                if !field_names.insert(field_name.clone()) {
+                    return Err(format!(
+                        "the Fix name `{}` of a field conflicts with another field",
+                        field_name
+                    ));
+                }
+                fields.push(format!("{} : {}", field_name, ty.name()));
+            }
+        }
+        if fields.is_empty() {
+            return Err("empty structs are not supported".to_string());
+        }
+        let name = self.add_type_name(c_name)?;
+        let comment = match tag_name {
+            Some(tag_name) => format!("`{} {}`", kind, tag_name),
+            None => format!("`{}`", c_name),
+        };
+        self.emit(
+            &comment,
+            &format!("type {} = unbox struct {{ {} }};", name, fields.join(", ")),
+        );
+        Ok(FixType::number(&name))
+    }
+
+    fn define_enumerators(&mut self, body: &[Tok]) {
+        let mut next_value = 0;
+        for enumerator in split_top_level(body, ",") {
+            let (c_name, line) = match enumerator.first() {
+                Some(tok) if tok.ident().is_some() => (tok.ident().unwrap().to_string(), tok.line),
+                _ => continue,
+            };
+            let value = if is_punct_at(enumerator, 1, "=") {
+                match ConstEvaluator::eval(&enumerator[2..], &self.constants) {
+                    Some(ConstValue::Int { value, .. }) => value,
+                    _ => {
+                        self.warn(
+                            line,
+                            format!(
+                                "enumerator `{}`: the value is not a constant expression",
+                                c_name
+                            ),
+                        );
+                        return;
+                    }
+                }
+            } else {
+                next_value
+            };
+            next_value = value + 1;
+            let value = ConstValue::Int {
+                value,
+                unsigned: false,
+            };
+            let comment = format!("Enumerator `{}`", c_name);
+            if let Err(e) = self.emit_constant(&c_name, value, Some("CInt"), &comment) {
+                self.warn(line, format!("enumerator `{}`: {}", c_name, e));
+            }
+        }
+    }
+
+    fn resolve_words(&self, words: &[String]) -> Result<FixType, String> {
+        let base_words = words
+            .iter()
+            .map(|w| w.as_str())
+            .filter(|w| C_BASE_TYPE_WORDS.contains(w))
+            .collect::<Vec<_>>();
+        if base_words.is_empty() {
+            // Words other than the last one are regarded as macros for annotations.
+            let name = words.last().ok_or("missing type".to_string())?;
+            if let Some(result) = self.typedefs.get(name) {
+                return result
+                    .clone()
+                    .map_err(|e| format!("`{}` is not supported ({})", name, e));
+            }
+            return standard_typedef(name).ok_or(format!("unknown type `{}`", name));
+        }
+        let has = |w: &str| base_words.contains(&w);
+        let longs = base_words.iter().filter(|w| **w == "long").count();
+        let unsigned = has("unsigned");
+        let name = if has("void") {
+            return Ok(FixType::Unit);
+        } else if has("_Bool") || has("bool") {
+            "U8"
+        } else if has("float") {
+            "CFloat"
+        } else if has("double") {
+            if longs > 0 {
+                return Err("`long double` is not supported".to_string());
+            }
+            "CDouble"
+        } else if has("char") {
+            if unsigned {
+                "CUnsignedChar"
+            } else if has("signed") {
+                "I8"
+            } else {
+                "CChar"
+            }
+        } else if has("short") {
+            if unsigned {
+                "CUnsignedShort"
+            } else {
+                "CShort"
+            }
+        } else if longs == 1 {
+            if unsigned {
+                "CUnsignedLong"
+            } else {
+                "CLong"
+            }
+        } else if longs >= 2 {
+            if unsigned {
+                "CUnsignedLongLong"
+            } else {
+                "CLongLong"
+            }
+        } else if unsigned {
+            "CUnsignedInt"
+        } else {
+            "CInt"
+        };
+        Ok(FixType::number(name))
+    }
+
+    fn resolve_tag(&self, kind: &str, tag_name: &str) -> Result<FixType, String> {
+        let key = format!("{} {}", kind, tag_name);
+        match self.tags.get(&key) {
+            Some(result) => result
+                .clone()
+                .map_err(|e| format!("`{}` is not supported ({})", key, e)),
+            None => match kind {
+                "enum" => Ok(FixType::number("CInt")),
+                "union" => Err(format!("`{}` is a union, which is not supported", key)),
+                _ => Err(format!("`{}` is an incomplete type", key)),
+            },
+        }
+    }
+
+    // Get the type of a declarator which is passed by value.
+    fn resolve_declarator(&self, decl: &Declarator) -> Result<FixType, String> {
+        if decl.is_pointer {
+            return Ok(FixType::number("Ptr"));
+        }
+        match &decl.base {
+            CBaseType::Words(words) => self.resolve_words(words),
+            CBaseType::Tag {
+                kind,
+                name: Some(tag_name),
+                body: None,
+            } => self.resolve_tag(kind, tag_name),
+            CBaseType::Tag { .. } => Err(
+                "struct, union or enum definitions in this position are not supported".to_string(),
+            ),
+        }
+    }
+
+    fn process_function(&mut self, toks: &[Tok], open: usize, line: usize) {
+        let c_name = match open
+            .checked_sub(1)
+            .and_then(|i| toks[i].ident())
+            .filter(|s| !C_BASE_TYPE_WORDS.contains(s))
+        {
+            Some(name) => name.to_string(),
+            None => {
+                self.warn(
+                    line,
+                    format!("`{}`: unsupported declarator", to_c_string(toks)),
+                );
+                return;
+            }
+        };
+        if let Err(e) = self.emit_function(&c_name, toks, open) {
+            self.warn(line, format!("function `{}`: {}", c_name, e));
+        }
+    }
+
+    fn emit_function(&mut self, c_name: &str, toks: &[Tok], open: usize) -> Result<(), String> {
+        let close = find_closing(toks, open)?;
+        if is_punct_at(toks, close + 1, "{") {
+            return Err("function definitions are not supported".to_string());
+        }
+        if close + 1 != toks.len() {
+            return Err("unsupported declarator".to_string());
+        }
+
+        // The return type.
+        let ret_decl = parse_declarator(&toks[..open - 1], false)?;
+        if !ret_decl.rest.is_empty() {
+            return Err("unsupported return type".to_string());
+        }
+        let ret_ty = self
+            .resolve_declarator(&ret_decl)
+            .map_err(|e| format!("the return type: {}", e))?;
+
+        // Parameters.
+        let params = &toks[open + 1..close];
+        let mut param_names = vec![];
+        let mut param_tys = vec![];
+        let is_void = params.len() == 1 && params[0].is_ident("void");
+        if !params.is_empty() && !is_void {
+            for (i, param) in split_top_level(params, ",").into_iter().enumerate() {
+                if param.len() == 1 && param[0].is_punct("...") {
+                    return Err("variadic functions are not supported".to_string());
+                }
+                let decl = parse_declarator(param, true)?;
+                if !decl.rest.is_empty() {
+                    return Err(format!("unsupported declarator of the parameter {}", i));
+                }
+                let param_name = decl
+                    .name
+                    .as_ref()
+                    .map(|name| to_value_name(name))
+                    .filter(|name| !param_names.contains(name))
+                    .unwrap_or(format!("arg{}", i));
+                // An array parameter is a pointer.
+                let ty = if decl.is_array {
+                    FixType::number("Ptr")
+                } else {
+                    self.resolve_declarator(&decl)
+                        .map_err(|e| format!("the parameter `{}`: {}", param_name, e))?
+                };
+                if let FixType::Unit = ty {
+                    return Err(format!("the parameter `{}` has type `void`", param_name));
+                }
+                param_names.push(param_name);
+                param_tys.push(ty);
+            }
+        }
+
+        let name = self.add_value_name(c_name)?;
+        let mut ty = format!("IO {}", ret_ty.name());
+        for param_ty in param_tys.iter().rev() {
+            ty = format!("{} -> {}", param_ty.name(), ty);
+        }
+        let ffi_call = format!(
+            "FFI_CALL[{} {}({}){}]",
+            ret_ty.ffi(),
+            c_name,
+            param_tys
+                .iter()
+                .map(|ty| ty.ffi())
+                .collect::<Vec<_>>()
+                .join(", "),
+            param_names
+                .iter()
+                .map(|name| format!(", {}", name))
+                .collect::<String>()
+        );
+        let io = format!("IO::from_func(|_| {})", ffi_call);
+        let expr = if param_names.is_empty() {
+            io
+        } else {
+            format!("|{}| {}", param_names.join(", "), io)
+        };
+        let comment = format!("Calls `{}`.", to_c_string(toks));
+        self.emit(
+            &comment,
+            &format!("{} : {};\n{} = {};", name, ty, name, expr),
+        );
+        Ok(())
+    }
+}
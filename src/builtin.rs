@@ -2,6 +2,7 @@ use std::sync::Arc;
 
 use crate::error::error_exit;
 use inkwell::module::Linkage;
+use inkwell::types::{BasicMetadataTypeEnum, BasicType};
 use num_bigint::BigInt;
 use serde::{Deserialize, Serialize};
 
@@ -435,6 +436,11 @@ pub fn make_lazy_ty() -> Arc<TypeNode> {
     type_tycon(&tycon(name))
 }
 
+// Get Boxed.
+pub fn make_boxed_ty() -> Arc<TypeNode> {
+    type_tycon(&tycon(FullName::from_strs(&[STD_NAME], BOXED_NAME)))
+}
+
 // Make type `IO`
 pub fn make_io_ty() -> Arc<TypeNode> {
     type_tycon(&tycon(FullName::from_strs(&[STD_NAME], IO_NAME)))
@@ -3942,38 +3948,7 @@ impl InlineLLVMGetReleaseFunctionOfBoxedValueFunctionBody {
         }
 
         // Get function pointer to release function.
-        let release_function_name = format!("release#{}", arg.ty.to_string_normalize());
-        let func = if let Some(func) = gc.module.get_function(&release_function_name) {
-            func
-        } else {
-            // Define release function.
-            let release_function_ty = gc
-                .context
-                .void_type()
-                .fn_type(&[ptr_to_object_type(gc.context).into()], false);
-            let release_function = gc.module.add_function(
-                &release_function_name,
-                release_function_ty,
-                Some(Linkage::Internal),
-            );
-            let bb = gc.context.append_basic_block(release_function, "entry");
-            let _builder_guard = gc.push_builder();
-            gc.builder().position_at_end(bb);
-
-            // Get pointer to object.
-            let obj_ptr = release_function
-                .get_nth_param(0)
-                .unwrap()
-                .into_pointer_value();
-            // Create object.
-            let obj = Object::new(obj_ptr, target_ty.clone());
-            // Release object.
-            gc.release(obj);
-            // Return.
-            gc.builder().build_return(None);
-
-            release_function
-        };
+        let func = get_release_function_of_boxed_type(gc, &target_ty);
         let func_ptr = func.as_global_value().as_pointer_value();
         let func_ptr = gc.cast_pointer(func_ptr, ptr_to_object_type(gc.context));
 
@@ -3993,6 +3968,46 @@ impl InlineLLVMGetReleaseFunctionOfBoxedValueFunctionBody {
     }
 }
 
+// Get the function `void release(void*)` which releases a boxed value of type `target_ty`.
+// The function is defined if it is not defined yet.
+fn get_release_function_of_boxed_type<'c, 'm>(
+    gc: &mut GenerationContext<'c, 'm>,
+    target_ty: &Arc<TypeNode>,
+) -> FunctionValue<'c> {
+    let release_function_name = format!("release#{}", target_ty.to_string_normalize());
+    if let Some(func) = gc.module.get_function(&release_function_name) {
+        return func;
+    }
+
+    // Define release function.
+    let release_function_ty = gc
+        .context
+        .void_type()
+        .fn_type(&[ptr_to_object_type(gc.context).into()], false);
+    let release_function = gc.module.add_function(
+        &release_function_name,
+        release_function_ty,
+        Some(Linkage::Internal),
+    );
+    let bb = gc.context.append_basic_block(release_function, "entry");
+    let _builder_guard = gc.push_builder();
+    gc.builder().position_at_end(bb);
+
+    // Get pointer to object.
+    let obj_ptr = release_function
+        .get_nth_param(0)
+        .unwrap()
+        .into_pointer_value();
+    // Create object.
+    let obj = Object::new(obj_ptr, target_ty.clone());
+    // Release object.
+    gc.release(obj);
+    // Return.
+    gc.builder().build_return(None);
+
+    release_function
+}
+
 pub fn get_release_function_of_boxed_value() -> (Arc<ExprNode>, Arc<Scheme>) {
     const TARGET_TY_NAME: &str = "a";
     const VAR_NAME: &str = "x";
@@ -4129,6 +4144,239 @@ pub fn get_retain_function_of_boxed_value() -> (Arc<ExprNode>, Arc<Scheme>) {
     (expr, scm)
 }
 
+#[derive(Clone, Serialize, Deserialize)]
+pub struct InlineLLVMMakeCCallbackFunctionBody {
+    var_name: String,
+}
+
+impl InlineLLVMMakeCCallbackFunctionBody {
+    pub fn generate<'c, 'm, 'b>(
+        &self,
+        gc: &mut GenerationContext<'c, 'm>,
+        ret_ty: &Arc<TypeNode>,
+        rvo: Option<Object<'c>>,
+        _borrowed_vars: &Vec<FullName>,
+    ) -> Object<'c> {
+        // Get argument
+        let closure = gc.get_var(&FullName::local(&self.var_name)).ptr.get(gc);
+        if !closure.ty.is_closure() {
+            error_exit(&format!(
+                "[Std::FFI::unsafe_make_c_callback] Trying to make a C callback from a value of type `{}`, which is not a closure.",
+                closure.ty.to_string_normalize()
+            ))
+        }
+
+        // Move the closure into a boxed value, whose retained pointer is passed to the callback as the context.
+        let context_ty = type_tyapp(make_boxed_ty(), closure.ty.clone());
+        let context = allocate_obj(
+            context_ty.clone(),
+            &vec![],
+            None,
+            gc,
+            Some("context@unsafe_make_c_callback"),
+        );
+        ObjectFieldType::set_struct_field_norelease(gc, &context, 0, &closure);
+        let context_ptr = context.ptr(gc);
+
+        // Get function pointers to the callback and the release function of the context.
+        let callback = get_c_callback_function(gc, &closure.ty, &context_ty);
+        let release = get_release_function_of_boxed_type(gc, &context_ty);
+
+        let ret = if rvo.is_some() {
+            rvo.unwrap()
+        } else {
+            allocate_obj(
+                ret_ty.clone(),
+                &vec![],
+                None,
+                gc,
+                Some("ret_val@unsafe_make_c_callback"),
+            )
+        };
+        let ptr_ty = gc.context.i8_type().ptr_type(AddressSpace::from(0));
+        let ptrs = [
+            callback.as_global_value().as_pointer_value(),
+            context_ptr,
+            release.as_global_value().as_pointer_value(),
+        ];
+        for (i, ptr) in ptrs.into_iter().enumerate() {
+            let ptr = gc.cast_pointer(ptr, ptr_ty);
+            let field = ObjectFieldType::get_struct_field_noclone(gc, &ret, i as u32);
+            field.store_field_nocap(gc, 0, ptr);
+        }
+        ret
+    }
+}
+
+// Get the C function which calls a closure of type `closure_ty` stored in the context of type `context_ty` (= `Boxed closure_ty`).
+// For a closure of type `A1 -> ... -> An -> B` (or `A1 -> ... -> An -> IO B`), the C function has the signature `B f(A1, ..., An, void* context)`.
+// A closure of type `() -> B` is called by `B f(void* context)`.
+// The function is defined if it is not defined yet.
+fn get_c_callback_function<'c, 'm>(
+    gc: &mut GenerationContext<'c, 'm>,
+    closure_ty: &Arc<TypeNode>,
+    context_ty: &Arc<TypeNode>,
+) -> FunctionValue<'c> {
+    let callback_name = format!("c_callback#{}", closure_ty.to_string_normalize());
+    if let Some(func) = gc.module.get_function(&callback_name) {
+        return func;
+    }
+
+    // Split the type into the parameters and the result.
+    let (mut doms, mut codom) = closure_ty.collect_app_src(usize::MAX);
+    let unit_ty = make_unit_ty();
+    let takes_unit = doms.len() == 1 && doms[0].to_string() == unit_ty.to_string();
+    if takes_unit {
+        doms.clear();
+    }
+    let mut is_io = false;
+    if let Type::TyApp(fun, arg) = &codom.ty {
+        if fun.to_string() == make_io_ty().to_string() {
+            codom = arg.clone();
+            is_io = true;
+        }
+    }
+    let is_void = codom.to_string() == unit_ty.to_string();
+    for ty in doms.iter().chain(if is_void { None } else { Some(&codom) }) {
+        if !is_ffi_scalar_type(ty) {
+            error_exit(&format!(
+                "[Std::FFI::unsafe_make_c_callback] Cannot make a C callback from a closure of type `{}`: `{}` is not a number type nor `Ptr`.",
+                closure_ty.to_string_normalize(),
+                ty.to_string_normalize()
+            ))
+        }
+    }
+    let c_layout = |gc: &GenerationContext<'c, 'm>, ty: &Arc<TypeNode>| {
+        CLayout::of_tycon(
+            &ty.toplevel_tycon().unwrap(),
+            gc.type_env(),
+            &gc.config.c_type_sizes,
+        )
+    };
+    let dom_layouts = doms.iter().map(|ty| c_layout(gc, ty)).collect::<Vec<_>>();
+    let codom_layout = if is_void {
+        None
+    } else {
+        Some(c_layout(gc, &codom))
+    };
+
+    // Declare the function.
+    let ptr_ty = gc.context.i8_type().ptr_type(AddressSpace::from(0));
+    let mut param_tys = dom_layouts
+        .iter()
+        .map(|layout| layout.llvm_type(gc.context).into())
+        .collect::<Vec<BasicMetadataTypeEnum>>();
+    param_tys.push(ptr_ty.into());
+    let func_ty = match &codom_layout {
+        None => gc.context.void_type().fn_type(&param_tys, false),
+        Some(layout) => layout.llvm_type(gc.context).fn_type(&param_tys, false),
+    };
+    let func = gc
+        .module
+        .add_function(&callback_name, func_ty, Some(Linkage::Internal));
+
+    // Implement the function.
+    let _builder_guard = gc.push_builder();
+    let _di_scope_guard = if gc.has_di() {
+        Some(gc.push_debug_scope(None))
+    } else {
+        None
+    };
+    let bb = gc.context.append_basic_block(func, "entry");
+    gc.builder().position_at_end(bb);
+
+    // Create Fix values from arguments.
+    let mut args = vec![];
+    for (i, layout) in dom_layouts.iter().enumerate() {
+        let arg = allocate_obj(doms[i].clone(), &vec![], None, gc, Some("arg@c_callback"));
+        let param = func.get_nth_param(i as u32).unwrap();
+        build_c_value_to_fix(gc, param, &arg, layout);
+        args.push(arg);
+    }
+    if takes_unit {
+        args.push(allocate_obj(
+            unit_ty.clone(),
+            &vec![],
+            None,
+            gc,
+            Some("unit@c_callback"),
+        ));
+    }
+
+    // Get the closure from the context. The context is still owned by the caller, so retain the closure.
+    let context_ptr = func
+        .get_nth_param(dom_layouts.len() as u32)
+        .unwrap()
+        .into_pointer_value();
+    let context_ptr = gc.cast_pointer(context_ptr, ptr_to_object_type(gc.context));
+    let context = Object::new(context_ptr, context_ty.clone());
+    let closure = ObjectFieldType::get_struct_field_noclone(gc, &context, 0);
+    let closure_val = closure.value(gc);
+    let mut fix_value = Object::create_from_value(closure_val, closure_ty.clone(), gc);
+    gc.retain(fix_value.clone());
+
+    // Pass the arguments to the closure.
+    while args.len() > 0 {
+        let arity = fix_value.ty.get_lambda_srcs().len();
+        let rest = args.split_off(arity);
+        fix_value = gc.apply_lambda(fix_value, args, None);
+        args = rest;
+    }
+
+    // If the result is `IO B`, then run it.
+    if is_io {
+        let runner = fix_value.load_field_nocap(gc, 0);
+        let runner_ty = type_fun(unit_ty.clone(), codom.clone());
+        let runner_obj = Object::create_from_value(runner, runner_ty, gc);
+        let unit = allocate_obj(unit_ty, &vec![], None, gc, Some("unit@c_callback"));
+        fix_value = gc.apply_lambda(runner_obj, vec![unit], None);
+    }
+
+    // Return the result.
+    match &codom_layout {
+        None => {
+            gc.builder().build_return(None);
+        }
+        Some(layout) => {
+            let ret = build_fix_to_c_value(gc, &fix_value, layout);
+            gc.builder().build_return(Some(&ret));
+        }
+    }
+
+    func
+}
+
+pub fn make_c_callback_function() -> (Arc<ExprNode>, Arc<Scheme>) {
+    const SRC_TYPE_NAME: &str = "a";
+    const DST_TYPE_NAME: &str = "b";
+    const VAR_NAME: &str = "f";
+    let closure_type = type_fun(
+        type_tyvar_star(SRC_TYPE_NAME),
+        type_tyvar_star(DST_TYPE_NAME),
+    );
+    let ret_type = make_tuple_ty(vec![make_ptr_ty(), make_ptr_ty(), make_ptr_ty()]);
+    let scm = Scheme::generalize(
+        &[],
+        vec![],
+        vec![],
+        type_fun(closure_type, ret_type.clone()),
+    );
+    let expr = expr_abs(
+        vec![var_local(VAR_NAME)],
+        expr_llvm(
+            LLVMGenerator::MakeCCallbackFunctionBody(InlineLLVMMakeCCallbackFunctionBody {
+                var_name: VAR_NAME.to_string(),
+            }),
+            vec![FullName::local(VAR_NAME)],
+            format!("unsafe_make_c_callback({})", VAR_NAME),
+            ret_type,
+            None,
+        ),
+        None,
+    );
+    (expr, scm)
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct InlineLLVMGetBoxedDataPtrFunctionBody {
     var_name: String,
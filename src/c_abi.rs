@@ -0,0 +1,625 @@
+// Passing structs by value to C functions called by `FFI_CALL`.
+//
+// In the signature of `FFI_CALL`, an unboxed struct without type parameters, whose fields are numbers, `Ptr` or such structs, can be used as a parameter type or the return type.
+// Such a struct is laid out as the C struct with the same fields in the same order, where the size of `Ptr` is taken from `CTypeSizes`.
+//
+// LLVM does not implement the C calling convention for aggregates, so we lower struct values as clang does:
+// - x86-64 (System V ABI): a struct of at most 16 bytes is split into "eightbytes", each of which is passed in a general purpose register or a SSE register depending on its fields.
+//   Larger structs, or structs for which not enough registers are left, are passed on the stack (`byval`). Larger structs are returned via a hidden pointer (`sret`).
+// - AArch64 (AAPCS64): a struct consisting of up to four floating point numbers of the same type (homogeneous floating-point aggregate) is passed in floating point registers,
+//   and other structs of at most 16 bytes in general purpose registers. Larger structs are passed by a pointer to a copy, and returned via a hidden pointer.
+use inkwell::{
+    attributes::{Attribute, AttributeLoc},
+    context::Context,
+    targets::TargetMachine,
+    types::{AnyType, BasicMetadataTypeEnum, BasicType, BasicTypeEnum, FunctionType},
+    values::{BasicMetadataValueEnum, BasicValueEnum, CallableValue},
+    AddressSpace,
+};
+
+use super::*;
+use crate::error::error_exit_with_src;
+
+// Number of general purpose registers and SSE registers for passing arguments on x86-64.
+const X86_64_INT_REGS: usize = 6;
+const X86_64_SSE_REGS: usize = 8;
+
+// The layout of a value of a C type.
+#[derive(Clone, PartialEq)]
+pub enum CLayout {
+    // An integer of the given size in bytes.
+    Int(u32),
+    // A floating point number of the given size in bytes.
+    Float(u32),
+    // A pointer of the given size in bytes.
+    Ptr(u32),
+    // A struct, with fields and their offsets.
+    Struct {
+        fields: Vec<(u32, CLayout)>,
+        size: u32,
+        align: u32,
+    },
+}
+
+impl CLayout {
+    // Get the layout of a type used in the signature of `FFI_CALL`.
+    // The type should have been checked by `check_ffi_type`, and should not be `()`.
+    pub fn of_tycon(tc: &Arc<TyCon>, type_env: &TypeEnv, sizes: &CTypeSizes) -> CLayout {
+        if let Some(layout) = scalar_layout(tc, (sizes.size_t / 8) as u32) {
+            return layout;
+        }
+        let ti = type_env.tycons.get(tc).unwrap();
+        let mut fields = vec![];
+        let mut size = 0;
+        let mut align = 1;
+        for field in &ti.fields {
+            let field_tc = field.ty.toplevel_tycon().unwrap();
+            let field = CLayout::of_tycon(&field_tc, type_env, sizes);
+            let offset = align_to(size, field.align());
+            size = offset + field.size();
+            align = align.max(field.align());
+            fields.push((offset, field));
+        }
+        CLayout::Struct {
+            fields,
+            size: align_to(size, align),
+            align,
+        }
+    }
+
+    pub fn size(&self) -> u32 {
+        match self {
+            CLayout::Int(size) | CLayout::Float(size) | CLayout::Ptr(size) => *size,
+            CLayout::Struct { size, .. } => *size,
+        }
+    }
+
+    pub fn align(&self) -> u32 {
+        match self {
+            CLayout::Struct { align, .. } => *align,
+            _ => self.size(),
+        }
+    }
+
+    pub fn is_struct(&self) -> bool {
+        match self {
+            CLayout::Struct { .. } => true,
+            _ => false,
+        }
+    }
+
+    // The LLVM type of the C type.
+    // Since all scalars are aligned to their sizes, LLVM lays out a struct type in the same way as C.
+    pub fn llvm_type<'c>(&self, ctx: &'c Context) -> BasicTypeEnum<'c> {
+        match self {
+            CLayout::Int(size) => ctx.custom_width_int_type(size * 8).into(),
+            CLayout::Float(4) => ctx.f32_type().into(),
+            CLayout::Float(_) => ctx.f64_type().into(),
+            CLayout::Ptr(_) => ctx.i8_type().ptr_type(AddressSpace::from(0)).into(),
+            CLayout::Struct { fields, .. } => {
+                let field_tys = fields
+                    .iter()
+                    .map(|(_, field)| field.llvm_type(ctx))
+                    .collect::<Vec<_>>();
+                ctx.struct_type(&field_tys, false).into()
+            }
+        }
+    }
+
+    // Collect scalars in the layout with their offsets.
+    fn scalars(&self, offset: u32, out: &mut Vec<(u32, CLayout)>) {
+        match self {
+            CLayout::Struct { fields, .. } => {
+                for (field_offset, field) in fields {
+                    field.scalars(offset + field_offset, out);
+                }
+            }
+            _ => out.push((offset, self.clone())),
+        }
+    }
+}
+
+fn align_to(offset: u32, align: u32) -> u32 {
+    (offset + align - 1) / align * align
+}
+
+// Get the layout of a number type or `Ptr`.
+fn scalar_layout(tc: &TyCon, ptr_size: u32) -> Option<CLayout> {
+    if tc.name.namespace != NameSpace::new_str(&[STD_NAME]) {
+        return None;
+    }
+    let layout = match tc.name.name.as_str() {
+        I8_NAME | U8_NAME => CLayout::Int(1),
+        I16_NAME | U16_NAME => CLayout::Int(2),
+        I32_NAME | U32_NAME => CLayout::Int(4),
+        I64_NAME | U64_NAME => CLayout::Int(8),
+        F32_NAME => CLayout::Float(4),
+        F64_NAME => CLayout::Float(8),
+        PTR_NAME => CLayout::Ptr(ptr_size),
+        _ => return None,
+    };
+    Some(layout)
+}
+
+// Is the type constructor one of the types written by keywords (e.g., `CInt` or `Ptr`) in the signature of `FFI_CALL`?
+// Other type constructors are written by users and need namespace resolution.
+pub fn is_ffi_builtin_tycon(tc: &TyCon) -> bool {
+    tc.name == make_tuple_name(0) || scalar_layout(tc, 0).is_some()
+}
+
+// Is the type a number type or `Ptr`?
+pub fn is_ffi_scalar_type(ty: &Arc<TypeNode>) -> bool {
+    match &ty.ty {
+        Type::TyCon(tc) => scalar_layout(tc, 0).is_some(),
+        _ => false,
+    }
+}
+
+// Check that a type can be used in the signature of `FFI_CALL`.
+pub fn check_ffi_type(
+    tc: &Arc<TyCon>,
+    is_return: bool,
+    type_env: &TypeEnv,
+    span: &Option<Span>,
+) -> Result<(), Errors> {
+    if tc.name == make_tuple_name(0) {
+        if is_return {
+            return Ok(());
+        }
+        return Err(Errors::from_msg_srcs(
+            "Cannot use `()` as a parameter type of C function.".to_string(),
+            &[span],
+        ));
+    }
+    if scalar_layout(tc, 0).is_some() {
+        return Ok(());
+    }
+    check_ffi_struct(tc, type_env, &mut vec![]).map_err(|reason| {
+        Errors::from_msg_srcs(
+            format!(
+                "Cannot use type `{}` in the signature of FFI_CALL: {}",
+                tc.to_string(),
+                reason
+            ),
+            &[span],
+        )
+    })
+}
+
+// Check that a type constructor is a struct which can be passed to C functions by value.
+// Returns the reason if it cannot.
+fn check_ffi_struct(
+    tc: &Arc<TyCon>,
+    type_env: &TypeEnv,
+    visiting: &mut Vec<Arc<TyCon>>,
+) -> Result<(), String> {
+    if type_env.aliases.contains_key(tc) {
+        return Err(format!(
+            "`{}` is a type alias. Use the name of the struct instead.",
+            tc.to_string()
+        ));
+    }
+    let ti = match type_env.tycons.get(tc) {
+        Some(ti) if ti.variant == TyConVariant::Struct => ti,
+        _ => {
+            return Err(format!(
+                "`{}` is neither a number type, `Ptr` nor a struct.",
+                tc.to_string()
+            ))
+        }
+    };
+    if !ti.is_unbox {
+        return Err(format!(
+            "`{}` is a boxed struct. Only unboxed structs can be passed to C functions.",
+            tc.to_string()
+        ));
+    }
+    if !ti.tyvars.is_empty() {
+        return Err(format!("`{}` has type parameters.", tc.to_string()));
+    }
+    if ti.fields.is_empty() {
+        return Err(format!("`{}` has no fields.", tc.to_string()));
+    }
+    if visiting.contains(tc) {
+        return Err(format!("`{}` is recursive.", tc.to_string()));
+    }
+    visiting.push(tc.clone());
+    for field in &ti.fields {
+        let field_tc = match &field.ty.ty {
+            Type::TyCon(field_tc) => field_tc,
+            _ => {
+                return Err(format!(
+                    "the type of field `{}` of `{}` is `{}`, which is not a number type, `Ptr` nor a struct.",
+                    field.name,
+                    tc.to_string(),
+                    field.ty.to_string()
+                ))
+            }
+        };
+        if scalar_layout(field_tc, 0).is_some() {
+            continue;
+        }
+        check_ffi_struct(field_tc, type_env, visiting)?;
+    }
+    visiting.pop();
+    Ok(())
+}
+
+// Convert a Fix value to the corresponding C value.
+pub fn build_fix_to_c_value<'c, 'm>(
+    gc: &mut GenerationContext<'c, 'm>,
+    obj: &Object<'c>,
+    layout: &CLayout,
+) -> BasicValueEnum<'c> {
+    match layout {
+        CLayout::Struct { fields, .. } => {
+            let mut val = layout.llvm_type(gc.context).into_struct_type().get_undef();
+            for (i, (_, field_layout)) in fields.iter().enumerate() {
+                let field = ObjectFieldType::get_struct_field_noclone(gc, obj, i as u32);
+                let field_val = build_fix_to_c_value(gc, &field, field_layout);
+                val = gc
+                    .builder()
+                    .build_insert_value(val, field_val, i as u32, "c_struct")
+                    .unwrap()
+                    .into_struct_value();
+            }
+            val.into()
+        }
+        _ => obj.load_field_nocap(gc, 0),
+    }
+}
+
+// Store a C value to a Fix object.
+pub fn build_c_value_to_fix<'c, 'm>(
+    gc: &mut GenerationContext<'c, 'm>,
+    val: BasicValueEnum<'c>,
+    obj: &Object<'c>,
+    layout: &CLayout,
+) {
+    match layout {
+        CLayout::Struct { fields, .. } => {
+            for (i, (_, field_layout)) in fields.iter().enumerate() {
+                let field_val = gc
+                    .builder()
+                    .build_extract_value(val.into_struct_value(), i as u32, "c_struct_field")
+                    .unwrap();
+                let field = ObjectFieldType::get_struct_field_noclone(gc, obj, i as u32);
+                build_c_value_to_fix(gc, field_val, &field, field_layout);
+            }
+        }
+        _ => obj.store_field_nocap(gc, 0, val),
+    }
+}
+
+// How a parameter or the return value of a C function is passed.
+enum AbiValue<'c> {
+    // Passed as a value of the LLVM type of the C type.
+    Direct,
+    // Passed as values of the given LLVM types, which reinterpret the memory of the struct.
+    // For the return value, a struct of these types is returned if there are two.
+    Coerced(Vec<BasicTypeEnum<'c>>),
+    // Passed by a pointer to a copy of the struct. The copy is on the callee's stack if `byval` is true.
+    // For the return value, the caller passes a pointer to the memory for the result as the first argument (`sret`).
+    Indirect { byval: bool },
+}
+
+#[derive(PartialEq)]
+enum Arch {
+    X86_64,
+    AArch64,
+    Other,
+}
+
+fn target_arch() -> Arch {
+    let triple = TargetMachine::get_default_triple();
+    let triple = triple.as_str().to_string_lossy();
+    if triple.starts_with("x86_64") && !triple.contains("windows") {
+        Arch::X86_64
+    } else if triple.starts_with("aarch64") || triple.starts_with("arm64") {
+        Arch::AArch64
+    } else {
+        Arch::Other
+    }
+}
+
+// The lowering of the signature of a C function to LLVM.
+pub struct CFunctionAbi<'c> {
+    ret: Option<(CLayout, AbiValue<'c>)>,
+    params: Vec<(CLayout, AbiValue<'c>)>,
+}
+
+impl<'c> CFunctionAbi<'c> {
+    // `ret` is `None` if the function returns `void`.
+    pub fn new(
+        ret: Option<CLayout>,
+        params: Vec<CLayout>,
+        ctx: &'c Context,
+        span: &Option<Span>,
+    ) -> CFunctionAbi<'c> {
+        let has_struct = ret.iter().chain(params.iter()).any(|l| l.is_struct());
+        let arch = if has_struct {
+            target_arch()
+        } else {
+            Arch::Other
+        };
+        if has_struct && arch == Arch::Other {
+            error_exit_with_src(
+                "Passing structs by value in FFI_CALL is not supported on this target.",
+                span,
+            );
+        }
+        if arch == Arch::AArch64 {
+            let ret = ret.map(|layout| {
+                let abi = aarch64_abi_value(&layout, ctx);
+                (layout, abi)
+            });
+            let params = params
+                .into_iter()
+                .map(|layout| {
+                    let abi = aarch64_abi_value(&layout, ctx);
+                    (layout, abi)
+                })
+                .collect();
+            return CFunctionAbi { ret, params };
+        }
+
+        // x86-64, or a function without structs.
+        let mut int_regs = X86_64_INT_REGS;
+        let mut sse_regs = X86_64_SSE_REGS;
+        let ret = ret.map(|layout| {
+            if !layout.is_struct() {
+                return (layout, AbiValue::Direct);
+            }
+            let abi = match x86_64_eightbytes(&layout, ctx) {
+                Some(tys) => AbiValue::Coerced(tys),
+                None => {
+                    int_regs -= 1; // The pointer for `sret` is passed in a register.
+                    AbiValue::Indirect { byval: false }
+                }
+            };
+            (layout, abi)
+        });
+        let mut lowered_params = vec![];
+        for layout in params {
+            let abi = match &layout {
+                CLayout::Int(_) | CLayout::Ptr(_) => {
+                    int_regs = int_regs.saturating_sub(1);
+                    AbiValue::Direct
+                }
+                CLayout::Float(_) => {
+                    sse_regs = sse_regs.saturating_sub(1);
+                    AbiValue::Direct
+                }
+                CLayout::Struct { .. } => match x86_64_eightbytes(&layout, ctx) {
+                    Some(tys) => {
+                        let needed_int = tys.iter().filter(|ty| ty.is_int_type()).count();
+                        let needed_sse = tys.len() - needed_int;
+                        if needed_int <= int_regs && needed_sse <= sse_regs {
+                            int_regs -= needed_int;
+                            sse_regs -= needed_sse;
+                            AbiValue::Coerced(tys)
+                        } else {
+                            AbiValue::Indirect { byval: true }
+                        }
+                    }
+                    None => AbiValue::Indirect { byval: true },
+                },
+            };
+            lowered_params.push((layout, abi));
+        }
+        CFunctionAbi {
+            ret,
+            params: lowered_params,
+        }
+    }
+
+    pub fn fn_type(&self, ctx: &'c Context) -> FunctionType<'c> {
+        let mut param_tys: Vec<BasicMetadataTypeEnum> = vec![];
+        if let Some((layout, AbiValue::Indirect { .. })) = &self.ret {
+            param_tys.push(ptr_to(layout.llvm_type(ctx)).into());
+        }
+        for (layout, abi) in &self.params {
+            match abi {
+                AbiValue::Direct => param_tys.push(layout.llvm_type(ctx).into()),
+                AbiValue::Coerced(tys) => {
+                    param_tys.extend(tys.iter().map(|ty| BasicMetadataTypeEnum::from(*ty)))
+                }
+                AbiValue::Indirect { .. } => param_tys.push(ptr_to(layout.llvm_type(ctx)).into()),
+            }
+        }
+        match &self.ret {
+            None | Some((_, AbiValue::Indirect { .. })) => {
+                ctx.void_type().fn_type(&param_tys, false)
+            }
+            Some((layout, AbiValue::Direct)) => layout.llvm_type(ctx).fn_type(&param_tys, false),
+            Some((_, AbiValue::Coerced(tys))) => coerced_type(tys, ctx).fn_type(&param_tys, false),
+        }
+    }
+
+    // Add `sret`, `byval` and `align` attributes to parameters.
+    // `add` is called for the declaration of the function and for each call.
+    pub fn add_attributes(&self, ctx: &'c Context, mut add: impl FnMut(AttributeLoc, Attribute)) {
+        let mut idx = 0;
+        if let Some((layout, AbiValue::Indirect { .. })) = &self.ret {
+            let sret = ctx.create_type_attribute(
+                Attribute::get_named_enum_kind_id("sret"),
+                layout.llvm_type(ctx).as_any_type_enum(),
+            );
+            add(AttributeLoc::Param(0), sret);
+            idx += 1;
+        }
+        for (layout, abi) in &self.params {
+            match abi {
+                AbiValue::Direct => idx += 1,
+                AbiValue::Coerced(tys) => idx += tys.len() as u32,
+                AbiValue::Indirect { byval } => {
+                    if *byval {
+                        let byval = ctx.create_type_attribute(
+                            Attribute::get_named_enum_kind_id("byval"),
+                            layout.llvm_type(ctx).as_any_type_enum(),
+                        );
+                        add(AttributeLoc::Param(idx), byval);
+                        let align = ctx.create_enum_attribute(
+                            Attribute::get_named_enum_kind_id("align"),
+                            layout.align().max(8) as u64,
+                        );
+                        add(AttributeLoc::Param(idx), align);
+                    }
+                    idx += 1;
+                }
+            }
+        }
+    }
+
+    // Call a C function declared with `fn_type` and `add_attributes`, or a pointer to a function of type `fn_type`.
+    // `args` are C values of the parameters. Returns the C value returned, or `None` if the function returns `void`.
+    pub fn build_call<'m>(
+        &self,
+        gc: &mut GenerationContext<'c, 'm>,
+        func: CallableValue<'c>,
+        args: Vec<BasicValueEnum<'c>>,
+        name: &str,
+    ) -> Option<BasicValueEnum<'c>> {
+        let mut call_args: Vec<BasicMetadataValueEnum> = vec![];
+        let sret_ptr = match &self.ret {
+            Some((layout, AbiValue::Indirect { .. })) => {
+                let ptr = gc.build_alloca_at_entry(layout.llvm_type(gc.context), "ffi_sret");
+                call_args.push(ptr.into());
+                Some(ptr)
+            }
+            _ => None,
+        };
+        for ((layout, abi), arg) in self.params.iter().zip(args) {
+            match abi {
+                AbiValue::Direct => call_args.push(arg.into()),
+                AbiValue::Coerced(tys) => {
+                    let buf = coercion_buffer(gc);
+                    let ptr = gc.cast_pointer(buf, ptr_to(layout.llvm_type(gc.context)));
+                    gc.builder().build_store(ptr, arg);
+                    let coerced_ty = coerced_type(tys, gc.context);
+                    let ptr = gc.cast_pointer(buf, ptr_to(coerced_ty));
+                    if tys.len() == 1 {
+                        call_args.push(gc.builder().build_load(ptr, "ffi_coerced_arg").into());
+                    } else {
+                        for i in 0..tys.len() {
+                            let elem_ptr = gc
+                                .builder()
+                                .build_struct_gep(ptr, i as u32, "ffi_coerced_arg_ptr")
+                                .unwrap();
+                            let elem = gc.builder().build_load(elem_ptr, "ffi_coerced_arg");
+                            call_args.push(elem.into());
+                        }
+                    }
+                }
+                AbiValue::Indirect { .. } => {
+                    let ptr =
+                        gc.build_alloca_at_entry(layout.llvm_type(gc.context), "ffi_indirect_arg");
+                    gc.builder().build_store(ptr, arg);
+                    call_args.push(ptr.into());
+                }
+            }
+        }
+
+        let call = gc.builder().build_call(func, &call_args, name);
+        self.add_attributes(gc.context, |loc, attr| call.add_attribute(loc, attr));
+
+        match &self.ret {
+            None => None,
+            Some((_, AbiValue::Direct)) => Some(call.try_as_basic_value().left().unwrap()),
+            Some((layout, AbiValue::Coerced(tys))) => {
+                let ret = call.try_as_basic_value().left().unwrap();
+                let buf = coercion_buffer(gc);
+                let ptr = gc.cast_pointer(buf, ptr_to(coerced_type(tys, gc.context)));
+                gc.builder().build_store(ptr, ret);
+                let ptr = gc.cast_pointer(buf, ptr_to(layout.llvm_type(gc.context)));
+                Some(gc.builder().build_load(ptr, "ffi_ret"))
+            }
+            Some((_, AbiValue::Indirect { .. })) => {
+                Some(gc.builder().build_load(sret_ptr.unwrap(), "ffi_ret"))
+            }
+        }
+    }
+}
+
+fn ptr_to<'c>(ty: BasicTypeEnum<'c>) -> inkwell::types::PointerType<'c> {
+    ty.ptr_type(AddressSpace::from(0))
+}
+
+// The type of coerced values passed as a single value.
+fn coerced_type<'c>(tys: &[BasicTypeEnum<'c>], ctx: &'c Context) -> BasicTypeEnum<'c> {
+    if tys.len() == 1 {
+        tys[0]
+    } else {
+        ctx.struct_type(tys, false).into()
+    }
+}
+
+// Allocate a memory to reinterpret a struct as coerced values.
+// Coerced values are at most 16 bytes, and may be larger than the struct.
+fn coercion_buffer<'c, 'm>(
+    gc: &mut GenerationContext<'c, 'm>,
+) -> inkwell::values::PointerValue<'c> {
+    let ty = gc.context.i64_type().array_type(2);
+    gc.build_alloca_at_entry(ty, "ffi_coercion_buf")
+}
+
+// Classify a struct by the System V ABI for x86-64.
+// Returns the types of eightbytes if the struct is passed in registers, or `None` if it is passed in memory.
+fn x86_64_eightbytes<'c>(layout: &CLayout, ctx: &'c Context) -> Option<Vec<BasicTypeEnum<'c>>> {
+    let size = layout.size();
+    if size > 16 {
+        return None;
+    }
+    let mut scalars = vec![];
+    layout.scalars(0, &mut scalars);
+    let mut tys = vec![];
+    for start in (0..size).step_by(8) {
+        let bytes = (size - start).min(8);
+        let in_eightbyte = scalars
+            .iter()
+            .filter(|(offset, _)| start <= *offset && *offset < start + 8)
+            .map(|(_, scalar)| scalar)
+            .collect::<Vec<_>>();
+        let is_sse = in_eightbyte.iter().all(|s| matches!(s, CLayout::Float(_)));
+        let ty: BasicTypeEnum = if !is_sse {
+            ctx.custom_width_int_type(bytes * 8).into()
+        } else if in_eightbyte.contains(&&CLayout::Float(8)) {
+            ctx.f64_type().into()
+        } else if bytes <= 4 {
+            ctx.f32_type().into()
+        } else {
+            ctx.f32_type().vec_type(2).into()
+        };
+        tys.push(ty);
+    }
+    Some(tys)
+}
+
+// Lower a parameter or the return value by AAPCS64.
+fn aarch64_abi_value<'c>(layout: &CLayout, ctx: &'c Context) -> AbiValue<'c> {
+    if !layout.is_struct() {
+        return AbiValue::Direct;
+    }
+    let mut scalars = vec![];
+    layout.scalars(0, &mut scalars);
+    let first = scalars[0].1.clone();
+    let is_hfa = scalars.len() <= 4
+        && matches!(first, CLayout::Float(_))
+        && scalars.iter().all(|(_, s)| *s == first);
+    if is_hfa {
+        let elem_ty = first.llvm_type(ctx);
+        let ty = match elem_ty {
+            BasicTypeEnum::FloatType(ty) => ty.array_type(scalars.len() as u32),
+            _ => unreachable!(),
+        };
+        return AbiValue::Coerced(vec![ty.into()]);
+    }
+    let size = layout.size();
+    if size <= 8 {
+        AbiValue::Coerced(vec![ctx.i64_type().into()])
+    } else if size <= 16 {
+        AbiValue::Coerced(vec![ctx.i64_type().array_type(2).into()])
+    } else {
+        AbiValue::Indirect { byval: false }
+    }
+}
@@ -13,6 +13,7 @@ use crate::ast::name::Name;
 use crate::configuration::Configuration;
 use crate::constants::COMPILATION_UNITS_PATH;
 use crate::split_by_max_size;
+use crate::thin_lto::summary_file_path;
 use crate::InstantiatedSymbol;
 
 pub struct CompileUnit {
@@ -60,7 +61,8 @@ impl CompileUnit {
     }
 
     pub fn is_cached(&self) -> bool {
-        self.object_file_path().exists()
+        // Under `FixOptimizationLevel::ThinLTO`, a compilation unit is cached as a bitcode file and a summary.
+        self.object_file_path().exists() || summary_file_path(&self.unit_hash).exists()
     }
 
     pub fn object_file_path(&self) -> PathBuf {
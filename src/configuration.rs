@@ -1,20 +1,25 @@
-use crate::constants::{CHECK_C_TYPES_EXEC_PATH, CHECK_C_TYPES_PATH, C_TYPES_JSON_PATH};
 use crate::cpu_features::CpuFeatures;
 use crate::error::{exit_if_err, Errors};
+use crate::pgo::PgoProfile;
 use crate::{error::error_exit, DEFAULT_COMPILATION_UNIT_MAX_SIZE};
 use crate::{
-    to_absolute_path, C_CHAR_NAME, C_DOUBLE_NAME, C_FLOAT_NAME, C_INT_NAME, C_LONG_LONG_NAME,
-    C_LONG_NAME, C_SHORT_NAME, C_SIZE_T_NAME, C_UNSIGNED_CHAR_NAME, C_UNSIGNED_INT_NAME,
-    C_UNSIGNED_LONG_LONG_NAME, C_UNSIGNED_LONG_NAME, C_UNSIGNED_SHORT_NAME,
-    OPTIMIZATION_LEVEL_DEFAULT, OPTIMIZATION_LEVEL_MINIMUM, OPTIMIZATION_LEVEL_NONE,
-    OPTIMIZATION_LEVEL_SEPARATED,
+    to_absolute_path, BUILD_PROFILE_DEV, BUILD_PROFILE_RELEASE, C_CHAR_NAME, C_DOUBLE_NAME,
+    C_FLOAT_NAME, C_INT_NAME, C_LONG_LONG_NAME, C_LONG_NAME, C_SHORT_NAME, C_SIZE_T_NAME,
+    C_UNSIGNED_CHAR_NAME, C_UNSIGNED_INT_NAME, C_UNSIGNED_LONG_LONG_NAME, C_UNSIGNED_LONG_NAME,
+    C_UNSIGNED_SHORT_NAME, OPTIMIZATION_LEVEL_DEFAULT, OPTIMIZATION_LEVEL_MINIMUM,
+    OPTIMIZATION_LEVEL_NONE, OPTIMIZATION_LEVEL_SEPARATED, OPTIMIZATION_LEVEL_THIN_LTO,
 };
 use build_time::build_time_utc;
 use inkwell::module::Linkage;
+use inkwell::targets::RelocMode;
 use inkwell::OptimizationLevel;
-use serde::{Deserialize, Serialize};
+use std::os::raw::{c_char, c_double, c_float, c_int, c_long, c_longlong, c_short};
 use std::process::Command;
-use std::{env, path::PathBuf};
+use std::sync::Arc;
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
 
 #[derive(Clone, Copy)]
 pub enum LinkType {
@@ -38,6 +43,7 @@ pub enum SubCommand {
     Build,
     Run,
     Test,
+    Repl,
     Diagnostics,
 }
 
@@ -45,6 +51,9 @@ pub enum SubCommand {
 pub struct Configuration {
     // Source files.
     pub source_files: Vec<PathBuf>,
+    // Directories where source files of imported modules are searched.
+    // If empty, source files are not searched automatically and only `source_files` are loaded.
+    pub source_roots: Vec<PathBuf>,
     // Object files to be linked.
     pub object_files: Vec<PathBuf>,
     // Runs memory sanitizer to detect memory leak and invalid memory reference at early time.
@@ -75,14 +84,55 @@ pub struct Configuration {
     pub max_cu_size: usize,
     // Run program with valgrind. Effective only in `run` mode.
     pub valgrind_tool: ValgrindTool,
+    // Run program on the JIT execution engine instead of building an executable file. Effective only in `run` and `test` mode.
+    pub jit: bool,
+    // Instrument the program to count executions of expressions, and write coverage reports after running it. Effective only in `test` mode.
+    pub coverage: bool,
+    // Also write coverage report in HTML. Effective only when `coverage` is true.
+    pub coverage_html: bool,
+    // Instrument entries and exits of functions to measure their execution times, and write profile reports after running the program. Effective only in `run` mode.
+    pub profile: bool,
+    // Instrument the program to count taken branches and function calls, and write a profile for profile-guided optimization at exit.
+    pub pgo_generate: bool,
+    // Profile used for profile-guided optimization.
+    pub pgo_profile: Option<Arc<PgoProfile>>,
+    // Names of LLVM passes used instead of the default optimization passes. See `llvm_pass_pipeline.rs`.
+    pub llvm_passes: Option<Vec<String>>,
     // Sizes of C types.
     pub c_type_sizes: CTypeSizes,
+    // The C compiler used to compile runtime.c and to link the executable.
+    pub c_compiler: String,
+    // The linker used by the C compiler, e.g., "lld". If `None`, the default linker of the C compiler is used.
+    pub linker: Option<String>,
+    // Extra flags passed to the C compiler when linking the executable.
+    pub link_flags: Vec<String>,
+    // Generate a position independent executable.
+    pub pie: bool,
+    // The name of the build profile selected from `[profile.*]` sections of the project file.
+    pub build_profile: String,
+    // Features of the root project requested by `--features` option.
+    pub features: Vec<String>,
+    // Features declared and enabled in each project, used to evaluate `CFG[...]` attributes.
+    pub feature_sets: Vec<ProjectFeatures>,
+    // Resolve and install dependencies without network access, using only vendored or already installed sources.
+    pub offline: bool,
     // Subcommand of the `fix` command.
     pub subcommand: SubCommand,
     // Extra build commands.
     pub extra_commands: Vec<ExtraCommand>,
 }
 
+// Features of a project.
+#[derive(Clone)]
+pub struct ProjectFeatures {
+    // The absolute path of the project directory. Source files under this directory belong to the project.
+    pub dir: PathBuf,
+    // Features declared in the project file.
+    pub declared: Vec<String>,
+    // Features enabled in the build.
+    pub enabled: Vec<String>,
+}
+
 #[derive(Clone)]
 pub struct ExtraCommand {
     pub work_dir: PathBuf,
@@ -120,6 +170,7 @@ pub enum FixOptimizationLevel {
     None,      // For debugging; skip even tail call optimization.
     Minimum,   // For fast compilation.
     Separated, // Perform almost all of the optimizations except for LLVM-level LTO.
+    ThinLTO,   // Separated, plus LLVM-level optimization across compilation units at link time.
     Default,   // For fast execution.
 }
 
@@ -129,6 +180,7 @@ impl std::fmt::Display for FixOptimizationLevel {
             FixOptimizationLevel::None => write!(f, "{}", OPTIMIZATION_LEVEL_NONE),
             FixOptimizationLevel::Minimum => write!(f, "{}", OPTIMIZATION_LEVEL_MINIMUM),
             FixOptimizationLevel::Separated => write!(f, "{}", OPTIMIZATION_LEVEL_SEPARATED),
+            FixOptimizationLevel::ThinLTO => write!(f, "{}", OPTIMIZATION_LEVEL_THIN_LTO),
             FixOptimizationLevel::Default => write!(f, "{}", OPTIMIZATION_LEVEL_DEFAULT),
         }
     }
@@ -140,6 +192,7 @@ impl FixOptimizationLevel {
             OPTIMIZATION_LEVEL_NONE => Some(FixOptimizationLevel::None),
             OPTIMIZATION_LEVEL_MINIMUM => Some(FixOptimizationLevel::Minimum),
             OPTIMIZATION_LEVEL_SEPARATED => Some(FixOptimizationLevel::Separated),
+            OPTIMIZATION_LEVEL_THIN_LTO => Some(FixOptimizationLevel::ThinLTO),
             OPTIMIZATION_LEVEL_DEFAULT => Some(FixOptimizationLevel::Default),
             _ => None,
         }
@@ -148,9 +201,16 @@ impl FixOptimizationLevel {
 
 impl Configuration {
     pub fn new(subcommand: SubCommand) -> Result<Self, Errors> {
+        // `fix build` uses the release profile by default, and other subcommands use the dev profile.
+        let build_profile = if subcommand == SubCommand::Build {
+            BUILD_PROFILE_RELEASE
+        } else {
+            BUILD_PROFILE_DEV
+        };
         Ok(Configuration {
             subcommand,
             source_files: vec![],
+            source_roots: vec![],
             object_files: vec![],
             sanitize_memory: false,
             fix_opt_level: FixOptimizationLevel::Default, // Fix's optimization level.
@@ -164,13 +224,40 @@ impl Configuration {
             verbose: false,
             max_cu_size: DEFAULT_COMPILATION_UNIT_MAX_SIZE,
             valgrind_tool: ValgrindTool::None,
+            jit: false,
+            coverage: false,
+            coverage_html: false,
+            profile: false,
+            pgo_generate: false,
+            pgo_profile: None,
+            llvm_passes: None,
             library_search_paths: vec![],
-            c_type_sizes: CTypeSizes::load_or_check()?,
+            c_type_sizes: CTypeSizes::of_host(),
+            c_compiler: default_c_compiler(),
+            linker: None,
+            link_flags: vec![],
+            pie: false,
+            build_profile: build_profile.to_string(),
+            features: vec![],
+            feature_sets: vec![],
+            offline: false,
             extra_commands: vec![],
         })
     }
 }
 
+// The C compiler used by default: the value of the environment variable `FIX_CC` or `CC`, or "gcc" if neither is set.
+fn default_c_compiler() -> String {
+    for var in ["FIX_CC", "CC"] {
+        if let Ok(cc) = env::var(var) {
+            if !cc.is_empty() {
+                return cc;
+            }
+        }
+    }
+    "gcc".to_string()
+}
+
 impl Configuration {
     // Configuration for release build.
     pub fn release_mode(subcommand: SubCommand) -> Configuration {
@@ -243,6 +330,16 @@ impl Configuration {
         }
     }
 
+    // Get the features of the project to which the source file belongs.
+    // If the file is under directories of multiple projects (e.g., a dependency installed in the directory of the root project), the innermost one is chosen.
+    pub fn project_features(&self, file_path: &Path) -> Option<&ProjectFeatures> {
+        let file_path = to_absolute_path(file_path);
+        self.feature_sets
+            .iter()
+            .filter(|features| file_path.starts_with(&features.dir))
+            .max_by_key(|features| features.dir.components().count())
+    }
+
     // Set threaded = true, and add ptherad library to linked_libraries.
     pub fn set_threaded(&mut self) {
         self.threaded = true;
@@ -254,6 +351,19 @@ impl Configuration {
         self.sanitize_memory = true;
     }
 
+    // Set the defaults of the built-in build profiles: the "minimum" optimization level for `dev`, and the "default" one for `release`.
+    // Debug info is not generated in both profiles, since it disables tail calls and deep recursions may overflow the stack.
+    // This should be called before reading the project file, so that `[build]` and `[profile.{name}]` sections overwrite the defaults.
+    pub fn set_build_profile_defaults(&mut self) {
+        if self.build_profile == BUILD_PROFILE_DEV {
+            self.debug_info = false;
+            self.fix_opt_level = FixOptimizationLevel::Minimum;
+        } else if self.build_profile == BUILD_PROFILE_RELEASE {
+            self.debug_info = false;
+            self.fix_opt_level = FixOptimizationLevel::Default;
+        }
+    }
+
     pub fn set_debug_info(&mut self) {
         self.debug_info = true;
         self.set_fix_opt_level(FixOptimizationLevel::None);
@@ -268,15 +378,31 @@ impl Configuration {
             FixOptimizationLevel::None => OptimizationLevel::None,
             FixOptimizationLevel::Minimum => OptimizationLevel::Less,
             FixOptimizationLevel::Separated => OptimizationLevel::Default,
+            FixOptimizationLevel::ThinLTO => OptimizationLevel::Default,
             FixOptimizationLevel::Default => OptimizationLevel::Default,
         }
     }
 
+    pub fn perform_inline_optimization(&self) -> bool {
+        // Inlining removes calls of functions measured by the profiler, and duplicates expressions counted by the coverage instrumentation.
+        if self.profile || self.coverage {
+            return false;
+        }
+        match self.fix_opt_level {
+            FixOptimizationLevel::None => false,
+            FixOptimizationLevel::Minimum => false,
+            FixOptimizationLevel::Separated => true,
+            FixOptimizationLevel::ThinLTO => true,
+            FixOptimizationLevel::Default => true,
+        }
+    }
+
     pub fn perform_uncurry_optimization(&self) -> bool {
         match self.fix_opt_level {
             FixOptimizationLevel::None => false,
             FixOptimizationLevel::Minimum => false,
             FixOptimizationLevel::Separated => true,
+            FixOptimizationLevel::ThinLTO => true,
             FixOptimizationLevel::Default => true,
         }
     }
@@ -286,6 +412,27 @@ impl Configuration {
             FixOptimizationLevel::None => false,
             FixOptimizationLevel::Minimum => false,
             FixOptimizationLevel::Separated => true,
+            FixOptimizationLevel::ThinLTO => true,
+            FixOptimizationLevel::Default => true,
+        }
+    }
+
+    pub fn perform_reuse_optimization(&self) -> bool {
+        match self.fix_opt_level {
+            FixOptimizationLevel::None => false,
+            FixOptimizationLevel::Minimum => false,
+            FixOptimizationLevel::Separated => true,
+            FixOptimizationLevel::ThinLTO => true,
+            FixOptimizationLevel::Default => true,
+        }
+    }
+
+    pub fn perform_escape_analysis(&self) -> bool {
+        match self.fix_opt_level {
+            FixOptimizationLevel::None => false,
+            FixOptimizationLevel::Minimum => false,
+            FixOptimizationLevel::Separated => true,
+            FixOptimizationLevel::ThinLTO => true,
             FixOptimizationLevel::Default => true,
         }
     }
@@ -298,12 +445,30 @@ impl Configuration {
         data.push_str(&self.debug_info.to_string());
         data.push_str(&self.threaded.to_string());
         data.push_str(&self.c_type_sizes.to_string());
+        data.push_str(&self.pgo_generate.to_string());
+        if let Some(profile) = &self.pgo_profile {
+            data.push_str(profile.hash());
+        }
+        if let Some(passes) = &self.llvm_passes {
+            data.push_str(&passes.join(","));
+        }
+        data.push_str(&self.pie.to_string());
+        data.push_str(&self.build_profile);
         data.push_str(build_time_utc!()); // Also add build time of the compiler.
         format!("{:x}", md5::compute(data))
     }
 
     pub fn separate_compilation(&self) -> bool {
+        // Coverage counters, profiled functions and PGO counters are numbered through the whole program, so we generate it as a single unit.
         self.fix_opt_level != FixOptimizationLevel::Default
+            && !self.coverage
+            && !self.profile
+            && !self.pgo_generate
+    }
+
+    // Are compilation units written as bitcode files and optimized together at link time?
+    pub fn thin_lto(&self) -> bool {
+        self.fix_opt_level == FixOptimizationLevel::ThinLTO && self.separate_compilation()
     }
 
     pub fn edit_features(&self, features: &mut CpuFeatures) {
@@ -337,6 +502,42 @@ impl Configuration {
         }
     }
 
+    // Relocation model of the generated object files, which should agree with whether the executable is position independent.
+    pub fn reloc_mode(&self) -> RelocMode {
+        if self.pie {
+            RelocMode::PIC
+        } else {
+            RelocMode::Default
+        }
+    }
+
+    // Create a command which runs the C compiler.
+    // The C compiler may be given with arguments, e.g., "ccache gcc" or "gcc -m64", so the first word is the program and the rest are its arguments.
+    pub fn c_compiler_command(&self) -> Result<Command, Errors> {
+        let mut words = self.c_compiler.split_whitespace();
+        let program = words
+            .next()
+            .ok_or_else(|| Errors::from_msg("The C compiler is not specified.".to_string()))?;
+        let mut com = Command::new(program);
+        com.args(words);
+        Ok(com)
+    }
+
+    // The flag to remove unused sections when linking, or `None` if the linker is not known to support it.
+    pub fn gc_sections_flag(&self) -> Option<&'static str> {
+        const KNOWN_LINKERS: [&str; 5] = ["bfd", "gold", "lld", "mold", "ld"];
+        if let Some(linker) = &self.linker {
+            if !KNOWN_LINKERS.contains(&linker.as_str()) {
+                return None;
+            }
+        }
+        if env::consts::OS == "macos" {
+            Some("-Wl,-dead_strip")
+        } else {
+            Some("-Wl,--gc-sections")
+        }
+    }
+
     pub fn run_extra_commands(&self) -> Result<(), Errors> {
         for com in &self.extra_commands {
             com.run()?;
@@ -345,7 +546,7 @@ impl Configuration {
     }
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone)]
 pub struct CTypeSizes {
     pub char: usize,
     pub short: usize,
@@ -390,154 +591,23 @@ impl CTypeSizes {
         .join(", ")
     }
 
-    // Get the size of each C types by compiling and running a C program.
-    fn from_gcc() -> Result<Self, Errors> {
-        // First, create a C source file to check the size of each C types.
-        let c_source = r#"
-#include <stdio.h>
-#include <stddef.h>
-#include <limits.h>
-int main() {
-    printf("%lu\n", sizeof(char) * CHAR_BIT);
-    printf("%lu\n", sizeof(short) * CHAR_BIT);
-    printf("%lu\n", sizeof(int) * CHAR_BIT);
-    printf("%lu\n", sizeof(long) * CHAR_BIT);
-    printf("%lu\n", sizeof(long long) * CHAR_BIT);
-    printf("%lu\n", sizeof(size_t) * CHAR_BIT);
-    printf("%lu\n", sizeof(float) * CHAR_BIT);
-    printf("%lu\n", sizeof(double) * CHAR_BIT);
-    return 0;
-}
-        "#;
-        // Then save it to a temporary file ".fixlang/check_c_types.c".
-        let check_c_types_path = PathBuf::from(CHECK_C_TYPES_PATH);
-
-        // Create parent folders.
-        let parent = check_c_types_path.parent().unwrap();
-        if let Err(e) = std::fs::create_dir_all(parent) {
-            return Err(Errors::from_msg(format!(
-                "Failed to create directory \"{}\": {}",
-                parent.to_string_lossy().to_string(),
-                e
-            )));
-        }
-        if let Err(e) = std::fs::write(&check_c_types_path, c_source) {
-            return Err(Errors::from_msg(format!(
-                "Failed to write file \"{}\": {}",
-                check_c_types_path.to_string_lossy().to_string(),
-                e
-            )));
-        }
-
-        // Run it by gcc.
-        let output = Command::new("gcc")
-            .arg(CHECK_C_TYPES_PATH)
-            .arg("-o")
-            .arg(CHECK_C_TYPES_EXEC_PATH)
-            .output();
-        if let Err(e) = output {
-            return Err(Errors::from_msg(format!(
-                "Failed to compile \"{}\": {}.",
-                CHECK_C_TYPES_PATH, e
-            )));
-        }
-        let output = output.unwrap();
-
-        // Run the program and parse the result to create CTypeSizes.
-        if !output.status.success() {
-            return Err(Errors::from_msg(format!(
-                "Failed to compile \"{}\": \"{}\".",
-                CHECK_C_TYPES_PATH,
-                String::from_utf8_lossy(&output.stderr)
-            )));
-        }
-        let output = Command::new(CHECK_C_TYPES_EXEC_PATH).output();
-        if let Err(e) = output {
-            return Err(Errors::from_msg(format!(
-                "Failed to run \"{}\": {}.",
-                CHECK_C_TYPES_EXEC_PATH, e
-            )));
-        }
-        let output = output.unwrap();
-        if !output.status.success() {
-            return Err(Errors::from_msg(format!(
-                "Failed to run \"{}\": \"{}\".",
-                CHECK_C_TYPES_EXEC_PATH,
-                String::from_utf8_lossy(&output.stderr)
-            )));
-        }
-        let output = String::from_utf8_lossy(&output.stdout);
-        let mut lines = output.lines();
-        let char = lines.next().unwrap().parse().unwrap();
-        let short = lines.next().unwrap().parse().unwrap();
-        let int = lines.next().unwrap().parse().unwrap();
-        let long = lines.next().unwrap().parse().unwrap();
-        let long_long = lines.next().unwrap().parse().unwrap();
-        let size_t = lines.next().unwrap().parse().unwrap();
-        let float = lines.next().unwrap().parse().unwrap();
-        let double = lines.next().unwrap().parse().unwrap();
-        let res = CTypeSizes {
-            char,
-            short,
-            int,
-            long,
-            long_long,
-            size_t,
-            float,
-            double,
-        };
-        Ok(res)
-    }
-
-    fn save_to_file(&self) -> Result<(), Errors> {
-        // Open json file.
-        let path = C_TYPES_JSON_PATH;
-        let file = std::fs::File::create(path);
-        if let Err(e) = file {
-            return Err(Errors::from_msg(format!(
-                "Failed to create \"{}\": {}",
-                path, e
-            )));
+    // Get the size of each C types on the host.
+    //
+    // The compiler runs on the platform it generates code for, so the sizes are the ones of the C types of Rust for the host.
+    // `size_t` is as large as `usize` on all platforms supported by Rust.
+    fn of_host() -> Self {
+        fn bits<T>() -> usize {
+            std::mem::size_of::<T>() * 8
         }
-        let file = file.unwrap();
-
-        // Serialize and write to the file.
-        if let Err(e) = serde_json::to_writer_pretty(file, self) {
-            return Err(Errors::from_msg(format!(
-                "Failed to write \"{}\": {}",
-                path, e
-            )));
-        }
-        Ok(())
-    }
-
-    fn load_file() -> Option<Self> {
-        let path = PathBuf::from(C_TYPES_JSON_PATH);
-        if !path.exists() {
-            return None;
-        }
-        let file = std::fs::File::open(path);
-        if file.is_err() {
-            eprintln!("Failed to open \"{}\".", C_TYPES_JSON_PATH);
-            return None;
-        }
-        let file = file.unwrap();
-        let sizes = serde_json::from_reader(file);
-        if sizes.is_err() {
-            eprintln!("Failed to parse the content of \"{}\".", C_TYPES_JSON_PATH);
-            return None;
-        }
-        Some(sizes.unwrap())
-    }
-
-    fn load_or_check() -> Result<Self, Errors> {
-        match Self::load_file() {
-            Some(sizes) => Ok(sizes),
-            None => {
-                let sizes = Self::from_gcc()?;
-                sizes.save_to_file()?;
-                Ok(sizes)
-            }
+        CTypeSizes {
+            char: bits::<c_char>(),
+            short: bits::<c_short>(),
+            int: bits::<c_int>(),
+            long: bits::<c_long>(),
+            long_long: bits::<c_longlong>(),
+            size_t: bits::<usize>(),
+            float: bits::<c_float>(),
+            double: bits::<c_double>(),
         }
     }
 }
@@ -40,6 +40,7 @@ pub const C_DOUBLE_NAME: &str = "CDouble";
 pub const BOOL_NAME: &str = "Bool";
 pub const ARRAY_NAME: &str = "Array";
 pub const LAZY_NAME: &str = "Lazy";
+pub const BOXED_NAME: &str = "Boxed";
 pub const FUNCTOR_NAME: &str = "Functor";
 pub const DESTRUCTOR_OBJECT_NAME: &str = "Destructor";
 pub const DESTRUCTOR_OBJECT_VALUE_FIELD_IDX: u32 = 0;
@@ -52,6 +53,13 @@ pub const MAIN_FUNCTION_NAME: &str = "main";
 pub const MAIN_MODULE_NAME: &str = "Main";
 pub const TEST_FUNCTION_NAME: &str = "test";
 pub const TEST_MODULE_NAME: &str = "Test";
+pub const REPL_MODULE_NAME: &str = "Repl";
+pub const REPL_VALUE_NAME: &str = "_repl_value";
+pub const REPL_INFER_FUNCTION_NAME: &str = "_repl_infer";
+pub const REPL_SHOW_FUNCTION_NAME: &str = "_repl_show";
+pub const REPL_EXEC_FUNCTION_NAME: &str = "_repl_exec";
+pub const REPL_EXEC_SHOW_FUNCTION_NAME: &str = "_repl_exec_show";
+pub const LSP_INFER_SOURCE_NAME: &str = "lsp_infer";
 
 // Array methods.
 pub const ARRAY_GETTER_FUNCTION_NAME: &str = "@";
@@ -67,6 +75,7 @@ pub const FUNPTR_NAME: &str = "#FunPtr";
 pub const DYNAMIC_OBJECT_NAME: &str = "#DynamicObject";
 pub const ARG_NAME: &str = "#arg";
 pub const EVAL_VAR_NAME: &str = "#eval_var";
+pub const MONADIC_VALUE_NAME: &str = "#monadic_value";
 pub const INSTANCIATED_NAME_SEPARATOR: &str = "#";
 pub const STRUCT_PUNCH_SYMBOL: &str = "#punch_";
 pub const STRUCT_PLUG_IN_SYMBOL: &str = "#plug_in_";
@@ -98,22 +107,31 @@ pub const TYPE_CHECK_CACHE_PATH: &str = ".fixlang/type_check_cache";
 pub const INTERMEDIATE_PATH: &str = ".fixlang/intermediate";
 pub const COMPILATION_UNITS_PATH: &str = ".fixlang/intermediate/units";
 pub const TEMPORARY_SRC_PATH: &str = ".fixlang/tmp_src";
-pub const CHECK_C_TYPES_PATH: &str = ".fixlang/check_c_types.c";
-pub const CHECK_C_TYPES_EXEC_PATH: &str = ".fixlang/check_c_types.out";
-pub const C_TYPES_JSON_PATH: &str = ".fixlang/c_types.json";
 #[allow(unused)]
 pub const COMPILER_TEST_WORKING_PATH: &str = ".fixlang/compiler_test";
 pub const LSP_LOG_FILE_PATH: &str = ".fixlang/lsp.log";
 pub const PROJECT_FILE_PATH: &str = "fixproj.toml";
 pub const LOCK_FILE_PATH: &str = "fixdeps.lock";
 pub const EXTERNAL_PROJ_INSTALL_PATH: &str = ".fixlang/deps";
+pub const VENDOR_PATH: &str = "vendor";
+pub const VENDOR_INFO_FILE_NAME: &str = ".fixvendor.toml";
+pub const COVERAGE_REPORT_PATH: &str = ".fixlang/coverage";
+pub const PROFILE_REPORT_PATH: &str = ".fixlang/profile";
 
 // Optimization levels
 pub const OPTIMIZATION_LEVEL_NONE: &str = "none";
 pub const OPTIMIZATION_LEVEL_MINIMUM: &str = "minimum";
 pub const OPTIMIZATION_LEVEL_SEPARATED: &str = "separated";
+pub const OPTIMIZATION_LEVEL_THIN_LTO: &str = "thin_lto";
 pub const OPTIMIZATION_LEVEL_DEFAULT: &str = "default";
 
+// Build profiles which can be selected even if they are not defined in the project file.
+pub const BUILD_PROFILE_DEV: &str = "dev";
+pub const BUILD_PROFILE_RELEASE: &str = "release";
+
+// The feature enabled unless a dependent project opts out by `default_features = false`.
+pub const FEATURE_DEFAULT: &str = "default";
+
 // Messages
 pub const TRY_FIX_RESOLVE: &str = "Try `fix deps update` to update the lock file.";
 
@@ -159,6 +177,10 @@ pub const DW_ATE_UNSINGED_CHAR: u32 = 8;
 
 // Max number of arguments of function pointer lambda.
 pub const FUNPTR_ARGS_MAX: u32 = 100;
+// Functions whose bodies have at most this number of expression nodes are inlined.
+pub const INLINE_SIZE_THRESHOLD: usize = 16;
+// Max depth of nested inlining.
+pub const INLINE_DEPTH_MAX: usize = 4;
 // The max size of tuples which are defined in any program.
 // Any bigger tuples are defined on demand.
 pub const TUPLE_SIZE_BASE: u32 = 3;
@@ -182,6 +204,16 @@ pub fn pthread_once_init_flag_value<'c>(ctx: &'c Context) -> IntValue<'c> {
 
 pub const GLOBAL_VAR_NAME_ARGC: &str = "fixruntime_argc";
 pub const GLOBAL_VAR_NAME_ARGV: &str = "fixruntime_argv";
+pub const COVERAGE_COUNTER_NAME_PREFIX: &str = "fixcoverage_counter.";
+pub const COVERAGE_COUNTERS_NAME: &str = "fixcoverage_counters";
+pub const PGO_COUNTER_NAME_PREFIX: &str = "fixpgo_counter.";
+pub const PGO_COUNTERS_NAME: &str = "fixpgo_counters";
+pub const PGO_KEYS_NAME: &str = "fixpgo_keys";
+pub const PGO_DEFAULT_PROFILE_PATH: &str = "default.fixprof";
+pub const PROFILE_TOP_N: usize = 20;
+
+// Functions with at most this number of instructions are imported to other compilation units by link-time optimization.
+pub const THIN_LTO_IMPORT_SIZE_THRESHOLD: usize = 100;
 
 pub const DEFAULT_COMPILATION_UNIT_MAX_SIZE: usize = 128;
 pub const DEFAULT_COMPILATION_UNIT_MAX_SIZE_STR: &str = "128";
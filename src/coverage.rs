@@ -0,0 +1,487 @@
+// Coverage instrumentation and generation of coverage reports.
+//
+// When `config.coverage` is true, the generator allocates a counter (a global `i64` variable) for each expression in the user's source files,
+// and for each branch of `if` expressions, and increments it when the code is executed.
+// The `main` function registers all counters to the runtime, which writes their values to a file at exit.
+// After the program finishes, the compiler reads the file and maps the counts back to the source files.
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use inkwell::{module::Linkage, values::BasicValue, AddressSpace, AtomicOrdering, AtomicRMWBinOp};
+use serde::Serialize;
+
+use crate::{
+    configuration::Configuration,
+    constants::{
+        COVERAGE_COUNTERS_NAME, COVERAGE_COUNTER_NAME_PREFIX, COVERAGE_REPORT_PATH,
+        EXTERNAL_PROJ_INSTALL_PATH,
+    },
+    error::Errors,
+    generator::GenerationContext,
+    misc::to_absolute_path,
+    runtime::RUNTIME_COVERAGE_REGISTER,
+    sourcefile::{SourceFile, Span},
+    ExprNode,
+};
+
+// A point of the source code whose executions are counted.
+#[derive(Clone)]
+pub struct CoveragePoint {
+    // The span of the counted expression.
+    pub span: Span,
+    // If this point is a branch of an `if` expression, the span of the `if` expression and the index of the branch (0 for "then", 1 for "else").
+    pub branch: Option<(Span, usize)>,
+}
+
+// The path of the file to which the counts are written by the instrumented program.
+pub fn coverage_counts_path(config: &Configuration) -> PathBuf {
+    to_absolute_path(
+        &config
+            .get_output_executable_file_path()
+            .with_extension("coverage"),
+    )
+}
+
+// Is the span in one of the source files of the project?
+// Codes in the standard library and in dependencies installed to `EXTERNAL_PROJ_INSTALL_PATH` are not instrumented.
+fn is_coverage_target(gc: &GenerationContext, span: &Span) -> bool {
+    let path = &span.input.file_path;
+    gc.config.source_files.contains(path) && !path.starts_with(EXTERNAL_PROJ_INSTALL_PATH)
+}
+
+// Generate code to increment the counter of the expression at `span`.
+pub fn build_coverage_counter<'c, 'm>(
+    gc: &mut GenerationContext<'c, 'm>,
+    span: &Span,
+    branch: Option<(Span, usize)>,
+) {
+    if !is_coverage_target(gc, span) {
+        return;
+    }
+
+    // Add a counter.
+    let i64_type = gc.context.i64_type();
+    let name = format!(
+        "{}{}",
+        COVERAGE_COUNTER_NAME_PREFIX,
+        gc.coverage_points.len()
+    );
+    let counter = gc.module.add_global(i64_type, None, &name);
+    counter.set_initializer(&i64_type.const_zero());
+    counter.set_linkage(Linkage::Internal);
+
+    // Increment the counter.
+    let ptr = counter.as_pointer_value();
+    let one = i64_type.const_int(1, false);
+    if gc.config.threaded {
+        gc.builder()
+            .build_atomicrmw(AtomicRMWBinOp::Add, ptr, one, AtomicOrdering::Monotonic)
+            .unwrap();
+    } else {
+        let count = gc
+            .builder()
+            .build_load(ptr, "coverage_count")
+            .into_int_value();
+        let count = gc.builder().build_int_add(count, one, "coverage_count");
+        gc.builder().build_store(ptr, count);
+    }
+
+    let point = CoveragePoint {
+        span: span.clone(),
+        branch,
+    };
+    gc.coverage_points.push((point, counter));
+}
+
+// Generate code to increment the counter of a branch of an `if` expression.
+// `branch_idx` is 0 for "then" branch and 1 for "else" branch.
+pub fn build_coverage_branch_counter<'c, 'm>(
+    gc: &mut GenerationContext<'c, 'm>,
+    if_src: &Option<Span>,
+    branch_expr: &ExprNode,
+    branch_idx: usize,
+) {
+    let (if_span, branch_span) = match (if_src, &branch_expr.source) {
+        (Some(if_span), Some(branch_span)) => (if_span, branch_span),
+        _ => return,
+    };
+    build_coverage_counter(gc, branch_span, Some((if_span.clone(), branch_idx)));
+}
+
+// Generate code to register all coverage counters to the runtime.
+// This should be called in the `main` function after all counters are created.
+pub fn build_coverage_registration<'c, 'm>(gc: &mut GenerationContext<'c, 'm>) {
+    let i64_ptr_type = gc.context.i64_type().ptr_type(AddressSpace::from(0));
+
+    // Create the array of pointers to counters.
+    let counter_ptrs = gc
+        .coverage_points
+        .iter()
+        .map(|(_, counter)| counter.as_pointer_value())
+        .collect::<Vec<_>>();
+    let counters_ty = i64_ptr_type.array_type(counter_ptrs.len() as u32);
+    let counters = gc
+        .module
+        .add_global(counters_ty, None, COVERAGE_COUNTERS_NAME);
+    counters.set_initializer(&i64_ptr_type.const_array(&counter_ptrs));
+    counters.set_linkage(Linkage::Internal);
+    counters.set_constant(true);
+    let counters = gc.builder().build_pointer_cast(
+        counters.as_pointer_value(),
+        i64_ptr_type.ptr_type(AddressSpace::from(0)),
+        "coverage_counters",
+    );
+
+    // Call the runtime function.
+    let len = gc
+        .context
+        .i64_type()
+        .const_int(counter_ptrs.len() as u64, false);
+    let output_path = coverage_counts_path(&gc.config);
+    let output_path = gc
+        .builder()
+        .build_global_string_ptr(output_path.to_str().unwrap(), "coverage_output_path")
+        .as_pointer_value();
+    gc.call_runtime(
+        RUNTIME_COVERAGE_REGISTER,
+        &[
+            counters.as_basic_value_enum().into(),
+            len.into(),
+            output_path.into(),
+        ],
+    );
+}
+
+// Take the coverage points out of the generation context, dropping the counters.
+pub fn take_coverage_points<'c, 'm>(gc: &mut GenerationContext<'c, 'm>) -> Vec<CoveragePoint> {
+    std::mem::replace(&mut gc.coverage_points, vec![])
+        .into_iter()
+        .map(|(point, _counter)| point)
+        .collect()
+}
+
+// Coverage of a source file.
+#[derive(Default, Serialize)]
+struct FileCoverage {
+    // The path of the source file.
+    path: PathBuf,
+    // Execution counts of lines.
+    lines: Vec<LineCoverage>,
+    // Counts of branches taken.
+    branches: Vec<BranchCoverage>,
+}
+
+#[derive(Serialize)]
+struct LineCoverage {
+    line: usize,
+    count: u64,
+}
+
+#[derive(Serialize)]
+struct BranchCoverage {
+    // The line of the `if` expression.
+    line: usize,
+    // The index of the `if` expression in the line.
+    block: usize,
+    // Count of "then" branch taken.
+    then_count: u64,
+    // Count of "else" branch taken.
+    else_count: u64,
+}
+
+impl FileCoverage {
+    fn lines_hit(&self) -> usize {
+        self.lines.iter().filter(|l| l.count > 0).count()
+    }
+
+    fn branches_hit(&self) -> usize {
+        self.branches
+            .iter()
+            .map(|b| (b.then_count > 0) as usize + (b.else_count > 0) as usize)
+            .sum()
+    }
+}
+
+// Read the counts written by the instrumented program, and aggregate them into the coverage of each source file.
+fn aggregate_coverage(
+    points: &[CoveragePoint],
+    counts_path: &Path,
+) -> Result<Vec<FileCoverage>, Errors> {
+    let counts = fs::read_to_string(counts_path).map_err(|e| {
+        Errors::from_msg(format!(
+            "Failed to read coverage counts from \"{}\": {}. The program may have terminated abnormally.",
+            counts_path.to_string_lossy(),
+            e
+        ))
+    })?;
+    let counts = counts
+        .lines()
+        .map(|line| line.trim().parse::<u64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| Errors::from_msg(format!("Invalid coverage counts: {}", e)))?;
+    if counts.len() != points.len() {
+        return Err(Errors::from_msg(format!(
+            "The number of coverage counts ({}) does not match the number of instrumented points ({}).",
+            counts.len(),
+            points.len()
+        )));
+    }
+
+    // An expression may be instrumented multiple times (e.g., for each instantiation of a generic function), so we sum up the counts by spans.
+    let mut sources: BTreeMap<PathBuf, SourceFile> = BTreeMap::new();
+    let mut exprs: BTreeMap<(PathBuf, usize), u64> = BTreeMap::new();
+    let mut branches: BTreeMap<(PathBuf, usize, usize), [u64; 2]> = BTreeMap::new();
+    for (point, count) in points.iter().zip(counts) {
+        let span = match &point.branch {
+            None => &point.span,
+            Some((if_span, _)) => if_span,
+        };
+        let path = span.input.file_path.clone();
+        if !sources.contains_key(&path) {
+            sources.insert(path.clone(), span.input.clone());
+        }
+        match &point.branch {
+            None => {
+                *exprs.entry((path, span.start)).or_default() += count;
+            }
+            Some((_, idx)) => {
+                branches.entry((path, span.start, span.end)).or_default()[*idx] += count;
+            }
+        }
+    }
+
+    // Map offsets to line numbers.
+    let mut line_starts: BTreeMap<PathBuf, Vec<usize>> = BTreeMap::new();
+    for (path, source) in &sources {
+        let content = source.string()?;
+        let mut starts = vec![0];
+        starts.extend(content.match_indices('\n').map(|(i, _)| i + 1));
+        line_starts.insert(path.clone(), starts);
+    }
+    let line_of = |path: &PathBuf, offset: usize| -> usize {
+        match line_starts[path].binary_search(&offset) {
+            Ok(i) => i + 1,
+            Err(i) => i,
+        }
+    };
+
+    // Execution count of a line is the maximum of the counts of expressions starting at the line.
+    let mut files: BTreeMap<PathBuf, (BTreeMap<usize, u64>, Vec<BranchCoverage>)> = BTreeMap::new();
+    for ((path, start), count) in exprs {
+        let line = line_of(&path, start);
+        let line_count = files.entry(path).or_default().0.entry(line).or_default();
+        *line_count = (*line_count).max(count);
+    }
+    for ((path, start, _end), [then_count, else_count]) in branches {
+        let line = line_of(&path, start);
+        let file = files.entry(path).or_default();
+        let block = file.1.iter().filter(|b| b.line == line).count();
+        file.1.push(BranchCoverage {
+            line,
+            block,
+            then_count,
+            else_count,
+        });
+    }
+
+    Ok(files
+        .into_iter()
+        .map(|(path, (lines, branches))| FileCoverage {
+            path: to_absolute_path(&path),
+            lines: lines
+                .into_iter()
+                .map(|(line, count)| LineCoverage { line, count })
+                .collect(),
+            branches,
+        })
+        .collect())
+}
+
+// Write the coverage in lcov tracefile format.
+fn to_lcov(files: &[FileCoverage]) -> String {
+    let mut lcov = String::new();
+    for file in files {
+        lcov += "TN:\n";
+        lcov += &format!("SF:{}\n", file.path.to_string_lossy());
+        for branch in &file.branches {
+            for (idx, count) in [branch.then_count, branch.else_count].iter().enumerate() {
+                lcov += &format!("BRDA:{},{},{},{}\n", branch.line, branch.block, idx, count);
+            }
+        }
+        lcov += &format!("BRF:{}\n", file.branches.len() * 2);
+        lcov += &format!("BRH:{}\n", file.branches_hit());
+        for line in &file.lines {
+            lcov += &format!("DA:{},{}\n", line.line, line.count);
+        }
+        lcov += &format!("LF:{}\n", file.lines.len());
+        lcov += &format!("LH:{}\n", file.lines_hit());
+        lcov += "end_of_record\n";
+    }
+    lcov
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn percentage(hit: usize, total: usize) -> String {
+    if total == 0 {
+        "-".to_string()
+    } else {
+        format!("{:.1}%", hit as f64 * 100.0 / total as f64)
+    }
+}
+
+// Write the coverage as an HTML page which shows the source files annotated with the execution counts.
+fn to_html(files: &[FileCoverage]) -> Result<String, Errors> {
+    let mut html = String::new();
+    html += "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Fix coverage report</title>\n";
+    html += "<style>\n\
+        body { font-family: sans-serif; }\n\
+        pre { font-family: monospace; }\n\
+        .hit { background-color: #d4f7d4; }\n\
+        .miss { background-color: #f7d4d4; }\n\
+        .partial { background-color: #f7f0c4; }\n\
+        .count { display: inline-block; width: 6em; text-align: right; color: #666; }\n\
+        .lineno { display: inline-block; width: 4em; text-align: right; color: #999; }\n\
+        </style>\n</head>\n<body>\n<h1>Fix coverage report</h1>\n";
+
+    // Summary table.
+    html += "<table>\n<tr><th>File</th><th>Lines</th><th>Branches</th></tr>\n";
+    for (i, file) in files.iter().enumerate() {
+        html += &format!(
+            "<tr><td><a href=\"#file{}\">{}</a></td><td>{} ({}/{})</td><td>{} ({}/{})</td></tr>\n",
+            i,
+            escape_html(&file.path.to_string_lossy()),
+            percentage(file.lines_hit(), file.lines.len()),
+            file.lines_hit(),
+            file.lines.len(),
+            percentage(file.branches_hit(), file.branches.len() * 2),
+            file.branches_hit(),
+            file.branches.len() * 2
+        );
+    }
+    html += "</table>\n";
+
+    // Annotated source files.
+    for (i, file) in files.iter().enumerate() {
+        let content = fs::read_to_string(&file.path).map_err(|e| {
+            Errors::from_msg(format!(
+                "Failed to read \"{}\": {}",
+                file.path.to_string_lossy(),
+                e
+            ))
+        })?;
+        let counts = file
+            .lines
+            .iter()
+            .map(|l| (l.line, l.count))
+            .collect::<BTreeMap<_, _>>();
+        let partial_lines = file
+            .branches
+            .iter()
+            .filter(|b| b.then_count == 0 || b.else_count == 0)
+            .map(|b| b.line)
+            .collect::<Vec<_>>();
+        html += &format!(
+            "<h2 id=\"file{}\">{}</h2>\n<pre>\n",
+            i,
+            escape_html(&file.path.to_string_lossy())
+        );
+        for (line_idx, line) in content.lines().enumerate() {
+            let line_no = line_idx + 1;
+            let (class, count) = match counts.get(&line_no) {
+                None => ("", "".to_string()),
+                Some(0) => ("miss", "0".to_string()),
+                Some(count) if partial_lines.contains(&line_no) => ("partial", count.to_string()),
+                Some(count) => ("hit", count.to_string()),
+            };
+            html += &format!(
+                "<span class=\"{}\"><span class=\"lineno\">{}</span><span class=\"count\">{}</span>  {}</span>\n",
+                class,
+                line_no,
+                count,
+                escape_html(line)
+            );
+        }
+        html += "</pre>\n";
+    }
+    html += "</body>\n</html>\n";
+    Ok(html)
+}
+
+fn write_report_file(path: &Path, content: &str) -> Result<(), Errors> {
+    fs::write(path, content).map_err(|e| {
+        Errors::from_msg(format!(
+            "Failed to write \"{}\": {}",
+            path.to_string_lossy(),
+            e
+        ))
+    })
+}
+
+// Read the counts written by the instrumented program, and write coverage reports to `COVERAGE_REPORT_PATH`.
+pub fn write_coverage_reports(
+    points: &[CoveragePoint],
+    config: &Configuration,
+) -> Result<(), Errors> {
+    let counts_path = coverage_counts_path(config);
+    let files = aggregate_coverage(points, &counts_path);
+    let _ = fs::remove_file(&counts_path);
+    let files = files?;
+
+    let report_dir = PathBuf::from(COVERAGE_REPORT_PATH);
+    fs::create_dir_all(&report_dir).map_err(|e| {
+        Errors::from_msg(format!(
+            "Failed to create directory \"{}\": {}",
+            COVERAGE_REPORT_PATH, e
+        ))
+    })?;
+
+    // lcov tracefile.
+    let lcov_path = report_dir.join("lcov.info");
+    write_report_file(&lcov_path, &to_lcov(&files))?;
+
+    // JSON report.
+    let json_path = report_dir.join("coverage.json");
+    let json = serde_json::to_string_pretty(&files)
+        .map_err(|e| Errors::from_msg(format!("Failed to serialize coverage: {}", e)))?;
+    write_report_file(&json_path, &json)?;
+
+    // HTML report.
+    let html_path = report_dir.join("index.html");
+    if config.coverage_html {
+        write_report_file(&html_path, &to_html(&files)?)?;
+    }
+
+    // Show summary.
+    let lines_total = files.iter().map(|f| f.lines.len()).sum::<usize>();
+    let lines_hit = files.iter().map(|f| f.lines_hit()).sum::<usize>();
+    let branches_total = files.iter().map(|f| f.branches.len() * 2).sum::<usize>();
+    let branches_hit = files.iter().map(|f| f.branches_hit()).sum::<usize>();
+    eprintln!(
+        "Coverage: lines {} ({}/{}), branches {} ({}/{}).",
+        percentage(lines_hit, lines_total),
+        lines_hit,
+        lines_total,
+        percentage(branches_hit, branches_total),
+        branches_hit,
+        branches_total
+    );
+    eprintln!(
+        "Coverage reports are written to \"{}\"{}.",
+        COVERAGE_REPORT_PATH,
+        if config.coverage_html {
+            format!(" (HTML report: \"{}\")", html_path.to_string_lossy())
+        } else {
+            "".to_string()
+        }
+    );
+    Ok(())
+}
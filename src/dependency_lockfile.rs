@@ -1,5 +1,6 @@
 use core::panic;
 use std::{
+    collections::HashMap,
     path::{Path, PathBuf},
     sync::{Arc, Mutex},
 };
@@ -14,31 +15,94 @@ use crate::{
     dependency_resolver::{self, Dependency, Package, PackageName},
     error::Errors,
     project_file::{ProjectFile, ProjectFileDependency, ProjectName},
-    EXTERNAL_PROJ_INSTALL_PATH, LOCK_FILE_PATH, PROJECT_FILE_PATH,
+    EXTERNAL_PROJ_INSTALL_PATH, LOCK_FILE_PATH, PROJECT_FILE_PATH, TRY_FIX_RESOLVE,
+    VENDOR_INFO_FILE_NAME, VENDOR_PATH,
 };
 
-#[derive(Deserialize, Serialize, Default)]
+#[derive(Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct DependecyLockFile {
     pub proj_file_hash: String,
     pub dependencies: Vec<DependencyLockFileEntry>,
+    // The directory where dependencies are vendored. This is not written to the lock file.
+    #[serde(skip, default = "default_vendor_root")]
+    pub vendor_root: PathBuf,
+}
+
+impl Default for DependecyLockFile {
+    fn default() -> Self {
+        DependecyLockFile {
+            proj_file_hash: String::default(),
+            dependencies: vec![],
+            vendor_root: default_vendor_root(),
+        }
+    }
+}
+
+fn default_vendor_root() -> PathBuf {
+    PathBuf::from(VENDOR_PATH)
 }
 
 impl DependecyLockFile {
     // Update configuration by adding source files, linking libraries, ... as required by dependencies.
-    pub fn set_config(&self, config: &mut Configuration) -> Result<(), Errors> {
+    // `root` is the project file of the root project, which determines the features and the optional dependencies to be enabled.
+    pub fn set_config(&self, root: &ProjectFile, config: &mut Configuration) -> Result<(), Errors> {
+        let mut proj_files: HashMap<ProjectName, ProjectFile> = HashMap::new();
         for dep in &self.dependencies {
-            let proj_file = dep.project_file()?;
-            proj_file.set_config(config, true)?;
+            proj_files.insert(dep.name.clone(), dep.project_file(&self.vendor_root)?);
+        }
+
+        // Collect the features requested to each dependency, starting from the root project.
+        // A dependency is used if it is required by a project in use, and its features are the union of those requested by the dependents.
+        let (_, mut queue) =
+            root.resolve_features(&ProjectFile::root_requested_features(config))?;
+        let mut requested: HashMap<ProjectName, Vec<String>> = HashMap::new();
+        while let Some((name, features)) = queue.pop() {
+            let mut updated = !requested.contains_key(&name);
+            let dep_features = requested.entry(name.clone()).or_default();
+            for feature in features {
+                if !dep_features.contains(&feature) {
+                    dep_features.push(feature);
+                    updated = true;
+                }
+            }
+            if !updated {
+                continue;
+            }
+            let proj_file = proj_files.get(&name).ok_or_else(|| {
+                Errors::from_msg(format!(
+                    "Dependent project \"{}\" is not found in the lock file. {}",
+                    name, TRY_FIX_RESOLVE
+                ))
+            })?;
+            let (_, deps) = proj_file.resolve_features(dep_features)?;
+            queue.extend(deps);
+        }
+
+        // Update the configuration by the dependencies in use.
+        for dep in &self.dependencies {
+            if let Some(features) = requested.get(&dep.name) {
+                let proj_file = &proj_files[&dep.name];
+                proj_file.set_config(config, true)?;
+                proj_file.set_config_features(config, features)?;
+            }
         }
         Ok(())
     }
 
     // Create the lock file (on memory, not on file) to satisfy the dependencies of the given project file.
-    pub fn create(proj_file: &ProjectFile) -> Result<DependecyLockFile, Errors> {
+    // - `offline`: If true, git repositories are not accessed, and only vendored or installed versions are considered.
+    // - `vendor_root`: The directory where dependencies are vendored.
+    pub fn create(
+        proj_file: &ProjectFile,
+        offline: bool,
+        vendor_root: &Path,
+    ) -> Result<DependecyLockFile, Errors> {
         // Resolve the dependency.
         let prjs_info = ProjectsInfo {
             projects: Arc::new(Mutex::new(vec![ProjectInfo::from_project_file(proj_file)])),
+            offline,
+            vendor_root: vendor_root.to_path_buf(),
         };
         let packages_retriever = create_package_retriever(prjs_info.clone());
         let versions_retriever = create_version_retriever(prjs_info.clone());
@@ -64,6 +128,7 @@ impl DependecyLockFile {
         let mut lock_file = DependecyLockFile {
             proj_file_hash: proj_file.hash.clone(),
             dependencies: Vec::new(),
+            vendor_root: vendor_root.to_path_buf(),
         };
         for prj in prjs {
             // Exclude the root project.
@@ -91,16 +156,24 @@ impl DependecyLockFile {
                 version: prj.version.to_string(),
                 path: match &prj_info.source {
                     ProjectSource::Local(path_buf) => path_buf.clone(),
-                    ProjectSource::Git(_, _) => {
+                    ProjectSource::Git(_, _, _) => {
                         let dir = PathBuf::from(EXTERNAL_PROJ_INSTALL_PATH);
                         dir.join(format!("{}_{}", prj.name, prj.version.to_string()))
                     }
                 },
                 git: match &prj_info.source {
                     ProjectSource::Local(_) => None,
-                    ProjectSource::Git(url, _) => Some(DependencyLockGit {
+                    ProjectSource::Git(url, reference, _) => Some(DependencyLockGit {
                         repo: url.clone(),
                         rev: ver_info.rev.to_string(),
+                        branch: match reference {
+                            Some(GitReference::Branch(branch)) => Some(branch.clone()),
+                            _ => None,
+                        },
+                        tag: match reference {
+                            Some(GitReference::Tag(tag)) => Some(tag.clone()),
+                            _ => None,
+                        },
                     }),
                 },
             };
@@ -114,25 +187,33 @@ impl DependecyLockFile {
     }
 
     // Install the dependencies.
-    pub fn install(&self) -> Result<(), Errors> {
+    // - `offline`: If true, git repositories are not cloned, and an error is raised if a dependency is neither vendored nor installed.
+    pub fn install(&self, offline: bool) -> Result<(), Errors> {
         for dep in &self.dependencies {
             if let Some(git_info) = &dep.git {
-                let target_rev = git2::Oid::from_str(&git_info.rev).unwrap();
                 // In case the source is a git repository,
+                // If the vendored copy is at the locked revision, use it.
+                if dep.is_vendored(&self.vendor_root) {
+                    dep.check_name_version_match_proj_file(&self.vendor_root)?;
+                    continue;
+                }
+
+                // If the path exists, check that the revision is correct.
+                // If the revision is incorrect, or any git error occurs, remove the directory and clone the repository again.
+                let target_rev = git2::Oid::from_str(&git_info.rev).unwrap();
+                if dep.path.exists() && dep.is_installed_at(target_rev) {
+                    // If the revision is ok, load the project file and validate whether it satisfies the dependency.
+                    dep.check_name_version_match_proj_file(&self.vendor_root)?;
+                    continue; // This dependency is already installed and ok.
+                }
+                if offline {
+                    return Err(Errors::from_msg(format!(
+                        "Dependent project \"{}\" is neither vendored nor installed at revision {}, and cannot be cloned from \"{}\" in offline mode. \
+                        Run `fix deps install` or `fix deps vendor` with network access.",
+                        dep.name, git_info.rev, git_info.repo
+                    )));
+                }
                 if dep.path.exists() {
-                    // If the path exists, check that the revision is correct.
-                    // If the revision is incorrect, or any git error occurs, remove the directory and clone the repository again.
-                    let rev_match = Repository::open(&dep.path).and_then(|repo| {
-                        let head = repo.head()?.target().unwrap_or(git2::Oid::zero());
-                        return Ok(head == target_rev);
-                    });
-                    if let Ok(v) = rev_match {
-                        if v {
-                            // If the revision is ok, load the project file and validate whether it satisfies the dependency.
-                            dep.check_name_version_match_proj_file()?;
-                            continue; // This dependency is already installed and ok.
-                        }
-                    }
                     // If something is wrong, remove the directory.
                     std::fs::remove_dir_all(&dep.path).map_err(|e| {
                         Errors::from_msg(format!(
@@ -162,6 +243,7 @@ impl DependecyLockFile {
                 })?;
 
                 // Checkout the specified revision.
+                // HEAD is also moved to the revision, so that the installed revision can be checked later.
                 let commit = repo
                     .find_commit(target_rev)
                     .map_err(|e| Errors::from_msg(format!("Failed to find commit: {:?}", e)))?;
@@ -169,9 +251,11 @@ impl DependecyLockFile {
                 checkout_opts.force();
                 repo.checkout_tree(&commit.into_object(), Some(&mut checkout_opts))
                     .map_err(|e| Errors::from_msg(format!("Failed to checkout commit: {:?}", e)))?;
+                repo.set_head_detached(target_rev)
+                    .map_err(|e| Errors::from_msg(format!("Failed to checkout commit: {:?}", e)))?;
 
                 // Load the project file and validate whether it satisfies the dependency.
-                dep.check_name_version_match_proj_file()?;
+                dep.check_name_version_match_proj_file(&self.vendor_root)?;
 
                 println!(
                     "Dependent project \"{}\" v{} installed successfully at \"{}\".",
@@ -192,21 +276,90 @@ impl DependecyLockFile {
                 }
 
                 // Load the project file and validate whether it satisfies the dependency.
-                dep.check_name_version_match_proj_file()?;
+                dep.check_name_version_match_proj_file(&self.vendor_root)?;
                 // This dependency is already installed and ok.
             }
         }
         Ok(())
     }
 
+    // Copy the installed sources of dependencies from git repositories to `self.vendor_root`, so that they can be built without network access.
+    // Dependencies on project directories are not copied.
+    // This should be called after `install`.
+    pub fn vendor(&self) -> Result<(), Errors> {
+        for dep in &self.dependencies {
+            let git_info = match &dep.git {
+                Some(git_info) => git_info,
+                None => continue,
+            };
+            if dep.is_vendored(&self.vendor_root) {
+                continue; // Already vendored.
+            }
+            let vendor_path = dep.vendor_path(&self.vendor_root);
+            if vendor_path.exists() {
+                std::fs::remove_dir_all(&vendor_path).map_err(|e| {
+                    Errors::from_msg_err(
+                        &format!(
+                            "Failed to remove the directory \"{}\"",
+                            vendor_path.to_string_lossy()
+                        ),
+                        e,
+                    )
+                })?;
+            }
+            copy_dir_except_git(&dep.path, &vendor_path).map_err(|e| {
+                Errors::from_msg_err(
+                    &format!(
+                        "Failed to copy \"{}\" to \"{}\"",
+                        dep.path.to_string_lossy(),
+                        vendor_path.to_string_lossy()
+                    ),
+                    e,
+                )
+            })?;
+
+            // Record the revision of the vendored copy.
+            let info = toml::to_string(git_info).map_err(|e| {
+                Errors::from_msg_err("Failed to serialize the vendored revision", e)
+            })?;
+            std::fs::write(vendor_path.join(VENDOR_INFO_FILE_NAME), info)
+                .map_err(|e| Errors::from_msg_err("Failed to write the vendored revision", e))?;
+            println!(
+                "Dependent project \"{}\" v{} vendored at \"{}\".",
+                dep.name,
+                dep.version,
+                vendor_path.to_string_lossy()
+            );
+        }
+
+        // Remove vendored copies which are no longer required.
+        if let Ok(entries) = std::fs::read_dir(&self.vendor_root) {
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if self
+                    .dependencies
+                    .iter()
+                    .any(|dep| dep.git.is_some() && dep.name == name)
+                {
+                    continue;
+                }
+                if read_vendor_info(&entry.path()).is_some() {
+                    let _ = std::fs::remove_dir_all(entry.path());
+                }
+            }
+        }
+        Ok(())
+    }
+
     // Update the lock file and install the dependencies.
-    pub fn update_and_install() -> Result<(), Errors> {
+    pub fn update_and_install(offline: bool) -> Result<(), Errors> {
         // Remove lock file.
         let lock_file_path = Path::new(LOCK_FILE_PATH);
         if lock_file_path.exists() {
             std::fs::remove_file(lock_file_path).expect("Failed to remove the lock file.");
         }
-        ProjectFile::read_root_file()?.open_or_create_lock_file_and_isntall()
+        let proj_file = ProjectFile::read_root_file()?;
+        proj_file.open_or_create_lock_file_and_isntall(offline || proj_file.offline())
     }
 }
 
@@ -220,23 +373,52 @@ pub struct DependencyLockFileEntry {
 }
 
 impl DependencyLockFileEntry {
-    pub fn project_file(&self) -> Result<ProjectFile, Errors> {
-        let proj_file_path = self.path.join(PROJECT_FILE_PATH);
+    pub fn project_file(&self, vendor_root: &Path) -> Result<ProjectFile, Errors> {
+        let proj_file_path = self.source_path(vendor_root).join(PROJECT_FILE_PATH);
         ProjectFile::read_file(&proj_file_path)
     }
 
-    pub fn check_name_version_match_proj_file(&self) -> Result<(), Errors> {
-        let proj_file = self.project_file()?;
+    // The directory of the source of this dependency: the vendored copy if it is at the locked revision, or the installed directory otherwise.
+    pub fn source_path(&self, vendor_root: &Path) -> PathBuf {
+        if self.is_vendored(vendor_root) {
+            self.vendor_path(vendor_root)
+        } else {
+            self.path.clone()
+        }
+    }
+
+    // The directory where the vendored copy of this dependency is placed.
+    fn vendor_path(&self, vendor_root: &Path) -> PathBuf {
+        vendor_root.join(&self.name)
+    }
+
+    // Whether the vendored copy of this dependency exists and is at the locked revision.
+    fn is_vendored(&self, vendor_root: &Path) -> bool {
+        match (&self.git, read_vendor_info(&self.vendor_path(vendor_root))) {
+            (Some(git), Some(info)) => info.repo == git.repo && info.rev == git.rev,
+            _ => false,
+        }
+    }
+
+    // Whether the git repository at `self.path` is checked out at the given revision.
+    fn is_installed_at(&self, rev: git2::Oid) -> bool {
+        Repository::open(&self.path)
+            .and_then(|repo| Ok(repo.head()?.target() == Some(rev)))
+            .unwrap_or(false)
+    }
+
+    pub fn check_name_version_match_proj_file(&self, vendor_root: &Path) -> Result<(), Errors> {
+        let proj_file = self.project_file(vendor_root)?;
         if proj_file.general.name != self.name {
             return Err(Errors::from_msg(format!(
                 "Dependent project \"{}\" installed at \"{}\" is not named \"{}\" as required in \"{}\".",
-                self.name, self.path.to_string_lossy().to_string(), self.name, LOCK_FILE_PATH,
+                self.name, self.source_path(vendor_root).to_string_lossy().to_string(), self.name, LOCK_FILE_PATH,
             )));
         }
         if proj_file.general.version() != Version::parse(&self.version).unwrap() {
             return Err(Errors::from_msg(format!(
                 "Dependent project \"{}\" installed at \"{}\" is not at version \"{}\" as required in \"{}\".",
-                self.name, self.path.to_string_lossy().to_string(), self.version, LOCK_FILE_PATH,
+                self.name, self.source_path(vendor_root).to_string_lossy().to_string(), self.version, LOCK_FILE_PATH,
             )));
         }
         Ok(())
@@ -247,7 +429,39 @@ impl DependencyLockFileEntry {
 #[serde(deny_unknown_fields)]
 struct DependencyLockGit {
     repo: String,
+    // The commit hash.
     rev: String,
+    // The branch to which the dependency is pinned, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    branch: Option<String>,
+    // The tag to which the dependency is pinned, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tag: Option<String>,
+}
+
+// Read the revision recorded in a vendored copy of a dependency.
+// Returns None if the directory is not a vendored copy.
+fn read_vendor_info(dir: &Path) -> Option<DependencyLockGit> {
+    let content = std::fs::read_to_string(dir.join(VENDOR_INFO_FILE_NAME)).ok()?;
+    toml::from_str(&content).ok()
+}
+
+// Copy a directory recursively, except `.git` directories.
+fn copy_dir_except_git(src: &Path, dst: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dst)?;
+    for entry in std::fs::read_dir(src)? {
+        let entry = entry?;
+        if entry.file_name() == ".git" {
+            continue;
+        }
+        let dst_path = dst.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir_except_git(&entry.path(), &dst_path)?;
+        } else {
+            std::fs::copy(entry.path(), &dst_path)?;
+        }
+    }
+    Ok(())
 }
 
 // Convert a `ProjectFileDependency` to a `dependency_resolver::Dependency`.
@@ -262,6 +476,8 @@ fn project_file_dep_to_dependency(dep: &ProjectFileDependency) -> Dependency {
 #[derive(Clone, Default)]
 struct ProjectsInfo {
     projects: Arc<Mutex<Vec<ProjectInfo>>>,
+    offline: bool,
+    vendor_root: PathBuf,
 }
 
 struct ProjectInfo {
@@ -269,6 +485,8 @@ struct ProjectInfo {
     source: ProjectSource,
     versions: Option<Vec<VersionInfo>>, // Available versions. None if not retrieved yet.
     proj_files: Vec<ProjectFile>,       // Project files at different versions.
+    offline: bool,                      // If true, git repositories are not accessed.
+    vendor_root: PathBuf,               // The directory where dependencies are vendored.
 }
 
 impl ProjectInfo {
@@ -281,6 +499,8 @@ impl ProjectInfo {
                 rev: git2::Oid::zero(),
             }]),
             proj_files: vec![proj_file.clone()],
+            offline: false,
+            vendor_root: default_vendor_root(),
         }
     }
 
@@ -290,7 +510,13 @@ impl ProjectInfo {
         if self.versions.is_some() {
             return Ok(());
         }
-        self.source.prepre_git_repository()?;
+        if self.offline {
+            if let ProjectSource::Git(url, reference, _) = &self.source {
+                let (url, reference) = (url.clone(), reference.clone());
+                return self.retrieve_offline_versions(&url, &reference);
+            }
+        }
+        self.source.prepre_git_repository(self.offline)?;
         match &mut self.source {
             ProjectSource::Local(proj_path) => {
                 // Read the project file.
@@ -302,14 +528,108 @@ impl ProjectInfo {
                     rev: git2::Oid::zero(),
                 }]);
             }
-            ProjectSource::Git(_url, repo) => {
+            ProjectSource::Git(_url, None, repo) => {
                 let repo = &repo.as_mut().unwrap().1;
                 self.versions = Some(get_versions_from_repo(repo)?);
             }
+            ProjectSource::Git(url, Some(reference), repo) => {
+                // The dependency is pinned to a commit, so the only available version is the one at the commit.
+                let repo = &repo.as_mut().unwrap().1;
+                let rev = reference.resolve(repo).map_err(|e| {
+                    Errors::from_msg_err(
+                        &format!("Failed to find {} in repository `{}`", reference, url),
+                        e,
+                    )
+                })?;
+                let commit = repo
+                    .find_commit(rev)
+                    .map_err(|e| Errors::from_msg_err("Failed to find commit", e))?;
+                let mut checkout_opts = CheckoutBuilder::default();
+                checkout_opts.force();
+                repo.checkout_tree(&commit.into_object(), Some(&mut checkout_opts))
+                    .map_err(|e| Errors::from_msg_err("Failed to checkout commit", e))?;
+                let proj_file =
+                    ProjectFile::read_file(&repo.workdir().unwrap().join(PROJECT_FILE_PATH))?;
+                self.versions = Some(vec![VersionInfo {
+                    version: proj_file.general.version(),
+                    rev,
+                }]);
+            }
         }
         Ok(())
     }
 
+    // Retrieve versions available without network access: the vendored copy, and copies installed at `EXTERNAL_PROJ_INSTALL_PATH`.
+    fn retrieve_offline_versions(
+        &mut self,
+        url: &str,
+        reference: &Option<GitReference>,
+    ) -> Result<(), Errors> {
+        // A dependency pinned to a commit, a branch or a tag can use only the copies at the commit recorded for it.
+        // Branches and tags are looked up in the vendored revision info or in the refs of installed repositories, since remotes cannot be fetched.
+        let mut candidates: Vec<(PathBuf, git2::Oid)> = vec![];
+        let vendor_path = self.vendor_root.join(&self.name);
+        if let Some(info) = read_vendor_info(&vendor_path) {
+            let pinned = match reference {
+                Some(reference) => reference.matches_vendor_info(&info),
+                None => true,
+            };
+            if let (true, true, Ok(rev)) =
+                (info.repo == url, pinned, git2::Oid::from_str(&info.rev))
+            {
+                candidates.push((vendor_path, rev));
+            }
+        }
+        if let Ok(entries) = std::fs::read_dir(EXTERNAL_PROJ_INSTALL_PATH) {
+            for entry in entries.flatten() {
+                let dir_name = entry.file_name().to_string_lossy().to_string();
+                if !dir_name.starts_with(&format!("{}_", self.name)) {
+                    continue;
+                }
+                let rev = Repository::open(entry.path()).and_then(|repo| {
+                    if repo.find_remote("origin")?.url() != Some(url) {
+                        return Ok(None);
+                    }
+                    let head = repo.head()?.target();
+                    let pinned = match reference {
+                        Some(GitReference::Rev(rev)) => {
+                            head.map_or(false, |head| head.to_string().starts_with(rev.as_str()))
+                        }
+                        Some(reference) => reference.resolve(&repo).ok() == head,
+                        None => true,
+                    };
+                    Ok(if pinned { head } else { None })
+                });
+                if let Ok(Some(rev)) = rev {
+                    candidates.push((entry.path(), rev));
+                }
+            }
+        }
+        if candidates.is_empty() {
+            let pinned = match reference {
+                Some(reference) => format!(" at {}", reference),
+                None => String::new(),
+            };
+            return Err(Errors::from_msg(format!(
+                "Project \"{}\" is neither vendored nor installed{}, and cannot be fetched from \"{}\" in offline mode.",
+                self.name, pinned, url
+            )));
+        }
+
+        let mut versions: Vec<VersionInfo> = vec![];
+        for (path, rev) in candidates {
+            let proj_file = ProjectFile::read_file(&path.join(PROJECT_FILE_PATH))?;
+            let version = proj_file.general.version();
+            if versions.iter().any(|info| info.version == version) {
+                continue;
+            }
+            versions.push(VersionInfo { version, rev });
+            self.proj_files.push(proj_file);
+        }
+        self.versions = Some(versions);
+        Ok(())
+    }
+
     // Get the project file at the given version.
     fn get_project_file(&mut self, version: &Version) -> Result<ProjectFile, Errors> {
         // See into the cache field `self.proj_files`.
@@ -348,7 +668,7 @@ impl ProjectInfo {
         }
 
         // If the source is a git repository, checkout the given revision and read the project file.
-        self.source.prepre_git_repository()?;
+        self.source.prepre_git_repository(self.offline)?;
         let repo = self.source.get_git_repository();
         let commit = repo
             .find_commit(rev)
@@ -428,15 +748,62 @@ struct VersionInfo {
 pub enum ProjectSource {
     // Just a fix project created at the given path.
     Local(PathBuf),
-    // Remote git repository. The second field is a temporary directory where the repository is cloned.
-    Git(String, Option<(TempDir, Repository)>),
+    // Remote git repository. The second field is the reference to which the dependency is pinned, and the third field is a temporary directory where the repository is cloned.
+    Git(String, Option<GitReference>, Option<(TempDir, Repository)>),
+}
+
+// A reference in a git repository to which a dependency is pinned.
+#[derive(Clone, PartialEq, Eq)]
+pub enum GitReference {
+    // A commit hash (or any revision understood by git, e.g., a prefix of a commit hash).
+    Rev(String),
+    Branch(String),
+    Tag(String),
+}
+
+impl std::fmt::Display for GitReference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GitReference::Rev(rev) => write!(f, "revision \"{}\"", rev),
+            GitReference::Branch(branch) => write!(f, "branch \"{}\"", branch),
+            GitReference::Tag(tag) => write!(f, "tag \"{}\"", tag),
+        }
+    }
+}
+
+impl GitReference {
+    // Get the commit which the reference points to in a cloned repository.
+    fn resolve(&self, repo: &Repository) -> Result<git2::Oid, git2::Error> {
+        let object = match self {
+            GitReference::Rev(rev) => repo.revparse_single(rev)?,
+            // Branches other than the default one exist only as remote-tracking branches in a cloned repository.
+            GitReference::Branch(branch) => repo
+                .find_reference(&format!("refs/remotes/origin/{}", branch))?
+                .peel(git2::ObjectType::Commit)?,
+            GitReference::Tag(tag) => repo
+                .find_reference(&format!("refs/tags/{}", tag))?
+                .peel(git2::ObjectType::Commit)?,
+        };
+        Ok(object.peel_to_commit()?.id())
+    }
+
+    // Whether a vendored copy was made for a dependency pinned to this reference.
+    fn matches_vendor_info(&self, info: &DependencyLockGit) -> bool {
+        match self {
+            GitReference::Rev(rev) => info.rev.starts_with(rev.as_str()),
+            GitReference::Branch(branch) => info.branch.as_ref() == Some(branch),
+            GitReference::Tag(tag) => info.tag.as_ref() == Some(tag),
+        }
+    }
 }
 
 impl ProjectSource {
     fn equivalent(&self, other: &Self) -> bool {
         match (self, other) {
             (ProjectSource::Local(path1), ProjectSource::Local(path2)) => path1 == path2,
-            (ProjectSource::Git(url1, _), ProjectSource::Git(url2, _)) => url1 == url2,
+            (ProjectSource::Git(url1, ref1, _), ProjectSource::Git(url2, ref2, _)) => {
+                url1 == url2 && ref1 == ref2
+            }
             _ => false,
         }
     }
@@ -447,23 +814,31 @@ impl ProjectSource {
             ProjectSource::Local(_path_buf) => {
                 panic!("Called `get_git_repository` for `ProjectDir`")
             }
-            ProjectSource::Git(_url, repo) => &mut repo.as_mut().unwrap().1,
+            ProjectSource::Git(_url, _reference, repo) => &mut repo.as_mut().unwrap().1,
         }
     }
 
     // Open the git repository and return it.
-    fn prepre_git_repository(&mut self) -> Result<(), Errors> {
+    // - `offline`: If true, an error is raised instead of cloning the repository.
+    fn prepre_git_repository(&mut self, offline: bool) -> Result<(), Errors> {
         match self {
             ProjectSource::Local(_path_buf) => {
                 // Nothing to do.
                 Ok(())
             }
-            ProjectSource::Git(url, repo) => {
+            ProjectSource::Git(url, _reference, repo) => {
                 // If the repository is already opened, nothing to do.
                 if let Some((_, _)) = repo {
                     return Ok(());
                 }
 
+                if offline {
+                    return Err(Errors::from_msg(format!(
+                        "Cannot clone repository `{}` in offline mode.",
+                        url
+                    )));
+                }
+
                 // Create a temporary directory to clone the repository.
                 let temp_dir = tempfile::tempdir().map_err(|e| {
                     Errors::from_msg_err("Failed to create a temporary directory", e)
@@ -501,6 +876,8 @@ fn create_package_retriever(
     projs: ProjectsInfo,
 ) -> Box<dyn Fn(&PackageName, &Version) -> Result<Package, Errors>> {
     Box::new(move |prj_name, ver| {
+        let projs_offline = projs.offline;
+        let projs_vendor_root = projs.vendor_root.clone();
         let mut projs = projs.projects.as_ref().lock().unwrap();
 
         // Find the project.
@@ -532,10 +909,40 @@ fn create_package_retriever(
                 source: dep_src,
                 versions: None,         // Filled on demand.
                 proj_files: Vec::new(), // Filled on demand.
+                offline: projs_offline,
+                vendor_root: projs_vendor_root.clone(),
             };
             projs.push(prj);
         }
 
+        // A dependency pinned to a git reference has only one version.
+        // Check here that it satisfies the version requirement, so that the reason is reported clearly instead of a failure of the resolution.
+        for dep in &proj_file.dependencies {
+            let git = match &dep.git {
+                Some(git) => git,
+                None => continue,
+            };
+            let reference = match git.reference() {
+                Some(reference) => reference,
+                None => continue,
+            };
+            let prj = projs.iter_mut().find(|pkg| pkg.name == dep.name).unwrap();
+            prj.retrieve_versions()?;
+            let ver_info = &prj.versions.as_ref().unwrap()[0];
+            if !dep.version().matches(&ver_info.version) {
+                return Err(Errors::from_msg(format!(
+                    "Project \"{}\" requires version \"{}\" of \"{}\", but {} of \"{}\" (commit {}) is at version \"{}\".",
+                    proj_file.general.name,
+                    dep.version(),
+                    dep.name,
+                    reference,
+                    git.url,
+                    ver_info.rev,
+                    ver_info.version
+                )));
+            }
+        }
+
         Ok(project_file_to_package(&proj_file))
     })
 }
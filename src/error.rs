@@ -1,5 +1,7 @@
 use std::{collections::HashMap, fmt::Display, path::PathBuf};
 
+use serde::{Deserialize, Serialize};
+
 use crate::{misc, sourcefile::Span};
 
 pub struct Errors {
@@ -23,6 +25,10 @@ impl Errors {
         }
     }
 
+    pub fn iter(&self) -> impl Iterator<Item = &Error> {
+        self.errs.iter()
+    }
+
     pub fn append(&mut self, mut other: Errors) {
         self.errs.append(&mut other.errs);
     }
@@ -59,6 +65,24 @@ impl Errors {
         }
     }
 
+    // Set `code` as the code of all errors.
+    pub fn set_code(mut self, code: ErrorCode) -> Errors {
+        for err in &mut self.errs {
+            err.code = Some(code.clone());
+        }
+        self
+    }
+
+    // Set `src` as the source of errors which have no source.
+    pub fn set_default_src(mut self, src: &Span) -> Errors {
+        for err in &mut self.errs {
+            if err.srcs.is_empty() {
+                err.srcs.push(src.clone());
+            }
+        }
+        self
+    }
+
     pub fn from_msg_err<E>(msg: &str, err: E) -> Errors
     where
         E: Display,
@@ -103,17 +127,24 @@ impl Errors {
 pub struct Error {
     pub msg: String,
     pub srcs: Vec<Span>,
+    // The kind of the error, which is used by the language server to offer quick fixes.
+    pub code: Option<ErrorCode>,
 }
 
 impl Error {
     pub fn from_msg(msg: String) -> Error {
-        Error { msg, srcs: vec![] }
+        Error {
+            msg,
+            srcs: vec![],
+            code: None,
+        }
     }
 
     pub fn from_msg_srcs(msg: String, srcs: &[&Option<Span>]) -> Error {
         Error {
             msg,
             srcs: srcs.iter().filter_map(|x| (*x).clone()).collect(),
+            code: None,
         }
     }
 
@@ -129,6 +160,20 @@ impl Error {
     }
 }
 
+// Kinds of errors which can be fixed mechanically.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Eq, Debug)]
+#[serde(tag = "kind")]
+pub enum ErrorCode {
+    // No value of the name is found.
+    UnknownValue { name: String },
+    // No type or trait of the name is found.
+    UnknownTypeOrTrait { name: String },
+    // A global value is defined without its type signature.
+    MissingTypeSignature { name: String },
+    // A field is missing in a struct literal.
+    MissingField { field: String },
+}
+
 pub fn error_exit(msg: &str) -> ! {
     // Default panic hook shows message such as "thread 'main' panicked at " or "note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace".
     // We replace it to empty.
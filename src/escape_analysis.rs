@@ -0,0 +1,182 @@
+use std::sync::Arc;
+
+use super::*;
+
+// Escape analysis.
+// Consider a let-expression `let x = v; e`, where `v` is a lambda expression or a boxed struct literal.
+// If `x` is used in `e` only in ways that cannot keep the object alive after `e` is evaluated, we say that the object does not escape.
+// Such an object can be allocated on the stack of the current function instead of the heap.
+//
+// Currently, the following usages of `x` are considered as non-escaping:
+// - Calling `x` as a function, i.e., `x(a, b, ...)` where `x` does not appear in the arguments.
+// - Getting a field of `x` by a field getter function, i.e., `x.@f`.
+// - Destructuring `x` by a struct pattern, i.e., `let S { f : y } = x`.
+// Other usages, e.g., passing `x` to a function, capturing `x` by a lambda, or returning `x`, are considered as escaping.
+//
+// An object allocated on the stack is marked as global so that it is not retained or released,
+// and its fields are released by the generator after `e` is evaluated.
+// Calls of a closure allocated on the stack are not marked as `tail`, since the callee accesses the captured objects on the stack of the caller.
+
+// Perform escape analysis and set `allocate_on_stack` field of lambda expressions and struct literals.
+pub fn escape_analysis(program: &mut Program) {
+    let mut exprs = vec![];
+    for (name, sym) in &program.instantiated_symbols {
+        let expr = sym.expr.as_ref().unwrap();
+        exprs.push((name.clone(), set_allocate_on_stack(expr, program)));
+    }
+    for (name, expr) in exprs {
+        program.instantiated_symbols.get_mut(&name).unwrap().expr = Some(expr);
+    }
+}
+
+// Set `allocate_on_stack` field of lambda expressions and struct literals bound by let-expressions in `expr`.
+fn set_allocate_on_stack(expr: &Arc<ExprNode>, program: &Program) -> Arc<ExprNode> {
+    match &*expr.expr {
+        Expr::Var(_) => expr.clone(),
+        Expr::LLVM(_) => expr.clone(),
+        Expr::App(fun, args) => {
+            let args = args
+                .iter()
+                .map(|arg| set_allocate_on_stack(arg, program))
+                .collect::<Vec<_>>();
+            let fun = set_allocate_on_stack(fun, program);
+            expr.set_app_func(fun).set_app_args(args)
+        }
+        Expr::Lam(_, body) => expr.set_lam_body(set_allocate_on_stack(body, program)),
+        Expr::Let(pat, bound, value) => {
+            let mut bound = set_allocate_on_stack(bound, program);
+            if let Pattern::Var(var, _) = &pat.pattern {
+                if is_stack_allocatable(&bound, program) && !escapes(&var.name, value, program) {
+                    bound = mark_allocate_on_stack(&bound);
+                }
+            }
+            expr.set_let_bound(bound)
+                .set_let_value(set_allocate_on_stack(value, program))
+        }
+        Expr::If(c, t, e) => expr
+            .set_if_cond(set_allocate_on_stack(c, program))
+            .set_if_then(set_allocate_on_stack(t, program))
+            .set_if_else(set_allocate_on_stack(e, program)),
+        Expr::TyAnno(e, _) => expr.set_tyanno_expr(set_allocate_on_stack(e, program)),
+        Expr::ArrayLit(elems) => {
+            let mut expr = expr.clone();
+            for (i, e) in elems.iter().enumerate() {
+                expr = expr.set_array_lit_elem(set_allocate_on_stack(e, program), i)
+            }
+            expr
+        }
+        Expr::MakeStruct(_, fields) => {
+            let fields = fields.clone();
+            let mut expr = expr.clone();
+            for (field_name, field_expr) in fields {
+                let field_expr = set_allocate_on_stack(&field_expr, program);
+                expr = expr.set_make_struct_field(&field_name, field_expr);
+            }
+            expr
+        }
+        Expr::FFICall(_, _, _, args) => {
+            let mut expr = expr.clone();
+            for (i, e) in args.iter().enumerate() {
+                expr = expr.set_ffi_call_arg(set_allocate_on_stack(e, program), i)
+            }
+            expr
+        }
+    }
+}
+
+// Can the object created by the expression be allocated on the stack if it does not escape?
+fn is_stack_allocatable(expr: &Arc<ExprNode>, program: &Program) -> bool {
+    match &*expr.expr {
+        Expr::TyAnno(e, _) => is_stack_allocatable(e, program),
+        Expr::Lam(_, _) => {
+            // Only closures with captured values have objects allocated on the heap.
+            let ty = expr.ty.as_ref().unwrap();
+            ty.is_closure() && expr.free_vars().iter().any(|v| v.is_local())
+        }
+        Expr::MakeStruct(_, _) => {
+            let ty = expr.ty.as_ref().unwrap();
+            ty.is_box(&program.type_env) && !ty.is_destructor_object()
+        }
+        _ => false,
+    }
+}
+
+// Set `allocate_on_stack` field of a lambda expression or a struct literal.
+fn mark_allocate_on_stack(expr: &Arc<ExprNode>) -> Arc<ExprNode> {
+    match &*expr.expr {
+        Expr::TyAnno(e, _) => expr.set_tyanno_expr(mark_allocate_on_stack(e)),
+        _ => expr.set_allocate_on_stack(true),
+    }
+}
+
+// Does the object bound to the local variable `name` escape in the evaluation of `expr`?
+fn escapes(name: &FullName, expr: &Arc<ExprNode>, program: &Program) -> bool {
+    if !expr.free_vars().contains(name) {
+        return false;
+    }
+    match &*expr.expr {
+        Expr::Var(var) => var.name == *name,
+        Expr::LLVM(_) => true,
+        Expr::App(fun, args) => {
+            let args_escape = args.iter().any(|arg| escapes(name, arg, program));
+            if fun.is_var() && fun.get_var().name == *name {
+                // Calling the closure.
+                return args_escape;
+            }
+            if args.len() == 1
+                && args[0].is_var()
+                && args[0].get_var().name == *name
+                && is_struct_getter(fun, program)
+            {
+                return false;
+            }
+            args_escape || escapes(name, fun, program)
+        }
+        Expr::Lam(_, _) => true,
+        Expr::Let(pat, bound, value) => {
+            let bound_escapes = match &pat.pattern {
+                Pattern::Struct(_, _) if bound.is_var() && bound.get_var().name == *name => false,
+                _ => escapes(name, bound, program),
+            };
+            if bound_escapes {
+                return true;
+            }
+            // If `name` is shadowed by the pattern, the object is not used in `value`.
+            !pat.pattern.vars().contains(name) && escapes(name, value, program)
+        }
+        Expr::If(c, t, e) => {
+            escapes(name, c, program) || escapes(name, t, program) || escapes(name, e, program)
+        }
+        Expr::TyAnno(e, _) => escapes(name, e, program),
+        Expr::ArrayLit(elems) => elems.iter().any(|e| escapes(name, e, program)),
+        Expr::MakeStruct(_, fields) => fields.iter().any(|(_, e)| escapes(name, e, program)),
+        Expr::FFICall(_, _, _, args) => args.iter().any(|e| escapes(name, e, program)),
+    }
+}
+
+// Is the expression a global field getter function of a struct?
+fn is_struct_getter(fun: &Arc<ExprNode>, program: &Program) -> bool {
+    if !fun.is_var() {
+        return false;
+    }
+    let fun_name = &fun.get_var().name;
+    if !fun_name.is_global() {
+        return false;
+    }
+    let sym = match program.instantiated_symbols.get(fun_name) {
+        Some(sym) => sym,
+        None => return false,
+    };
+    let lam = sym.expr.as_ref().unwrap();
+    if !lam.is_lam() {
+        return false;
+    }
+    let body = lam.get_lam_body();
+    if !body.is_llvm() {
+        return false;
+    }
+    match &body.get_llvm().generator {
+        LLVMGenerator::StructGetBody(_) => true,
+        _ => false,
+    }
+}
@@ -5,8 +5,6 @@
 use std::{cell::RefCell, env, sync::Arc};
 
 use crate::error::error_exit;
-use crate::error::error_exit_with_src;
-use either::Either;
 use inkwell::{
     basic_block::BasicBlock,
     debug_info::{
@@ -15,8 +13,8 @@ use inkwell::{
     intrinsics::Intrinsic,
     module::Linkage,
     targets::{TargetData, TargetMachine},
-    types::{AnyType, BasicMetadataTypeEnum, BasicType},
-    values::{BasicMetadataValueEnum, CallSiteValue, StructValue},
+    types::{AnyType, BasicType},
+    values::{BasicMetadataValueEnum, CallSiteValue, GlobalValue, StructValue},
 };
 
 use super::*;
@@ -25,6 +23,8 @@ use super::*;
 pub struct Variable<'c> {
     pub ptr: VarValue<'c>,
     used_later: u32,
+    // Whether the object is allocated on the stack of the current function by escape analysis.
+    on_stack: bool,
 }
 
 #[derive(Clone)]
@@ -233,7 +233,7 @@ pub struct Scope<'c> {
 }
 
 impl<'c> Scope<'c> {
-    fn push_local(self: &mut Self, var: &FullName, obj: &Object<'c>) {
+    fn push_local(self: &mut Self, var: &FullName, obj: &Object<'c>, on_stack: bool) {
         // TODO: add assertion that var is local (or change var to Name).
         if !self.data.contains_key(var) {
             self.data.insert(var.clone(), Default::default());
@@ -241,6 +241,7 @@ impl<'c> Scope<'c> {
         self.data.get_mut(var).unwrap().push(Variable {
             ptr: VarValue::Local(obj.clone()),
             used_later: 0,
+            on_stack,
         });
     }
 
@@ -290,6 +291,19 @@ fn add_i32_to_u32(u: u32, i: i32) -> u32 {
     }
 }
 
+// Memory of a unique boxed object which has been destructured, and can be reused for another object of the same size.
+// Currently, tokens are made only by struct patterns of `let` and consumed only by struct literals.
+// Unions are not handled: union patterns are disabled in the grammar, and union values are made and destructured in builtin functions (`some`, `as_some` etc.) which are not inlined, so no token is visible there.
+// `if` is the only branching expression, so `eval_if` is the only place which has to merge tokens of branches.
+pub struct ReuseToken<'c> {
+    // Size of the memory.
+    size: u64,
+    // Pointer to the memory. Null if the destructured object was shared.
+    ptr: PointerValue<'c>,
+    // Whether the memory is already reused (or freed) in the current control flow.
+    used: bool,
+}
+
 pub struct GenerationContext<'c, 'm> {
     pub context: &'c Context,
     pub module: &'m Module<'c>,
@@ -302,6 +316,16 @@ pub struct GenerationContext<'c, 'm> {
     type_env: TypeEnv,
     pub target_data: TargetData,
     pub config: Configuration,
+    // Points of coverage instrumentation and their counters, in the order of counter indices.
+    pub coverage_points: Vec<(CoveragePoint, GlobalValue<'c>)>,
+    // Functions instrumented by the profiler, in the order of site indices.
+    pub profile_sites: Vec<ProfileSite>,
+    // Keys of counters of PGO instrumentation and the counters, in the order of counter indices.
+    pub pgo_counters: Vec<(String, GlobalValue<'c>)>,
+    // The generic name of the symbol being implemented. This is used to name lambdas in profile reports.
+    current_symbol: Option<FullName>,
+    // Reuse tokens available in the current control flow. `None` is a barrier which separates tokens of different functions.
+    reuse_tokens: Vec<Option<ReuseToken<'c>>>,
 }
 
 pub struct PopBuilderGuard<'c> {
@@ -423,6 +447,11 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
             type_env,
             target_data: target_data,
             config,
+            coverage_points: vec![],
+            profile_sites: vec![],
+            pgo_counters: vec![],
+            current_symbol: None,
+            reuse_tokens: vec![],
         };
         ret
     }
@@ -495,6 +524,7 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
                 Variable {
                     ptr: VarValue::Global(function, ty),
                     used_later,
+                    on_stack: false,
                 },
             );
         }
@@ -562,11 +592,16 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
 
     // Push scope.
     fn scope_push(self: &mut Self, var: &FullName, obj: &Object<'c>) {
+        self.scope_push_with(var, obj, false)
+    }
+
+    // Push a local variable, which may hold an object allocated on the stack.
+    fn scope_push_with(self: &mut Self, var: &FullName, obj: &Object<'c>, on_stack: bool) {
         self.scope
             .borrow_mut()
             .last_mut()
             .unwrap()
-            .push_local(var, obj)
+            .push_local(var, obj, on_stack)
     }
 
     // Pop scope.
@@ -848,6 +883,18 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
         fun: Object<'c>,
         args: Vec<Object<'c>>,
         rvo: Option<Object<'c>>,
+    ) -> Object<'c> {
+        self.apply_lambda_with(fun, args, rvo, true)
+    }
+
+    // Apply objects to a lambda.
+    // - `tail`: If false, the call is not marked as `tail`, e.g., because the closure is allocated on the stack of the caller.
+    fn apply_lambda_with(
+        &mut self,
+        fun: Object<'c>,
+        args: Vec<Object<'c>>,
+        rvo: Option<Object<'c>>,
+        tail: bool,
     ) -> Object<'c> {
         let src_tys = fun.ty.get_lambda_srcs();
         let ret_ty = fun.ty.get_lambda_dst();
@@ -898,7 +945,7 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
             call_args.push(rvo_ptr.into());
 
             let ret = self.builder().build_call(func, &call_args, "call_lambda");
-            ret.set_tail_call(!self.has_di());
+            ret.set_tail_call(tail && !self.has_di());
             rvo
         } else {
             // If return type is boxed,
@@ -914,7 +961,7 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
             }
 
             let ret = self.builder().build_call(func, &call_args, "call_lambda");
-            ret.set_tail_call(!self.has_di());
+            ret.set_tail_call(tail && !self.has_di());
             let ret = ret.try_as_basic_value().unwrap_left();
             Object::create_from_value(ret, ret_ty, self)
         }
@@ -1237,19 +1284,36 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
             self.push_debug_location(expr.source.clone())
         };
 
+        // Count the execution of this expression.
+        // Lambda expressions are skipped since evaluating them does not execute their bodies, and type annotations are counted by their inner expressions.
+        if self.config.coverage {
+            match &*expr.expr {
+                Expr::Lam(_, _) | Expr::TyAnno(_, _) => {}
+                _ => {
+                    if let Some(span) = &expr.source {
+                        build_coverage_counter(self, span, None);
+                    }
+                }
+            }
+        }
+
         let mut ret = match &*expr.expr {
             Expr::Var(var) => self.eval_var(var.clone(), rvo),
             Expr::LLVM(lit) => self.eval_llvm(lit.clone(), expr.ty.clone().unwrap().clone(), rvo),
             Expr::App(lambda, args) => self.eval_app(lambda.clone(), args.clone(), rvo),
             Expr::Lam(_, _) => self.eval_lam(expr.clone(), rvo),
             Expr::Let(pat, bound, expr) => self.eval_let(pat, bound.clone(), expr.clone(), rvo),
-            Expr::If(cond_expr, then_expr, else_expr) => {
-                self.eval_if(cond_expr.clone(), then_expr.clone(), else_expr.clone(), rvo)
-            }
+            Expr::If(cond_expr, then_expr, else_expr) => self.eval_if(
+                expr.source.clone(),
+                cond_expr.clone(),
+                then_expr.clone(),
+                else_expr.clone(),
+                rvo,
+            ),
             Expr::TyAnno(e, _) => self.eval_expr(e.clone(), rvo),
             Expr::MakeStruct(_, fields) => {
                 let struct_ty = expr.ty.clone().unwrap();
-                self.eval_make_struct(fields.clone(), struct_ty, rvo)
+                self.eval_make_struct(fields.clone(), struct_ty, expr.allocate_on_stack, rvo)
             }
             Expr::ArrayLit(elems) => self.eval_array_lit(elems, expr.ty.clone().unwrap(), rvo),
             Expr::FFICall(fun_name, ret_ty, param_tys, args) => {
@@ -1294,6 +1358,15 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
             self.scope_lock_as_used_later(arg.free_vars());
         }
 
+        // A closure allocated on the stack passes the pointer to its captured objects, which is an alloca of this function, to the callee.
+        // Similarly, a struct allocated on the stack can be passed to a getter as an argument.
+        // Such a call cannot be marked as `tail`, since a tail call is assumed not to access allocas of the caller.
+        let is_stack_var = |expr: &Arc<ExprNode>| match &*expr.expr {
+            Expr::Var(var) => var.name.is_local() && self.get_var(&var.name).on_stack,
+            _ => false,
+        };
+        let tail = !is_stack_var(&fun) && !args.iter().any(is_stack_var);
+
         // Evaluate the function object.
         let fun_obj = self.eval_expr(fun, None);
 
@@ -1317,7 +1390,7 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
         }
 
         // Call the function.
-        self.apply_lambda(fun_obj, arg_objs, rvo)
+        self.apply_lambda_with(fun_obj, arg_objs, rvo, tail)
     }
 
     // Evaluate literal
@@ -1451,11 +1524,13 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
     }
 
     // Implement function of lambda expression
+    // If the lambda is the value of a global function, give its generic name as `global_name`.
     pub fn implement_lambda_function(
         &mut self,
         lam: Arc<ExprNode>,
         lam_fn: FunctionValue<'c>,
         cap_vars: Option<Vec<(FullName, Arc<TypeNode>)>>,
+        global_name: Option<&FullName>,
     ) {
         let lam_ty = lam.ty.clone().unwrap();
         let (args, body) = lam.destructure_lam();
@@ -1482,9 +1557,31 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
             None
         };
 
+        // Notify the profiler that this function is entered.
+        if self.config.profile {
+            let site = ProfileSite {
+                name: global_name.cloned().or(self.current_symbol.clone()),
+                is_lambda: global_name.is_none(),
+                ty: lam_ty.to_string(),
+                span: lam.source.clone(),
+            };
+            build_profile_enter(self, site);
+        }
+
+        // Count calls of global functions for profile-guided optimization.
+        if global_name.is_some() {
+            if self.config.pgo_generate {
+                build_pgo_function_counter(self, lam_fn);
+            }
+            set_pgo_function_entry_count(self, lam_fn);
+        }
+
         // Create new scope
         let _scope_guard = self.push_scope();
 
+        // Reuse tokens of the outer function cannot be used in this function.
+        self.reuse_tokens.push(None);
+
         // Push argments on scope.
         let mut arg_objs = vec![];
         for ((i, arg), arg_ty) in args.iter().enumerate().zip(lam_ty.get_lambda_srcs().iter()) {
@@ -1559,6 +1656,13 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
 
         // Calculate body.
         let val = self.eval_expr(body.clone(), rvo.clone());
+        let barrier = self.reuse_tokens.pop().unwrap();
+        assert!(barrier.is_none());
+
+        // Notify the profiler that this function exits.
+        if self.config.profile {
+            build_profile_exit(self);
+        }
 
         // Return lambda function.
         if rvo.is_some() {
@@ -1572,6 +1676,11 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
     fn eval_lam(&mut self, lam: Arc<ExprNode>, rvo: Option<Object<'c>>) -> Object<'c> {
         let (args, body) = lam.destructure_lam();
         let lam_ty = lam.ty.clone().unwrap();
+        let allocation = if lam.allocate_on_stack {
+            ObjectAllocation::Stack
+        } else {
+            ObjectAllocation::Heap
+        };
 
         // Calculate captured variables.
         let cap_vars = self.calculate_captured_vars_of_lambda(lam.clone());
@@ -1582,7 +1691,7 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
 
         // Define lambda function
         let lam_fn = self.declare_lambda_function(lam.clone(), None);
-        self.implement_lambda_function(lam, lam_fn, Some(cap_vars.clone()));
+        self.implement_lambda_function(lam, lam_fn, Some(cap_vars.clone()), None);
 
         // Allocate lambda
         let name = expr_abs(args.clone(), body.clone(), None).expr.to_string();
@@ -1611,10 +1720,11 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
 
                 // Allocate dynamic object to store captured objects.
                 let dynamic_obj_ty = make_dynamic_object_ty();
-                let cap_obj = allocate_obj(
+                let cap_obj = allocate_obj_with(
                     dynamic_obj_ty.clone(),
                     &cap_tys,
                     None,
+                    allocation,
                     self,
                     Some(&format!("captured_objects_of_{}", name)),
                 );
@@ -1650,6 +1760,93 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
         lam
     }
 
+    // Take an unused reuse token of the given size available in the current function, and mark it as used.
+    // The returned pointer may be null at runtime.
+    fn take_reuse_token(&mut self, size: u64) -> Option<PointerValue<'c>> {
+        for token in self.reuse_tokens.iter_mut().rev() {
+            match token {
+                None => return None, // Reached the barrier.
+                Some(token) => {
+                    if !token.used && token.size == size {
+                        token.used = true;
+                        return Some(token.ptr);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    // Get flags whether or not reuse tokens available in the current function are used.
+    fn reuse_tokens_used(&self) -> Vec<bool> {
+        let mut used = vec![];
+        for token in self.reuse_tokens.iter().rev() {
+            match token {
+                None => break,
+                Some(token) => used.push(token.used),
+            }
+        }
+        used.reverse();
+        used
+    }
+
+    // Set flags whether or not reuse tokens available in the current function are used.
+    fn set_reuse_tokens_used(&mut self, used: &Vec<bool>) {
+        let offset = self.reuse_tokens.len() - used.len();
+        for (i, used) in used.iter().enumerate() {
+            self.reuse_tokens[offset + i].as_mut().unwrap().used = *used;
+        }
+    }
+
+    // Get the object allocated on the stack by a lambda expression or a struct literal whose `allocate_on_stack` is true.
+    fn get_stack_obj(&mut self, obj: &Object<'c>) -> Object<'c> {
+        if obj.ty.is_closure() {
+            // The captured objects are allocated on the stack.
+            let cap_obj_ptr = obj
+                .load_field_nocap(self, CLOSURE_CAPTURE_IDX)
+                .into_pointer_value();
+            Object::new(cap_obj_ptr, make_dynamic_object_ty())
+        } else {
+            obj.clone()
+        }
+    }
+
+    // Release fields of an object allocated on the stack.
+    // Since such an object is marked as global, releasing it does nothing, and we need to release its fields instead.
+    fn release_fields_of_stack_obj(&mut self, obj: &Object<'c>) {
+        let current_bb = self.builder().get_insert_block().unwrap();
+        let current_func = current_bb.get_parent().unwrap();
+        let call_bb = self
+            .context
+            .append_basic_block(current_func, "call_bb@release_fields_of_stack_obj");
+        let cont_bb = self
+            .context
+            .append_basic_block(current_func, "cont_bb@release_fields_of_stack_obj");
+
+        // Call the traverser if it is not null.
+        let traverser = obj.get_traverser_ptr_boxed(self);
+        let is_null = self.builder().build_is_null(traverser, "is_traverser_null");
+        self.builder()
+            .build_conditional_branch(is_null, cont_bb, call_bb);
+
+        self.builder().position_at_end(call_bb);
+        let ptr = obj.ptr(self);
+        let traverser = CallableValue::try_from(traverser).unwrap();
+        self.builder().build_call(
+            traverser,
+            &[
+                ptr.into(),
+                traverser_work_type(self.context)
+                    .const_int(TRAVERSER_WORK_RELEASE as u64, false)
+                    .into(),
+            ],
+            "release_fields_of_stack_obj",
+        );
+        self.builder().build_unconditional_branch(cont_bb);
+
+        self.builder().position_at_end(cont_bb);
+    }
+
     // Evaluate let
     fn eval_let(
         &mut self,
@@ -1664,12 +1861,20 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
             used_in_val_except_pat.remove(&v);
         }
         self.scope_lock_as_used_later(&used_in_val_except_pat);
+        let on_stack = bound.is_allocate_on_stack();
         let bound = self.eval_expr(bound.clone(), None);
         self.scope_unlock_as_used_later(&used_in_val_except_pat);
+        // Get the object allocated on the stack, whose fields should be released after evaluating `val`.
+        let stack_obj = if on_stack {
+            Some(self.get_stack_obj(&bound))
+        } else {
+            None
+        };
+        let reuse_tokens_len = self.reuse_tokens.len();
         let suboobjs = self.destructure_object_by_pattern(pat, &bound);
         for (var_name, obj) in &suboobjs {
             if val.free_vars().contains(&var_name) {
-                self.scope_push(var_name, &obj);
+                self.scope_push_with(var_name, &obj, on_stack);
             } else {
                 self.release(obj.clone());
             }
@@ -1684,6 +1889,17 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
                 self.scope_pop(var_name);
             }
         }
+        // Free memories of destructured objects which were not reused.
+        while self.reuse_tokens.len() > reuse_tokens_len {
+            let token = self.reuse_tokens.pop().unwrap().unwrap();
+            if !token.used {
+                // `free(NULL)` does nothing, so we don't need to check whether the token is null.
+                self.builder().build_free(token.ptr);
+            }
+        }
+        if let Some(stack_obj) = stack_obj {
+            self.release_fields_of_stack_obj(&stack_obj);
+        }
         val_code
     }
 
@@ -1716,11 +1932,35 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
                     .collect::<HashMap<_, _>>();
 
                 // Extract fields.
-                let field_indices_rvo = field_to_pat
-                    .iter()
-                    .map(|(name, _)| (field_to_idx[name], None))
-                    .collect::<Vec<_>>();
-                let fields = ObjectFieldType::get_struct_fields(self, obj, field_indices_rvo);
+                let fields = if self.config.perform_reuse_optimization()
+                    && obj.is_box(self.type_env())
+                    && !obj.is_destructor_object()
+                {
+                    // Keep the memory of the struct for reuse if it is unique.
+                    let field_indices = field_to_pat
+                        .iter()
+                        .map(|(name, _)| field_to_idx[name])
+                        .collect::<Vec<_>>();
+                    let (fields, token) = ObjectFieldType::get_struct_fields_with_reuse_token(
+                        self,
+                        obj,
+                        field_indices,
+                    );
+                    let str_ty = obj.struct_ty(self);
+                    let size = self.sizeof(&str_ty);
+                    self.reuse_tokens.push(Some(ReuseToken {
+                        size,
+                        ptr: token,
+                        used: false,
+                    }));
+                    fields
+                } else {
+                    let field_indices_rvo = field_to_pat
+                        .iter()
+                        .map(|(name, _)| (field_to_idx[name], None))
+                        .collect::<Vec<_>>();
+                    ObjectFieldType::get_struct_fields(self, obj, field_indices_rvo)
+                };
 
                 // Match to subpatterns.
                 for (i, (_, pat)) in field_to_pat.iter().enumerate() {
@@ -1755,6 +1995,7 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
     // Evaluate if
     fn eval_if(
         &mut self,
+        if_src: Option<Span>,
         cond_expr: Arc<ExprNode>,
         then_expr: Arc<ExprNode>,
         else_expr: Arc<ExprNode>,
@@ -1775,8 +2016,10 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
         let then_bb = self.context.append_basic_block(func, "then");
         let else_bb = self.context.append_basic_block(func, "else");
         let cont_bb = self.context.append_basic_block(func, "cont");
-        self.builder()
+        let cond_br = self
+            .builder()
             .build_conditional_branch(cond_val, then_bb, else_bb);
+        set_pgo_branch_weights(self, &if_src, cond_br);
 
         self.builder().position_at_end(then_bb);
         // Release variables used only in the else block.
@@ -1786,10 +2029,19 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
                 self.release(self.get_var(var_name).ptr.get(self));
             }
         }
+        if self.config.coverage {
+            build_coverage_branch_counter(self, &if_src, &then_expr, 0);
+        }
+        if self.config.pgo_generate {
+            build_pgo_branch_counter(self, &if_src, 0);
+        }
+        let reuse_tokens_before = self.reuse_tokens_used();
         let then_val = self.eval_expr(then_expr.clone(), rvo.clone());
         let then_val_ptr = then_val.ptr(self);
         let then_bb = self.builder().get_insert_block().unwrap();
-        self.builder().build_unconditional_branch(cont_bb);
+        let then_br = self.builder().build_unconditional_branch(cont_bb);
+        let reuse_tokens_then = self.reuse_tokens_used();
+        self.set_reuse_tokens_used(&reuse_tokens_before);
 
         self.builder().position_at_end(else_bb);
         // Release variables used only in the then block.
@@ -1799,10 +2051,38 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
                 self.release(self.get_var(var_name).ptr.get(self));
             }
         }
+        if self.config.coverage {
+            build_coverage_branch_counter(self, &if_src, &else_expr, 1);
+        }
+        if self.config.pgo_generate {
+            build_pgo_branch_counter(self, &if_src, 1);
+        }
         let else_val = self.eval_expr(else_expr, rvo.clone());
         let else_val_ptr = else_val.ptr(self);
         let else_bb = self.builder().get_insert_block().unwrap();
-        self.builder().build_unconditional_branch(cont_bb);
+        let else_br = self.builder().build_unconditional_branch(cont_bb);
+        let reuse_tokens_else = self.reuse_tokens_used();
+
+        // If a reuse token is used only in one branch, free it in the other branch.
+        for i in 0..reuse_tokens_before.len() {
+            let (used_then, used_else) = (reuse_tokens_then[i], reuse_tokens_else[i]);
+            if used_then == used_else {
+                continue;
+            }
+            let ptr = self.reuse_tokens[self.reuse_tokens.len() - reuse_tokens_before.len() + i]
+                .as_ref()
+                .unwrap()
+                .ptr;
+            let other_br = if used_then { else_br } else { then_br };
+            self.builder().position_before(&other_br);
+            self.builder().build_free(ptr);
+        }
+        let used = reuse_tokens_then
+            .iter()
+            .zip(reuse_tokens_else.iter())
+            .map(|(then_used, else_used)| *then_used || *else_used)
+            .collect::<Vec<_>>();
+        self.set_reuse_tokens_used(&used);
 
         self.builder().position_at_end(cont_bb);
         if rvo.is_none() {
@@ -1829,16 +2109,40 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
         &mut self,
         fields: Vec<(Name, Arc<ExprNode>)>,
         struct_ty: Arc<TypeNode>,
+        on_stack: bool,
         rvo: Option<Object<'c>>,
     ) -> Object<'c> {
         let pair = if rvo.is_some() {
             assert!(struct_ty.is_unbox(self.type_env()));
             rvo.unwrap()
+        } else if on_stack {
+            allocate_obj_with(
+                struct_ty.clone(),
+                &vec![],
+                None,
+                ObjectAllocation::Stack,
+                self,
+                Some("allocate_MakeStruct"),
+            )
         } else {
-            allocate_obj(
+            // Reuse the memory of a destructured struct of the same size if available.
+            let reuse = if struct_ty.is_box(self.type_env()) {
+                let str_ty = ty_to_object_ty(&struct_ty, &vec![], self.type_env())
+                    .to_struct_type(self, vec![]);
+                let size = self.sizeof(&str_ty);
+                self.take_reuse_token(size)
+            } else {
+                None
+            };
+            let allocation = match reuse {
+                Some(ptr) => ObjectAllocation::Reuse(ptr),
+                None => ObjectAllocation::Heap,
+            };
+            allocate_obj_with(
                 struct_ty.clone(),
                 &vec![],
                 None,
+                allocation,
                 self,
                 Some("allocate_MakeStruct"),
             )
@@ -1874,7 +2178,7 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
     fn eval_ffi_call(
         &mut self,
         expr: &Arc<ExprNode>,
-        fun_name: &Name,
+        fun_name: &Option<Name>,
         ret_ty: &Arc<TyCon>,
         param_tys: &Vec<Arc<TyCon>>,
         args: &Vec<Arc<ExprNode>>,
@@ -1888,34 +2192,26 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
             allocate_obj(ret_ty.clone(), &vec![], None, self, Some("allocate_CallC"))
         };
 
-        // Get c function
-        let c_fun = match self.module.get_function(&fun_name) {
-            Some(fun) => fun,
-            None => {
-                let ret_c_ty = ret_ty.get_c_type(self.context);
-                let parm_c_tys: Vec<BasicMetadataTypeEnum> = param_tys
-                    .iter()
-                    .map(|param_ty| {
-                        let c_type = param_ty.get_c_type(self.context);
-                        if c_type.is_none() {
-                            error_exit_with_src(
-                                "Cannot use `()` as a parameter type of C function.",
-                                &expr.source,
-                            )
-                        }
-                        c_type.unwrap().into()
-                    })
-                    .collect::<Vec<_>>();
-                let fn_ty = match ret_c_ty {
-                    None => {
-                        // Void case.
-                        self.context.void_type().fn_type(&parm_c_tys, false)
-                    }
-                    Some(ret_c_ty) => ret_c_ty.fn_type(&parm_c_tys, false),
-                };
-                self.module.add_function(&fun_name, fn_ty, None)
-            }
+        // Lower the signature to LLVM following the C ABI.
+        let ret_layout = if ret_ty.name == make_tuple_name(0) {
+            None
+        } else {
+            Some(CLayout::of_tycon(
+                ret_ty,
+                self.type_env(),
+                &self.config.c_type_sizes,
+            ))
         };
+        let param_layouts = param_tys
+            .iter()
+            .map(|param_ty| CLayout::of_tycon(param_ty, self.type_env(), &self.config.c_type_sizes))
+            .collect::<Vec<_>>();
+        let abi = CFunctionAbi::new(
+            ret_layout.clone(),
+            param_layouts.clone(),
+            self.context,
+            &expr.source,
+        );
 
         // Evaluate arguments
         let mut arg_objs = vec![];
@@ -1927,19 +2223,45 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
             arg_objs.push(self.eval_expr(args[i].clone(), None));
         }
 
+        // Get c function
+        let c_fun: CallableValue = match fun_name {
+            Some(fun_name) => match self.module.get_function(&fun_name) {
+                Some(fun) => fun.into(),
+                None => {
+                    let fun = self
+                        .module
+                        .add_function(&fun_name, abi.fn_type(self.context), None);
+                    abi.add_attributes(self.context, |loc, attr| fun.add_attribute(loc, attr));
+                    fun.into()
+                }
+            },
+            None => {
+                // `FFI_CALL_PTR`: the first argument is the function pointer.
+                let fun_ptr_obj = arg_objs.remove(0);
+                let fun_ptr = fun_ptr_obj.load_field_nocap(self, 0).into_pointer_value();
+                let fun_ptr = self.cast_pointer(
+                    fun_ptr,
+                    abi.fn_type(self.context).ptr_type(AddressSpace::from(0)),
+                );
+                CallableValue::try_from(fun_ptr).unwrap()
+            }
+        };
+
         // Get argment values
         let args_vals = arg_objs
             .iter()
-            .map(|obj| obj.load_field_nocap(self, 0).into())
+            .zip(param_layouts.iter())
+            .map(|(obj, layout)| build_fix_to_c_value(self, obj, layout))
             .collect::<Vec<_>>();
 
         // Call c function
-        let ret_c_val =
-            self.builder()
-                .build_call(c_fun, &args_vals, &format!("FFI_CALL({})", fun_name));
-        match ret_c_val.try_as_basic_value() {
-            Either::Left(ret_c_val) => obj.store_field_nocap(self, 0, ret_c_val),
-            Either::Right(_) => {}
+        let call_name = match fun_name {
+            Some(fun_name) => format!("FFI_CALL({})", fun_name),
+            None => "FFI_CALL_PTR".to_string(),
+        };
+        let ret_c_val = abi.build_call(self, c_fun, args_vals, &call_name);
+        if let Some(ret_c_val) = ret_c_val {
+            build_c_value_to_fix(self, ret_c_val, &obj, ret_layout.as_ref().unwrap());
         }
 
         obj
@@ -2093,6 +2415,7 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
 
     pub fn implement_symbol(&mut self, sym: &InstantiatedSymbol) {
         let name = &sym.instantiated_name;
+        self.current_symbol = Some(sym.generic_name.clone());
         // Get the function to implement.
         let global_obj = self.global.get(name);
         let sym_fn = match global_obj {
@@ -2114,14 +2437,16 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
             let lam_fn = sym_fn;
             let lam = sym.expr.as_ref().unwrap().clone();
             let lam = lam.set_inferred_type(obj_ty.clone());
-            self.implement_lambda_function(lam, lam_fn, None);
+            self.implement_lambda_function(lam, lam_fn, None, Some(&sym.generic_name));
         } else {
             // Prepare global variable to store the initialized global value.
             let obj_embed_ty = obj_ty.get_embedded_type(self, &vec![]);
             let global_var_name = format!("GlobalVar#{}", name.to_string());
             let global_var = self.module.add_global(obj_embed_ty, None, &global_var_name);
             global_var.set_initializer(&obj_embed_ty.const_zero());
-            global_var.set_linkage(Linkage::Internal);
+            // Under separate compilation, the accessor function may be imported to other units by link-time optimization,
+            // so the state of the global value should not be local to this unit.
+            global_var.set_linkage(self.config.external_if_separated());
             let global_var = global_var.as_basic_value_enum().into_pointer_value();
 
             // Prepare initialized flag.
@@ -2137,7 +2462,7 @@ impl<'c, 'm> GenerationContext<'c, 'm> {
             };
             let init_flag = self.module.add_global(flag_ty, None, &flag_name);
             init_flag.set_initializer(&flag_init_val);
-            init_flag.set_linkage(Linkage::Internal);
+            init_flag.set_linkage(self.config.external_if_separated());
             let init_flag = init_flag.as_basic_value_enum().into_pointer_value();
 
             // Start to implement accessor function.
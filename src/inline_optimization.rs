@@ -0,0 +1,236 @@
+use std::sync::Arc;
+
+use super::*;
+
+// Inlining optimization.
+// Consider an application `f(a, b)` of a global function `f = |x, y| e`.
+// If `f` is small enough, inlining optimization replaces the application with `let x = a; let y = b; e`.
+// This removes function calls which LLVM cannot inline because `f` is implemented in another compilation unit (`FixOptimizationLevel::Separated`),
+// and exposes the body of `f` to later Fix-level optimizations such as uncurrying, borrowing and escape analysis.
+//
+// A global function `f` (including an instance of a trait method) is inlined if
+// - `f` is annotated by `INLINE[f];`, or the body of `f` has at most `INLINE_SIZE_THRESHOLD` nodes and `f` is not annotated by `NOINLINE[f];`,
+// - `f` does not refer to itself,
+// - the body of `f` does not contain inline LLVM (builtin functions are optimized by borrowing optimization instead), and
+// - `f` is applied to all of its parameters at once.
+// Inlining is nested at most `INLINE_DEPTH_MAX` times, and a function is not inlined into itself even if it is mutually recursive with another function.
+
+// Perform inlining optimization.
+pub fn inline_optimization(program: &mut Program) {
+    // Collect functions to be inlined. The bodies of them are taken from the program before inlining.
+    let attributes = program.inline_attributes();
+    let mut inlinables: HashMap<FullName, Arc<ExprNode>> = Default::default();
+    for (name, sym) in &program.instantiated_symbols {
+        let expr = sym.expr.as_ref().unwrap();
+        if is_inlinable(name, expr, attributes.get(&sym.generic_name)) {
+            inlinables.insert(name.clone(), expr.clone());
+        }
+    }
+
+    // Inline applications in each symbol.
+    let mut ctx = InlineContext {
+        inlinables,
+        stack: vec![],
+        var_counter: 0,
+    };
+    for (name, sym) in &mut program.instantiated_symbols {
+        ctx.stack.push(name.clone());
+        let expr = inline_subexprs(sym.expr.as_ref().unwrap(), &mut ctx);
+        ctx.stack.pop();
+        sym.expr = Some(calculate_free_vars(expr));
+    }
+}
+
+struct InlineContext {
+    // Functions which can be inlined and their expressions.
+    inlinables: HashMap<FullName, Arc<ExprNode>>,
+    // Functions being inlined. The bottom is the symbol being optimized.
+    stack: Vec<FullName>,
+    // Counter to generate fresh variable names.
+    var_counter: usize,
+}
+
+impl InlineContext {
+    // Generate a fresh name of a local variable.
+    fn fresh_var_name(&mut self, base: &FullName) -> FullName {
+        let name = FullName::local(&format!("{}@inline{}", base.name, self.var_counter));
+        self.var_counter += 1;
+        name
+    }
+}
+
+// Can the global function be inlined at its applications?
+fn is_inlinable(
+    name: &FullName,
+    expr: &Arc<ExprNode>,
+    attribute: Option<&InlineAttribute>,
+) -> bool {
+    if attribute == Some(&InlineAttribute::NoInline) {
+        return false;
+    }
+    // We do not inline values other than functions, since they would be evaluated at each place where they are used.
+    if !expr.is_lam() {
+        return false;
+    }
+    let (_, body) = uncurry_optimization::collect_abs(expr, usize::MAX);
+    let size = match expr_size(&body) {
+        Some(size) => size,
+        None => return false,
+    };
+    if attribute != Some(&InlineAttribute::Inline) && size > INLINE_SIZE_THRESHOLD {
+        return false;
+    }
+    // Do not inline recursive functions.
+    let expr = calculate_free_vars(expr.clone());
+    !expr.free_vars().contains(name)
+}
+
+// Count the nodes of an expression.
+// Returns `None` if the expression contains inline LLVM, which cannot be inlined.
+fn expr_size(expr: &Arc<ExprNode>) -> Option<usize> {
+    let children_size = match &*expr.expr {
+        Expr::Var(_) => 0,
+        Expr::LLVM(_) => return None,
+        Expr::App(fun, args) => {
+            let mut size = expr_size(fun)?;
+            for arg in args {
+                size += expr_size(arg)?;
+            }
+            size
+        }
+        Expr::Lam(_, body) => expr_size(body)?,
+        Expr::Let(_, bound, value) => expr_size(bound)? + expr_size(value)?,
+        Expr::If(c, t, e) => expr_size(c)? + expr_size(t)? + expr_size(e)?,
+        Expr::TyAnno(e, _) => expr_size(e)?,
+        Expr::ArrayLit(elems) => {
+            let mut size = 0;
+            for e in elems {
+                size += expr_size(e)?;
+            }
+            size
+        }
+        Expr::MakeStruct(_, fields) => {
+            let mut size = 0;
+            for (_, e) in fields {
+                size += expr_size(e)?;
+            }
+            size
+        }
+        Expr::FFICall(_, _, _, args) => {
+            let mut size = 0;
+            for e in args {
+                size += expr_size(e)?;
+            }
+            size
+        }
+    };
+    Some(children_size + 1)
+}
+
+// Inline the application if possible.
+// Returns `None` if the expression is not an application of an inlinable function.
+fn inline_app(expr: &Arc<ExprNode>, ctx: &mut InlineContext) -> Option<Arc<ExprNode>> {
+    let (fun, args) = collect_app(expr);
+    if !fun.is_var() {
+        return None;
+    }
+    let fun_name = fun.get_var().name.clone();
+    if !fun_name.is_global() || ctx.stack.contains(&fun_name) || ctx.stack.len() > INLINE_DEPTH_MAX
+    {
+        return None;
+    }
+    let callee = ctx.inlinables.get(&fun_name)?.clone();
+    let (params, body) = uncurry_optimization::collect_abs(&callee, args.len());
+    if params.len() != args.len() {
+        return None;
+    }
+
+    // Inline applications in the arguments and the body of the callee.
+    let args = args
+        .iter()
+        .map(|arg| calculate_free_vars(inline_subexprs(arg, ctx)))
+        .collect::<Vec<_>>();
+    ctx.stack.push(fun_name);
+    let body = inline_subexprs(&body, ctx);
+    ctx.stack.pop();
+
+    // Bind arguments to parameters: `let x = a; let y = b; e`.
+    // If an argument refers to a variable having the same name as a parameter bound before it, e.g., `f(y, x)`,
+    // we first bind arguments to fresh variables to avoid the capture: `let a0 = y; let a1 = x; let x = a0; let y = a1; e`.
+    let capture =
+        (0..args.len()).any(|j| (0..j).any(|i| args[j].free_vars().contains(&params[i].name)));
+    let mut bounds = args.clone();
+    let mut temps = vec![];
+    if capture {
+        for (i, arg) in args.iter().enumerate() {
+            let temp = ctx.fresh_var_name(&params[i].name);
+            let arg_ty = arg.ty.clone().unwrap();
+            bounds[i] = expr_var(temp.clone(), None).set_inferred_type(arg_ty);
+            temps.push(var_var(temp));
+        }
+    }
+    let ty = expr.ty.clone().unwrap();
+    let mut inlined = body;
+    for (param, bound) in params.iter().zip(bounds.iter()).rev() {
+        let pat = PatternNode::make_var(param.clone(), None);
+        inlined = expr_let(pat, bound.clone(), inlined, expr.source.clone())
+            .set_inferred_type(ty.clone());
+    }
+    for (temp, arg) in temps.iter().zip(args.iter()).rev() {
+        let pat = PatternNode::make_var(temp.clone(), None);
+        inlined =
+            expr_let(pat, arg.clone(), inlined, expr.source.clone()).set_inferred_type(ty.clone());
+    }
+    Some(inlined)
+}
+
+// Inline applications in all subexpressions.
+fn inline_subexprs(expr: &Arc<ExprNode>, ctx: &mut InlineContext) -> Arc<ExprNode> {
+    match &*expr.expr {
+        Expr::Var(_) => expr.clone(),
+        Expr::LLVM(_) => expr.clone(),
+        Expr::App(fun, args) => {
+            if let Some(inlined) = inline_app(expr, ctx) {
+                return inlined;
+            }
+            let args = args
+                .iter()
+                .map(|arg| inline_subexprs(arg, ctx))
+                .collect::<Vec<_>>();
+            let fun = inline_subexprs(fun, ctx);
+            expr.set_app_func(fun).set_app_args(args)
+        }
+        Expr::Lam(_, body) => expr.set_lam_body(inline_subexprs(body, ctx)),
+        Expr::Let(_, bound, value) => expr
+            .set_let_bound(inline_subexprs(bound, ctx))
+            .set_let_value(inline_subexprs(value, ctx)),
+        Expr::If(c, t, e) => expr
+            .set_if_cond(inline_subexprs(c, ctx))
+            .set_if_then(inline_subexprs(t, ctx))
+            .set_if_else(inline_subexprs(e, ctx)),
+        Expr::TyAnno(e, _) => expr.set_tyanno_expr(inline_subexprs(e, ctx)),
+        Expr::ArrayLit(elems) => {
+            let mut expr = expr.clone();
+            for (i, e) in elems.iter().enumerate() {
+                expr = expr.set_array_lit_elem(inline_subexprs(e, ctx), i)
+            }
+            expr
+        }
+        Expr::MakeStruct(_, fields) => {
+            let fields = fields.clone();
+            let mut expr = expr.clone();
+            for (field_name, field_expr) in fields {
+                let field_expr = inline_subexprs(&field_expr, ctx);
+                expr = expr.set_make_struct_field(&field_name, field_expr);
+            }
+            expr
+        }
+        Expr::FFICall(_, _, _, args) => {
+            let mut expr = expr.clone();
+            for (i, e) in args.iter().enumerate() {
+                expr = expr.set_ffi_call_arg(inline_subexprs(e, ctx), i)
+            }
+            expr
+        }
+    }
+}
@@ -0,0 +1,154 @@
+/*
+Execution of Fix programs on LLVM's JIT execution engine.
+*/
+
+use super::*;
+use ast::export_statement::ExportStatement;
+use error::Errors;
+use inkwell::support::load_library_permanently;
+use std::{os::raw::c_void, sync::Arc};
+
+extern "C" {
+    fn fflush(stream: *mut c_void) -> i32;
+}
+
+// Generate a single LLVM module for the instantiated program, and run its `main()` function in the current process.
+// `main_expr` is the expression of type `IO ()` to be executed, and `args` are passed to the program as command line arguments.
+// Returns the exit code of `main()`.
+pub fn run_program_jit(
+    mut program: Program,
+    main_expr: Arc<ExprNode>,
+    args: &[&str],
+    config: &Configuration,
+) -> Result<i32, Errors> {
+    // Make functions of runtime.c and linked libraries available to the execution engine.
+    load_runtime_library(config)?;
+    load_linked_libraries(config)?;
+
+    // Collect all instantiated symbols. All of them are implemented in a single module.
+    let mut symbols = program
+        .instantiated_symbols
+        .values()
+        .cloned()
+        .collect::<Vec<_>>();
+    symbols.sort_by(|a, b| a.instantiated_name.cmp(&b.instantiated_name));
+    let export_statements: Vec<ExportStatement> =
+        std::mem::replace(&mut program.export_statements, vec![]);
+
+    // Generate the module.
+    let context = Context::create();
+    let target_machine = get_target_machine(config.get_llvm_opt_level(), config);
+    let module = GenerationContext::create_module("Module-jit", &context, &target_machine);
+    {
+        let mut gc = GenerationContext::new(
+            &context,
+            &module,
+            target_machine.get_target_data(),
+            config.clone(),
+            program.type_env(),
+        );
+        if config.debug_info {
+            gc.create_debug_info();
+        }
+        generate_module(
+            &mut gc,
+            &symbols,
+            &symbols,
+            Some((export_statements.as_slice(), main_expr)),
+            config,
+        );
+    }
+
+    // Create the execution engine and run `main()`.
+    let engine = module
+        .create_jit_execution_engine(config.get_llvm_opt_level())
+        .map_err(|e| {
+            Errors::from_msg(format!(
+                "Failed to create JIT execution engine: {}",
+                e.to_string()
+            ))
+        })?;
+    let main_fn = match module.get_function("main") {
+        Some(f) => f,
+        None => return Err(Errors::from_msg("`main()` function is not found.".to_string())),
+    };
+    let exit_code = unsafe { engine.run_function_as_main(main_fn, args) };
+
+    // Flush buffers of C's standard streams, since the program shares them with the compiler.
+    unsafe {
+        fflush(std::ptr::null_mut());
+    }
+
+    Ok(exit_code)
+}
+
+// Build runtime.c as a shared library and load it into the current process.
+fn load_runtime_library(config: &Configuration) -> Result<(), Errors> {
+    let runtime_lib_path = build_runtime_c(config, true)?;
+    let runtime_lib_path = to_absolute_path(&runtime_lib_path);
+    if load_library_permanently(runtime_lib_path.to_str().unwrap()) {
+        return Err(Errors::from_msg(format!(
+            "Failed to load \"{}\".",
+            runtime_lib_path.to_string_lossy().to_string()
+        )));
+    }
+    Ok(())
+}
+
+// Load libraries specified in the configuration into the current process.
+fn load_linked_libraries(config: &Configuration) -> Result<(), Errors> {
+    if !config.object_files.is_empty() {
+        return Err(Errors::from_msg(
+            "Object files cannot be linked in JIT mode. Build them into a shared library and link it dynamically instead.".to_string(),
+        ));
+    }
+    for (lib_name, link_type) in &config.linked_libraries {
+        match link_type {
+            LinkType::Static => {
+                return Err(Errors::from_msg(format!(
+                    "Static library \"{}\" cannot be linked in JIT mode. Link it dynamically instead.",
+                    lib_name
+                )));
+            }
+            LinkType::Dynamic => {}
+        }
+        // pthread is always loaded since the compiler itself depends on it.
+        if lib_name == "pthread" {
+            continue;
+        }
+        let file_name = format!("lib{}.{}", lib_name, shared_library_extension());
+        // Search the library in the library search paths first, and then let the dynamic loader search it.
+        let lib_path = config
+            .library_search_paths
+            .iter()
+            .map(|dir| to_absolute_path(&dir.join(&file_name)))
+            .find(|path| path.exists())
+            .map(|path| path.to_string_lossy().to_string())
+            .unwrap_or(file_name);
+        if load_library_permanently(&lib_path) {
+            return Err(Errors::from_msg(format!(
+                "Failed to load library \"{}\".",
+                lib_path
+            )));
+        }
+    }
+    if config.sanitize_memory {
+        let sanitizer_path = to_absolute_path(Path::new("./sanitizer/libfixsanitizer.so"));
+        if load_library_permanently(sanitizer_path.to_str().unwrap()) {
+            return Err(Errors::from_msg(format!(
+                "Failed to load \"{}\".",
+                sanitizer_path.to_string_lossy().to_string()
+            )));
+        }
+    }
+    Ok(())
+}
+
+// The file extension of shared libraries on the current platform.
+fn shared_library_extension() -> &'static str {
+    if std::env::consts::OS == "macos" {
+        "dylib"
+    } else {
+        "so"
+    }
+}
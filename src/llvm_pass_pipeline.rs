@@ -0,0 +1,143 @@
+// Custom pipelines of LLVM optimization passes.
+//
+// By default, modules are optimized by the passes listed in `llvm_passes.rs`, which are tuned by `passes_optimizer.py`.
+// Users can replace them by a list of pass names given by `--llvm-passes` option or `llvm_passes` in the project file.
+// The name of a pass is the name of the corresponding method of `PassManager` without `add_` prefix and `_pass`, e.g., `instruction_combining` for `add_instruction_combining_pass`.
+// The special name `default` stands for all passes in `llvm_passes.rs`.
+//
+// Passes known to break Fix programs (e.g., `gvn`, `licm` and `merge_functions`) are not available.
+use super::*;
+
+pub const LLVM_PASS_NAME_DEFAULT: &str = "default";
+
+type AddPass = fn(&PassManager<Module>);
+
+const LLVM_PASSES: &[(&str, AddPass)] = &[
+    ("aggressive_dce", |pm| pm.add_aggressive_dce_pass()),
+    ("aggressive_inst_combiner", |pm| {
+        pm.add_aggressive_inst_combiner_pass()
+    }),
+    ("alignment_from_assumptions", |pm| {
+        pm.add_alignment_from_assumptions_pass()
+    }),
+    ("always_inliner", |pm| pm.add_always_inliner_pass()),
+    ("basic_alias_analysis", |pm| {
+        pm.add_basic_alias_analysis_pass()
+    }),
+    ("bit_tracking_dce", |pm| pm.add_bit_tracking_dce_pass()),
+    ("cfg_simplification", |pm| pm.add_cfg_simplification_pass()),
+    ("constant_merge", |pm| pm.add_constant_merge_pass()),
+    ("correlated_value_propagation", |pm| {
+        pm.add_correlated_value_propagation_pass()
+    }),
+    ("dead_arg_elimination", |pm| {
+        pm.add_dead_arg_elimination_pass()
+    }),
+    ("dead_store_elimination", |pm| {
+        pm.add_dead_store_elimination_pass()
+    }),
+    ("demote_memory_to_register", |pm| {
+        pm.add_demote_memory_to_register_pass()
+    }),
+    ("early_cse", |pm| pm.add_early_cse_pass()),
+    ("function_attrs", |pm| pm.add_function_attrs_pass()),
+    ("function_inlining", |pm| pm.add_function_inlining_pass()),
+    ("global_dce", |pm| pm.add_global_dce_pass()),
+    ("global_optimizer", |pm| pm.add_global_optimizer_pass()),
+    ("ind_var_simplify", |pm| pm.add_ind_var_simplify_pass()),
+    ("instruction_combining", |pm| {
+        pm.add_instruction_combining_pass()
+    }),
+    ("instruction_simplify", |pm| {
+        pm.add_instruction_simplify_pass()
+    }),
+    ("ipsccp", |pm| pm.add_ipsccp_pass()),
+    ("jump_threading", |pm| pm.add_jump_threading_pass()),
+    ("loop_deletion", |pm| pm.add_loop_deletion_pass()),
+    ("loop_idiom", |pm| pm.add_loop_idiom_pass()),
+    ("loop_reroll", |pm| pm.add_loop_reroll_pass()),
+    ("loop_rotate", |pm| pm.add_loop_rotate_pass()),
+    ("loop_unroll_and_jam", |pm| {
+        pm.add_loop_unroll_and_jam_pass()
+    }),
+    ("loop_unroll", |pm| pm.add_loop_unroll_pass()),
+    ("loop_vectorize", |pm| pm.add_loop_vectorize_pass()),
+    ("lower_expect_intrinsic", |pm| {
+        pm.add_lower_expect_intrinsic_pass()
+    }),
+    ("lower_switch", |pm| pm.add_lower_switch_pass()),
+    ("memcpy_optimize", |pm| pm.add_memcpy_optimize_pass()),
+    ("merged_load_store_motion", |pm| {
+        pm.add_merged_load_store_motion_pass()
+    }),
+    ("partially_inline_lib_calls", |pm| {
+        pm.add_partially_inline_lib_calls_pass()
+    }),
+    ("promote_memory_to_register", |pm| {
+        pm.add_promote_memory_to_register_pass()
+    }),
+    ("prune_eh", |pm| pm.add_prune_eh_pass()),
+    ("reassociate", |pm| pm.add_reassociate_pass()),
+    ("scalar_repl_aggregates", |pm| {
+        pm.add_scalar_repl_aggregates_pass()
+    }),
+    ("scalar_repl_aggregates_ssa", |pm| {
+        pm.add_scalar_repl_aggregates_pass_ssa()
+    }),
+    ("scalarizer", |pm| pm.add_scalarizer_pass()),
+    ("sccp", |pm| pm.add_sccp_pass()),
+    ("scoped_no_alias_aa", |pm| pm.add_scoped_no_alias_aa_pass()),
+    ("simplify_lib_calls", |pm| pm.add_simplify_lib_calls_pass()),
+    ("slp_vectorize", |pm| pm.add_slp_vectorize_pass()),
+    ("strip_dead_prototypes", |pm| {
+        pm.add_strip_dead_prototypes_pass()
+    }),
+    ("strip_symbol", |pm| pm.add_strip_symbol_pass()),
+    ("tail_call_elimination", |pm| {
+        pm.add_tail_call_elimination_pass()
+    }),
+    ("type_based_alias_analysis", |pm| {
+        pm.add_type_based_alias_analysis_pass()
+    }),
+];
+
+// Check that all names in a pass pipeline are known.
+pub fn validate_llvm_pass_names(names: &[String]) -> Result<(), Errors> {
+    let mut errors = Errors::empty();
+    for name in names {
+        if name == LLVM_PASS_NAME_DEFAULT || LLVM_PASSES.iter().any(|(n, _)| n == name) {
+            continue;
+        }
+        errors.append(Errors::from_msg(format!(
+            "Unknown LLVM pass `{}`. Available passes are: {}, {}.",
+            name,
+            LLVM_PASS_NAME_DEFAULT,
+            LLVM_PASSES
+                .iter()
+                .map(|(n, _)| *n)
+                .collect::<Vec<_>>()
+                .join(", ")
+        )));
+    }
+    errors.to_result()
+}
+
+// Add passes in a pipeline to the pass manager. The names should be validated by `validate_llvm_pass_names`.
+pub fn add_llvm_passes_by_names(passmgr: &PassManager<Module>, names: &[String]) {
+    for name in names {
+        if name == LLVM_PASS_NAME_DEFAULT {
+            llvm_passes::add_optimization_passes(passmgr);
+            continue;
+        }
+        let (_, add_pass) = LLVM_PASSES.iter().find(|(n, _)| n == name).unwrap();
+        add_pass(passmgr);
+    }
+}
+
+// Parse a comma-separated list of pass names.
+pub fn parse_llvm_pass_names(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
@@ -1,21 +1,31 @@
 use crate::ast::expr::ExprNode;
 use crate::ast::program::Program;
-use crate::constants::INSTANCIATED_NAME_SEPARATOR;
+use crate::ast::import::{self, shortest_accessible_name, ImportStatement};
+use crate::ast::types::{Scheme, TypeNode};
+use crate::builtin::get_tuple_n;
+use crate::constants::{INSTANCIATED_NAME_SEPARATOR, LSP_INFER_SOURCE_NAME};
+use crate::misc::{save_temporary_source, temporary_source_path};
+use crate::runner::{create_typechecker, load_source_files};
 use crate::{
     constants::LSP_LOG_FILE_PATH,
-    error::{any_to_string, Error, Errors},
+    error::{any_to_string, Error, ErrorCode, Errors},
     project_file::ProjectFile,
     runner::build_file,
     Configuration, Span,
 };
-use crate::{to_absolute_path, FullName, SubCommand};
+use crate::typecheck::Scope;
+use crate::{to_absolute_path, FullName, Name, SubCommand};
 use difference::diff;
 use lsp_types::{
-    CompletionItem, CompletionItemKind, CompletionItemLabelDetails, CompletionOptions,
+    CodeAction, CodeActionKind, CodeActionOrCommand, CodeActionParams,
+    CodeActionProviderCapability, CompletionItem, CompletionItemKind, CompletionItemLabelDetails, CompletionOptions,
     CompletionParams, DiagnosticSeverity, DidChangeTextDocumentParams, DidOpenTextDocumentParams,
     DidSaveTextDocumentParams, Documentation, GotoDefinitionParams, HoverParams,
-    HoverProviderCapability, InitializeParams, InitializeResult, InitializedParams, MarkupContent,
-    PublishDiagnosticsParams, SaveOptions, ServerCapabilities, TextDocumentPositionParams,
+    HoverProviderCapability, InitializeParams, InitializeResult, InitializedParams, InlayHint,
+    InlayHintKind, InlayHintLabel, InlayHintParams, MarkupContent,
+    ParameterInformation, ParameterLabel, PublishDiagnosticsParams, SaveOptions,
+    ServerCapabilities, SignatureHelp, SignatureHelpOptions, SignatureHelpParams,
+    SignatureInformation, TextDocumentPositionParams, TextEdit, WorkspaceEdit,
     TextDocumentSyncCapability, TextDocumentSyncKind, TextDocumentSyncOptions,
     TextDocumentSyncSaveOptions, WorkDoneProgressOptions,
 };
@@ -81,6 +91,15 @@ enum DiagnosticsMessage {
     Stop,
 }
 
+// Data attached to a diagnostic, which is sent back from the client in "textDocument/codeAction" requests.
+#[derive(Serialize, Deserialize)]
+struct DiagnosticData {
+    code: ErrorCode,
+    // For `ErrorCode::MissingTypeSignature`, the type signature inferred in the diagnostics thread.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signature: Option<String>,
+}
+
 // The result of diagnostics.
 pub struct DiagnosticsResult {
     pub prgoram: Program,
@@ -347,6 +366,69 @@ pub fn launch_language_server() {
                     &uri_to_latest_content,
                     log_file.clone(),
                 );
+            } else if method == "textDocument/signatureHelp" {
+                if last_diag.is_none() {
+                    continue;
+                }
+                let program = &last_diag.as_ref().unwrap().prgoram;
+                let id = parse_id(&message, method, log_file.clone());
+                if id.is_none() {
+                    continue;
+                }
+                let params: Option<SignatureHelpParams> =
+                    parase_params(message.params.unwrap(), log_file.clone());
+                if params.is_none() {
+                    continue;
+                }
+                handle_signature_help(
+                    id.unwrap(),
+                    &params.unwrap(),
+                    program,
+                    &uri_to_latest_content,
+                    log_file.clone(),
+                );
+            } else if method == "textDocument/inlayHint" {
+                if last_diag.is_none() {
+                    continue;
+                }
+                let program = &last_diag.as_ref().unwrap().prgoram;
+                let id = parse_id(&message, method, log_file.clone());
+                if id.is_none() {
+                    continue;
+                }
+                let params: Option<InlayHintParams> =
+                    parase_params(message.params.unwrap(), log_file.clone());
+                if params.is_none() {
+                    continue;
+                }
+                handle_inlay_hint(
+                    id.unwrap(),
+                    &params.unwrap(),
+                    program,
+                    &uri_to_latest_content,
+                    log_file.clone(),
+                );
+            } else if method == "textDocument/codeAction" {
+                if last_diag.is_none() {
+                    continue;
+                }
+                let program = &last_diag.as_ref().unwrap().prgoram;
+                let id = parse_id(&message, method, log_file.clone());
+                if id.is_none() {
+                    continue;
+                }
+                let params: Option<CodeActionParams> =
+                    parase_params(message.params.unwrap(), log_file.clone());
+                if params.is_none() {
+                    continue;
+                }
+                handle_code_action(
+                    id.unwrap(),
+                    &params.unwrap(),
+                    program,
+                    &uri_to_latest_content,
+                    log_file.clone(),
+                );
             }
         }
     }
@@ -488,7 +570,11 @@ fn handle_initialize(id: u32, _params: &InitializeParams, _log_file: Arc<Mutex<F
                 work_done_progress_options: WorkDoneProgressOptions::default(),
                 completion_item: None,
             }),
-            signature_help_provider: None,
+            signature_help_provider: Some(SignatureHelpOptions {
+                trigger_characters: Some(vec!["(".to_string(), ",".to_string()]),
+                retrigger_characters: None,
+                work_done_progress_options: WorkDoneProgressOptions::default(),
+            }),
             definition_provider: Some(lsp_types::OneOf::Left(true)),
             type_definition_provider: None,
             implementation_provider: None,
@@ -496,7 +582,7 @@ fn handle_initialize(id: u32, _params: &InitializeParams, _log_file: Arc<Mutex<F
             document_highlight_provider: None,
             document_symbol_provider: None,
             workspace_symbol_provider: None,
-            code_action_provider: None,
+            code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
             code_lens_provider: None,
             document_formatting_provider: None,
             document_range_formatting_provider: None,
@@ -513,7 +599,7 @@ fn handle_initialize(id: u32, _params: &InitializeParams, _log_file: Arc<Mutex<F
             moniker_provider: None,
             linked_editing_range_provider: None,
             inline_value_provider: None,
-            inlay_hint_provider: None,
+            inlay_hint_provider: Some(lsp_types::OneOf::Left(true)),
             diagnostic_provider: None,
             experimental: None,
         },
@@ -953,25 +1039,610 @@ fn handle_hover(
     send_response(id, Ok::<_, ()>(hover))
 }
 
+// Handle "textDocument/signatureHelp" method.
+fn handle_signature_help(
+    id: u32,
+    params: &SignatureHelpParams,
+    program: &Program,
+    uri_to_content: &HashMap<lsp_types::Uri, String>,
+    log_file: Arc<Mutex<File>>,
+) {
+    // Get the latest file content.
+    let text_position = &params.text_document_position_params;
+    let uri = &text_position.text_document.uri;
+    let latest_content = uri_to_content.get(uri);
+    if latest_content.is_none() {
+        let msg = format!("No stored content for the uri \"{}\".", uri.to_string());
+        write_log(log_file.clone(), msg.as_str());
+        send_response(id, Ok::<_, ()>(None::<()>));
+        return;
+    }
+    let latest_content = latest_content.unwrap();
+
+    // Find the function application which contains the cursor.
+    // Since the latest content may not be parsable, we analyze the text directly.
+    let pos = position_to_bytes(latest_content, text_position.position);
+    let pos = std::cmp::min(pos, latest_content.len());
+    let app = find_function_application_at(&latest_content[..pos]);
+    if app.is_none() {
+        send_response(id, Ok::<_, ()>(None::<()>));
+        return;
+    }
+    let (func_name, active_param) = app.unwrap();
+    let func_name = FullName::parse(&func_name).unwrap();
+
+    // Get import statements of the module defined in the file.
+    let path = PathBuf::from(uri.path().to_string());
+    let import_stmts = get_import_statements_of_file(program, &path);
+    if let Err(e) = import_stmts {
+        write_log(log_file.clone(), &e);
+        send_response(id, Ok::<_, ()>(None::<()>));
+        return;
+    }
+    let import_stmts = import_stmts.ok().unwrap();
+
+    // Collect candidates of overload resolution.
+    let mut scope: Scope<Arc<Scheme>> = Default::default();
+    for (name, gv) in &program.global_values {
+        let _ = scope.add_global(name.name.clone(), &name.namespace, &gv.scm);
+    }
+    let mut candidates = scope
+        .overloaded_candidates(&func_name, &import_stmts)
+        .into_iter()
+        .map(|(ns, _)| FullName::new(&ns, &func_name.name))
+        .collect::<Vec<_>>();
+    candidates.sort();
+    if candidates.is_empty() {
+        send_response(id, Ok::<_, ()>(None::<()>));
+        return;
+    }
+
+    // Create signature informations.
+    let mut signatures = vec![];
+    for name in &candidates {
+        let gv = program.global_values.get(name).unwrap();
+        let (scm_str, param_ranges) = gv.scm.to_string_normalize_with_param_ranges();
+        let label_prefix = format!("{} : ", name.to_string());
+        let label = label_prefix.clone() + &scm_str;
+        let parameters = param_ranges
+            .iter()
+            .map(|(start, end)| ParameterInformation {
+                label: ParameterLabel::LabelOffsets([
+                    utf16_len(&label[..label_prefix.len() + start]),
+                    utf16_len(&label[..label_prefix.len() + end]),
+                ]),
+                documentation: None,
+            })
+            .collect::<Vec<_>>();
+        let documentation = gv.get_document().map(|doc| {
+            Documentation::MarkupContent(MarkupContent {
+                kind: lsp_types::MarkupKind::Markdown,
+                value: doc,
+            })
+        });
+        signatures.push(SignatureInformation {
+            label,
+            documentation,
+            parameters: Some(parameters),
+            active_parameter: Some(active_param),
+        });
+    }
+    let signature_help = SignatureHelp {
+        signatures,
+        active_signature: Some(0),
+        active_parameter: Some(active_param),
+    };
+    send_response(id, Ok::<_, ()>(signature_help))
+}
+
+// Handle "textDocument/inlayHint" method.
+fn handle_inlay_hint(
+    id: u32,
+    params: &InlayHintParams,
+    program: &Program,
+    uri_to_content: &HashMap<lsp_types::Uri, String>,
+    log_file: Arc<Mutex<File>>,
+) {
+    let uri = &params.text_document.uri;
+    let path = PathBuf::from(uri.path().to_string());
+
+    // Get the file content at the time of the last successful diagnostics, and the latest one.
+    let saved_content = get_file_content_at_previous_diagnostics(program, &path);
+    if let Err(e) = saved_content {
+        write_log(log_file.clone(), &e);
+        send_response(id, Ok::<_, ()>(None::<()>));
+        return;
+    }
+    let saved_content = saved_content.ok().unwrap();
+    let latest_content = uri_to_content.get(uri).unwrap_or(&saved_content);
+
+    // Get import statements of the module defined in the file, which are used to shorten names of types.
+    let import_stmts = get_import_statements_of_file(program, &path);
+    if let Err(e) = import_stmts {
+        write_log(log_file.clone(), &e);
+        send_response(id, Ok::<_, ()>(None::<()>));
+        return;
+    }
+    let import_stmts = import_stmts.ok().unwrap();
+    let tycon_names = program.tycon_names_with_aliases();
+
+    let mut hints = vec![];
+    for (span, ty) in program.collect_binder_types(&path) {
+        // Put the hint just after the binder, moving it to the corresponding line in the latest content.
+        let end = span_to_range(&span).end;
+        let line = calculate_corresponding_line(&saved_content, latest_content, end.line);
+        if line.is_none() {
+            continue;
+        }
+        let position = lsp_types::Position {
+            line: line.unwrap(),
+            character: end.character,
+        };
+        if position < params.range.start || params.range.end < position {
+            continue;
+        }
+        let ty = shorten_type_for_display(&ty, &import_stmts, &tycon_names);
+        hints.push(InlayHint {
+            position,
+            label: InlayHintLabel::String(format!(": {}", ty)),
+            kind: Some(InlayHintKind::TYPE),
+            text_edits: None,
+            tooltip: None,
+            padding_left: None,
+            padding_right: None,
+            data: None,
+        });
+    }
+    send_response(id, Ok::<_, ()>(hints))
+}
+
+// Handle "textDocument/codeAction" method.
+// Quick fixes are offered for diagnostics sent from the client, using the data attached to them by `error_to_diagnostics`.
+fn handle_code_action(
+    id: u32,
+    params: &CodeActionParams,
+    program: &Program,
+    uri_to_content: &HashMap<lsp_types::Uri, String>,
+    log_file: Arc<Mutex<File>>,
+) {
+    let uri = &params.text_document.uri;
+    let path = PathBuf::from(uri.path().to_string());
+    let latest_content = uri_to_content.get(uri);
+    if latest_content.is_none() {
+        let msg = format!("No stored content for the uri \"{}\".", uri.to_string());
+        write_log(log_file.clone(), msg.as_str());
+        send_response(id, Ok::<_, ()>(None::<()>));
+        return;
+    }
+    let latest_content = latest_content.unwrap();
+
+    let mut actions: Vec<CodeActionOrCommand> = vec![];
+    for diag in &params.context.diagnostics {
+        let data = diag
+            .data
+            .clone()
+            .and_then(|data| serde_json::from_value::<DiagnosticData>(data).ok());
+        if data.is_none() {
+            continue;
+        }
+        let data = data.unwrap();
+
+        // Create text edits for the diagnostic.
+        let edits = match &data.code {
+            ErrorCode::UnknownValue { name } => {
+                code_action_import(name, true, &path, latest_content, program)
+            }
+            ErrorCode::UnknownTypeOrTrait { name } => {
+                code_action_import(name, false, &path, latest_content, program)
+            }
+            ErrorCode::MissingTypeSignature { .. } => match &data.signature {
+                Some(sign) => Ok(code_action_type_signature(sign, diag, latest_content)),
+                None => continue,
+            },
+            ErrorCode::MissingField { field } => {
+                code_action_missing_field(field, diag, latest_content)
+            }
+        };
+        let edits = match edits {
+            Ok(edits) => edits,
+            Err(e) => {
+                write_log(log_file.clone(), &e);
+                continue;
+            }
+        };
+
+        for (title, edit) in edits {
+            actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                title,
+                kind: Some(CodeActionKind::QUICKFIX),
+                diagnostics: Some(vec![diag.clone()]),
+                edit: Some(WorkspaceEdit {
+                    changes: Some(HashMap::from([(uri.clone(), vec![edit])])),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }));
+        }
+    }
+    send_response(id, Ok::<_, ()>(actions))
+}
+
+// Create text edits which insert an import statement for an unknown name.
+// For each entity which has the name as a suffix and is not accessible from the module, `import Mod::{name};` is inserted.
+fn code_action_import(
+    name: &str,
+    is_value: bool,
+    path: &Path,
+    content: &str,
+    program: &Program,
+) -> Result<Vec<(String, TextEdit)>, String> {
+    let short_name = FullName::parse(name).unwrap();
+    let module = get_module_of_file(program, path)?;
+    let import_stmts = get_import_statements_of_file(program, path)?;
+
+    // Collect entities which may be referred by the name.
+    let entities: Vec<FullName> = if is_value {
+        program.global_values.keys().cloned().collect()
+    } else {
+        let mut names = program.tycon_names_with_aliases();
+        names.extend(program.trait_names_with_aliases());
+        names.into_iter().collect()
+    };
+    let mut candidates = entities
+        .into_iter()
+        .filter(|entity| short_name.is_suffix(entity))
+        .filter(|entity| !import::is_accessible(&import_stmts, entity))
+        .filter(|entity| ImportStatement::single_entity(&module, entity).is_accessible(entity))
+        .collect::<Vec<_>>();
+    candidates.sort();
+    Ok(import_statement_edits(&candidates, content))
+}
+
+// Create text edits which insert `import Mod::{entity};` for each entity.
+// The import statement is inserted after the last import statement, or after the module declaration.
+pub(crate) fn import_statement_edits(
+    entities: &[FullName],
+    content: &str,
+) -> Vec<(String, TextEdit)> {
+    let mut insert_line = 0;
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim_start();
+        if line.starts_with("module ") || line.starts_with("import ") {
+            insert_line = i + 1;
+        }
+    }
+    let position = lsp_types::Position {
+        line: insert_line as u32,
+        character: 0,
+    };
+
+    let mut edits = vec![];
+    for entity in entities {
+        let mut item = entity.clone();
+        let module = item.namespace.names[0].clone();
+        item.pop_front_namespace();
+        let stmt = format!("import {}::{{{}}};", module, item.to_string());
+        edits.push((
+            format!("Add `{}`", stmt),
+            TextEdit {
+                range: lsp_types::Range {
+                    start: position,
+                    end: position,
+                },
+                new_text: stmt + "\n",
+            },
+        ));
+    }
+    edits
+}
+
+// Create a text edit which inserts the type signature above the definition of a global value, keeping its indentation.
+pub(crate) fn code_action_type_signature(
+    sign: &str,
+    diag: &lsp_types::Diagnostic,
+    content: &str,
+) -> Vec<(String, TextEdit)> {
+    let line = content
+        .lines()
+        .nth(diag.range.start.line as usize)
+        .unwrap_or_default();
+    let indent = &line[..line.len() - line.trim_start().len()];
+    let position = lsp_types::Position {
+        line: diag.range.start.line,
+        character: 0,
+    };
+    vec![(
+        format!("Add type signature `{}`", sign),
+        TextEdit {
+            range: lsp_types::Range {
+                start: position,
+                end: position,
+            },
+            new_text: format!("{}{}\n", indent, sign),
+        },
+    )]
+}
+
+// Infer type signatures of global values which lack them, and stringify them as `name : <scheme>;`.
+// The type signature `name : a;` is inserted before each definition, and the scheme of the value is inferred from its expression.
+// This is run in the diagnostics thread, since it loads the whole program again.
+fn infer_missing_type_signatures(errs: &Errors) -> Result<HashMap<FullName, String>, Errors> {
+    // Collect the values lacking their type signatures, grouped by the file.
+    let mut file_to_defns: HashMap<PathBuf, (Span, Vec<(FullName, usize)>)> = HashMap::new();
+    for err in errs.iter() {
+        let name = match &err.code {
+            Some(ErrorCode::MissingTypeSignature { name }) => FullName::parse(name),
+            _ => None,
+        };
+        let span = err.srcs.first();
+        if name.is_none() || span.is_none() {
+            continue;
+        }
+        let (name, span) = (name.unwrap(), span.unwrap());
+        file_to_defns
+            .entry(to_absolute_path(&span.input.file_path))
+            .or_insert_with(|| (span.clone(), vec![]))
+            .1
+            .push((name, span.start));
+    }
+    if file_to_defns.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    // Replace the source files by the ones where type signatures are inserted.
+    let mut config = create_diagnostics_config()?;
+    let mut names = vec![];
+    for (path, (span, mut defns)) in file_to_defns {
+        let mut source = span.input.string()?;
+        defns.sort_by_key(|(_, start)| std::cmp::Reverse(*start));
+        for (name, start) in defns {
+            source.insert_str(start, &format!("{} : a; ", name.name));
+            names.push(name);
+        }
+        let hash = format!("{:x}", md5::compute(&source));
+        save_temporary_source(&source, LSP_INFER_SOURCE_NAME, &hash);
+        if let Some(file) = config
+            .source_files
+            .iter_mut()
+            .find(|file| to_absolute_path(file) == path)
+        {
+            *file = temporary_source_path(LSP_INFER_SOURCE_NAME, &hash);
+        }
+    }
+    let mut program = load_source_files(&mut config)?;
+    let tc = create_typechecker(&mut program, &config)?;
+
+    // Infer the schemes.
+    let tycon_names = program.tycon_names_with_aliases();
+    let trait_names = program.trait_names_with_aliases();
+    let mut signatures = HashMap::new();
+    for name in names {
+        let scm = match program.infer_scheme_of_global_value(&tc, &name) {
+            Ok(scm) => scm,
+            Err(_) => continue,
+        };
+        let import_stmts = program
+            .mod_to_import_stmts
+            .get(&name.module())
+            .cloned()
+            .unwrap_or_default();
+        let scm = shorten_scheme_for_display(&scm, &import_stmts, &tycon_names, &trait_names);
+        signatures.insert(name.clone(), format!("{} : {};", name.name, scm));
+    }
+    Ok(signatures)
+}
+
+// Create a text edit which adds a missing field to a struct literal.
+// The value of the field is `undefined()`, which should be replaced by the user.
+pub(crate) fn code_action_missing_field(
+    field: &str,
+    diag: &lsp_types::Diagnostic,
+    content: &str,
+) -> Result<Vec<(String, TextEdit)>, String> {
+    // Find the braces of the struct literal.
+    let end = position_to_bytes(content, diag.range.end);
+    let literal_end = content[..end].rfind('}');
+    if literal_end.is_none() {
+        return Err("Failed to find the struct literal.".to_string());
+    }
+    let literal_end = literal_end.unwrap();
+    let bytes = content.as_bytes();
+    let mut depth = 0;
+    let mut literal_start = None;
+    for i in (0..literal_end).rev() {
+        match bytes[i] {
+            b')' | b']' | b'}' => depth += 1,
+            b'(' | b'[' => depth -= 1,
+            b'{' if depth == 0 => {
+                literal_start = Some(i);
+                break;
+            }
+            b'{' => depth -= 1,
+            _ => {}
+        }
+    }
+    if literal_start.is_none() {
+        return Err("Failed to find the struct literal.".to_string());
+    }
+    let has_fields = !content[literal_start.unwrap() + 1..literal_end]
+        .trim()
+        .is_empty();
+
+    // Insert the field before the closing brace.
+    let field_text = format!("{}: undefined()", field);
+    let new_text = if has_fields {
+        format!(", {}", field_text)
+    } else {
+        field_text.clone()
+    };
+    let insert_pos = content[..literal_end].trim_end().len();
+    let position = bytes_to_position(content, insert_pos);
+    Ok(vec![(
+        format!("Add field `{}`", field_text),
+        TextEdit {
+            range: lsp_types::Range {
+                start: position,
+                end: position,
+            },
+            new_text,
+        },
+    )])
+}
+
+// Stringify a type for display, shortening names of type constructors as far as they are unambiguous under the import statements.
+pub(crate) fn shorten_type_for_display(
+    ty: &Arc<TypeNode>,
+    import_stmts: &[ImportStatement],
+    tycon_names: &HashSet<FullName>,
+) -> String {
+    ty.map_tycon_names(&|name| shorten_tycon_name(name, import_stmts, tycon_names))
+        .to_string_normalize()
+}
+
+// Stringify a scheme for display, shortening names of type constructors and traits as `shorten_type_for_display`.
+pub(crate) fn shorten_scheme_for_display(
+    scm: &Arc<Scheme>,
+    import_stmts: &[ImportStatement],
+    tycon_names: &HashSet<FullName>,
+    trait_names: &HashSet<FullName>,
+) -> String {
+    let shorten = |name: &FullName| shorten_tycon_name(name, import_stmts, tycon_names);
+    let mut scm = scm.as_ref().clone();
+    for pred in &mut scm.predicates {
+        pred.ty = pred.ty.map_tycon_names(&shorten);
+        pred.trait_id.name =
+            shortest_accessible_name(import_stmts, &pred.trait_id.name, trait_names);
+    }
+    for eq in &mut scm.equalities {
+        for arg in &mut eq.args {
+            *arg = arg.map_tycon_names(&shorten);
+        }
+        eq.value = eq.value.map_tycon_names(&shorten);
+    }
+    scm.ty = scm.ty.map_tycon_names(&shorten);
+    scm.to_string_normalize()
+}
+
+// Shorten the name of a type constructor as far as it is unambiguous under the import statements.
+fn shorten_tycon_name(
+    name: &FullName,
+    import_stmts: &[ImportStatement],
+    tycon_names: &HashSet<FullName>,
+) -> FullName {
+    // Names of tuples are needed to show them as `(a, b)`.
+    if get_tuple_n(name).is_some() {
+        return name.clone();
+    }
+    shortest_accessible_name(import_stmts, name, tycon_names)
+}
+
+// Get the name of the module defined in the file.
+fn get_module_of_file(program: &Program, path: &Path) -> Result<Name, String> {
+    let module = program
+        .modules
+        .iter()
+        .find(|mi| to_absolute_path(&mi.source.input.file_path) == to_absolute_path(path));
+    if module.is_none() {
+        let msg = format!(
+            "No module is defined in the file: \"{}\"",
+            path.to_string_lossy().to_string()
+        );
+        return Err(msg);
+    }
+    Ok(module.unwrap().name.clone())
+}
+
+// Get import statements of the module defined in the file.
+fn get_import_statements_of_file(
+    program: &Program,
+    path: &Path,
+) -> Result<Vec<ImportStatement>, String> {
+    let module = get_module_of_file(program, path)?;
+    Ok(program
+        .mod_to_import_stmts
+        .get(&module)
+        .cloned()
+        .unwrap_or_default())
+}
+
+// Given the text before the cursor, find the innermost function application `f(...` which is not closed yet.
+// Returns the name of the function and the index of the parameter at the cursor.
+// For a method call `x.f(...`, the receiver `x` is passed as the last parameter, so the index is unchanged.
+pub(crate) fn find_function_application_at(text: &str) -> Option<(String, u32)> {
+    let bytes = text.as_bytes();
+    let mut depth = 0;
+    let mut commas = 0;
+    let mut open_paren = None;
+    for i in (0..bytes.len()).rev() {
+        match bytes[i] {
+            b')' | b']' | b'}' => depth += 1,
+            b'(' | b'[' | b'{' => {
+                if depth > 0 {
+                    depth -= 1;
+                } else if bytes[i] == b'(' {
+                    open_paren = Some(i);
+                    break;
+                } else {
+                    // The cursor is in an array literal or a block.
+                    return None;
+                }
+            }
+            b',' if depth == 0 => commas += 1,
+            b';' if depth == 0 => return None,
+            _ => {}
+        }
+    }
+    let open_paren = open_paren?;
+
+    // Get the name of the function just before `(`.
+    let is_name_char = |c: u8| c.is_ascii_alphanumeric() || c == b'_' || c == b':';
+    let end = open_paren;
+    let mut start = end;
+    while start > 0 && is_name_char(bytes[start - 1]) {
+        start -= 1;
+    }
+    let name = text[start..end].trim_start_matches(':');
+    if name.is_empty() || !name.chars().next().unwrap().is_ascii_alphabetic() {
+        return None;
+    }
+    Some((name.to_string(), commas))
+}
+
+// The length of a string in UTF-16 code units, which is used for positions in LSP.
+pub(crate) fn utf16_len(str: &str) -> u32 {
+    str.encode_utf16().count() as u32
+}
+
 // Convert a `lsp_types::Position` into a bytes position in a string.
-fn position_to_bytes(string: &str, position: lsp_types::Position) -> usize {
+pub(crate) fn position_to_bytes(string: &str, position: lsp_types::Position) -> usize {
     let mut bytes = 0;
     let mut line = 0;
     let mut pos = 0;
     for c in string.chars() {
+        if line == position.line && pos == position.character as usize {
+            break;
+        }
         bytes += c.len_utf8();
         pos += 1;
         if c == '\n' {
             line += 1;
             pos = 0;
         }
-        if line == position.line && pos == position.character as usize {
-            break;
-        }
     }
     bytes
 }
 
+// Convert a bytes position in a string into a `lsp_types::Position`.
+pub(crate) fn bytes_to_position(string: &str, bytes: usize) -> lsp_types::Position {
+    let before = &string[..bytes];
+    let line = before.matches('\n').count();
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    lsp_types::Position {
+        line: line as u32,
+        character: before[line_start..].chars().count() as u32,
+    }
+}
+
 // The entry point of the diagnostics thread.
 fn diagnostics_thread(
     req_recv: Receiver<DiagnosticsMessage>,
@@ -1006,8 +1677,14 @@ fn diagnostics_thread(
             }
             Err(errs) => errs,
         };
+        let signatures = infer_missing_type_signatures(&errs).unwrap_or_else(|e| {
+            let msg = format!("Failed to infer type signatures: {}\n", e.to_string());
+            write_log(log_file.clone(), &msg);
+            HashMap::new()
+        });
         prev_err_paths = send_diagnostics_notification(
             errs,
+            &signatures,
             std::mem::replace(&mut prev_err_paths, HashSet::new()),
             log_file.clone(),
         );
@@ -1039,6 +1716,7 @@ fn span_to_range(span: &Span) -> lsp_types::Range {
 // - `prev_err_paths`: The paths of the files that have errors in the previous diagnostics. This is used to clear the diagnostics for the files that have no errors.
 fn send_diagnostics_notification(
     errs: Errors,
+    signatures: &HashMap<FullName, String>,
     mut prev_err_paths: HashSet<PathBuf>,
     log_file: Arc<Mutex<File>>,
 ) -> HashSet<PathBuf> {
@@ -1075,7 +1753,7 @@ fn send_diagnostics_notification(
             uri,
             diagnostics: errs
                 .iter()
-                .map(|err| error_to_diagnostics(err, &cdir, log_file.clone()))
+                .map(|err| error_to_diagnostics(err, signatures, &cdir, log_file.clone()))
                 .collect(),
             version: None,
         };
@@ -1160,6 +1838,7 @@ fn send_diagnostics_error_message(msg: String, log_file: Arc<Mutex<File>>) {
 // Convert an `Error` into a diagnostic message.
 fn error_to_diagnostics(
     err: &Error,
+    signatures: &HashMap<FullName, String>,
     cdir: &PathBuf,
     log_file: Arc<Mutex<File>>,
 ) -> lsp_types::Diagnostic {
@@ -1200,6 +1879,21 @@ fn error_to_diagnostics(
         Some(related_information)
     };
 
+    // Attach the code of the error, which is used to offer quick fixes.
+    let data = err.code.as_ref().map(|code| {
+        let signature = match code {
+            ErrorCode::MissingTypeSignature { name } => FullName::parse(name)
+                .and_then(|name| signatures.get(&name))
+                .cloned(),
+            _ => None,
+        };
+        serde_json::to_value(DiagnosticData {
+            code: code.clone(),
+            signature,
+        })
+        .unwrap()
+    });
+
     lsp_types::Diagnostic {
         range,
         severity: Some(DiagnosticSeverity::ERROR),
@@ -1209,7 +1903,7 @@ fn error_to_diagnostics(
         message: err.msg.clone(),
         tags: None,
         related_information,
-        data: None,
+        data,
     }
 }
 
@@ -1229,6 +1923,16 @@ fn path_to_uri(path: &PathBuf) -> Result<lsp_types::Uri, String> {
 pub fn run_diagnostics() -> Result<DiagnosticsResult, Errors> {
     // TODO: maybe we should check if the file has been changed actually after previous diagnostics?
 
+    let mut config = create_diagnostics_config()?;
+
+    // Build the file and get the errors.
+    let program = build_file(&mut config)?.program.unwrap();
+
+    Ok(DiagnosticsResult { prgoram: program })
+}
+
+// Create the configuration for diagnostics from the project file and the lock file.
+fn create_diagnostics_config() -> Result<Configuration, Errors> {
     // Read the project file.
     let proj_file = ProjectFile::read_root_file()?;
 
@@ -1236,11 +1940,12 @@ pub fn run_diagnostics() -> Result<DiagnosticsResult, Errors> {
     let mut config = Configuration::new(SubCommand::Diagnostics)?;
 
     // Set up the configuration by the project file and the lock file.
-    proj_file.set_config(&mut config, false)?;
-    proj_file.open_lock_file()?.set_config(&mut config)?;
-
-    // Build the file and get the errors.
-    let program = build_file(&mut config)?.program.unwrap();
-
-    Ok(DiagnosticsResult { prgoram: program })
+    // Errors which are not related to a specific source are reported at the project file, so that they are shown in the editor.
+    proj_file
+        .set_config(&mut config, false)
+        .and_then(|_| proj_file.open_lock_file())
+        .and_then(|lock_file| lock_file.set_config(&proj_file, &mut config))
+        .map_err(|errs| errs.set_default_src(&proj_file.file_span()))?;
+
+    Ok(config)
 }
@@ -21,25 +21,35 @@ extern crate tempfile;
 extern crate toml;
 
 mod ast;
+mod bindgen;
 mod borrowing_optimization;
 mod builtin;
+mod c_abi;
 mod compile_unit;
 mod configuration;
 mod constants;
+mod coverage;
 mod cpu_features;
 mod dependency_lockfile;
 mod dependency_resolver;
 mod docgen;
 mod error;
+mod escape_analysis;
 mod generator;
 mod graph;
+mod inline_optimization;
+mod jit;
+mod llvm_pass_pipeline;
 mod llvm_passes;
 mod lsp;
 mod misc;
 mod object;
 mod parser;
+mod pgo;
+mod profiler;
 mod project_file;
 mod registry_file;
+mod repl;
 mod runner;
 mod runtime;
 mod sourcefile;
@@ -47,6 +57,7 @@ mod stdlib;
 mod stopwatch;
 #[cfg(test)]
 mod tests;
+mod thin_lto;
 mod typecheck;
 mod uncurry_optimization;
 
@@ -54,6 +65,7 @@ use crate::error::Errors;
 use ast::expr::*;
 use ast::import::*;
 use ast::inline_llvm::*;
+use ast::inline_statement::*;
 use ast::name::*;
 use ast::pattern::*;
 use ast::program::*;
@@ -62,13 +74,17 @@ use ast::typedecl::*;
 use ast::types::*;
 use borrowing_optimization::*;
 use builtin::*;
+use c_abi::*;
 use clap::ArgMatches;
 use clap::PossibleValue;
-use clap::{App, AppSettings, Arg};
+use clap::{App, AppSettings, Arg, ValueSource};
 use configuration::*;
 use constants::*;
+use coverage::*;
 use dependency_lockfile::DependecyLockFile;
 use error::exit_if_err;
+use error::error_exit;
+use escape_analysis::*;
 use generator::*;
 use graph::*;
 use inkwell::builder::Builder;
@@ -80,13 +96,19 @@ use inkwell::values::{
     BasicValue, BasicValueEnum, CallableValue, FunctionValue, IntValue, PointerValue,
 };
 use inkwell::{AddressSpace, IntPredicate, OptimizationLevel};
+use inline_optimization::*;
+use jit::*;
+use llvm_pass_pipeline::*;
 use lsp::language_server::launch_language_server;
 use misc::*;
 use object::*;
 use parser::*;
+use pgo::*;
+use profiler::*;
 use pest::iterators::{Pair, Pairs};
 use pest::Parser;
 use project_file::ProjectFile;
+use repl::launch_repl;
 use runner::*;
 use runtime::*;
 use sourcefile::*;
@@ -95,8 +117,10 @@ use std::fs::File;
 use std::io::Read;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::Arc;
 use std::vec::Vec;
 use stdlib::*;
+use thin_lto::*;
 use typecheck::*;
 use uncurry_optimization::*;
 
@@ -109,6 +133,14 @@ fn main() {
         .multiple_values(true)
         .takes_value(true)
         .help("Source files to be compiled and linked.");
+    let source_roots = Arg::new("source-roots")
+        .long("source-roots")
+        .short('R')
+        .action(clap::ArgAction::Append)
+        .multiple_values(true)
+        .takes_value(true)
+        .help("Add directories where source files of imported modules are searched. \n\
+              A module `Foo.Bar` is searched as \"Foo/Bar.fix\" or \"Foo.Bar.fix\" (or in lowercase, e.g., \"foo/bar.fix\") under these directories.");
     let object_file = Arg::new("object-files")
         .long("object")
         .short('O')
@@ -150,6 +182,7 @@ fn main() {
         .possible_value(PossibleValue::new("none").help("Perform no optimizations. Good for debugging, but tail call recursion is not optimized and may cause stack overflow."))
         .possible_value(PossibleValue::new("minimum").help("Perform only few optimizations for fast compilation. Tail call recursion is optimized."))
         .possible_value(PossibleValue::new("separated").help("Perform optimizations which can be done under separate compilation."))
+        .possible_value(PossibleValue::new("thin_lto").help("Perform optimizations under separate compilation, and optimize across compilation units at link time by importing small functions. Compilation units are cached as LLVM bitcode."))
         .possible_value(PossibleValue::new("default").help("Perform all optimizations to minimize runtime. Separate compilation is disabled."))
         // .default_value("default") // we do not set default value because we want to check if this option is specified by user.
         .help("Optimization level.");
@@ -171,6 +204,91 @@ fn main() {
         .short('v')
         .takes_value(false)
         .help("Show verbose messages.");
+    let jit = Arg::new("jit")
+        .long("jit")
+        .takes_value(false)
+        .help("Run the program by the JIT compiler, without building an executable file by an external linker. This reduces overhead of running small programs.\n\
+              Object files and static libraries cannot be linked in this mode.");
+    let coverage = Arg::new("coverage")
+        .long("coverage")
+        .takes_value(false)
+        .help("Instrument the program to count executions of expressions and branches, and write coverage reports (\"lcov.info\" and \"coverage.json\") to \".fixlang/coverage\" after running the program.\n\
+              Only the source files of the project are instrumented. This option cannot be used with `--jit`.");
+    let coverage_html = Arg::new("coverage-html")
+        .long("coverage-html")
+        .takes_value(false)
+        .requires("coverage")
+        .help("Also write an HTML report which shows source files annotated with execution counts. Requires `--coverage`.");
+    let profile = Arg::new("profile")
+        .long("profile")
+        .takes_value(false)
+        .help("Instrument entries and exits of global functions and lambdas to measure their execution times, and write profile reports to \".fixlang/profile\" after running the program:\n\
+              folded stacks (\"stacks.folded\") which can be fed to flamegraph tools, and a table of functions sorted by self time (\"summary.txt\").\n\
+              This option cannot be used with `--jit`.");
+    let pgo_generate = Arg::new("pgo-generate")
+        .long("pgo-generate")
+        .takes_value(false)
+        .help("Instrument the program to count taken branches and calls of global functions for profile-guided optimization.\n\
+              The instrumented program writes the profile to \"default.fixprof\" (or to the path given by the environment variable `FIX_PGO_PROFILE`) at exit.");
+    let pgo_use = Arg::new("pgo-use")
+        .long("pgo-use")
+        .action(clap::ArgAction::Append)
+        .multiple_values(true)
+        .takes_value(true)
+        .help("Optimize the program using profiles written by a program built with `--pgo-generate`. If multiple profiles are given, their counts are summed up.");
+    let llvm_passes = Arg::new("llvm-passes")
+        .long("llvm-passes")
+        .takes_value(true)
+        .help("Comma-separated names of LLVM passes used instead of the default optimization passes, e.g., \"function_inlining,instruction_combining,default\".\n\
+              The name \"default\" stands for the default passes. Effective only under the \"separated\" or \"default\" optimization level.");
+    let c_compiler = Arg::new("c-compiler")
+        .long("cc")
+        .takes_value(true)
+        .help("The C compiler used to compile the runtime and to link the executable. Defaults to the value of the environment variable `FIX_CC` or `CC`, or \"gcc\".");
+    let linker = Arg::new("linker")
+        .long("linker")
+        .takes_value(true)
+        .help("The linker used by the C compiler, e.g., \"lld\". This is passed to the C compiler as `-fuse-ld={linker}`.");
+    let link_flags = Arg::new("link-flags")
+        .long("link-flag")
+        .action(clap::ArgAction::Append)
+        .takes_value(true)
+        .allow_hyphen_values(true)
+        .help("Add a flag passed to the C compiler when linking the executable, e.g., `--link-flag=-static`.");
+    let pie = Arg::new("pie")
+        .long("pie")
+        .takes_value(false)
+        .conflicts_with("no-pie")
+        .help("Generate a position independent executable.");
+    let no_pie = Arg::new("no-pie")
+        .long("no-pie")
+        .takes_value(false)
+        .help("Generate a position dependent executable. This is the default.");
+    let build_profile = Arg::new("build-profile")
+        .long("profile")
+        .alias("build-profile")
+        .takes_value(true)
+        .help("The build profile defined by `[profile.{name}]` section in the project file. \"dev\" and \"release\" are available even if they are not defined.\n\
+              \"dev\" uses the \"minimum\" optimization level and \"release\" uses the \"default\" one, both without debug info, unless the project file overwrites them.\n\
+              Defaults to \"release\" for `fix build`, and \"dev\" for other subcommands. `--build-profile` is an alias of this option.");
+    // In `fix run`, `--profile` turns on the profiler, so a build profile is selected by `--build-profile`.
+    let build_profile_run = Arg::new("build-profile")
+        .long("build-profile")
+        .takes_value(true)
+        .help("The build profile defined by `[profile.{name}]` section in the project file. \"dev\" and \"release\" are available even if they are not defined.\n\
+              \"dev\" uses the \"minimum\" optimization level and \"release\" uses the \"default\" one, both without debug info, unless the project file overwrites them.\n\
+              Defaults to \"dev\".");
+    let features = Arg::new("features")
+        .long("features")
+        .action(clap::ArgAction::Append)
+        .takes_value(true)
+        .use_value_delimiter(true)
+        .help("Enable features of the project, separated by commas, e.g., `--features=threaded,json`.\n\
+              A feature of a dependency can be enabled by \"dep-name/feature\".");
+    let offline = Arg::new("offline")
+        .long("offline")
+        .takes_value(false)
+        .help("Resolve and install dependencies without network access, using only vendored or already installed sources.");
     let max_cu_size = Arg::new("max-cu-size")
         .long("max-cu-size")
         .takes_value(true)
@@ -186,6 +304,7 @@ fn main() {
     let build_subc = App::new("build")
         .about("Builds the binary of a Fix program.")
         .arg(source_file.clone())
+        .arg(source_roots.clone())
         .arg(object_file.clone())
         .arg(output_file.clone())
         .arg(static_link_library.clone())
@@ -196,12 +315,24 @@ fn main() {
         .arg(emit_llvm.clone())
         .arg(threaded.clone())
         .arg(verbose.clone())
-        .arg(max_cu_size.clone());
+        .arg(max_cu_size.clone())
+        .arg(pgo_generate.clone())
+        .arg(pgo_use.clone())
+        .arg(llvm_passes.clone())
+        .arg(c_compiler.clone())
+        .arg(linker.clone())
+        .arg(link_flags.clone())
+        .arg(pie.clone())
+        .arg(no_pie.clone())
+        .arg(build_profile.clone())
+        .arg(features.clone())
+        .arg(offline.clone());
 
     // "fix run" subcommand
     let run_subc = App::new("run")
         .about("Runs a Fix program. Executes \"Main::main\" of type `IO ()`.")
         .arg(source_file.clone())
+        .arg(source_roots.clone())
         .arg(object_file.clone())
         .arg(output_file.clone())
         .arg(static_link_library.clone())
@@ -212,12 +343,25 @@ fn main() {
         .arg(emit_llvm.clone())
         .arg(threaded.clone())
         .arg(verbose.clone())
-        .arg(max_cu_size.clone());
+        .arg(max_cu_size.clone())
+        .arg(jit.clone())
+        .arg(profile.clone())
+        .arg(pgo_use.clone())
+        .arg(llvm_passes.clone())
+        .arg(c_compiler.clone())
+        .arg(linker.clone())
+        .arg(link_flags.clone())
+        .arg(pie.clone())
+        .arg(no_pie.clone())
+        .arg(build_profile_run.clone())
+        .arg(features.clone())
+        .arg(offline.clone());
 
     // "fix test" subcommand
     let test_subc = App::new("test")
         .about("Tests a Fix program. Executes \"Test::test\" of type `IO ()`.")
         .arg(source_file.clone())
+        .arg(source_roots.clone())
         .arg(object_file.clone())
         .arg(output_file.clone())
         .arg(static_link_library.clone())
@@ -228,27 +372,71 @@ fn main() {
         .arg(emit_llvm.clone())
         .arg(threaded.clone())
         .arg(verbose.clone())
-        .arg(max_cu_size.clone());
+        .arg(max_cu_size.clone())
+        .arg(jit.clone())
+        .arg(coverage.clone())
+        .arg(coverage_html.clone())
+        .arg(llvm_passes.clone())
+        .arg(c_compiler.clone())
+        .arg(linker.clone())
+        .arg(link_flags.clone())
+        .arg(pie.clone())
+        .arg(no_pie.clone())
+        .arg(build_profile.clone())
+        .arg(features.clone())
+        .arg(offline.clone());
+
+    // "fix repl" subcommand
+    // Programs are executed by the JIT compiler, so options for object files and executables are not accepted.
+    let repl_subc = App::new("repl")
+        .about("Starts an interactive session to evaluate Fix expressions. Modules of the project can be imported by `import` statements.")
+        .arg(source_file.clone())
+        .arg(source_roots.clone())
+        .arg(dynamic_link_library.clone())
+        .arg(library_paths.clone())
+        .arg(debug_info.clone())
+        .arg(opt_level.clone())
+        .arg(threaded.clone())
+        .arg(build_profile.clone())
+        .arg(features.clone())
+        .arg(offline.clone());
+
+    // "fix check" subcommand
+    // Only options read before type checking are accepted.
+    let check_subc = App::new("check")
+        .about("Type-checks a Fix program without generating code. Unlike `fix build`, all global values are checked, including ones which are not used from \"Main::main\".")
+        .arg(source_file.clone())
+        .arg(source_roots.clone())
+        .arg(build_profile.clone())
+        .arg(features.clone())
+        .arg(offline.clone());
 
     // "fix deps" subcommand
     let deps = App::new("deps").about("Manage dependencies.");
-    let deps_install =
-        App::new("install").about("Install dependencies specified in the lock file.");
+    let deps_install = App::new("install")
+        .about("Install dependencies specified in the lock file.")
+        .arg(offline.clone());
     let deps_update = App::new("update").about(
         "Update the lock file and install dependencies so that it satisfies the dependencies specified in the project file.",
-    );
+    )
+    .arg(offline.clone());
     let deps_add = App::new("add")
         .about("Add dependency to the specified project. Fix projects are searched in the registry files. This command requires \"fixproj.toml\" to be present in the current directory.")
         .arg(Arg::new("projects")
             .multiple_values(true)
             .takes_value(true)
             .help("Projects to be added. \nEach entry be in the form \"proj-name\" or \"proj-name@ver_req\" (e.g.,\"hashmap@0.1.0\").")
-        );
+        )
+        .arg(offline.clone());
+    let deps_vendor = App::new("vendor")
+        .about("Install dependencies and copy the sources of ones from git repositories into \"vendor\" directory, so that the project can be built without network access.")
+        .arg(offline.clone());
 
     let deps = deps
         .subcommand(deps_install)
         .subcommand(deps_update)
-        .subcommand(deps_add);
+        .subcommand(deps_add)
+        .subcommand(deps_vendor);
 
     // "fix clean" subcommand
     let clean_subc = App::new("clean").about("Removes intermediate files or cache files.");
@@ -273,6 +461,35 @@ fn main() {
                 .help("Modules for which documents should be generated."),
         );
 
+    // "fix bindgen" subcommand
+    let bindgen_subc = App::new("bindgen")
+        .about(
+            "Generate a Fix module which binds C functions, typedefs and constants declared in a C header.\n\
+            Function prototypes are converted to functions returning `IO` which call the C function by `FFI_CALL`, structs to unboxed structs, and `#define`s of numbers and enumerators to global values.\n\
+            The preprocessor is not run, and unsupported declarations (variadic functions, unions, bitfields, etc.) are skipped with warnings.",
+        )
+        .arg(
+            Arg::new("header")
+                .required(true)
+                .takes_value(true)
+                .help("The C header file."),
+        )
+        .arg(
+            Arg::new("module")
+                .long("module")
+                .short('m')
+                .required(true)
+                .takes_value(true)
+                .help("The name of the generated Fix module."),
+        )
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .short('o')
+                .takes_value(true)
+                .help("The path of the generated Fix source file. Defaults to \"{header-name}.fix\" in the current directory."),
+        );
+
     // "fix init" subcommand
     let init_subc = App::new("init")
         .about("Generates a project file \"fixproj.toml\" in the current directory.");
@@ -283,11 +500,14 @@ fn main() {
         .subcommand(build_subc)
         .subcommand(run_subc)
         .subcommand(test_subc)
+        .subcommand(repl_subc)
+        .subcommand(check_subc)
         .subcommand(clean_subc)
         .subcommand(lsp_subc)
         .subcommand(deps)
         .subcommand(docs_subc)
-        .subcommand(init_subc);
+        .subcommand(init_subc)
+        .subcommand(bindgen_subc);
 
     fn read_source_files_options(m: &ArgMatches) -> Result<Vec<PathBuf>, Errors> {
         let files = m.get_many::<String>("source-files");
@@ -302,8 +522,17 @@ fn main() {
         Ok(pathbufs)
     }
 
+    fn read_source_roots_option(m: &ArgMatches) -> Vec<PathBuf> {
+        m.try_get_many::<String>("source-roots")
+            .unwrap_or_default()
+            .unwrap_or_default()
+            .map(|v| PathBuf::from(v))
+            .collect::<Vec<_>>()
+    }
+
     fn read_object_files_options(m: &ArgMatches) -> Result<Vec<PathBuf>, Errors> {
-        let files = m.get_many::<String>("object-files");
+        // Not all subcommands accept object files.
+        let files = m.try_get_many::<String>("object-files").unwrap_or_default();
         if files.is_none() {
             return Ok(vec![]);
         }
@@ -329,7 +558,9 @@ fn main() {
     }
 
     fn read_output_file_option(m: &ArgMatches) -> Option<PathBuf> {
-        m.get_one::<String>("output-file").map(|s| PathBuf::from(s))
+        m.try_get_one::<String>("output-file")
+            .unwrap_or_default()
+            .map(|s| PathBuf::from(s))
     }
 
     fn read_library_options(m: &ArgMatches) -> Vec<(String, LinkType)> {
@@ -372,6 +603,11 @@ fn main() {
             .source_files
             .append(&mut read_source_files_options(args)?);
 
+        // Set `source_roots`.
+        config
+            .source_roots
+            .append(&mut read_source_roots_option(args));
+
         // Set `object_files`.
         config
             .object_files
@@ -391,20 +627,20 @@ fn main() {
             .append(&mut read_library_paths_option(args));
 
         // Set `emit_llvm`.
-        config.emit_llvm = args.contains_id("emit-llvm");
+        config.emit_llvm = args.try_contains_id("emit-llvm").unwrap_or(false);
 
         // Set `threaded`.
-        if args.contains_id("threaded") {
+        if args.try_contains_id("threaded").unwrap_or(false) {
             config.set_threaded();
         }
 
         // Set `debug_info`.
-        if args.contains_id("debug-info") {
+        if args.try_contains_id("debug-info").unwrap_or(false) {
             config.set_debug_info();
         }
 
         // Set `opt_level`.
-        if args.contains_id("opt-level") {
+        if args.try_contains_id("opt-level").unwrap_or(false) {
             // These lines should be after calling `set_debug_info`; otherwise, user cannot specify the optimization level while generating debug information.
             let opt_level = args.get_one::<String>("opt-level").unwrap();
             match opt_level.as_str() {
@@ -415,6 +651,9 @@ fn main() {
                 OPTIMIZATION_LEVEL_SEPARATED => {
                     config.set_fix_opt_level(FixOptimizationLevel::Separated)
                 }
+                OPTIMIZATION_LEVEL_THIN_LTO => {
+                    config.set_fix_opt_level(FixOptimizationLevel::ThinLTO)
+                }
                 OPTIMIZATION_LEVEL_DEFAULT => {
                     config.set_fix_opt_level(FixOptimizationLevel::Default)
                 }
@@ -423,14 +662,68 @@ fn main() {
         }
 
         // Set `verbose`.
-        if args.contains_id("verbose") {
+        if args.try_contains_id("verbose").unwrap_or(false) {
             config.verbose = true;
         }
 
+        // Set `jit`.
+        if args.try_contains_id("jit").unwrap_or(false) {
+            config.jit = true;
+        }
+
+        // Set `coverage` and `coverage_html`.
+        if args.try_contains_id("coverage").unwrap_or(false) {
+            config.coverage = true;
+        }
+        if args.try_contains_id("coverage-html").unwrap_or(false) {
+            config.coverage_html = true;
+        }
+
+        // Set `profile`.
+        if args.try_contains_id("profile").unwrap_or(false) {
+            config.profile = true;
+        }
+
+        // Set `pgo_generate` and `pgo_profile`.
+        if args.try_contains_id("pgo-generate").unwrap_or(false) {
+            config.pgo_generate = true;
+        }
+        if let Ok(Some(paths)) = args.try_get_many::<String>("pgo-use") {
+            let paths = paths.map(PathBuf::from).collect::<Vec<_>>();
+            config.pgo_profile = Some(Arc::new(PgoProfile::load(&paths)?));
+        }
+
+        // Set `llvm_passes`.
+        if let Ok(Some(passes)) = args.try_get_one::<String>("llvm-passes") {
+            let passes = parse_llvm_pass_names(passes);
+            validate_llvm_pass_names(&passes)?;
+            config.llvm_passes = Some(passes);
+        }
+
+        // Set the C toolchain.
+        if let Ok(Some(cc)) = args.try_get_one::<String>("c-compiler") {
+            config.c_compiler = cc.clone();
+        }
+        if let Ok(Some(linker)) = args.try_get_one::<String>("linker") {
+            config.linker = Some(linker.clone());
+        }
+        if let Ok(Some(flags)) = args.try_get_many::<String>("link-flags") {
+            config.link_flags.extend(flags.cloned());
+        }
+        if args.try_contains_id("pie").unwrap_or(false) {
+            config.pie = true;
+        }
+        if args.try_contains_id("no-pie").unwrap_or(false) {
+            config.pie = false;
+        }
+
         // Set `max_cu_size`.
-        config.max_cu_size = *args
-            .get_one::<usize>("max-cu-size")
-            .unwrap_or(&DEFAULT_COMPILATION_UNIT_MAX_SIZE);
+        // The default value of the option should not overwrite the value given by the build profile.
+        if args.try_contains_id("max-cu-size").unwrap_or(false)
+            && args.value_source("max-cu-size") == Some(ValueSource::CommandLine)
+        {
+            config.max_cu_size = *args.get_one::<usize>("max-cu-size").unwrap();
+        }
 
         Ok(())
     }
@@ -439,11 +732,34 @@ fn main() {
     fn create_config(subcommand: SubCommand, args: &ArgMatches) -> Configuration {
         let mut config = Configuration::release_mode(subcommand);
 
+        // Select the build profile, which is applied while reading the project file.
+        if let Ok(Some(profile)) = args.try_get_one::<String>("build-profile") {
+            config.build_profile = profile.clone();
+        }
+        config.set_build_profile_defaults();
+
+        // Features are also resolved while reading the project file.
+        if let Ok(Some(features)) = args.try_get_many::<String>("features") {
+            config.features.extend(features.cloned());
+        }
+
+        // Dependencies are installed while reading the project file.
+        if args.try_contains_id("offline").unwrap_or(false) {
+            config.offline = true;
+        }
+
         // First, set up configuration from the project file if it exists.
         if Path::new(PROJECT_FILE_PATH).exists() {
             let proj_file = exit_if_err(ProjectFile::read_root_file());
             exit_if_err(proj_file.set_config(&mut config, false));
             exit_if_err(proj_file.install_dependencies(&mut config));
+        } else if config.build_profile != BUILD_PROFILE_DEV
+            && config.build_profile != BUILD_PROFILE_RELEASE
+        {
+            error_exit(&format!(
+                "Unknown build profile \"{}\": the project file is not found.",
+                config.build_profile
+            ));
         }
 
         // Secondly, set up configuration from the command line arguments, to overwrite the configuration described in the project file.
@@ -461,19 +777,35 @@ fn main() {
         Some(("test", args)) => {
             run_file(create_config(SubCommand::Test, args));
         }
+        Some(("repl", args)) => {
+            launch_repl(create_config(SubCommand::Repl, args));
+        }
+        Some(("check", args)) => {
+            exit_if_err(check_file(&mut create_config(SubCommand::Diagnostics, args)));
+        }
         Some(("deps", args)) => match args.subcommand() {
-            Some(("install", _args)) => {
+            Some(("install", args)) => {
                 let proj_file = exit_if_err(ProjectFile::read_root_file());
-                exit_if_err(proj_file.open_lock_file().and_then(|lf| lf.install()));
+                let offline = args.contains_id("offline") || proj_file.offline();
+                exit_if_err(proj_file.open_lock_file().and_then(|lf| lf.install(offline)));
             }
-            Some(("update", _args)) => {
-                exit_if_err(DependecyLockFile::update_and_install());
+            Some(("update", args)) => {
+                exit_if_err(DependecyLockFile::update_and_install(
+                    args.contains_id("offline"),
+                ));
             }
             Some(("add", args)) => {
                 let projects = read_projects_option(args);
                 let proj_file = exit_if_err(ProjectFile::read_root_file());
-                exit_if_err(proj_file.add_dependencies(&projects));
-                exit_if_err(DependecyLockFile::update_and_install());
+                let offline = args.contains_id("offline") || proj_file.offline();
+                exit_if_err(proj_file.add_dependencies(&projects, offline));
+                exit_if_err(DependecyLockFile::update_and_install(offline));
+            }
+            Some(("vendor", args)) => {
+                let proj_file = exit_if_err(ProjectFile::read_root_file());
+                let offline = args.contains_id("offline") || proj_file.offline();
+                let lock_file = exit_if_err(proj_file.open_or_create_lock_file(offline));
+                exit_if_err(lock_file.install(offline).and_then(|_| lock_file.vendor()));
             }
             _ => eprintln!("Unknown command!"),
         },
@@ -490,6 +822,12 @@ fn main() {
         Some(("init", _args)) => {
             exit_if_err(ProjectFile::create_example_file());
         }
+        Some(("bindgen", args)) => {
+            let header = args.get_one::<String>("header").unwrap();
+            let module = args.get_one::<String>("module").unwrap();
+            let output = args.get_one::<String>("output").map(|path| Path::new(path));
+            exit_if_err(bindgen::run_bindgen(Path::new(header), module, output));
+        }
         _ => eprintln!("Unknown command!"),
     }
 }
@@ -827,6 +827,70 @@ impl ObjectFieldType {
         ret
     }
 
+    // Get fields of a boxed struct as Objects (with refcnt managed), and a "reuse token" of the struct.
+    // If the struct is unique, the struct is not freed and the returned token points to its memory, which can be reused for another object of the same size.
+    // Otherwise, the struct is released as `get_struct_fields` does and the token is null.
+    // The caller is responsible to free the token if it is not reused.
+    pub fn get_struct_fields_with_reuse_token<'c, 'm>(
+        gc: &mut GenerationContext<'c, 'm>,
+        str: &Object<'c>,
+        field_indices: Vec<u32>,
+    ) -> (Vec<Object<'c>>, PointerValue<'c>) {
+        assert!(str.is_box(gc.type_env()));
+        assert!(!str.is_destructor_object());
+
+        // Collect unretained (but cloned) fields.
+        let mut ret = vec![];
+        for field_idx in &field_indices {
+            let field = ObjectFieldType::get_struct_field_noclone(gc, str, *field_idx);
+            let field_val = field.value(gc);
+            ret.push(Object::create_from_value(field_val, field.ty, gc));
+        }
+
+        let str_ptr = str.ptr(gc);
+        let (unique_bb, shared_bb) = gc.build_branch_by_is_unique(str_ptr);
+        let current_func = unique_bb.get_parent().unwrap();
+        let cont_bb = gc
+            .context
+            .append_basic_block(current_func, "cont_bb@get_struct_fields_with_reuse_token");
+        let token_ty = gc.context.i8_type().ptr_type(AddressSpace::from(0));
+
+        // If the struct is unique, release fields that are not in `ret` and keep the memory of the struct.
+        gc.builder().position_at_end(unique_bb);
+        let field_indices: HashSet<u32> = HashSet::from_iter(field_indices.into_iter());
+        for field_idx in 0..str.ty.field_types(gc.type_env()).len() {
+            let field_idx = field_idx as u32;
+            if !field_indices.contains(&field_idx) {
+                let field = ObjectFieldType::get_struct_field_noclone(gc, str, field_idx);
+                gc.release(field);
+            }
+        }
+        if gc.config.sanitize_memory {
+            let obj_id = gc.get_obj_id(str_ptr);
+            gc.call_runtime(RUNTIME_REPORT_RELEASE, &[str_ptr.into(), obj_id.into()]);
+        }
+        let unique_token = gc.cast_pointer(str_ptr, token_ty);
+        let unique_bb = gc.builder().get_insert_block().unwrap();
+        gc.builder().build_unconditional_branch(cont_bb);
+
+        // If the struct is shared, retain fields and release the struct.
+        gc.builder().position_at_end(shared_bb);
+        for field in &ret {
+            gc.retain(field.clone());
+        }
+        gc.release(str.clone());
+        let shared_bb = gc.builder().get_insert_block().unwrap();
+        gc.builder().build_unconditional_branch(cont_bb);
+
+        gc.builder().position_at_end(cont_bb);
+        let token = gc.builder().build_phi(token_ty, "reuse_token");
+        token.add_incoming(&[
+            (&unique_token, unique_bb),
+            (&token_ty.const_null(), shared_bb),
+        ]);
+        (ret, token.as_basic_value().into_pointer_value())
+    }
+
     // Set an Object to field of struct. The old value isn't released in this function.
     pub fn set_struct_field_norelease<'c, 'm>(
         gc: &mut GenerationContext<'c, 'm>,
@@ -1264,6 +1328,38 @@ pub fn allocate_obj<'c, 'm>(
     array_capacity: Option<IntValue<'c>>, // used in array
     gc: &mut GenerationContext<'c, 'm>,
     name: Option<&str>,
+) -> Object<'c> {
+    allocate_obj_with(
+        ty,
+        capture,
+        array_capacity,
+        ObjectAllocation::Heap,
+        gc,
+        name,
+    )
+}
+
+// How to allocate memory for a boxed (non-array) object.
+#[derive(Clone, Copy)]
+pub enum ObjectAllocation<'c> {
+    // Allocate by `malloc`.
+    Heap,
+    // Reuse the given memory unless it is null, otherwise allocate by `malloc`.
+    // The memory should be obtained by `malloc` and have the same size as the object.
+    Reuse(PointerValue<'c>),
+    // Allocate on the stack of the current function.
+    // The object is marked as global so that it will not be retained or released; its fields should be released by `release_fields_of_stack_obj`.
+    Stack,
+}
+
+// Allocate an object by the specified method.
+pub fn allocate_obj_with<'c, 'm>(
+    ty: Arc<TypeNode>,
+    capture: &Vec<Arc<TypeNode>>,         // used in dynamic object
+    array_capacity: Option<IntValue<'c>>, // used in array
+    allocation: ObjectAllocation<'c>,
+    gc: &mut GenerationContext<'c, 'm>,
+    name: Option<&str>,
 ) -> Object<'c> {
     assert!(ty.free_vars().is_empty());
     assert!(ty.is_dynamic() || capture.is_empty());
@@ -1283,6 +1379,36 @@ pub fn allocate_obj<'c, 'm>(
     } else {
         if object_type.is_unbox {
             gc.build_alloca_at_entry(struct_type, "alloca@allocate_obj")
+        } else if let ObjectAllocation::Stack = allocation {
+            gc.build_alloca_at_entry(struct_type, "alloca_boxed@allocate_obj")
+        } else if let ObjectAllocation::Reuse(reuse) = allocation {
+            // Call `malloc` only if the memory to be reused is null.
+            let current_bb = gc.builder().get_insert_block().unwrap();
+            let current_func = current_bb.get_parent().unwrap();
+            let malloc_bb = gc
+                .context
+                .append_basic_block(current_func, "malloc_bb@allocate_obj");
+            let cont_bb = gc
+                .context
+                .append_basic_block(current_func, "cont_bb@allocate_obj");
+            let reuse = gc.cast_pointer(reuse, ptr_type(struct_type));
+            let is_null = gc.builder().build_is_null(reuse, "is_null@allocate_obj");
+            gc.builder()
+                .build_conditional_branch(is_null, malloc_bb, cont_bb);
+
+            gc.builder().position_at_end(malloc_bb);
+            let malloced = gc
+                .builder()
+                .build_malloc(struct_type, "malloc@allocate_obj")
+                .unwrap();
+            gc.builder().build_unconditional_branch(cont_bb);
+
+            gc.builder().position_at_end(cont_bb);
+            let phi = gc
+                .builder()
+                .build_phi(ptr_type(struct_type), "ptr@allocate_obj");
+            phi.add_incoming(&[(&reuse, current_bb), (&malloced, malloc_bb)]);
+            phi.as_basic_value().into_pointer_value()
         } else {
             gc.builder()
                 .build_malloc(struct_type, "malloc@allocate_obj")
@@ -1292,7 +1418,8 @@ pub fn allocate_obj<'c, 'm>(
 
     // If sanitize memory, create object id.
     let mut object_id = obj_id_type(gc.context).const_int(0, false);
-    if gc.config.sanitize_memory && !object_type.is_unbox {
+    let is_stack = matches!(allocation, ObjectAllocation::Stack);
+    if gc.config.sanitize_memory && !object_type.is_unbox && !is_stack {
         let string_ptr = name.unwrap_or("N/A");
         let string_ptr = gc
             .builder()
@@ -1328,13 +1455,19 @@ pub fn allocate_obj<'c, 'm>(
                     .build_store(ptr_to_refcnt, refcnt_type(context).const_int(1, false));
 
                 // Initialize the reference counter state to REFCNT_STATE_LOCAL.
+                // An object on the stack is marked as global so that it will not be freed.
                 let ptr_to_refcnt_state = gc
                     .builder()
                     .build_struct_gep(ptr_to_control_block, 1, "ptr_to_refcnt_state")
                     .unwrap();
+                let refcnt_state = if is_stack {
+                    REFCNT_STATE_GLOBAL
+                } else {
+                    REFCNT_STATE_LOCAL
+                };
                 gc.builder().build_store(
                     ptr_to_refcnt_state,
-                    refcnt_state_type(context).const_int(REFCNT_STATE_LOCAL as u64, false),
+                    refcnt_state_type(context).const_int(refcnt_state as u64, false),
                 );
 
                 // If sanitize memory, set object id.
@@ -53,7 +53,7 @@ struct BindOperatorInfo {
 impl DoContext {
     // Pushes monadic value, and returns expression that represents the result of monadic action.
     fn push_monad(&mut self, monad: Arc<ExprNode>, operator_src: Span) -> Arc<ExprNode> {
-        let var_name = FullName::local(&format!("#monadic_value{}", self.counter));
+        let var_name = FullName::local(&format!("{}{}", MONADIC_VALUE_NAME, self.counter));
         let var_var = var_var(var_name.clone());
         let var_expr = expr_var(var_name, None);
         self.counter += 1;
@@ -160,8 +160,18 @@ fn parse_module(
     let mut trait_impls: Vec<TraitInstance> = vec![];
     let mut import_statements: Vec<ImportStatement> = vec![];
     let mut export_statements: Vec<ExportStatement> = vec![];
+    let mut inline_statements: Vec<InlineStatement> = vec![];
 
     for pair in pairs {
+        // Skip the item if its `CFG[...]` attribute is not satisfied.
+        match parse_cfg_attr(&pair, &ctx) {
+            Ok(true) => {}
+            Ok(false) => continue,
+            Err(e) => {
+                errors.append(e);
+                continue;
+            }
+        }
         match pair.as_rule() {
             Rule::global_defns => errors.eat_err(parse_global_defns(
                 pair,
@@ -172,6 +182,7 @@ fn parse_module(
                 &mut trait_infos,
                 &mut trait_aliases,
                 &mut export_statements,
+                &mut inline_statements,
             )),
             Rule::trait_impl => {
                 errors.eat_err_or(parse_trait_impl(pair, &mut ctx), |ti| trait_impls.push(ti));
@@ -189,6 +200,7 @@ fn parse_module(
     errors.eat_err(fix_mod.add_import_statements(import_statements));
     fix_mod.used_tuple_sizes.append(&mut ctx.tuple_sizes);
     fix_mod.export_statements = std::mem::replace(&mut export_statements, vec![]);
+    fix_mod.inline_statements = std::mem::replace(&mut inline_statements, vec![]);
 
     errors.to_result().map(|_| fix_mod)
 }
@@ -202,6 +214,7 @@ fn parse_global_defns(
     trait_infos: &mut Vec<TraitInfo>,
     trait_aliases: &mut Vec<TraitAlias>,
     export_statements: &mut Vec<ExportStatement>,
+    inline_statements: &mut Vec<InlineStatement>,
 ) -> Result<(), Errors> {
     assert_eq!(pair.as_rule(), Rule::global_defns);
     let mut errors = Errors::empty();
@@ -209,6 +222,9 @@ fn parse_global_defns(
     let pairs = pair.into_inner();
     for pair in pairs {
         match pair.as_rule() {
+            Rule::cfg_attr => {
+                // Already evaluated by the caller.
+            }
             Rule::global_defns_in_namespace => {
                 errors.eat_err(parse_global_defns_in_namespace(
                     pair,
@@ -219,6 +235,7 @@ fn parse_global_defns(
                     trait_infos,
                     trait_aliases,
                     export_statements,
+                    inline_statements,
                 ));
             }
             Rule::type_defn => {
@@ -243,12 +260,49 @@ fn parse_global_defns(
             Rule::export_statement => {
                 export_statements.push(parse_export_statement(pair, ctx));
             }
+            Rule::inline_statement => {
+                inline_statements.push(parse_inline_statement(pair, ctx));
+            }
             _ => unreachable!(),
         }
     }
     errors.to_result()
 }
 
+// Evaluate the `CFG[...]` attribute of a definition, an `impl` or an `import` statement.
+// Returns true if the attribute is absent or the condition is satisfied.
+fn parse_cfg_attr(pair: &Pair<Rule>, ctx: &ParseContext) -> Result<bool, Errors> {
+    let attr = match pair.clone().into_inner().next() {
+        Some(attr) if attr.as_rule() == Rule::cfg_attr => attr,
+        _ => return Ok(true),
+    };
+    let mut pairs = attr.into_inner();
+    let mut pair = pairs.next().unwrap();
+    let negated = pair.as_rule() == Rule::cfg_not;
+    if negated {
+        pair = pairs.next().unwrap();
+    }
+    assert_eq!(pair.as_rule(), Rule::feature_name);
+    let feature = pair.as_str();
+    let enabled = match ctx.config.project_features(&ctx.source.file_path) {
+        Some(features) => {
+            if !features.declared.iter().any(|f| f == feature) {
+                return Err(Errors::from_msg_srcs(
+                    format!(
+                        "Unknown feature `{}`. Declare it in `[features]` section of the project file.",
+                        feature
+                    ),
+                    &[&Some(Span::from_pair(&ctx.source, &pair))],
+                ));
+            }
+            features.enabled.iter().any(|f| f == feature)
+        }
+        // Source files which do not belong to any project use the features given by `--features` option.
+        None => ctx.config.features.iter().any(|f| f == feature),
+    };
+    Ok(enabled != negated)
+}
+
 fn parse_global_defns_in_namespace(
     pair: Pair<Rule>,
     ctx: &mut ParseContext,
@@ -258,6 +312,7 @@ fn parse_global_defns_in_namespace(
     trait_infos: &mut Vec<TraitInfo>,
     trait_aliases: &mut Vec<TraitAlias>,
     export_statements: &mut Vec<ExportStatement>,
+    inline_statements: &mut Vec<InlineStatement>,
 ) -> Result<(), Errors> {
     assert_eq!(pair.as_rule(), Rule::global_defns_in_namespace);
     let src = Span::from_pair(&ctx.source, &pair);
@@ -274,8 +329,18 @@ fn parse_global_defns_in_namespace(
     }
     let bak_namespace = ctx.namespace.clone();
     ctx.namespace = ctx.namespace.append(namespace);
+    let mut errors = Errors::empty();
     for pair in pairs {
-        parse_global_defns(
+        // Skip the item if its `CFG[...]` attribute is not satisfied.
+        match parse_cfg_attr(&pair, ctx) {
+            Ok(true) => {}
+            Ok(false) => continue,
+            Err(e) => {
+                errors.append(e);
+                continue;
+            }
+        }
+        errors.eat_err(parse_global_defns(
             pair,
             ctx,
             global_value_decls,
@@ -284,10 +349,11 @@ fn parse_global_defns_in_namespace(
             trait_infos,
             trait_aliases,
             export_statements,
-        )?;
+            inline_statements,
+        ));
     }
     ctx.namespace = bak_namespace;
-    Ok(())
+    errors.to_result()
 }
 
 fn parse_trait_alias(pair: Pair<Rule>, ctx: &mut ParseContext) -> TraitAlias {
@@ -462,6 +528,9 @@ fn parse_trait_impl(pair: Pair<Rule>, ctx: &mut ParseContext) -> Result<TraitIns
     assert_eq!(pair.as_rule(), Rule::trait_impl);
     let span = Span::from_pair(&ctx.source, &pair);
     let mut pairs = pair.into_inner();
+    if pairs.peek().unwrap().as_rule() == Rule::cfg_attr {
+        pairs.next();
+    }
     let qual_pred = parse_predicate_qualified(pairs.next().unwrap(), ctx)?;
     let impl_type = qual_pred.predicate.ty.clone();
     let mut methods: HashMap<Name, Arc<ExprNode>> = HashMap::default();
@@ -565,6 +634,27 @@ fn parse_export_statement(pair: Pair<Rule>, ctx: &mut ParseContext) -> ExportSta
     )
 }
 
+fn parse_inline_statement(pair: Pair<Rule>, ctx: &mut ParseContext) -> InlineStatement {
+    assert_eq!(pair.as_rule(), Rule::inline_statement);
+    let span = Span::from_pair(&ctx.source, &pair);
+    let mut pairs = pair.into_inner();
+    let attribute = match pairs.next().unwrap().as_str() {
+        "INLINE" => InlineAttribute::Inline,
+        "NOINLINE" => InlineAttribute::NoInline,
+        _ => unreachable!(),
+    };
+    let mut namespace = ctx.namespace.clone();
+    if pairs.peek().unwrap().as_rule() == Rule::namespace {
+        namespace = namespace.append(parse_namespace(pairs.next().unwrap(), ctx));
+    }
+    let fix_value_name = pairs.next().unwrap().as_str().to_string();
+    InlineStatement::new(
+        FullName::new(&namespace, &fix_value_name),
+        attribute,
+        Some(span),
+    )
+}
+
 fn parse_predicate_qualified(
     pair: Pair<Rule>,
     ctx: &mut ParseContext,
@@ -1365,6 +1455,7 @@ fn parse_expr_nlr(pair: Pair<Rule>, ctx: &mut ParseContext) -> Result<Arc<ExprNo
         Rule::expr_tuple => parse_expr_tuple(pair, ctx)?,
         Rule::expr_make_struct => parse_expr_make_struct(pair, ctx)?,
         Rule::expr_call_c => parse_expr_call_c(pair, ctx)?,
+        Rule::expr_call_c_ptr => parse_expr_call_c_ptr(pair, ctx)?,
         _ => unreachable!(),
     })
 }
@@ -1549,11 +1640,47 @@ fn parse_expr_call_c(pair: Pair<Rule>, ctx: &mut ParseContext) -> Result<Arc<Exp
         ));
     }
 
-    Ok(expr_ffi_call(fun_name, ret_ty, param_tys, args, Some(span)))
+    Ok(expr_ffi_call(
+        Some(fun_name),
+        ret_ty,
+        param_tys,
+        args,
+        Some(span),
+    ))
+}
+
+fn parse_expr_call_c_ptr(
+    pair: Pair<Rule>,
+    ctx: &mut ParseContext,
+) -> Result<Arc<ExprNode>, Errors> {
+    assert_eq!(pair.as_rule(), Rule::expr_call_c_ptr);
+    let span = Span::from_pair(&ctx.source, &pair);
+    let mut pairs = pair.into_inner();
+    let ret_ty = parse_ffi_c_fun_ty(pairs.next().unwrap(), ctx);
+    let param_tys = parse_ffi_param_tys(pairs.next().unwrap(), ctx);
+    let args = pairs
+        .map(|pair| parse_expr(pair, ctx))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Validate number of arguments. The first argument is the function pointer.
+    if args.len() != param_tys.len() + 1 {
+        return Err(Errors::from_msg_srcs(
+            "Wrong number of arguments in FFI_CALL_PTR expression.".to_string(),
+            &[&Some(span)],
+        ));
+    }
+
+    Ok(expr_ffi_call(None, ret_ty, param_tys, args, Some(span)))
 }
 
 fn parse_ffi_c_fun_ty(pair: Pair<Rule>, ctx: &mut ParseContext) -> Arc<TyCon> {
     assert_eq!(pair.as_rule(), Rule::ffi_c_fun_ty);
+    if let Some(inner) = pair.clone().into_inner().next() {
+        if inner.as_rule() == Rule::type_tycon {
+            // A struct type, whose namespace is resolved later.
+            return parse_tycon(inner);
+        }
+    }
     let name = if pair.as_str() == "()" {
         make_tuple_name(0)
     } else {
@@ -2013,7 +2140,10 @@ fn parse_pattern_union(pair: Pair<Rule>, ctx: &mut ParseContext) -> Arc<PatternN
 fn parse_import_statement(pair: Pair<Rule>, ctx: &mut ParseContext) -> ImportStatement {
     assert_eq!(pair.as_rule(), Rule::import_statement);
     let span = Span::from_pair(&ctx.source, &pair);
-    let pair = pair.into_inner().next().unwrap();
+    let pair = pair
+        .into_inner()
+        .find(|pair| pair.as_rule() != Rule::cfg_attr)
+        .unwrap();
     assert_eq!(pair.as_rule(), Rule::importee);
     let mut importee_pairs = pair.into_inner();
     let module_pair = importee_pairs.next().unwrap();
@@ -2114,7 +2244,10 @@ fn rule_to_string(r: &Rule) -> String {
         Rule::semicolon => "`;`".to_string(),
         Rule::extra_comma => ",".to_string(),
         Rule::export_symbol => "FFI_EXPORT".to_string(),
+        Rule::inline_symbol => join_by_or(&["INLINE", "NOINLINE"]),
         Rule::global_defns => "definitions".to_string(),
+        Rule::cfg_attr => "CFG".to_string(),
+        Rule::feature_name => "feature name".to_string(),
         Rule::exported_c_function_name => "C function name".to_string(),
         _ => format!("{:?}", r),
     }
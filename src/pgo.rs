@@ -0,0 +1,297 @@
+// Profile-guided optimization (PGO).
+//
+// When `config.pgo_generate` is true, the generator allocates a counter (a global `i64` variable) for each branch of `if` expressions
+// and for each function implementing a global value, and increments it when the branch is taken or the function is called.
+// The `main` function registers all counters and their keys to the runtime, which writes them to a profile file at exit.
+//
+// When a profile is given by `config.pgo_profile`, the generator passes the counts to LLVM:
+// conditional branches of `if` expressions get branch weights (`!prof` metadata) and functions get their entry counts.
+// Functions which have never been called while profiling are also marked as `cold`.
+//
+// The profile is a text file in Fix's own format (one "<count>\t<key>" per line), not an LLVM profile (".profraw" / ".profdata").
+// Counters are keyed by `if` expressions and global values of the Fix source, so the profile stays valid as long as the source is unchanged,
+// whereas LLVM profiles are keyed by hashes of the control flow graph of the generated IR.
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use inkwell::{
+    attributes::{Attribute, AttributeLoc},
+    module::Linkage,
+    values::{BasicValue, FunctionValue, InstructionValue},
+    AddressSpace, AtomicOrdering, AtomicRMWBinOp,
+};
+
+use crate::{
+    constants::{
+        PGO_COUNTERS_NAME, PGO_COUNTER_NAME_PREFIX, PGO_DEFAULT_PROFILE_PATH, PGO_KEYS_NAME,
+    },
+    error::Errors,
+    generator::GenerationContext,
+    runtime::RUNTIME_PGO_REGISTER,
+    sourcefile::Span,
+};
+
+// Counts loaded from profile files.
+pub struct PgoProfile {
+    // Map from the key of a counter to its count.
+    counts: HashMap<String, u64>,
+    // Hash value of the counts, which affects the object file generation.
+    hash: String,
+}
+
+impl PgoProfile {
+    // Load profile files written by instrumented programs.
+    // If multiple files are given, counts of the same key are summed up.
+    pub fn load(paths: &[PathBuf]) -> Result<PgoProfile, Errors> {
+        let mut counts: HashMap<String, u64> = HashMap::new();
+        for path in paths {
+            let content = fs::read(path).map_err(|e| {
+                Errors::from_msg(format!(
+                    "Failed to read profile \"{}\": {}",
+                    path.to_string_lossy(),
+                    e
+                ))
+            })?;
+            if is_llvm_profile(&content) {
+                return Err(Errors::from_msg(format!(
+                    "\"{}\" is an LLVM profile, which is not supported. Give a profile written by a program built with `--pgo-generate`.",
+                    path.to_string_lossy()
+                )));
+            }
+            let content = String::from_utf8(content).map_err(|_| {
+                Errors::from_msg(format!(
+                    "Invalid profile \"{}\": it is not a text file.",
+                    path.to_string_lossy()
+                ))
+            })?;
+            for (line_idx, line) in content.lines().enumerate() {
+                if line.trim().is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                let count = line
+                    .split_once('\t')
+                    .and_then(|(count, key)| Some((count.parse::<u64>().ok()?, key)));
+                let (count, key) = match count {
+                    Some(entry) => entry,
+                    None => {
+                        return Err(Errors::from_msg(format!(
+                            "Invalid profile \"{}\" at line {}.",
+                            path.to_string_lossy(),
+                            line_idx + 1
+                        )))
+                    }
+                };
+                *counts.entry(key.to_string()).or_default() += count;
+            }
+        }
+
+        let mut entries = counts
+            .iter()
+            .map(|(key, count)| format!("{}\t{}", count, key))
+            .collect::<Vec<_>>();
+        entries.sort(); // To remove randomness introduced by HashMap.
+        let hash = format!("{:x}", md5::compute(entries.join("\n")));
+        Ok(PgoProfile { counts, hash })
+    }
+
+    pub fn count(&self, key: &str) -> Option<u64> {
+        self.counts.get(key).cloned()
+    }
+
+    pub fn hash(&self) -> &str {
+        &self.hash
+    }
+}
+
+// Check if the content is a raw or indexed LLVM profile, whose magic number is "\xfflprof(r|i)\x81" stored in little endian.
+fn is_llvm_profile(content: &[u8]) -> bool {
+    content.len() >= 8 && content[0] == 0x81 && content[2..8] == *b"forpl\xff"
+}
+
+// The key of the counter of a branch of an `if` expression.
+// `branch_idx` is 0 for "then" branch and 1 for "else" branch.
+fn branch_key(if_span: &Span, branch_idx: usize) -> String {
+    format!(
+        "if {}:{}:{} {}",
+        if_span.input.file_path.to_string_lossy(),
+        if_span.start,
+        if_span.end,
+        branch_idx
+    )
+}
+
+// The key of the counter of a function.
+fn function_key(func: FunctionValue) -> String {
+    format!("fn {}", func.get_name().to_str().unwrap())
+}
+
+// Generate code to increment the counter of the given key.
+fn build_pgo_counter<'c, 'm>(gc: &mut GenerationContext<'c, 'm>, key: String) {
+    // Add a counter.
+    let i64_type = gc.context.i64_type();
+    let name = format!("{}{}", PGO_COUNTER_NAME_PREFIX, gc.pgo_counters.len());
+    let counter = gc.module.add_global(i64_type, None, &name);
+    counter.set_initializer(&i64_type.const_zero());
+    counter.set_linkage(Linkage::Internal);
+
+    // Increment the counter.
+    let ptr = counter.as_pointer_value();
+    let one = i64_type.const_int(1, false);
+    if gc.config.threaded {
+        gc.builder()
+            .build_atomicrmw(AtomicRMWBinOp::Add, ptr, one, AtomicOrdering::Monotonic)
+            .unwrap();
+    } else {
+        let count = gc.builder().build_load(ptr, "pgo_count").into_int_value();
+        let count = gc.builder().build_int_add(count, one, "pgo_count");
+        gc.builder().build_store(ptr, count);
+    }
+
+    gc.pgo_counters.push((key, counter));
+}
+
+// Generate code to increment the counter of a branch of an `if` expression.
+pub fn build_pgo_branch_counter<'c, 'm>(
+    gc: &mut GenerationContext<'c, 'm>,
+    if_src: &Option<Span>,
+    branch_idx: usize,
+) {
+    if let Some(if_span) = if_src {
+        build_pgo_counter(gc, branch_key(if_span, branch_idx));
+    }
+}
+
+// Generate code to increment the counter of a function.
+// This should be called at the entry of the function.
+pub fn build_pgo_function_counter<'c, 'm>(
+    gc: &mut GenerationContext<'c, 'm>,
+    func: FunctionValue<'c>,
+) {
+    build_pgo_counter(gc, function_key(func));
+}
+
+// Generate code to register all counters to the runtime.
+// This should be called in the `main` function after all counters are created.
+pub fn build_pgo_registration<'c, 'm>(gc: &mut GenerationContext<'c, 'm>) {
+    let i64_ptr_type = gc.context.i64_type().ptr_type(AddressSpace::from(0));
+    let i8_ptr_type = gc.context.i8_type().ptr_type(AddressSpace::from(0));
+
+    // Create the array of pointers to counters.
+    let counter_ptrs = gc
+        .pgo_counters
+        .iter()
+        .map(|(_, counter)| counter.as_pointer_value())
+        .collect::<Vec<_>>();
+    let counters_ty = i64_ptr_type.array_type(counter_ptrs.len() as u32);
+    let counters = gc.module.add_global(counters_ty, None, PGO_COUNTERS_NAME);
+    counters.set_initializer(&i64_ptr_type.const_array(&counter_ptrs));
+    counters.set_linkage(Linkage::Internal);
+    counters.set_constant(true);
+    let counters = gc.builder().build_pointer_cast(
+        counters.as_pointer_value(),
+        i64_ptr_type.ptr_type(AddressSpace::from(0)),
+        "pgo_counters",
+    );
+
+    // Create the array of keys.
+    let keys = gc
+        .pgo_counters
+        .iter()
+        .map(|(key, _)| key.clone())
+        .collect::<Vec<_>>();
+    let keys = keys
+        .iter()
+        .map(|key| {
+            gc.builder()
+                .build_global_string_ptr(key, "pgo_key")
+                .as_pointer_value()
+        })
+        .collect::<Vec<_>>();
+    // The array is a global variable since it is read by the runtime after `main` returns.
+    let keys_ty = i8_ptr_type.array_type(keys.len() as u32);
+    let keys_global = gc.module.add_global(keys_ty, None, PGO_KEYS_NAME);
+    keys_global.set_initializer(&i8_ptr_type.const_array(&keys));
+    keys_global.set_linkage(Linkage::Internal);
+    keys_global.set_constant(true);
+    let keys_ptr = gc.builder().build_pointer_cast(
+        keys_global.as_pointer_value(),
+        i8_ptr_type.ptr_type(AddressSpace::from(0)),
+        "pgo_keys",
+    );
+
+    // Call the runtime function.
+    let len = gc
+        .context
+        .i64_type()
+        .const_int(counter_ptrs.len() as u64, false);
+    let output_path = gc
+        .builder()
+        .build_global_string_ptr(PGO_DEFAULT_PROFILE_PATH, "pgo_output_path")
+        .as_pointer_value();
+    gc.call_runtime(
+        RUNTIME_PGO_REGISTER,
+        &[
+            counters.as_basic_value_enum().into(),
+            keys_ptr.into(),
+            len.into(),
+            output_path.into(),
+        ],
+    );
+}
+
+// Set branch weights to the conditional branch instruction of an `if` expression.
+pub fn set_pgo_branch_weights<'c, 'm>(
+    gc: &GenerationContext<'c, 'm>,
+    if_src: &Option<Span>,
+    cond_br: InstructionValue<'c>,
+) {
+    let (profile, if_span) = match (&gc.config.pgo_profile, if_src) {
+        (Some(profile), Some(if_span)) => (profile, if_span),
+        _ => return,
+    };
+    let then_count = profile.count(&branch_key(if_span, 0)).unwrap_or(0);
+    let else_count = profile.count(&branch_key(if_span, 1)).unwrap_or(0);
+    if then_count == 0 && else_count == 0 {
+        // The `if` expression has never been evaluated, or it did not exist when the profile was written.
+        return;
+    }
+
+    // Branch weights are 32-bit integers, so scale down the counts if necessary.
+    let scale = then_count.max(else_count) / (u32::MAX as u64) + 1;
+    let i32_type = gc.context.i32_type();
+    let weights = gc.context.metadata_node(&[
+        gc.context.metadata_string("branch_weights").into(),
+        i32_type.const_int(then_count / scale, false).into(),
+        i32_type.const_int(else_count / scale, false).into(),
+    ]);
+    cond_br
+        .set_metadata(weights, gc.context.get_kind_id("prof"))
+        .unwrap();
+}
+
+// Set the entry count to a function.
+// A function which has never been called is marked as `cold`.
+pub fn set_pgo_function_entry_count<'c, 'm>(
+    gc: &GenerationContext<'c, 'm>,
+    func: FunctionValue<'c>,
+) {
+    let profile = match &gc.config.pgo_profile {
+        Some(profile) => profile,
+        None => return,
+    };
+    let count = match profile.count(&function_key(func)) {
+        Some(count) => count,
+        None => return, // The function did not exist when the profile was written.
+    };
+    let entry_count = gc.context.metadata_node(&[
+        gc.context.metadata_string("function_entry_count").into(),
+        gc.context.i64_type().const_int(count, false).into(),
+    ]);
+    func.as_global_value()
+        .set_metadata(entry_count, gc.context.get_kind_id("prof"));
+    if count == 0 {
+        let cold = gc
+            .context
+            .create_enum_attribute(Attribute::get_named_enum_kind_id("cold"), 0);
+        func.add_attribute(AttributeLoc::Function, cold);
+    }
+}
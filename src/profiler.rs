@@ -0,0 +1,326 @@
+// Instrumentation-based function profiler.
+//
+// When `config.profile` is true, the generator calls `fixruntime_profile_enter` at the entry of each global function and lambda,
+// and `fixruntime_profile_exit` before it returns.
+// The runtime records the call tree with call counts and elapsed times for each thread, and writes the trees to a file at exit (or abort).
+// After the program finishes, the compiler reads the file and writes folded stacks (which can be fed to flamegraph tools) and a summary table.
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    configuration::Configuration,
+    constants::{PROFILE_REPORT_PATH, PROFILE_TOP_N},
+    error::Errors,
+    generator::GenerationContext,
+    misc::to_absolute_path,
+    runtime::{RUNTIME_PROFILE_ENTER, RUNTIME_PROFILE_EXIT, RUNTIME_PROFILE_REGISTER},
+    sourcefile::Span,
+    FullName,
+};
+
+// A function instrumented by the profiler.
+#[derive(Clone)]
+pub struct ProfileSite {
+    // The name of the global function, or the name of the global value in which the lambda is defined.
+    pub name: Option<FullName>,
+    // Is this site a lambda expression, rather than a global function?
+    pub is_lambda: bool,
+    // The type of the function.
+    pub ty: String,
+    // The source of the function.
+    pub span: Option<Span>,
+}
+
+impl ProfileSite {
+    // The location of the function in the form "file:line:column".
+    fn location(&self) -> String {
+        match &self.span {
+            None => "unknown".to_string(),
+            Some(span) => {
+                let (line, col) = span.start_line_col();
+                format!(
+                    "{}:{}:{}",
+                    span.input.file_path.to_string_lossy(),
+                    line,
+                    col
+                )
+            }
+        }
+    }
+
+    // The name of the function shown in reports.
+    // Lambdas are named as "{global value}::{lambda@file:line:column}".
+    fn label(&self) -> String {
+        let name = self
+            .name
+            .as_ref()
+            .map(|name| name.to_string())
+            .unwrap_or_default();
+        if !self.is_lambda {
+            return name;
+        }
+        let location = match &self.span {
+            None => "unknown".to_string(),
+            Some(span) => {
+                let (line, col) = span.start_line_col();
+                format!("{}:{}:{}", span.input.get_file_name(), line, col)
+            }
+        };
+        if name.is_empty() {
+            format!("{{lambda@{}}}", location)
+        } else {
+            format!("{}::{{lambda@{}}}", name, location)
+        }
+    }
+}
+
+// The path of the file to which the call tree is written by the instrumented program.
+pub fn profile_data_path(config: &Configuration) -> PathBuf {
+    to_absolute_path(
+        &config
+            .get_output_executable_file_path()
+            .with_extension("profile"),
+    )
+}
+
+// Generate code to notify the runtime that the function `site` is entered.
+pub fn build_profile_enter<'c, 'm>(gc: &mut GenerationContext<'c, 'm>, site: ProfileSite) {
+    let site_idx = gc.profile_sites.len();
+    gc.profile_sites.push(site);
+    let site_idx = gc.context.i64_type().const_int(site_idx as u64, false);
+    gc.call_runtime(RUNTIME_PROFILE_ENTER, &[site_idx.into()]);
+}
+
+// Generate code to notify the runtime that the current function exits.
+pub fn build_profile_exit<'c, 'm>(gc: &mut GenerationContext<'c, 'm>) {
+    gc.call_runtime(RUNTIME_PROFILE_EXIT, &[]);
+}
+
+// Generate code to register the output path of the profile data to the runtime.
+pub fn build_profile_registration<'c, 'm>(gc: &mut GenerationContext<'c, 'm>) {
+    let output_path = profile_data_path(&gc.config);
+    let output_path = gc
+        .builder()
+        .build_global_string_ptr(output_path.to_str().unwrap(), "profile_output_path")
+        .as_pointer_value();
+    gc.call_runtime(RUNTIME_PROFILE_REGISTER, &[output_path.into()]);
+}
+
+// Take the profiled sites out of the generation context.
+pub fn take_profile_sites<'c, 'm>(gc: &mut GenerationContext<'c, 'm>) -> Vec<ProfileSite> {
+    std::mem::replace(&mut gc.profile_sites, vec![])
+}
+
+// A node of the call tree written by the runtime.
+struct ProfileNode {
+    parent: i64,
+    site: i64,
+    count: i64,
+    total_ns: i64,
+    self_ns: i64,
+}
+
+// Statistics of a function.
+#[derive(Default)]
+struct ProfileStat {
+    calls: i64,
+    self_ns: i64,
+    // Time spent in recursive calls is counted only once.
+    total_ns: i64,
+}
+
+fn read_profile_nodes(path: &Path) -> Result<Vec<ProfileNode>, Errors> {
+    let content = fs::read_to_string(path).map_err(|e| {
+        Errors::from_msg(format!(
+            "Failed to read profile data from \"{}\": {}. The program may have terminated abnormally.",
+            path.to_string_lossy(),
+            e
+        ))
+    })?;
+    let mut nodes = vec![];
+    for line in content.lines() {
+        let values = line
+            .split_whitespace()
+            .map(|v| v.parse::<i64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| Errors::from_msg(format!("Invalid profile data: {}", e)))?;
+        if values.len() != 5 {
+            return Err(Errors::from_msg(format!(
+                "Invalid profile data: \"{}\"",
+                line
+            )));
+        }
+        nodes.push(ProfileNode {
+            parent: values[0],
+            site: values[1],
+            count: values[2],
+            total_ns: values[3],
+            self_ns: values[4],
+        });
+    }
+    Ok(nodes)
+}
+
+fn write_report_file(path: &Path, content: &str) -> Result<(), Errors> {
+    fs::write(path, content).map_err(|e| {
+        Errors::from_msg(format!(
+            "Failed to write \"{}\": {}",
+            path.to_string_lossy(),
+            e
+        ))
+    })
+}
+
+fn ns_to_ms(ns: i64) -> f64 {
+    ns as f64 / 1_000_000.0
+}
+
+// Format the table of functions sorted by self time.
+fn format_table(sites: &[ProfileSite], stats: &[(usize, ProfileStat)], whole_ns: i64) -> String {
+    let percentage = |ns: i64| {
+        if whole_ns == 0 {
+            0.0
+        } else {
+            ns as f64 * 100.0 / whole_ns as f64
+        }
+    };
+    let mut table = format!(
+        "{:>12} {:>7} {:>12} {:>7} {:>10}  {}\n",
+        "self (ms)", "self%", "total (ms)", "total%", "calls", "function"
+    );
+    for (site_idx, stat) in stats {
+        let site = &sites[*site_idx];
+        table += &format!(
+            "{:>12.3} {:>6.1}% {:>12.3} {:>6.1}% {:>10}  {} : {} ({})\n",
+            ns_to_ms(stat.self_ns),
+            percentage(stat.self_ns),
+            ns_to_ms(stat.total_ns),
+            percentage(stat.total_ns),
+            stat.calls,
+            site.label(),
+            site.ty,
+            site.location()
+        );
+    }
+    table
+}
+
+// Read the call tree written by the instrumented program, and write profile reports to `PROFILE_REPORT_PATH`.
+pub fn write_profile_reports(sites: &[ProfileSite], config: &Configuration) -> Result<(), Errors> {
+    let data_path = profile_data_path(config);
+    let nodes = read_profile_nodes(&data_path);
+    let _ = fs::remove_file(&data_path);
+    let nodes = nodes?;
+
+    let labels = sites.iter().map(|site| site.label()).collect::<Vec<_>>();
+    // The root of the call tree of each thread has no parent.
+    let mut roots = vec![];
+    let mut children = vec![vec![]; nodes.len()];
+    for (i, node) in nodes.iter().enumerate() {
+        if node.parent >= 0 {
+            children[node.parent as usize].push(i);
+        } else {
+            roots.push(i);
+        }
+    }
+
+    // Traverse the call tree, and calculate folded stacks and statistics of each function.
+    // Consecutive frames of the same function (i.e., direct recursion) are merged into one frame in folded stacks.
+    let mut folded: HashMap<String, i64> = HashMap::new();
+    let mut stats: HashMap<usize, ProfileStat> = HashMap::new();
+    let mut active: HashMap<usize, usize> = HashMap::new(); // The number of frames of each function in the current stack.
+    let mut stack: Vec<&str> = vec![];
+    // Elements are (node index, whether the label is pushed to `stack`, whether we are leaving the node).
+    let mut dfs = vec![];
+    for root in roots.iter().rev() {
+        for child in children[*root].iter().rev() {
+            dfs.push((*child, false, false));
+        }
+    }
+    while let Some((idx, pushed, leaving)) = dfs.pop() {
+        let node = &nodes[idx];
+        let site = node.site as usize;
+        if leaving {
+            if pushed {
+                stack.pop();
+            }
+            *active.get_mut(&site).unwrap() -= 1;
+            continue;
+        }
+
+        let label = labels[site].as_str();
+        let pushed = stack.last() != Some(&label);
+        if pushed {
+            stack.push(label);
+        }
+        if node.self_ns > 0 {
+            *folded.entry(stack.join(";")).or_default() += node.self_ns;
+        }
+
+        let active_count = active.entry(site).or_default();
+        let stat = stats.entry(site).or_default();
+        stat.calls += node.count;
+        stat.self_ns += node.self_ns;
+        if *active_count == 0 {
+            stat.total_ns += node.total_ns;
+        }
+        *active_count += 1;
+
+        dfs.push((idx, pushed, true));
+        for child in children[idx].iter().rev() {
+            dfs.push((*child, false, false));
+        }
+    }
+
+    let report_dir = PathBuf::from(PROFILE_REPORT_PATH);
+    fs::create_dir_all(&report_dir).map_err(|e| {
+        Errors::from_msg(format!(
+            "Failed to create directory \"{}\": {}",
+            PROFILE_REPORT_PATH, e
+        ))
+    })?;
+
+    // Folded stacks. The values are in nanoseconds.
+    let mut folded = folded.into_iter().collect::<Vec<_>>();
+    folded.sort();
+    let folded = folded
+        .into_iter()
+        .map(|(stack, ns)| format!("{} {}\n", stack, ns))
+        .collect::<String>();
+    let folded_path = report_dir.join("stacks.folded");
+    write_report_file(&folded_path, &folded)?;
+
+    // Table of functions sorted by self time.
+    // The whole time is the sum over all threads.
+    let whole_ns = roots
+        .iter()
+        .flat_map(|root| children[*root].iter())
+        .map(|c| nodes[*c].total_ns)
+        .sum::<i64>();
+    let mut stats = stats.into_iter().collect::<Vec<_>>();
+    stats.sort_by(|(lhs_idx, lhs), (rhs_idx, rhs)| {
+        rhs.self_ns
+            .cmp(&lhs.self_ns)
+            .then(labels[*lhs_idx].cmp(&labels[*rhs_idx]))
+    });
+    let summary_path = report_dir.join("summary.txt");
+    write_report_file(&summary_path, &format_table(sites, &stats, whole_ns))?;
+
+    // Show top functions.
+    stats.truncate(PROFILE_TOP_N);
+    eprintln!(
+        "Top {} functions by self time (total {:.3} ms):",
+        stats.len(),
+        ns_to_ms(whole_ns)
+    );
+    eprint!("{}", format_table(sites, &stats, whole_ns));
+    eprintln!(
+        "Profile reports are written to \"{}\" and \"{}\".",
+        folded_path.to_string_lossy(),
+        summary_path.to_string_lossy()
+    );
+    Ok(())
+}
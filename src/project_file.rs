@@ -1,17 +1,22 @@
 use crate::{
-    dependency_lockfile::{DependecyLockFile, ProjectSource},
+    dependency_lockfile::{DependecyLockFile, GitReference, ProjectSource},
     error::Errors,
+    llvm_pass_pipeline::validate_llvm_pass_names,
     registry_file::RegistryFile,
-    Configuration, ExtraCommand, FixOptimizationLevel, LinkType, SourceFile, Span, SubCommand,
-    LOCK_FILE_PATH, PROJECT_FILE_PATH, TRY_FIX_RESOLVE,
+    to_absolute_path, Configuration, ExtraCommand, FixOptimizationLevel, LinkType, ProjectFeatures,
+    SourceFile, Span, SubCommand, ValgrindTool, BUILD_PROFILE_DEV, BUILD_PROFILE_RELEASE,
+    FEATURE_DEFAULT, LOCK_FILE_PATH, PROJECT_FILE_PATH, TRY_FIX_RESOLVE, VENDOR_PATH,
 };
 use semver::{Version, VersionReq};
 use serde::Deserialize;
 use std::{
+    collections::HashMap,
     fs::File,
     io::{Read, Write},
+    ops::Range,
     path::{Path, PathBuf},
 };
+use toml::Spanned;
 
 // The name of a project.
 pub type ProjectName = String;
@@ -51,6 +56,7 @@ impl ProjectFileGeneral {
 #[serde(deny_unknown_fields)]
 pub struct ProjectFileBuild {
     files: Vec<PathBuf>,
+    source_roots: Option<Vec<PathBuf>>,
     #[serde(default)]
     objects: Vec<PathBuf>,
     static_links: Option<Vec<String>>,
@@ -59,6 +65,13 @@ pub struct ProjectFileBuild {
     threaded: Option<bool>,
     debug: Option<bool>,
     opt_level: Option<String>,
+    llvm_passes: Option<Vec<String>>,
+    c_compiler: Option<String>,
+    linker: Option<String>,
+    #[serde(default)]
+    link_flags: Vec<String>,
+    pie: Option<bool>,
+    offline: Option<bool>,
     output: Option<PathBuf>,
     #[serde(default)]
     preliminary_commands: Vec<Vec<String>>,
@@ -83,6 +96,20 @@ pub struct ProjectFileBuildTest {
     memcheck: Option<bool>,
 }
 
+// A `[profile.{name}]` section of the project file.
+// The profile selected by `--build-profile` option (or `dev` / `release` by default) overrides the settings in `[build]` section.
+#[derive(Deserialize, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectFileProfile {
+    opt_level: Option<String>,
+    debug: Option<bool>,
+    threaded: Option<bool>,
+    memcheck: Option<bool>,
+    #[serde(default)]
+    link_flags: Vec<String>,
+    max_cu_size: Option<usize>,
+}
+
 // The entry of `dependencies` section of the project file.
 #[derive(Deserialize, Default, Clone)]
 #[serde(deny_unknown_fields)]
@@ -96,6 +123,14 @@ pub struct ProjectFileDependency {
     // Version requirement for the dependent project.
     // If None, the latest version is used.
     pub version: Option<String>,
+    // If true, the dependency is used only when it is enabled by a feature.
+    #[serde(default)]
+    pub optional: bool,
+    // Features of the dependent project to be enabled.
+    #[serde(default)]
+    pub features: Vec<String>,
+    // Whether to enable the `default` feature of the dependent project.
+    pub default_features: Option<bool>,
 }
 
 impl ProjectFileDependency {
@@ -106,6 +141,15 @@ impl ProjectFileDependency {
             None => VersionReq::STAR,
         }
     }
+
+    // Get the features of the dependent project enabled by this dependency.
+    pub fn requested_features(&self) -> Vec<String> {
+        let mut features = self.features.clone();
+        if self.default_features.unwrap_or(true) {
+            features.push(FEATURE_DEFAULT.to_string());
+        }
+        features
+    }
 }
 
 // The `git` field of the dependency.
@@ -114,6 +158,29 @@ impl ProjectFileDependency {
 pub struct ProjectFileDependencyGit {
     // The URL of the git repository.
     pub url: String,
+    // The commit to which the dependency is pinned.
+    pub rev: Option<String>,
+    // The branch to which the dependency is pinned.
+    pub branch: Option<String>,
+    // The tag to which the dependency is pinned.
+    pub tag: Option<String>,
+}
+
+impl ProjectFileDependencyGit {
+    // Get the reference to which the dependency is pinned.
+    // If None, the version is selected from tags of the repository.
+    pub fn reference(&self) -> Option<GitReference> {
+        if let Some(rev) = &self.rev {
+            return Some(GitReference::Rev(rev.clone()));
+        }
+        if let Some(branch) = &self.branch {
+            return Some(GitReference::Branch(branch.clone()));
+        }
+        if let Some(tag) = &self.tag {
+            return Some(GitReference::Tag(tag.clone()));
+        }
+        None
+    }
 }
 
 // The project file.
@@ -127,12 +194,73 @@ pub struct ProjectFile {
     // `dependencies` section
     #[serde(default)]
     pub dependencies: Vec<ProjectFileDependency>,
+    // `profile` sections
+    #[serde(default)]
+    pub profile: HashMap<String, ProjectFileProfile>,
+    // `features` section, which maps a feature to the features, optional dependencies and features of dependencies (written as "dep-name/feature") it enables.
+    #[serde(default)]
+    pub features: HashMap<String, Vec<String>>,
     // The hash value of the project file.
     #[serde(skip)]
     pub hash: String,
     // The path to the project file.
     #[serde(skip)]
     pub path: PathBuf,
+    // The spans of values in the project file.
+    #[serde(skip)]
+    spans: ProjectFileSpans,
+}
+
+// A spanned value in the project file.
+type SpannedValue = Option<Spanned<toml::Value>>;
+
+// Spans of values in the project file which are validated, used to report an error at the offending key or array entry.
+// This is deserialized from the same content as `ProjectFile`, so the fields mirror those of `ProjectFile`.
+#[derive(Deserialize, Default, Clone)]
+struct ProjectFileSpans {
+    #[serde(default)]
+    general: ProjectFileGeneralSpans,
+    #[serde(default)]
+    build: ProjectFileBuildSpans,
+    #[serde(default)]
+    dependencies: Vec<ProjectFileDependencySpans>,
+    #[serde(default)]
+    profile: HashMap<String, ProjectFileProfileSpans>,
+    #[serde(default)]
+    features: HashMap<String, SpannedValue>,
+}
+
+#[derive(Deserialize, Default, Clone)]
+struct ProjectFileGeneralSpans {
+    name: SpannedValue,
+    version: SpannedValue,
+}
+
+#[derive(Deserialize, Default, Clone)]
+struct ProjectFileBuildSpans {
+    opt_level: SpannedValue,
+    #[serde(default)]
+    llvm_passes: Vec<SpannedValue>,
+    #[serde(default)]
+    test: ProjectFileBuildTestSpans,
+}
+
+#[derive(Deserialize, Default, Clone)]
+struct ProjectFileBuildTestSpans {
+    opt_level: SpannedValue,
+}
+
+#[derive(Deserialize, Default, Clone)]
+struct ProjectFileProfileSpans {
+    opt_level: SpannedValue,
+}
+
+#[derive(Deserialize, Default, Clone)]
+struct ProjectFileDependencySpans {
+    name: SpannedValue,
+    path: SpannedValue,
+    git: SpannedValue,
+    version: SpannedValue,
 }
 
 impl ProjectFile {
@@ -190,6 +318,10 @@ impl ProjectFile {
         // Set `path` field.
         proj_file.path = path.to_path_buf();
 
+        // Set `spans` field.
+        // Parsing as `ProjectFileSpans` does not fail, since the content has been parsed as `ProjectFile` successfully.
+        proj_file.spans = toml::from_str(&content).unwrap_or_default();
+
         // Perform validation.
         proj_file.validate()?;
 
@@ -217,55 +349,259 @@ impl ProjectFile {
         // Validate the general section.
 
         // Validate the project name.
-        Self::validate_project_name(&self.general.name, Some(self.project_file_span(0, 0)))?;
+        Self::validate_project_name(
+            &self.general.name,
+            Some(self.value_span(&self.spans.general.name)),
+        )?;
 
         // Validate the version.
         Version::parse(&self.general.version).map_err(|e| {
             Errors::from_msg_srcs(
                 format!("Failed to parse version: {}", e),
-                &[&Some(self.project_file_span(0, 0))],
+                &[&Some(self.value_span(&self.spans.general.version))],
             )
         })?;
 
         // Validate the dependencies section.
-        let mut dep_names = vec![];
-        for dep in &self.dependencies {
+        let mut dep_names: Vec<&ProjectName> = vec![];
+        for (i, dep) in self.dependencies.iter().enumerate() {
+            let dep_spans = self.spans.dependencies.get(i).cloned().unwrap_or_default();
+            let name_span = self.value_span(&dep_spans.name);
+
             // Cannot have duplicate dependencies.
-            if dep_names.contains(&dep.name) {
+            if let Some(j) = dep_names.iter().position(|name| *name == &dep.name) {
+                let prev_name_span = self.value_span(
+                    &self
+                        .spans
+                        .dependencies
+                        .get(j)
+                        .and_then(|spans| spans.name.clone()),
+                );
                 return Err(Errors::from_msg_srcs(
                     format!("Duplicate dependency on \"{}\"", dep.name),
-                    &[&Some(self.project_file_span(0, 0))],
+                    &[&Some(name_span), &Some(prev_name_span)],
                 ));
             }
-            dep_names.push(dep.name.clone());
+            dep_names.push(&dep.name);
 
             // Validate the project name.
-            Self::validate_project_name(&dep.name, Some(self.project_file_span(0, 0)))?;
+            Self::validate_project_name(&dep.name, Some(name_span.clone()))?;
 
             // Either of `path` or `git` should be specified.
-            if (dep.path.is_none() && dep.git.is_none())
-                || (dep.path.is_some() && dep.git.is_some())
-            {
+            if dep.path.is_none() && dep.git.is_none() {
                 return Err(Errors::from_msg_srcs(
                     "Either of `path` or `git` should be specified in a dependency.".to_string(),
-                    &[&Some(self.project_file_span(0, 0))],
+                    &[&Some(name_span)],
+                ));
+            }
+            if dep.path.is_some() && dep.git.is_some() {
+                return Err(Errors::from_msg_srcs(
+                    "Either of `path` or `git` should be specified in a dependency.".to_string(),
+                    &[
+                        &Some(self.value_span(&dep_spans.path)),
+                        &Some(self.value_span(&dep_spans.git)),
+                    ],
                 ));
             }
 
+            // At most one of `rev`, `branch` and `tag` can be specified.
+            if let Some(git) = &dep.git {
+                let pins = [&git.rev, &git.branch, &git.tag];
+                if pins.iter().filter(|pin| pin.is_some()).count() > 1 {
+                    return Err(Errors::from_msg_srcs(
+                        "At most one of `rev`, `branch` and `tag` can be specified in a git dependency."
+                            .to_string(),
+                        &[&Some(self.value_span(&dep_spans.git))],
+                    ));
+                }
+            }
+
             // Validate the version.
             if let Some(version) = &dep.version {
                 VersionReq::parse(version).map_err(|e| {
                     Errors::from_msg_srcs(
                         format!("Failed to parse version: {}", e),
-                        &[&Some(self.project_file_span(0, 0))],
+                        &[&Some(self.value_span(&dep_spans.version))],
                     )
                 })?;
             }
         }
 
+        // Validate the features section.
+        let mut features = self.features.keys().collect::<Vec<_>>();
+        features.sort();
+        for feature in features {
+            let span = self.value_span(&self.spans.features.get(feature).cloned().flatten());
+
+            // The feature name can only contain alphanumeric characters, hyphens and underscores, so that it can be written in `CFG[...]`.
+            if feature.is_empty()
+                || !feature
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                return Err(Errors::from_msg_srcs(
+                    format!(
+                        "Invalid feature name \"{}\": it should only contain alphanumeric characters, hyphens and underscores.",
+                        feature
+                    ),
+                    &[&Some(span)],
+                ));
+            }
+
+            // Each item should be a feature, an optional dependency, or a feature of a dependency.
+            for item in &self.features[feature] {
+                let valid = match item.split_once('/') {
+                    Some((dep_name, dep_feature)) => {
+                        !dep_feature.is_empty()
+                            && self.dependencies.iter().any(|dep| dep.name == dep_name)
+                    }
+                    None => {
+                        self.features.contains_key(item)
+                            || self
+                                .dependencies
+                                .iter()
+                                .any(|dep| dep.optional && &dep.name == item)
+                    }
+                };
+                if !valid {
+                    return Err(Errors::from_msg_srcs(
+                        format!(
+                            "Feature \"{}\" enables \"{}\", which is neither a feature, an optional dependency nor a feature of a dependency.",
+                            feature, item
+                        ),
+                        &[&Some(span)],
+                    ));
+                }
+            }
+        }
+
+        // Validate the LLVM passes.
+        if let Some(passes) = self.build.llvm_passes.as_ref() {
+            let mut errors = Errors::empty();
+            for (i, pass) in passes.iter().enumerate() {
+                let span = self.value_span(&self.spans.build.llvm_passes.get(i).cloned().flatten());
+                errors.eat_err(
+                    validate_llvm_pass_names(std::slice::from_ref(pass))
+                        .map_err(|e| e.set_default_src(&span)),
+                );
+            }
+            errors.to_result()?;
+        }
+
+        // Validate the profile sections, including those which are not selected.
+        let mut profiles = self.profile.keys().collect::<Vec<_>>();
+        profiles.sort();
+        for name in profiles {
+            if let Some(opt_level) = self.profile[name].opt_level.as_ref() {
+                if FixOptimizationLevel::from_str(opt_level).is_none() {
+                    let span = self.value_span(
+                        &self
+                            .spans
+                            .profile
+                            .get(name)
+                            .and_then(|spans| spans.opt_level.clone()),
+                    );
+                    return Err(Errors::from_msg_srcs(
+                        format!("Unknown optimization level: \"{}\"", opt_level),
+                        &[&Some(span)],
+                    ));
+                }
+            }
+        }
+
         Ok(())
     }
 
+    // Resolve the features of this project enabled by `requested`, following the `features` section.
+    // Each item of `requested` is a feature, an optional dependency, or a feature of a dependency written as "dep-name/feature".
+    // Returns the enabled features, and the dependencies used with the features requested to them.
+    pub fn resolve_features(
+        &self,
+        requested: &[String],
+    ) -> Result<(Vec<String>, Vec<(ProjectName, Vec<String>)>), Errors> {
+        let mut enabled: Vec<String> = vec![];
+
+        // Non-optional dependencies are always used.
+        let mut deps: Vec<(ProjectName, Vec<String>)> = self
+            .dependencies
+            .iter()
+            .filter(|dep| !dep.optional)
+            .map(|dep| (dep.name.clone(), dep.requested_features()))
+            .collect();
+
+        let mut stack: Vec<String> = requested.iter().rev().cloned().collect();
+        while let Some(item) = stack.pop() {
+            let (dep_name, dep_feature) = match item.split_once('/') {
+                Some((dep_name, dep_feature)) => (dep_name, Some(dep_feature)),
+                None => {
+                    if let Some(items) = self.features.get(&item) {
+                        if !enabled.contains(&item) {
+                            enabled.push(item.clone());
+                            stack.extend(items.iter().rev().cloned());
+                        }
+                        continue;
+                    }
+                    if item == FEATURE_DEFAULT {
+                        // `default` feature does not need to be declared.
+                        continue;
+                    }
+                    (item.as_str(), None)
+                }
+            };
+
+            // Use the dependency, and request the feature to it.
+            let dep = self
+                .dependencies
+                .iter()
+                .find(|dep| dep.name == dep_name && (dep.optional || dep_feature.is_some()))
+                .ok_or_else(|| {
+                    Errors::from_msg(format!(
+                        "Project \"{}\" has no feature \"{}\".",
+                        self.general.name, item
+                    ))
+                })?;
+            let idx = match deps.iter().position(|(name, _)| name == &dep.name) {
+                Some(idx) => idx,
+                None => {
+                    deps.push((dep.name.clone(), dep.requested_features()));
+                    deps.len() - 1
+                }
+            };
+            if let Some(dep_feature) = dep_feature {
+                if !deps[idx].1.iter().any(|f| f == dep_feature) {
+                    deps[idx].1.push(dep_feature.to_string());
+                }
+            }
+        }
+
+        Ok((enabled, deps))
+    }
+
+    // Add the features of this project enabled by `requested` to the configuration.
+    // Returns the dependencies used with the features requested to them.
+    pub fn set_config_features(
+        &self,
+        config: &mut Configuration,
+        requested: &[String],
+    ) -> Result<Vec<(ProjectName, Vec<String>)>, Errors> {
+        let (enabled, deps) = self.resolve_features(requested)?;
+        let mut declared = self.features.keys().cloned().collect::<Vec<_>>();
+        declared.sort();
+        config.feature_sets.push(ProjectFeatures {
+            dir: to_absolute_path(self.path.parent().unwrap()),
+            declared,
+            enabled,
+        });
+        Ok(deps)
+    }
+
+    // Get the features requested to the root project: those given by `--features` option and the `default` feature.
+    pub fn root_requested_features(config: &Configuration) -> Vec<String> {
+        let mut requested = config.features.clone();
+        requested.push(FEATURE_DEFAULT.to_string());
+        requested
+    }
+
     // Update a configuration from a project file.
     // - `dependent_proj`: If true, self is the project file of a dependent project. In this case, append the source files, libraries, library search paths, threaded mode to the configuration but ignore other fields such as debug mode, optimization level, output file, etc.
     pub fn set_config(
@@ -298,6 +634,16 @@ impl ProjectFile {
                 }));
         }
 
+        // Append source roots.
+        if let Some(roots) = self.build.source_roots.as_ref() {
+            config.source_roots.append(
+                &mut roots
+                    .iter()
+                    .map(|p| self.join_to_project_dir(p))
+                    .collect(),
+            );
+        }
+
         // Append object files.
         config.object_files.append(
             &mut self
@@ -441,6 +787,10 @@ impl ProjectFile {
             return Ok(());
         }
 
+        // Enable features.
+        // Features of dependent projects are enabled by `DependecyLockFile::set_config`.
+        self.set_config_features(config, &Self::root_requested_features(config))?;
+
         // Set debug mode.
         if let Some(debug) = self.build.debug {
             if debug {
@@ -462,7 +812,7 @@ impl ProjectFile {
             } else {
                 return Err(Errors::from_msg_srcs(
                     format!("Unknown optimization level: \"{}\"", opt_level),
-                    &[&Some(self.project_file_span(0, 0))],
+                    &[&Some(self.value_span(&self.spans.build.opt_level))],
                 ));
             }
         }
@@ -478,17 +828,106 @@ impl ProjectFile {
                 } else {
                     return Err(Errors::from_msg_srcs(
                         format!("Unknown optimization level: \"{}\"", opt_level),
-                        &[&Some(self.project_file_span(0, 0))],
+                        &[&Some(self.value_span(&self.spans.build.test.opt_level))],
                     ));
                 }
             }
         }
 
+        // Set LLVM passes, which have been validated in `validate`.
+        if let Some(passes) = self.build.llvm_passes.as_ref() {
+            config.llvm_passes = Some(passes.clone());
+        }
+
+        // Set the C toolchain.
+        if let Some(cc) = self.build.c_compiler.as_ref() {
+            config.c_compiler = cc.clone();
+        }
+        if let Some(linker) = self.build.linker.as_ref() {
+            config.linker = Some(linker.clone());
+        }
+        config.link_flags.append(&mut self.build.link_flags.clone());
+        if let Some(pie) = self.build.pie {
+            config.pie = pie;
+        }
+
+        // Set offline mode.
+        if self.offline() {
+            config.offline = true;
+        }
+
         // Set output file.
         if let Some(output) = self.build.output.as_ref() {
             config.out_file_path = Some(PathBuf::from(output));
         }
 
+        // Apply the selected build profile.
+        self.apply_profile(config)?;
+
+        Ok(())
+    }
+
+    // Update a configuration by the build profile `config.build_profile`.
+    fn apply_profile(&self, config: &mut Configuration) -> Result<(), Errors> {
+        let name = config.build_profile.clone();
+        let profile = match self.profile.get(&name) {
+            Some(profile) => profile,
+            None => {
+                // `dev` and `release` profiles are available without defining them. Their defaults are set by `Configuration::set_build_profile_defaults`.
+                if name == BUILD_PROFILE_DEV || name == BUILD_PROFILE_RELEASE {
+                    return Ok(());
+                }
+                return Err(Errors::from_msg_srcs(
+                    format!(
+                        "Unknown build profile \"{}\". Define it by `[profile.{}]` section in the project file.",
+                        name, name
+                    ),
+                    &[&Some(self.file_span())],
+                ));
+            }
+        };
+
+        // Set debug mode.
+        if let Some(debug) = profile.debug {
+            if debug {
+                config.set_debug_info();
+            } else {
+                config.debug_info = false;
+            }
+        }
+
+        // Set optimization level, which has been validated in `validate`.
+        if let Some(opt_level) = profile.opt_level.as_ref() {
+            if let Some(opt_level) = FixOptimizationLevel::from_str(opt_level) {
+                config.fix_opt_level = opt_level;
+            }
+        }
+
+        // Set threaded-mode.
+        // `threaded = false` turns off the threaded mode enabled in `[build]` section. The pthread library may remain linked, which is harmless.
+        match profile.threaded {
+            Some(true) if !config.threaded => config.set_threaded(),
+            Some(false) => config.threaded = false,
+            _ => {}
+        }
+
+        // Set the memory check mode.
+        if let Some(memcheck) = profile.memcheck {
+            config.set_valgrind(if memcheck {
+                ValgrindTool::MemCheck
+            } else {
+                ValgrindTool::None
+            });
+        }
+
+        // Append link flags.
+        config.link_flags.append(&mut profile.link_flags.clone());
+
+        // Set the maximum size of compilation units.
+        if let Some(max_cu_size) = profile.max_cu_size {
+            config.max_cu_size = max_cu_size;
+        }
+
         Ok(())
     }
 
@@ -523,12 +962,17 @@ impl ProjectFile {
         Ok(lock_file)
     }
 
+    // Whether dependencies should be resolved and installed without network access, as specified by `offline` field.
+    pub fn offline(&self) -> bool {
+        self.build.offline.unwrap_or(false)
+    }
+
     // Open the lock file or create a new one if it does not exist.
-    pub fn open_or_create_lock_file(&self) -> Result<DependecyLockFile, Errors> {
+    pub fn open_or_create_lock_file(&self, offline: bool) -> Result<DependecyLockFile, Errors> {
         Ok(match self.open_lock_file() {
             Ok(lock_file) => lock_file,
             Err(_) => {
-                let lock_file = DependecyLockFile::create(self)?;
+                let lock_file = DependecyLockFile::create(self, offline, Path::new(VENDOR_PATH))?;
                 let content = toml::to_string(&lock_file).map_err(|e| {
                     Errors::from_msg(format!("Failed to serialize the lock file: {:?}", e))
                 })?;
@@ -541,8 +985,9 @@ impl ProjectFile {
     }
 
     // Open the lock file, create a new one if it does not exist, and install the dependencies.
-    pub fn open_or_create_lock_file_and_isntall(&self) -> Result<(), Errors> {
-        self.open_or_create_lock_file().and_then(|lf| lf.install())
+    pub fn open_or_create_lock_file_and_isntall(&self, offline: bool) -> Result<(), Errors> {
+        self.open_or_create_lock_file(offline)
+            .and_then(|lf| lf.install(offline))
     }
 
     // Update configuration by adding source files, linking libraries, ... as required by dependencies.
@@ -551,13 +996,13 @@ impl ProjectFile {
         config: &mut Configuration,
     ) -> Result<(), Errors> {
         // Update the lock file if necessary.
-        let lock_file = self.open_or_create_lock_file()?;
+        let lock_file = self.open_or_create_lock_file(config.offline)?;
 
         // Install the dependencies.
-        lock_file.install()?;
+        lock_file.install(config.offline)?;
 
         // See the dependencies and update the configuration.
-        lock_file.set_config(config)?;
+        lock_file.set_config(self, config)?;
 
         Ok(())
     }
@@ -568,6 +1013,18 @@ impl ProjectFile {
         Span { start, end, input }
     }
 
+    // Create span at the start of the project file.
+    pub fn file_span(&self) -> Span {
+        self.project_file_span(0, 0)
+    }
+
+    // Create span for a value in the project file.
+    // If the span of the value is not available, the span at the start of the project file is returned.
+    fn value_span(&self, value: &SpannedValue) -> Span {
+        let Range { start, end } = value.as_ref().map_or(0..0, |v| v.span());
+        self.project_file_span(start, end)
+    }
+
     // Convert a relative path to an absolute path by joining it with the directory of the project file.
     fn join_to_project_dir(&self, path: &Path) -> PathBuf {
         if path.is_absolute() {
@@ -587,7 +1044,7 @@ impl ProjectFile {
                 return ProjectSource::Local(self.join_to_project_dir(path));
             }
             if let Some(git) = &dep.git {
-                return ProjectSource::Git(git.url.clone(), None);
+                return ProjectSource::Git(git.url.clone(), git.reference(), None);
             }
             panic!("No source specified for dependency `{}`.", name);
         }
@@ -607,7 +1064,7 @@ impl ProjectFile {
     }
 
     // Add dependencies to Fix projects to the project file.
-    pub fn add_dependencies(&self, proj_vers: &Vec<String>) -> Result<(), Errors> {
+    pub fn add_dependencies(&self, proj_vers: &Vec<String>, offline: bool) -> Result<(), Errors> {
         let mut added = "".to_string();
 
         // Parse each element of `proj_vars` as the form `proj-name@ver_req`.
@@ -661,6 +1118,12 @@ impl ProjectFile {
 
         // Fetch the registry files.
         for reg_url in &self.general.registries {
+            if offline {
+                return Err(Errors::from_msg(format!(
+                    "Cannot fetch registry file \"{}\" in offline mode.",
+                    reg_url
+                )));
+            }
             let reg_res = reqwest::blocking::get(reg_url).map_err(|e| {
                 Errors::from_msg(format!(
                     "Failed to fetch registry file \"{}\": {:?}",
@@ -0,0 +1,394 @@
+/*
+Implementation of `fix repl` command.
+
+Inputs given to the REPL are collected into a module named `Repl`, which is compiled together with the source files of the project.
+An expression is evaluated by type-checking and instantiating a global value of type `IO ()` generated from it,
+and running the instantiated program on the JIT execution engine.
+*/
+
+use super::*;
+use error::Errors;
+use regex::Regex;
+use std::io::{self, BufRead, Write};
+use std::sync::Arc;
+
+const REPL_HELP: &str = "\
+Commands:
+  :help            Show this message.
+  :type <expr>     Show the type of an expression without evaluating it.
+  :reset           Forget all imports, global values and let-bindings.
+  :quit            Exit the REPL.
+
+Inputs:
+  import Mod;                Import a module.
+  let x = <expr>;            Bind a value to a local name which can be used in later inputs.
+  name : <type>;             Declare a global value.
+  name = <expr>;             Define a global value.
+  type / trait / impl ...;   Define a type, a trait or an implementation.
+  <expr>                     Evaluate an expression and print its value (via `ToString`) and its type.
+                             If the expression has type `IO a`, the IO action is performed.";
+
+// Definitions given to the REPL so far.
+#[derive(Default, Clone)]
+struct ReplState {
+    // Import statements.
+    imports: Vec<String>,
+    // Declarations and definitions of global values, types, traits and trait implementations.
+    globals: Vec<String>,
+    // Let-bindings, which are put before an evaluated expression.
+    lets: Vec<String>,
+    // Type signatures of global values waiting for their definitions.
+    pending_decls: Vec<String>,
+}
+
+impl ReplState {
+    // Generate the source code of the `Repl` module for evaluating an expression.
+    fn generate_source(&self, expr: &str) -> String {
+        let mut src = format!("module {};\n\n", REPL_MODULE_NAME);
+        for import in &self.imports {
+            src += import;
+            src += "\n";
+        }
+        src += "\n";
+        for global in &self.globals {
+            src += global;
+            src += "\n";
+        }
+        let lets = self
+            .lets
+            .iter()
+            .map(|l| format!("    {}\n", l))
+            .collect::<String>();
+        let value = format!(
+            "{}    let {} = (\n{}\n    );\n",
+            lets, REPL_VALUE_NAME, expr
+        );
+        let io_value = format!("{}    (\n{}\n    )", lets, expr);
+        let entries = [
+            (REPL_INFER_FUNCTION_NAME, format!("{}    pure()", value)),
+            (
+                REPL_SHOW_FUNCTION_NAME,
+                format!("{}    print({}.to_string)", value, REPL_VALUE_NAME),
+            ),
+            (
+                REPL_EXEC_FUNCTION_NAME,
+                format!("{}.bind(|_| pure())", io_value),
+            ),
+            (
+                REPL_EXEC_SHOW_FUNCTION_NAME,
+                format!(
+                    "{}.bind(|{}| print({}.to_string))",
+                    io_value, REPL_VALUE_NAME, REPL_VALUE_NAME
+                ),
+            ),
+        ];
+        for (name, body) in entries {
+            src += &format!("\n{} : IO ();\n{} = (\n{}\n);\n", name, name, body);
+        }
+        src
+    }
+}
+
+// An input to the REPL.
+#[derive(PartialEq, Eq, Debug)]
+pub(crate) enum ReplInput {
+    Command(String, String),
+    Import(String),
+    Let(String),
+    Global(String),
+    Expr(String),
+}
+
+impl ReplInput {
+    pub(crate) fn parse(input: &str) -> ReplInput {
+        let input = input.trim();
+        if input.starts_with(':') {
+            let (cmd, arg) = input.split_once(char::is_whitespace).unwrap_or((input, ""));
+            return ReplInput::Command(cmd.to_string(), arg.trim().to_string());
+        }
+        let with_semicolon = if input.ends_with(';') {
+            input.to_string()
+        } else {
+            input.to_string() + ";"
+        };
+        if Regex::new(r"^import\s").unwrap().is_match(input) {
+            return ReplInput::Import(with_semicolon);
+        }
+        if Regex::new(r"^let\s").unwrap().is_match(input) {
+            return ReplInput::Let(with_semicolon);
+        }
+        if Regex::new(r"^(type|trait|impl)\s").unwrap().is_match(input) {
+            return ReplInput::Global(input.to_string());
+        }
+        // `name : type;` and `name = expr;` are definitions of global values, whereas `expr : type` is an expression with type annotation.
+        if input.ends_with(';')
+            && Regex::new(r"^[a-z_][A-Za-z0-9_]*\s*(:[^:]|=[^=])")
+                .unwrap()
+                .is_match(input)
+        {
+            return ReplInput::Global(input.to_string());
+        }
+        ReplInput::Expr(input.trim_end_matches(';').to_string())
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy)]
+enum EvalMode {
+    // Evaluate the expression and print its value and type.
+    Evaluate,
+    // Only show the type of the expression.
+    TypeOnly,
+}
+
+// Launch the REPL, reading inputs from stdin.
+pub fn launch_repl(config: Configuration) {
+    println!("Fix REPL. Type `:help` for help, `:quit` to exit.");
+    let mut state = ReplState::default();
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        // Read an input. Continue reading lines while parentheses are not balanced.
+        let mut input = String::new();
+        loop {
+            print!("{}", if input.is_empty() { "fix> " } else { "   | " });
+            io::stdout().flush().unwrap();
+            match lines.next() {
+                Some(Ok(line)) => {
+                    input += &line;
+                    input += "\n";
+                }
+                _ => return,
+            }
+            if is_balanced(&input) {
+                break;
+            }
+        }
+        if input.trim().is_empty() {
+            continue;
+        }
+
+        let res = match ReplInput::parse(&input) {
+            ReplInput::Command(cmd, arg) => match cmd.as_str() {
+                ":quit" | ":q" => return,
+                ":help" | ":h" => {
+                    println!("{}", REPL_HELP);
+                    Ok(())
+                }
+                ":reset" => {
+                    state = ReplState::default();
+                    Ok(())
+                }
+                ":type" | ":t" => evaluate(&state, &arg, EvalMode::TypeOnly, &config),
+                _ => Err(Errors::from_msg(format!(
+                    "Unknown command `{}`. Type `:help` for help.",
+                    cmd
+                ))),
+            },
+            ReplInput::Import(import) => {
+                let mut new_state = state.clone();
+                new_state.imports.push(import);
+                commit(&mut state, new_state, &config)
+            }
+            ReplInput::Let(binding) => {
+                let mut new_state = state.clone();
+                new_state.lets.push(binding);
+                commit(&mut state, new_state, &config)
+            }
+            ReplInput::Global(defn) => {
+                if is_type_signature(&defn) {
+                    // A type signature is checked together with the definition given later.
+                    state.pending_decls.push(defn);
+                    Ok(())
+                } else {
+                    let mut new_state = state.clone();
+                    let mut decls = std::mem::replace(&mut new_state.pending_decls, vec![]);
+                    new_state.globals.append(&mut decls);
+                    new_state.globals.push(defn);
+                    commit(&mut state, new_state, &config)
+                }
+            }
+            ReplInput::Expr(expr) => evaluate(&state, &expr, EvalMode::Evaluate, &config),
+        };
+        if let Err(errs) = res {
+            eprintln!("{}", errs.to_string());
+        }
+    }
+}
+
+// Check that the new state compiles, and replace the current state with it.
+fn commit(
+    state: &mut ReplState,
+    new_state: ReplState,
+    config: &Configuration,
+) -> Result<(), Errors> {
+    let mut program = load_program(&new_state, "()", config)?;
+    check_program(&mut program, config)?;
+    *state = new_state;
+    Ok(())
+}
+
+// Load the source files of the project together with the `Repl` module generated from the state and the expression.
+fn load_program(state: &ReplState, expr: &str, config: &Configuration) -> Result<Program, Errors> {
+    let source = state.generate_source(expr);
+    let hash = format!("{:x}", md5::compute(&source));
+    save_temporary_source(&source, REPL_MODULE_NAME, &hash);
+    let mut config = config.clone();
+    config
+        .source_files
+        .push(temporary_source_path(REPL_MODULE_NAME, &hash));
+    load_source_files(&mut config)
+}
+
+// Type-check the values defined in the REPL and infer the type of the evaluated expression.
+fn check_program(
+    program: &mut Program,
+    config: &Configuration,
+) -> Result<(TypeCheckContext, Arc<TypeNode>), Errors> {
+    let tc = create_typechecker(program, config)?;
+
+    // Check global values defined by the user.
+    let mut errors = Errors::empty();
+    let user_values = program
+        .global_values
+        .iter()
+        .filter(|(name, gv)| {
+            name.module() == REPL_MODULE_NAME
+                && !name.name.starts_with("_repl")
+                && gv.is_simple_value()
+        })
+        .map(|(name, _)| name.clone())
+        .collect::<Vec<_>>();
+    for name in &user_values {
+        errors.eat_err(program.resolve_namespace_and_check_type(&tc, name, |_| Ok(true)));
+    }
+    errors.to_result()?;
+
+    // Infer the type of the expression.
+    let infer_name = FullName::from_strs(&[REPL_MODULE_NAME], REPL_INFER_FUNCTION_NAME);
+    program.resolve_namespace_and_check_type(&tc, &infer_name, |_| Ok(true))?;
+    let ty = match &program.global_values[&infer_name].expr {
+        SymbolExpr::Simple(te) => te
+            .expr
+            .find_let_bound_type(REPL_VALUE_NAME)
+            .map(|ty| te.substitution.substitute_type(&ty))
+            .unwrap(),
+        SymbolExpr::Method(_) => unreachable!(),
+    };
+    Ok((tc, ty))
+}
+
+// Evaluate an expression, or show its type.
+fn evaluate(
+    state: &ReplState,
+    expr: &str,
+    mode: EvalMode,
+    config: &Configuration,
+) -> Result<(), Errors> {
+    if expr.trim().is_empty() {
+        return Err(Errors::from_msg("Expression is required.".to_string()));
+    }
+    let mut program = load_program(state, expr, config)?;
+    let (tc, ty) = check_program(&mut program, config)?;
+    let ty_str = ty.to_string_normalize();
+    if mode == EvalMode::TypeOnly {
+        println!("{} : {}", expr.trim(), ty_str);
+        return Ok(());
+    }
+    if !ty.free_vars().is_empty() {
+        println!(
+            ": {}\n(The value is not evaluated since its type contains undetermined type variables. Maybe you need to add type annotation.)",
+            ty_str
+        );
+        return Ok(());
+    }
+
+    // Select the entry point: perform the IO action if the expression is an IO action, and print the value if it implements `ToString`.
+    let candidates = match io_result_type(&ty) {
+        Some(res_ty) if res_ty.to_string() == make_unit_ty().to_string() => {
+            vec![(REPL_EXEC_FUNCTION_NAME, false)]
+        }
+        Some(_) => vec![
+            (REPL_EXEC_SHOW_FUNCTION_NAME, true),
+            (REPL_EXEC_FUNCTION_NAME, false),
+        ],
+        None => vec![
+            (REPL_SHOW_FUNCTION_NAME, true),
+            (REPL_INFER_FUNCTION_NAME, false),
+        ],
+    };
+    let mut entry = None;
+    for (name, shows_value) in candidates {
+        let name = FullName::from_strs(&[REPL_MODULE_NAME], name);
+        if program
+            .resolve_namespace_and_check_type(&tc, &name, |_| Ok(true))
+            .is_ok()
+        {
+            entry = Some((name, shows_value));
+            break;
+        }
+    }
+    let (entry_name, shows_value) = match entry {
+        Some(entry) => entry,
+        None => {
+            return Err(Errors::from_msg(format!(
+                "Cannot evaluate an expression of type `{}`.",
+                ty_str
+            )))
+        }
+    };
+
+    // Instantiate the entry point and run it.
+    let (main_expr, _) =
+        program.instantiate_exported_value(&entry_name, Some(make_io_unit_ty()), &None, &tc)?;
+    program.instantiate_exported_values(&tc)?;
+    optimize_program(&mut program, config);
+    run_program_jit(program, main_expr, &["fix"], config)?;
+
+    if shows_value {
+        println!(" : {}", ty_str);
+    } else if io_result_type(&ty).is_none() {
+        println!(": {}", ty_str);
+    }
+    Ok(())
+}
+
+// If the type is `IO a`, returns `a`.
+fn io_result_type(ty: &Arc<TypeNode>) -> Option<Arc<TypeNode>> {
+    match &ty.ty {
+        Type::TyApp(fun, arg) if fun.to_string() == make_io_ty().to_string() => Some(arg.clone()),
+        _ => None,
+    }
+}
+
+// Check whether the input is a type signature of a global value, e.g., `name : type;`.
+fn is_type_signature(input: &str) -> bool {
+    Regex::new(r"^[a-z_][A-Za-z0-9_]*\s*:[^:]")
+        .unwrap()
+        .is_match(input)
+}
+
+// Check whether parentheses, brackets and braces in the input are balanced, ignoring string literals.
+pub(crate) fn is_balanced(input: &str) -> bool {
+    let mut depth: i32 = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    for c in input.chars() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            _ => {}
+        }
+    }
+    depth <= 0 && !in_string
+}
@@ -8,7 +8,7 @@ use error::error_exit;
 use error::Errors;
 use inkwell::{
     passes::PassManager,
-    targets::{CodeModel, InitializationConfig, RelocMode, Target, TargetMachine},
+    targets::{CodeModel, InitializationConfig, Target, TargetMachine},
 };
 use rand::Rng;
 use std::panic::panic_any;
@@ -30,21 +30,30 @@ pub struct BuildObjFilesResult {
     // The program parsed.
     // This field is only set when the function is running for language server.
     program: Option<Program>,
-}
 
-// Compile the program, and returns the path of object files to be linked.
-fn build_object_files<'c>(
-    mut program: Program,
-    config: Configuration,
-) -> Result<BuildObjFilesResult, Errors> {
-    let _sw = StopWatch::new("build_module", config.show_build_times);
+    // Points of coverage instrumentation, in the order of counter indices.
+    // This field is only set when `config.coverage` is true.
+    coverage_points: Vec<CoveragePoint>,
+
+    // Functions instrumented by the profiler, in the order of site indices.
+    // This field is only set when `config.profile` is true.
+    profile_sites: Vec<ProfileSite>,
+
+    // Names of foreign functions called by the program.
+    called_ffi_functions: HashSet<Name>,
+}
 
+// Validate the program and create a typechecker in which type declarations of all global values are registered.
+pub fn create_typechecker(
+    program: &mut Program,
+    config: &Configuration,
+) -> Result<TypeCheckContext, Errors> {
     // Add tuple definitions.
     program.add_tuple_defns();
 
     // Add trait implementations for tuples such as ToString or Eq.
     program.link(
-        make_tuple_traits_mod(&program.used_tuple_sizes, &config)?,
+        make_tuple_traits_mod(&program.used_tuple_sizes, config)?,
         true,
     )?;
 
@@ -82,6 +91,10 @@ fn build_object_files<'c>(
     // This check should be done after `add_methods` and `create_trait_method_symbols`.
     program.validate_import_statements()?;
 
+    // Check if all values referred in inline statements are defined.
+    // This check should be done after `add_methods` and `create_trait_method_symbols`.
+    program.validate_inline_statements()?;
+
     // Set and check kinds that appear in the module.
     program.set_kinds()?;
 
@@ -100,12 +113,28 @@ fn build_object_files<'c>(
             .add_global(name.name.clone(), &name.namespace, &defn.scm)?;
     }
 
+    Ok(typechecker)
+}
+
+// Compile the program, and returns the path of object files to be linked.
+fn build_object_files<'c>(
+    mut program: Program,
+    config: Configuration,
+) -> Result<BuildObjFilesResult, Errors> {
+    let _sw = StopWatch::new("build_module", config.show_build_times);
+
+    // Validate and elaborate the program, and create a typechecker.
+    let typechecker = create_typechecker(&mut program, &config)?;
+
     // When running diagnostics, perform type checking of all values and return here.
     if config.subcommand == SubCommand::Diagnostics {
         program.resolve_namespace_and_check_type_all(&typechecker)?;
         return Ok(BuildObjFilesResult {
             obj_paths: vec![],
             program: Some(program),
+            coverage_points: vec![],
+            profile_sites: vec![],
+            called_ffi_functions: Default::default(),
         });
     }
 
@@ -116,15 +145,8 @@ fn build_object_files<'c>(
     // Instantiate all exported values and values called from them.
     program.instantiate_exported_values(&typechecker)?;
 
-    // Perform uncurrying optimization.
-    if config.perform_uncurry_optimization() {
-        uncurry_optimization(&mut program);
-    }
-
-    // Perform borrowing optimization.
-    if config.perform_borrowing_optimization() {
-        borrowing_optimization(&mut program);
-    }
+    // Perform Fix-level optimizations.
+    optimize_program(&mut program, &config);
 
     // Determine compilation units.
     let mut units = vec![];
@@ -160,6 +182,8 @@ fn build_object_files<'c>(
 
     // Paths of object files to be linked.
     let mut obj_paths = vec![];
+    // Hashes of compilation units, which are used for link-time optimization.
+    let mut unit_hashes = vec![];
 
     // Generate object files in parallel.
     let mut threads = vec![];
@@ -168,8 +192,12 @@ fn build_object_files<'c>(
         // We generate the main unit in the last.
         let is_main_unit = i == units_count - 1;
 
-        obj_paths.push(unit.object_file_path());
-        // If the object file is cached, skip the generation.
+        if config.thin_lto() {
+            unit_hashes.push(unit.unit_hash().to_string());
+        } else {
+            obj_paths.push(unit.object_file_path());
+        }
+        // If the object file (or the bitcode file) is cached, skip the generation.
         if unit.is_cached() {
             if config.verbose {
                 eprintln!(
@@ -217,67 +245,131 @@ fn build_object_files<'c>(
                 gc.create_debug_info();
             }
 
-            // Declare runtime functions.
-            runtime::build_runtime(&mut gc, BuildMode::Declare);
-
-            // Declare all symbols in this program.
-            // TODO: Optimize so that only necessary symbols are declared.
-            for symbol in &all_symbols {
-                gc.declare_symbol(symbol);
+            // Generate the LLVM module of this unit.
+            let main_unit = if is_main_unit {
+                assert!(!unit.is_cached()); // Main unit should not be cached.
+                Some((export_statements.as_slice(), main_expr.clone()))
+            } else {
+                None
+            };
+            generate_module(&mut gc, &all_symbols, unit.symbols(), main_unit, &config);
+
+            // Generate object file, or bitcode file for link-time optimization.
+            if config.thin_lto() {
+                exit_if_err(write_unit_bitcode(gc.module, unit.unit_hash()));
+            } else {
+                write_to_object_file(gc.module, &target_machine, &unit.object_file_path());
             }
 
-            // Implement all symbols in this unit.
-            for symbol in unit.symbols() {
-                gc.implement_symbol(symbol);
+            (take_coverage_points(&mut gc), take_profile_sites(&mut gc))
+        }));
+    }
+    // Wait for all threads to finish.
+    let mut coverage_points = vec![];
+    let mut profile_sites = vec![];
+    for t in threads {
+        match t.join() {
+            Err(e) => panic_any(e),
+            Ok((mut points, mut sites)) => {
+                coverage_points.append(&mut points);
+                profile_sites.append(&mut sites);
             }
+        }
+    }
 
-            if is_main_unit {
-                assert!(!unit.is_cached()); // Main unit should not be cached.
+    // Optimize compilation units across them and generate object files.
+    if config.thin_lto() {
+        obj_paths = thin_lto_link_units(&unit_hashes, &config)?;
+    }
+
+    Ok(BuildObjFilesResult {
+        obj_paths,
+        program: None,
+        coverage_points,
+        profile_sites,
+        called_ffi_functions: std::mem::take(&mut program.called_ffi_functions),
+    })
+}
 
-                // Implement runtime functions.
-                build_runtime(&mut gc, BuildMode::Implement);
+// Declare and implement symbols in an LLVM module, and optimize it.
+// For the main unit, pass the export statements and the expression of the entry point as `main_unit`.
+pub fn generate_module<'c, 'm>(
+    gc: &mut GenerationContext<'c, 'm>,
+    all_symbols: &[InstantiatedSymbol],
+    unit_symbols: &[InstantiatedSymbol],
+    main_unit: Option<(&[ExportStatement], Arc<ExprNode>)>,
+    config: &Configuration,
+) {
+    // Declare runtime functions.
+    runtime::build_runtime(gc, BuildMode::Declare);
 
-                // Implement exported C functions.
-                build_exported_c_functions(&mut gc, &export_statements);
+    // Declare all symbols in this program.
+    // TODO: Optimize so that only necessary symbols are declared.
+    for symbol in all_symbols {
+        gc.declare_symbol(symbol);
+    }
 
-                // Implement the `main()` function.
-                build_main_function(&mut gc, main_expr.clone());
-            }
+    // Implement all symbols in this unit.
+    for symbol in unit_symbols {
+        gc.implement_symbol(symbol);
+    }
 
-            // If debug info is generated, finalize it.
-            gc.finalize_di();
+    if let Some((export_statements, main_expr)) = main_unit {
+        // Implement runtime functions.
+        build_runtime(gc, BuildMode::Implement);
 
-            if config.emit_llvm {
-                // Print LLVM-IR to file before optimization.
-                emit_llvm(gc.module, &config, false);
-            }
+        // Implement exported C functions.
+        build_exported_c_functions(gc, export_statements);
 
-            // LLVM level optimization.
-            optimize_and_verify(gc.module, &config);
+        // Implement the `main()` function.
+        build_main_function(gc, main_expr);
+    }
 
-            if config.emit_llvm {
-                // Print LLVM-IR to file after optimization.
-                emit_llvm(gc.module, &config, true);
-            }
+    // If debug info is generated, finalize it.
+    gc.finalize_di();
 
-            // Generate object file.
-            write_to_object_file(gc.module, &target_machine, &unit.object_file_path());
-        }));
+    if config.emit_llvm {
+        // Print LLVM-IR to file before optimization.
+        emit_llvm(gc.module, config, false);
     }
-    // Wait for all threads to finish.
-    for t in threads {
-        if let Err(e) = t.join() {
-            panic_any(e);
-        }
+
+    // LLVM level optimization.
+    optimize_and_verify(gc.module, config);
+
+    if config.emit_llvm {
+        // Print LLVM-IR to file after optimization.
+        emit_llvm(gc.module, config, true);
     }
+}
 
-    Ok(BuildObjFilesResult {
-        obj_paths,
-        program: None,
-    })
+// Perform Fix-level optimizations on the instantiated program.
+pub fn optimize_program(program: &mut Program, config: &Configuration) {
+    // Perform inlining optimization.
+    if config.perform_inline_optimization() {
+        inline_optimization(program);
+    }
+
+    // Perform uncurrying optimization.
+    if config.perform_uncurry_optimization() {
+        uncurry_optimization(program);
+    }
+
+    // Perform borrowing optimization.
+    if config.perform_borrowing_optimization() {
+        borrowing_optimization(program);
+    }
+
+    // Perform escape analysis.
+    if config.perform_escape_analysis() {
+        escape_analysis(program);
+    }
 }
 
-fn write_to_object_file<'c>(module: &Module<'c>, target_machine: &TargetMachine, obj_path: &Path) {
+pub fn write_to_object_file<'c>(
+    module: &Module<'c>,
+    target_machine: &TargetMachine,
+    obj_path: &Path,
+) {
     // Create directory if it doesn't exist.
     let dir_path = obj_path.parent().unwrap();
     match fs::create_dir_all(dir_path) {
@@ -336,12 +428,12 @@ fn optimize_and_verify<'c>(module: &Module<'c>, config: &Configuration) {
         FixOptimizationLevel::Minimum => {
             passmgr.add_tail_call_elimination_pass();
         }
-        FixOptimizationLevel::Separated => {
-            llvm_passes::add_optimization_passes(&passmgr);
+        FixOptimizationLevel::Separated | FixOptimizationLevel::ThinLTO => {
+            add_optimization_passes(&passmgr, config);
         }
         FixOptimizationLevel::Default => {
             llvm_passes::add_internalize_and_strip_passes(&passmgr);
-            llvm_passes::add_optimization_passes(&passmgr);
+            add_optimization_passes(&passmgr, config);
             llvm_passes::add_internalize_and_strip_passes(&passmgr);
         }
     }
@@ -349,6 +441,14 @@ fn optimize_and_verify<'c>(module: &Module<'c>, config: &Configuration) {
     passmgr.run_on(module);
 }
 
+// Add the optimization passes given by `config.llvm_passes`, or the default ones.
+pub fn add_optimization_passes<'c>(passmgr: &PassManager<Module<'c>>, config: &Configuration) {
+    match &config.llvm_passes {
+        Some(names) => add_llvm_passes_by_names(passmgr, names),
+        None => llvm_passes::add_optimization_passes(passmgr),
+    }
+}
+
 // Build exported c functions.
 fn build_exported_c_functions<'c, 'm>(
     gc: &mut GenerationContext<'c, 'm>,
@@ -392,6 +492,22 @@ fn build_main_function<'c, 'm>(gc: &mut GenerationContext<'c, 'm>, main_expr: Ar
 
     // Run main object.
     let main_obj = gc.eval_expr(main_expr, None); // `IO ()`
+
+    // Register coverage counters to the runtime, so that they are written to a file at exit.
+    if gc.config.coverage {
+        build_coverage_registration(gc);
+    }
+
+    // Register PGO counters to the runtime, so that the profile is written at exit.
+    if gc.config.pgo_generate {
+        build_pgo_registration(gc);
+    }
+
+    // Register the output path of the profiler to the runtime.
+    if gc.config.profile {
+        build_profile_registration(gc);
+    }
+
     let main_lambda_val = main_obj.load_field_nocap(gc, 0);
     let main_lambda_ty = type_fun(make_tuple_ty(vec![]), make_tuple_ty(vec![]));
     let main_lambda = Object::create_from_value(main_lambda_val, main_lambda_ty, gc);
@@ -499,14 +615,99 @@ pub fn load_source_files(config: &mut Configuration) -> Result<Program, Errors>
         target_mod.link(mod_, false)?; // If an error occurres in linking, return the error.
     }
 
+    // Load source files of imported modules from source roots.
+    if !config.source_roots.is_empty() {
+        load_imported_modules(&mut target_mod, config)?;
+    }
+
     // Resolve imports.
     target_mod.resolve_imports()?;
 
     Ok(target_mod)
 }
 
+// Type-check the program specified in the configuration without generating code.
+// `config.subcommand` should be `SubCommand::Diagnostics`, so that all global values are type-checked.
+pub fn check_file(config: &mut Configuration) -> Result<(), Errors> {
+    assert!(config.subcommand == SubCommand::Diagnostics);
+    if config.source_files.is_empty() {
+        return Err(Errors::from_msg(
+            "No source file is specified. Specify source files by `-f` option or in the project file.".to_string(),
+        ));
+    }
+    build_file(config)?;
+    Ok(())
+}
+
+// Walk the import graph from the loaded modules, and load source files of imported modules which are not loaded yet from the source roots.
+// The loaded files are added to `config.source_files`.
+fn load_imported_modules(program: &mut Program, config: &mut Configuration) -> Result<(), Errors> {
+    let mut searched: HashSet<Name> = HashSet::new();
+    loop {
+        // Find source files of modules which are imported but not loaded.
+        let mut found_files = vec![];
+        for stmt in program.import_statements() {
+            if program.is_linked(&stmt.module) || searched.contains(&stmt.module) {
+                continue;
+            }
+            searched.insert(stmt.module.clone());
+            if let Some(path) = find_module_file(&stmt.module, &config.source_roots) {
+                found_files.push(path);
+            }
+        }
+        if found_files.is_empty() {
+            // If a module is not found, an error will be raised in `resolve_imports`.
+            break;
+        }
+
+        // Load the found files. They may import other modules, so we repeat the process.
+        for file_path in found_files {
+            let prog = parse_file_path(file_path.clone(), config)?;
+            program.link(prog, false)?;
+            config.source_files.push(file_path);
+        }
+    }
+    Ok(())
+}
+
+// Find the source file of a module in the source roots.
+// Module `Foo.Bar` is searched as "Foo/Bar.fix", "Foo.Bar.fix", "foo/bar.fix" and "foo.bar.fix" in this order.
+// The lowercase names are searched since source files are conventionally named in lowercase, e.g., "examples/import/lib2.lib3.fix" for `Lib2.Lib3`.
+pub fn find_module_file(module: &Name, source_roots: &[PathBuf]) -> Option<PathBuf> {
+    let components = module.split('.').collect::<Vec<_>>();
+    let mut file_names = vec![
+        PathBuf::from(components.join("/") + ".fix"),
+        PathBuf::from(components.join(".") + ".fix"),
+    ];
+    let lower = components
+        .iter()
+        .map(|c| c.to_lowercase())
+        .collect::<Vec<_>>();
+    file_names.push(PathBuf::from(lower.join("/") + ".fix"));
+    file_names.push(PathBuf::from(lower.join(".") + ".fix"));
+    for root in source_roots {
+        for file_name in &file_names {
+            let path = root.join(file_name);
+            if path.is_file() {
+                return Some(path);
+            }
+        }
+    }
+    None
+}
+
 // Run the program specified in the configuration, and return the exit code.
 pub fn run_file(mut config: Configuration) -> i32 {
+    if config.jit {
+        if config.coverage {
+            error_exit("Coverage instrumentation is not supported in JIT mode.");
+        }
+        if config.profile {
+            error_exit("Profiling is not supported in JIT mode.");
+        }
+        return exit_if_err(run_file_jit(&mut config));
+    }
+
     fs::create_dir_all(DOT_FIXLANG).expect("Failed to create \".fixlang\" directory.");
 
     // For parallel execution, use different file name for each execution.
@@ -514,7 +715,7 @@ pub fn run_file(mut config: Configuration) -> i32 {
     config.out_file_path = Some(PathBuf::from(a_out_path.clone()));
 
     // Build executable file.
-    exit_if_err(build_file(&mut config));
+    let build_res = exit_if_err(build_file(&mut config));
 
     // Run the executable file.
     let mut com = if config.valgrind_tool == ValgrindTool::None {
@@ -534,6 +735,16 @@ pub fn run_file(mut config: Configuration) -> i32 {
     // Remove the executable file.
     fs::remove_file(a_out_path.clone()).expect(&format!("Failed to remove \"{}\".", a_out_path));
 
+    // Write coverage reports.
+    if config.coverage {
+        exit_if_err(write_coverage_reports(&build_res.coverage_points, &config));
+    }
+
+    // Write profile reports.
+    if config.profile {
+        exit_if_err(write_profile_reports(&build_res.profile_sites, &config));
+    }
+
     if let Some(code) = output.status.code() {
         code
     } else {
@@ -541,7 +752,42 @@ pub fn run_file(mut config: Configuration) -> i32 {
     }
 }
 
-fn get_target_machine(opt_level: OptimizationLevel, config: &Configuration) -> TargetMachine {
+// Run the program specified in the configuration on the JIT execution engine, and return the exit code.
+fn run_file_jit(config: &mut Configuration) -> Result<i32, Errors> {
+    if config.valgrind_tool != ValgrindTool::None {
+        return Err(Errors::from_msg(
+            "Running a program with valgrind is not supported in JIT mode.".to_string(),
+        ));
+    }
+
+    // Run extra commands.
+    config.run_extra_commands()?;
+
+    // Create intermediate directory.
+    fs::create_dir_all(INTERMEDIATE_PATH).map_err(|e| {
+        Errors::from_msg(format!(
+            "Failed to create directory \"{}\": {:?}",
+            INTERMEDIATE_PATH, e
+        ))
+    })?;
+
+    let mut program = load_source_files(config)?;
+    let typechecker = create_typechecker(&mut program, config)?;
+
+    // Instantiate Main::main (or Test::test) and exported values.
+    let main_expr =
+        program.instantiate_main_function(&typechecker, config.subcommand == SubCommand::Test)?;
+    program.instantiate_exported_values(&typechecker)?;
+
+    // Perform Fix-level optimizations.
+    optimize_program(&mut program, config);
+
+    // Run the program. The path of the compiler is passed as `argv[0]`.
+    let program_name = std::env::args().next().unwrap_or("fix".to_string());
+    run_program_jit(program, main_expr, &[&program_name], config)
+}
+
+pub fn get_target_machine(opt_level: OptimizationLevel, config: &Configuration) -> TargetMachine {
     let _native = Target::initialize_native(&InitializationConfig::default())
         .map_err(|e| error_exit(&format!("failed to initialize native: {}", e)))
         .unwrap();
@@ -559,7 +805,7 @@ fn get_target_machine(opt_level: OptimizationLevel, config: &Configuration) -> T
         cpu_name.to_str().unwrap(),
         &features.to_string(),
         opt_level,
-        RelocMode::Default,
+        config.reloc_mode(),
         CodeModel::Default,
     );
     match target_machine {
@@ -574,6 +820,14 @@ pub struct BuildFileResult {
     // The program parsed.
     // This field is only set when the function is running for language server.
     pub program: Option<Program>,
+
+    // Points of coverage instrumentation.
+    // This field is only set when `config.coverage` is true.
+    pub coverage_points: Vec<CoveragePoint>,
+
+    // Functions instrumented by the profiler.
+    // This field is only set when `config.profile` is true.
+    pub profile_sites: Vec<ProfileSite>,
 }
 
 pub fn build_file(config: &mut Configuration) -> Result<BuildFileResult, Errors> {
@@ -600,6 +854,8 @@ pub fn build_file(config: &mut Configuration) -> Result<BuildFileResult, Errors>
         let program = build_res.program.unwrap();
         return Ok(BuildFileResult {
             program: Some(program),
+            coverage_points: vec![],
+            profile_sites: vec![],
         });
     }
 
@@ -628,14 +884,72 @@ pub fn build_file(config: &mut Configuration) -> Result<BuildFileResult, Errors>
         libs_opts.push("-Wl,-Bdynamic".to_string());
         libs_opts.push("-lfixsanitizer".to_string());
     }
+    if std::env::consts::OS == "linux" && needs_libdl(&build_res.called_ffi_functions, config) {
+        // `dlopen` etc. used by `Std::FFI::DynamicLibrary` are in libdl on glibc older than 2.34.
+        libs_opts.push("-Wl,-Bdynamic".to_string());
+        libs_opts.push("-ldl".to_string());
+    }
 
     // Build runtime.c to object file.
+    let runtime_obj_path = build_runtime_c(config, false)?;
+
+    let mut com = config.c_compiler_command()?;
+    com.arg("-Wno-unused-command-line-argument")
+        .arg(if config.pie { "-pie" } else { "-no-pie" });
+    if let Some(linker) = &config.linker {
+        com.arg(format!("-fuse-ld={}", linker));
+    }
+    if let Some(flag) = config.gc_sections_flag() {
+        com.arg(flag);
+    }
+    com.arg("-o").arg(exec_path.to_str().unwrap());
+
+    let mut obj_paths = build_res.obj_paths;
+    obj_paths.append(&mut config.object_files.clone());
+    for obj_path in obj_paths {
+        com.arg(obj_path.to_str().unwrap());
+    }
+    com.arg(runtime_obj_path.to_str().unwrap())
+        .args(library_search_path_opts)
+        .args(libs_opts)
+        .args(&config.link_flags);
+    run_c_compiler(&mut com, config)?;
+
+    Ok(BuildFileResult {
+        program: None,
+        coverage_points: build_res.coverage_points,
+        profile_sites: build_res.profile_sites,
+    })
+}
+
+// Whether libdl should be linked to the program.
+// It is needed when the program calls the wrappers of `dlopen` etc. in runtime.c.
+// If unused sections cannot be removed by the linker, the wrappers remain in the executable, so libdl is always needed.
+pub fn needs_libdl(called_ffi_functions: &HashSet<Name>, config: &Configuration) -> bool {
+    const DL_WRAPPERS: [&str; 4] = [
+        "fixruntime_dlopen",
+        "fixruntime_dlsym",
+        "fixruntime_dlclose",
+        "fixruntime_dlerror",
+    ];
+    config.gc_sections_flag().is_none()
+        || DL_WRAPPERS
+            .iter()
+            .any(|name| called_ffi_functions.contains(*name))
+}
+
+// Build runtime.c and return the path of the generated file.
+// If `shared_library` is true, a shared library is generated instead of an object file.
+pub fn build_runtime_c(config: &Configuration, shared_library: bool) -> Result<PathBuf, Errors> {
     let mut runtime_obj_hash_source = "".to_string();
     runtime_obj_hash_source += build_time_utc!();
     runtime_obj_hash_source += &config.runtime_c_macro.join("_");
+    runtime_obj_hash_source += &config.c_compiler;
+    runtime_obj_hash_source += &config.pie.to_string();
     let runtime_obj_path = PathBuf::from(INTERMEDIATE_PATH).join(format!(
-        "fixruntime.{:x}.o",
-        md5::compute(runtime_obj_hash_source)
+        "fixruntime.{:x}.{}",
+        md5::compute(runtime_obj_hash_source),
+        if shared_library { "so" } else { "o" }
     ));
     if !runtime_obj_path.exists() {
         // Random number for temporary file name.
@@ -652,27 +966,26 @@ pub fn build_file(config: &mut Configuration) -> Result<BuildFileResult, Errors>
             "Failed to generate \"{}\"",
             runtime_c_path.to_string_lossy().to_string()
         ));
-        // Create library object file.
-        let mut com = Command::new("gcc");
+        // Create library object file (or shared library).
+        let mut com = config.c_compiler_command()?;
         let mut com = com
             .arg("-ffunction-sections")
             .arg("-fdata-sections")
             .arg("-o")
-            .arg(runtime_tmp_path.to_str().unwrap())
-            .arg("-c")
-            .arg(runtime_c_path.to_str().unwrap());
+            .arg(runtime_tmp_path.to_str().unwrap());
+        if shared_library {
+            com = com.arg("-shared").arg("-fPIC");
+        } else {
+            com = com.arg("-c");
+            if config.pie {
+                com = com.arg("-fPIE");
+            }
+        }
+        com = com.arg(runtime_c_path.to_str().unwrap());
         for m in &config.runtime_c_macro {
             com = com.arg(format!("-D{}", m));
         }
-        let output = com.output().expect("Failed to run gcc.");
-
-        if output.stderr.len() > 0 {
-            eprintln!(
-                "{}",
-                String::from_utf8(output.stderr)
-                    .unwrap_or("(failed to parse stderr from gcc as UTF8.)".to_string())
-            );
-        }
+        run_c_compiler(com, config)?;
 
         // Rename the temporary file to the final file.
         fs::rename(&runtime_tmp_path, &runtime_obj_path).expect(&format!(
@@ -681,34 +994,30 @@ pub fn build_file(config: &mut Configuration) -> Result<BuildFileResult, Errors>
             runtime_obj_path.to_string_lossy().to_string()
         ));
     }
+    Ok(runtime_obj_path)
+}
 
-    let mut com = Command::new("gcc");
-    com.arg("-Wno-unused-command-line-argument").arg("-no-pie");
-    if std::env::consts::OS == "macos" {
-        com.arg("-Wl,-dead_strip");
-    } else {
-        com.arg("-Wl,--gc-sections");
-    }
-    com.arg("-o").arg(exec_path.to_str().unwrap());
-
-    let mut obj_paths = build_res.obj_paths;
-    obj_paths.append(&mut config.object_files.clone());
-    for obj_path in obj_paths {
-        com.arg(obj_path.to_str().unwrap());
-    }
-    com.arg(runtime_obj_path.to_str().unwrap())
-        .args(library_search_path_opts)
-        .args(libs_opts);
-    let output = com.output().expect("Failed to run gcc.");
+// Run the C compiler, and print its error messages.
+fn run_c_compiler(com: &mut Command, config: &Configuration) -> Result<(), Errors> {
+    let output = com.output().map_err(|e| {
+        Errors::from_msg_err(&format!("Failed to run \"{}\"", config.c_compiler), e)
+    })?;
     if output.stderr.len() > 0 {
         eprintln!(
             "{}",
-            String::from_utf8(output.stderr)
-                .unwrap_or("(failed to parse stderr from gcc as UTF8.)".to_string())
+            String::from_utf8(output.stderr).unwrap_or(format!(
+                "(failed to parse stderr from {} as UTF8.)",
+                config.c_compiler
+            ))
         );
     }
-
-    Ok(BuildFileResult::default())
+    if !output.status.success() {
+        return Err(Errors::from_msg(format!(
+            "\"{}\" failed with {}.",
+            config.c_compiler, output.status
+        )));
+    }
+    Ok(())
 }
 
 // A function implementing `fix clean` command.
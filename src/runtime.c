@@ -6,16 +6,22 @@ C functions / values for implementing Fix standard library.
 
 #include <ctype.h>
 #include <errno.h>
+#include <fcntl.h>
 #include <inttypes.h>
 #include <stdio.h>
 #include <stdint.h>
 #include <stdlib.h>
 #include <string.h>
+#include <time.h>
 #ifndef __MINGW32__
 #include <sys/wait.h>
+#include <dlfcn.h>
+#else
+#include <windows.h>
 #endif // __MINGW32__
 #include <unistd.h>
 #include <pthread.h>
+#include <signal.h>
 
 // Print message to stderr, and flush it.
 void fixruntime_eprint(const char *msg)
@@ -291,4 +297,444 @@ int fixruntime_get_errno()
 void fixruntime_clear_errno()
 {
     errno = 0;
-}
\ No newline at end of file
+}
+
+// Open a shared library. Returns NULL on failure; the reason can be obtained by `fixruntime_dlerror`.
+void *fixruntime_dlopen(const char *path)
+{
+#ifndef __MINGW32__
+    return dlopen(path, RTLD_NOW | RTLD_LOCAL);
+#else
+    return (void *)LoadLibraryA(path);
+#endif
+}
+
+// Get the address of a symbol in a shared library. Returns NULL if the symbol is not found.
+void *fixruntime_dlsym(void *handle, const char *name)
+{
+#ifndef __MINGW32__
+    dlerror(); // Clear the previous error.
+    return dlsym(handle, name);
+#else
+    return (void *)GetProcAddress((HMODULE)handle, name);
+#endif
+}
+
+void fixruntime_dlclose(void *handle)
+{
+#ifndef __MINGW32__
+    dlclose(handle);
+#else
+    FreeLibrary((HMODULE)handle);
+#endif
+}
+
+// Get the message of the last error of `fixruntime_dlopen` or `fixruntime_dlsym`.
+const char *fixruntime_dlerror()
+{
+#ifndef __MINGW32__
+    const char *msg = dlerror();
+    return msg ? msg : "unknown error";
+#else
+    return "unknown error";
+#endif
+}
+
+// Write `len` bytes of `buf` to `fd`. This is async-signal-safe.
+static void fixruntime_write_all(int fd, const char *buf, size_t len)
+{
+    while (len > 0)
+    {
+        ssize_t written = write(fd, buf, len);
+        if (written < 0)
+        {
+            if (errno == EINTR)
+            {
+                continue;
+            }
+            return;
+        }
+        buf += written;
+        len -= written;
+    }
+}
+
+// Write `value` in decimal to `fd`. This is async-signal-safe.
+static void fixruntime_write_i64(int fd, int64_t value)
+{
+    char buf[24];
+    int pos = sizeof(buf);
+    uint64_t abs = value < 0 ? -(uint64_t)value : (uint64_t)value;
+    do
+    {
+        buf[--pos] = '0' + (abs % 10);
+        abs /= 10;
+    } while (abs > 0);
+    if (value < 0)
+    {
+        buf[--pos] = '-';
+    }
+    fixruntime_write_all(fd, buf + pos, sizeof(buf) - pos);
+}
+
+// Open the file to which counts are written at exit.
+// The file is opened in advance so that counts can be written in a signal handler.
+static int fixruntime_open_output(const char *path, const char *what)
+{
+    int fd = open(path, O_WRONLY | O_CREAT | O_TRUNC, 0644);
+    if (fd < 0)
+    {
+        fprintf(stderr, "Failed to open \"%s\" to write %s.\n", path, what);
+    }
+    return fd;
+}
+
+// Call the SIGABRT handler which was installed before ours, so that handlers of coverage and PGO can be combined.
+static void fixruntime_call_prev_handler(void (*handler)(int), int sig)
+{
+    if (handler != SIG_DFL && handler != SIG_IGN && handler != SIG_ERR)
+    {
+        handler(sig);
+    }
+}
+
+// Counters of coverage instrumentation, registered by `fixruntime_coverage_register`.
+static int64_t **fixruntime_coverage_counters = NULL;
+static int64_t fixruntime_coverage_counters_len = 0;
+static int fixruntime_coverage_output_fd = -1;
+static void (*fixruntime_coverage_prev_abort_handler)(int) = SIG_DFL;
+
+// Write values of coverage counters to the output file, one value per line.
+// Only async-signal-safe functions are used, since this is also called from the SIGABRT handler.
+static void fixruntime_coverage_write()
+{
+    int fd = fixruntime_coverage_output_fd;
+    if (fd < 0)
+    {
+        return;
+    }
+    fixruntime_coverage_output_fd = -1; // Write only once.
+    for (int64_t i = 0; i < fixruntime_coverage_counters_len; i++)
+    {
+        fixruntime_write_i64(fd, *fixruntime_coverage_counters[i]);
+        fixruntime_write_all(fd, "\n", 1);
+    }
+    close(fd);
+}
+
+static void fixruntime_coverage_write_on_abort(int sig)
+{
+    fixruntime_coverage_write();
+    fixruntime_call_prev_handler(fixruntime_coverage_prev_abort_handler, sig);
+}
+
+// Register coverage counters, whose values are written to `output_path` at exit.
+// Counts are also written when the program is aborted, e.g., by a failed assertion.
+void fixruntime_coverage_register(int64_t **counters, int64_t len, const char *output_path)
+{
+    fixruntime_coverage_counters = counters;
+    fixruntime_coverage_counters_len = len;
+    fixruntime_coverage_output_fd = fixruntime_open_output(output_path, "coverage counts");
+    atexit(fixruntime_coverage_write);
+    fixruntime_coverage_prev_abort_handler = signal(SIGABRT, fixruntime_coverage_write_on_abort);
+}
+
+// Counters of PGO instrumentation and their keys, registered by `fixruntime_pgo_register`.
+static int64_t **fixruntime_pgo_counters = NULL;
+static const char **fixruntime_pgo_keys = NULL;
+static int64_t fixruntime_pgo_counters_len = 0;
+static int fixruntime_pgo_output_fd = -1;
+static void (*fixruntime_pgo_prev_abort_handler)(int) = SIG_DFL;
+
+// Write the profile to the output file, one counter per line in the form "<count>\t<key>".
+// Only async-signal-safe functions are used, since this is also called from the SIGABRT handler.
+static void fixruntime_pgo_write()
+{
+    int fd = fixruntime_pgo_output_fd;
+    if (fd < 0)
+    {
+        return;
+    }
+    fixruntime_pgo_output_fd = -1; // Write only once.
+    for (int64_t i = 0; i < fixruntime_pgo_counters_len; i++)
+    {
+        fixruntime_write_i64(fd, *fixruntime_pgo_counters[i]);
+        fixruntime_write_all(fd, "\t", 1);
+        fixruntime_write_all(fd, fixruntime_pgo_keys[i], strlen(fixruntime_pgo_keys[i]));
+        fixruntime_write_all(fd, "\n", 1);
+    }
+    close(fd);
+}
+
+static void fixruntime_pgo_write_on_abort(int sig)
+{
+    fixruntime_pgo_write();
+    fixruntime_call_prev_handler(fixruntime_pgo_prev_abort_handler, sig);
+}
+
+// Register PGO counters, whose values are written to the profile at exit.
+// The profile is written to the path given by the environment variable `FIX_PGO_PROFILE`, or to `default_path` if it is not set.
+void fixruntime_pgo_register(int64_t **counters, const char **keys, int64_t len, const char *default_path)
+{
+    const char *path = getenv("FIX_PGO_PROFILE");
+    fixruntime_pgo_counters = counters;
+    fixruntime_pgo_keys = keys;
+    fixruntime_pgo_counters_len = len;
+    fixruntime_pgo_output_fd = fixruntime_open_output(path && path[0] ? path : default_path, "the profile");
+    atexit(fixruntime_pgo_write);
+    fixruntime_pgo_prev_abort_handler = signal(SIGABRT, fixruntime_pgo_write_on_abort);
+}
+
+// A node of the call tree recorded by the profiler.
+// Node 0 is the root, which does not correspond to any function.
+typedef struct
+{
+    int64_t site;
+    int64_t parent;
+    int64_t first_child;
+    int64_t next_sibling;
+    int64_t count;
+    int64_t total_ns;
+    int64_t self_ns;
+} FixProfileNode;
+
+// A frame of the call stack of the profiler.
+typedef struct
+{
+    int64_t start_ns;
+    int64_t children_ns;
+} FixProfileFrame;
+
+// The call tree and the call stack of the profiler recorded by a thread.
+typedef struct FixProfileThread
+{
+    FixProfileNode *nodes;
+    int64_t nodes_len;
+    int64_t nodes_cap;
+    FixProfileFrame *frames;
+    int64_t frames_len;
+    int64_t frames_cap;
+    int64_t current;
+    // Non-zero while the thread updates the buffers, so that the writer does not read them in the middle of an update.
+    int busy;
+    struct FixProfileThread *next;
+} FixProfileThread;
+
+// The buffers of the current thread.
+static __thread FixProfileThread *fixruntime_profile_thread = NULL;
+// The list of buffers of all threads. Buffers of finished threads are kept, so that their data is also written.
+static FixProfileThread *fixruntime_profile_threads = NULL;
+// Non-zero after the writer started. Then threads stop updating their buffers.
+static int fixruntime_profile_writing = 0;
+static int fixruntime_profile_output_fd = -1;
+static void (*fixruntime_profile_prev_abort_handler)(int) = SIG_DFL;
+
+// The number of times the writer checks whether a thread finished its update before giving up the data of the thread.
+#define FIXRUNTIME_PROFILE_WAIT_MAX 100000000
+
+static int64_t fixruntime_profile_now()
+{
+    struct timespec ts;
+    clock_gettime(CLOCK_MONOTONIC, &ts);
+    return (int64_t)ts.tv_sec * 1000000000 + (int64_t)ts.tv_nsec;
+}
+
+static int64_t fixruntime_profile_add_node(FixProfileThread *t, int64_t site, int64_t parent)
+{
+    if (t->nodes_len == t->nodes_cap)
+    {
+        t->nodes_cap = t->nodes_cap == 0 ? 1024 : t->nodes_cap * 2;
+        t->nodes = (FixProfileNode *)realloc(t->nodes, sizeof(FixProfileNode) * t->nodes_cap);
+        if (!t->nodes)
+        {
+            fixruntime_eprint("Failed to allocate memory for the profiler.\n");
+            abort();
+        }
+    }
+    int64_t idx = t->nodes_len++;
+    FixProfileNode *node = &t->nodes[idx];
+    node->site = site;
+    node->parent = parent;
+    node->first_child = -1;
+    node->next_sibling = -1;
+    node->count = 0;
+    node->total_ns = 0;
+    node->self_ns = 0;
+    if (parent >= 0)
+    {
+        node->next_sibling = t->nodes[parent].first_child;
+        t->nodes[parent].first_child = idx;
+    }
+    return idx;
+}
+
+// Get the buffers of the current thread to update them, or NULL if the writer has started.
+// The caller should call `fixruntime_profile_release_thread` after the update.
+static FixProfileThread *fixruntime_profile_acquire_thread()
+{
+    FixProfileThread *t = fixruntime_profile_thread;
+    if (!t)
+    {
+        t = (FixProfileThread *)calloc(1, sizeof(FixProfileThread));
+        if (!t)
+        {
+            fixruntime_eprint("Failed to allocate memory for the profiler.\n");
+            abort();
+        }
+        t->current = fixruntime_profile_add_node(t, -1, -1);
+        FixProfileThread *head = __atomic_load_n(&fixruntime_profile_threads, __ATOMIC_SEQ_CST);
+        do
+        {
+            t->next = head;
+        } while (!__atomic_compare_exchange_n(&fixruntime_profile_threads, &head, t, 0, __ATOMIC_SEQ_CST, __ATOMIC_SEQ_CST));
+        fixruntime_profile_thread = t;
+    }
+    // Set `busy` before checking `writing`, and the writer does in the reverse order, so that they do not access the buffers at the same time.
+    __atomic_store_n(&t->busy, 1, __ATOMIC_SEQ_CST);
+    if (__atomic_load_n(&fixruntime_profile_writing, __ATOMIC_SEQ_CST))
+    {
+        __atomic_store_n(&t->busy, 0, __ATOMIC_SEQ_CST);
+        return NULL;
+    }
+    return t;
+}
+
+static void fixruntime_profile_release_thread(FixProfileThread *t)
+{
+    __atomic_store_n(&t->busy, 0, __ATOMIC_SEQ_CST);
+}
+
+// Pop a frame of the call stack, and add the elapsed time to the current node.
+static void fixruntime_profile_pop_frame(FixProfileThread *t, int64_t now)
+{
+    FixProfileFrame *frame = &t->frames[--t->frames_len];
+    int64_t elapsed = now - frame->start_ns;
+    FixProfileNode *node = &t->nodes[t->current];
+    node->count += 1;
+    node->total_ns += elapsed;
+    node->self_ns += elapsed - frame->children_ns;
+    if (t->frames_len > 0)
+    {
+        t->frames[t->frames_len - 1].children_ns += elapsed;
+    }
+    t->current = node->parent;
+}
+
+// Called at the entry of an instrumented function.
+void fixruntime_profile_enter(int64_t site)
+{
+    FixProfileThread *t = fixruntime_profile_acquire_thread();
+    if (!t)
+    {
+        return;
+    }
+
+    // Find the node of the callee, or create it.
+    int64_t child = t->nodes[t->current].first_child;
+    while (child >= 0 && t->nodes[child].site != site)
+    {
+        child = t->nodes[child].next_sibling;
+    }
+    if (child < 0)
+    {
+        child = fixruntime_profile_add_node(t, site, t->current);
+    }
+    t->current = child;
+
+    // Push a frame.
+    if (t->frames_len == t->frames_cap)
+    {
+        t->frames_cap = t->frames_cap == 0 ? 1024 : t->frames_cap * 2;
+        t->frames = (FixProfileFrame *)realloc(t->frames, sizeof(FixProfileFrame) * t->frames_cap);
+        if (!t->frames)
+        {
+            fixruntime_eprint("Failed to allocate memory for the profiler.\n");
+            abort();
+        }
+    }
+    FixProfileFrame *frame = &t->frames[t->frames_len++];
+    frame->children_ns = 0;
+    frame->start_ns = fixruntime_profile_now();
+    fixruntime_profile_release_thread(t);
+}
+
+// Called at the exit of an instrumented function.
+void fixruntime_profile_exit()
+{
+    int64_t now = fixruntime_profile_now();
+    FixProfileThread *t = fixruntime_profile_acquire_thread();
+    if (!t)
+    {
+        return;
+    }
+    if (t->frames_len > 0)
+    {
+        fixruntime_profile_pop_frame(t, now);
+    }
+    fixruntime_profile_release_thread(t);
+}
+
+// Write the call trees of all threads to the output file, one node per line in the form "parent site count total_ns self_ns".
+// Nodes are numbered through all threads, and the root of the tree of each thread has parent -1.
+// Only async-signal-safe functions are used, since this is also called from the SIGABRT handler.
+static void fixruntime_profile_write()
+{
+    int fd = __atomic_exchange_n(&fixruntime_profile_output_fd, -1, __ATOMIC_SEQ_CST); // Write only once.
+    if (fd < 0)
+    {
+        return;
+    }
+    __atomic_store_n(&fixruntime_profile_writing, 1, __ATOMIC_SEQ_CST);
+    int64_t now = fixruntime_profile_now();
+    int64_t offset = 0;
+    for (FixProfileThread *t = __atomic_load_n(&fixruntime_profile_threads, __ATOMIC_SEQ_CST); t; t = t->next)
+    {
+        // Wait for the thread to finish its update.
+        // It may never finish if the current thread aborted in the middle of an update, and then the data of the thread is lost.
+        int64_t wait = 0;
+        while (__atomic_load_n(&t->busy, __ATOMIC_SEQ_CST) && wait < FIXRUNTIME_PROFILE_WAIT_MAX)
+        {
+            wait++;
+        }
+        if (__atomic_load_n(&t->busy, __ATOMIC_SEQ_CST))
+        {
+            continue;
+        }
+        // Close frames which are still open, e.g., when `exit` is called in a function or another thread is running.
+        while (t->frames_len > 0)
+        {
+            fixruntime_profile_pop_frame(t, now);
+        }
+        for (int64_t i = 0; i < t->nodes_len; i++)
+        {
+            FixProfileNode *node = &t->nodes[i];
+            fixruntime_write_i64(fd, node->parent >= 0 ? node->parent + offset : -1);
+            fixruntime_write_all(fd, " ", 1);
+            fixruntime_write_i64(fd, node->site);
+            fixruntime_write_all(fd, " ", 1);
+            fixruntime_write_i64(fd, node->count);
+            fixruntime_write_all(fd, " ", 1);
+            fixruntime_write_i64(fd, node->total_ns);
+            fixruntime_write_all(fd, " ", 1);
+            fixruntime_write_i64(fd, node->self_ns);
+            fixruntime_write_all(fd, "\n", 1);
+        }
+        offset += t->nodes_len;
+    }
+    close(fd);
+}
+
+static void fixruntime_profile_write_on_abort(int sig)
+{
+    fixruntime_profile_write();
+    fixruntime_call_prev_handler(fixruntime_profile_prev_abort_handler, sig);
+}
+
+// Register the path to which profile data is written at exit.
+// The data is also written when the program is aborted, e.g., by a failed assertion.
+void fixruntime_profile_register(const char *output_path)
+{
+    fixruntime_profile_output_fd = fixruntime_open_output(output_path, "profile data");
+    atexit(fixruntime_profile_write);
+    fixruntime_profile_prev_abort_handler = signal(SIGABRT, fixruntime_profile_write_on_abort);
+}
@@ -1,5 +1,3 @@
-use inkwell::module::Linkage;
-
 use super::*;
 
 pub const RUNTIME_ABORT: &str = "abort";
@@ -20,6 +18,11 @@ pub const RUNTIME_PTHREAD_ONCE: &str = "pthread_once";
 // pub const RUNTIME_RUN_FUNCTION: &str = "fixruntime_run_function_llvm";
 pub const RUNTIME_GET_ARGC: &str = "fixruntime_get_argc";
 pub const RUNTIME_GET_ARGV: &str = "fixruntime_get_argv";
+pub const RUNTIME_COVERAGE_REGISTER: &str = "fixruntime_coverage_register";
+pub const RUNTIME_PGO_REGISTER: &str = "fixruntime_pgo_register";
+pub const RUNTIME_PROFILE_ENTER: &str = "fixruntime_profile_enter";
+pub const RUNTIME_PROFILE_EXIT: &str = "fixruntime_profile_exit";
+pub const RUNTIME_PROFILE_REGISTER: &str = "fixruntime_profile_register";
 
 pub fn build_runtime<'c, 'm, 'b>(gc: &mut GenerationContext<'c, 'm>, mode: BuildMode) {
     build_abort_function(gc, mode);
@@ -44,6 +47,15 @@ pub fn build_runtime<'c, 'm, 'b>(gc: &mut GenerationContext<'c, 'm>, mode: Build
     // build_run_function(gc, mode); // This should be built after `build_mark_threaded_boxed_object_function`.
     build_get_argc_function(gc, mode);
     build_get_argv_function(gc, mode);
+    if gc.config.coverage {
+        build_coverage_register_function(gc, mode);
+    }
+    if gc.config.pgo_generate {
+        build_pgo_register_function(gc, mode);
+    }
+    if gc.config.profile {
+        build_profile_functions(gc, mode);
+    }
 }
 
 #[derive(PartialEq, Eq, Debug, Copy, Clone)]
@@ -112,6 +124,89 @@ fn build_sprintf_function<'c, 'm, 'b>(gc: &GenerationContext<'c, 'm>, mode: Buil
     return;
 }
 
+fn build_coverage_register_function<'c, 'm>(gc: &GenerationContext<'c, 'm>, mode: BuildMode) {
+    if mode != BuildMode::Declare {
+        return;
+    }
+    if let Some(_func) = gc.module.get_function(RUNTIME_COVERAGE_REGISTER) {
+        return;
+    }
+
+    let i64_type = gc.context.i64_type();
+    let i8_ptr_type = gc.context.i8_type().ptr_type(AddressSpace::from(0));
+    let counters_type = i64_type
+        .ptr_type(AddressSpace::from(0))
+        .ptr_type(AddressSpace::from(0));
+
+    let fn_ty = gc.context.void_type().fn_type(
+        &[
+            counters_type.into(), /* counters */
+            i64_type.into(),      /* number of counters */
+            i8_ptr_type.into(),   /* output path */
+        ],
+        false,
+    );
+    gc.module
+        .add_function(RUNTIME_COVERAGE_REGISTER, fn_ty, None);
+
+    return;
+}
+
+fn build_pgo_register_function<'c, 'm>(gc: &GenerationContext<'c, 'm>, mode: BuildMode) {
+    if mode != BuildMode::Declare {
+        return;
+    }
+    if let Some(_func) = gc.module.get_function(RUNTIME_PGO_REGISTER) {
+        return;
+    }
+
+    let i64_type = gc.context.i64_type();
+    let i8_ptr_type = gc.context.i8_type().ptr_type(AddressSpace::from(0));
+    let counters_type = i64_type
+        .ptr_type(AddressSpace::from(0))
+        .ptr_type(AddressSpace::from(0));
+    let keys_type = i8_ptr_type.ptr_type(AddressSpace::from(0));
+
+    let fn_ty = gc.context.void_type().fn_type(
+        &[
+            counters_type.into(), /* counters */
+            keys_type.into(),     /* keys */
+            i64_type.into(),      /* number of counters */
+            i8_ptr_type.into(),   /* default output path */
+        ],
+        false,
+    );
+    gc.module.add_function(RUNTIME_PGO_REGISTER, fn_ty, None);
+
+    return;
+}
+
+fn build_profile_functions<'c, 'm>(gc: &GenerationContext<'c, 'm>, mode: BuildMode) {
+    if mode != BuildMode::Declare {
+        return;
+    }
+    if let Some(_func) = gc.module.get_function(RUNTIME_PROFILE_ENTER) {
+        return;
+    }
+
+    let void_type = gc.context.void_type();
+    let i64_type = gc.context.i64_type();
+    let i8_ptr_type = gc.context.i8_type().ptr_type(AddressSpace::from(0));
+
+    let enter_ty = void_type.fn_type(&[i64_type.into() /* site */], false);
+    gc.module
+        .add_function(RUNTIME_PROFILE_ENTER, enter_ty, None);
+
+    let exit_ty = void_type.fn_type(&[], false);
+    gc.module.add_function(RUNTIME_PROFILE_EXIT, exit_ty, None);
+
+    let register_ty = void_type.fn_type(&[i8_ptr_type.into() /* output path */], false);
+    gc.module
+        .add_function(RUNTIME_PROFILE_REGISTER, register_ty, None);
+
+    return;
+}
+
 fn build_report_malloc_function<'c, 'm>(gc: &GenerationContext<'c, 'm>, mode: BuildMode) {
     if mode != BuildMode::Declare {
         return;
@@ -773,7 +868,7 @@ fn build_get_argc_function<'c, 'm, 'b>(gc: &mut GenerationContext<'c, 'm>, mode:
     // Add GLOBAL_VAR_NAME_ARGC global variable.
     let argc_gv = gc.module.add_global(argc_gv_ty, None, GLOBAL_VAR_NAME_ARGC);
     argc_gv.set_initializer(&argc_gv_ty.const_zero());
-    argc_gv.set_linkage(Linkage::Internal);
+    argc_gv.set_linkage(gc.config.external_if_separated());
 
     let bb = gc.context.append_basic_block(func, "entry");
 
@@ -825,7 +920,7 @@ fn build_get_argv_function<'c, 'm, 'b>(gc: &mut GenerationContext<'c, 'm>, mode:
     // Add GLOBAL_VAR_NAME_ARGV global variable.
     let argv_gv = gc.module.add_global(argv_gv_ty, None, GLOBAL_VAR_NAME_ARGV);
     argv_gv.set_initializer(&argv_gv_ty.const_zero());
-    argv_gv.set_linkage(Linkage::Internal);
+    argv_gv.set_linkage(gc.config.external_if_separated());
 
     let bb = gc.context.append_basic_block(func, "entry");
 
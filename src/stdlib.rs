@@ -525,6 +525,12 @@ pub fn make_std_mod(config: &Configuration) -> Result<Program, Errors> {
             include_str!("./docs/std_ffi_unsafe_get_retain_function_of_boxed_value.md").to_string(),
         ),
     ));
+    errors.eat_err(fix_module.add_global_value(
+        FullName::from_strs(&[STD_NAME, FFI_NAME], "unsafe_make_c_callback"),
+        make_c_callback_function(),
+        None,
+        Some(include_str!("./docs/std_ffi_unsafe_make_c_callback.md").to_string()),
+    ));
     errors.eat_err(fix_module.add_global_value(
         FullName::from_strs(&[STD_NAME, FFI_NAME], "_unsafe_get_boxed_data_ptr"),
         get_unsafe_get_boxed_ptr(),
@@ -4254,6 +4254,54 @@ pub fn test_run_examples() {
     test_files_in_directory(Path::new("./examples"));
 }
 
+#[test]
+pub fn test_load_imported_modules_from_source_roots() {
+    // Only "main.fix" is given, and other modules are searched in the source root.
+    let mut config = Configuration::develop_compiler_mode();
+    config
+        .source_files
+        .push(PathBuf::from("./examples/import/main.fix"));
+    config.source_roots.push(PathBuf::from("./examples/import"));
+    assert_eq!(run_file(config), 0);
+
+    // Without source roots, imported modules are not searched.
+    let mut config = Configuration::develop_compiler_mode();
+    config
+        .source_files
+        .push(PathBuf::from("./examples/import/main.fix"));
+    assert!(load_source_files(&mut config).is_err());
+}
+
+#[test]
+pub fn test_find_module_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().to_path_buf();
+    fs::create_dir_all(root.join("Foo")).unwrap();
+    fs::create_dir_all(root.join("baz")).unwrap();
+    fs::write(root.join("Foo/Bar.fix"), "").unwrap();
+    fs::write(root.join("Foo.Bar.fix"), "").unwrap();
+    fs::write(root.join("baz/qux.fix"), "").unwrap();
+    fs::write(root.join("baz.qux.fix"), "").unwrap();
+    fs::write(root.join("quux.fix"), "").unwrap();
+    let roots = vec![root.clone()];
+
+    // The directory layout is preferred to the dotted file name.
+    assert_eq!(
+        find_module_file(&"Foo.Bar".to_string(), &roots),
+        Some(root.join("Foo/Bar.fix"))
+    );
+    // Lowercase file names are searched if the module name does not match, in the same order.
+    assert_eq!(
+        find_module_file(&"Baz.Qux".to_string(), &roots),
+        Some(root.join("baz/qux.fix"))
+    );
+    assert_eq!(
+        find_module_file(&"Quux".to_string(), &roots),
+        Some(root.join("quux.fix"))
+    );
+    assert_eq!(find_module_file(&"Corge".to_string(), &roots), None);
+}
+
 // Run all "*.fix" files in the specified directory.
 // If the directory contains subdirectories, run Fix program consists of all "*.fix" files in each subdirectory.
 pub fn test_files_in_directory(path: &Path) {
@@ -6304,98 +6352,174 @@ pub fn test_export() {
 }
 
 #[test]
-pub fn test_unsafe_get_release_retain_function_of_boxed_value_decltype_technique_1() {
-    // Actual usage of `unsafe_get_release_function_of_boxed_value` is tested in asynctask.fix.
+pub fn test_run_by_jit() {
     let source = r##"
         module Main;
 
-        type VoidType = box struct {};
-        // No constructor for `VoidType` is provided.
+        fact : I64 -> I64;
+        fact = |n| if n == 0 { 1 } else { n * fact(n - 1) };
 
-        main: IO ();
+        main : IO ();
         main = (
-            let release = (|_| undefined() : VoidType).unsafe_get_release_function_of_boxed_value;
-            let retain = (|_| undefined() : VoidType).unsafe_get_retain_function_of_boxed_value;
+            eval assert_eq(|_|"", fact(10), 3628800);
+            eval *println("Hello from JIT!");
             pure()
         );
     "##;
-    test_source(&source, Configuration::develop_compiler_mode());
+    let mut config = Configuration::release_mode(SubCommand::Run);
+    config.jit = true;
+    test_source(&source, config);
 }
 
 #[test]
-pub fn test_unsafe_get_release_retain_function_of_boxed_value_decltype_technique_2() {
-    // Actual usage of `unsafe_get_release_function_of_boxed_value` is tested in asynctask.fix.
+pub fn test_repl_input() {
+    use repl::{is_balanced, ReplInput};
+
+    // Inputs spanning multiple lines are continued while brackets are not balanced.
+    assert!(is_balanced("1 + 2\n"));
+    assert!(!is_balanced("let x = (\n"));
+    assert!(!is_balanced("[1, 2,\n"));
+    assert!(!is_balanced("|x| {\n"));
+    assert!(is_balanced("let x = (\n  1 + 2\n);\n"));
+    assert!(is_balanced("[(1, {2}), (3, [4])]\n"));
+    // Brackets in string literals are ignored.
+    assert!(is_balanced("\"(\"\n"));
+    assert!(!is_balanced("(\")\"\n"));
+    assert!(is_balanced("\"\\\"(\"\n"));
+    assert!(!is_balanced("\"unterminated\n"));
+    // Too many closing brackets are reported by the parser, not by continuing the input.
+    assert!(is_balanced(")\n"));
+
+    assert_eq!(
+        ReplInput::parse(":type 1 + 2\n"),
+        ReplInput::Command(":type".to_string(), "1 + 2".to_string())
+    );
+    assert_eq!(
+        ReplInput::parse(":quit"),
+        ReplInput::Command(":quit".to_string(), "".to_string())
+    );
+    assert_eq!(
+        ReplInput::parse("import Character"),
+        ReplInput::Import("import Character;".to_string())
+    );
+    assert_eq!(
+        ReplInput::parse("let x = (\n  1 + 2\n);\n"),
+        ReplInput::Let("let x = (\n  1 + 2\n);".to_string())
+    );
+    assert_eq!(
+        ReplInput::parse("twice : I64 -> I64;"),
+        ReplInput::Global("twice : I64 -> I64;".to_string())
+    );
+    assert_eq!(
+        ReplInput::parse("twice = |x| 2 * x;"),
+        ReplInput::Global("twice = |x| 2 * x;".to_string())
+    );
+    assert_eq!(
+        ReplInput::parse("type Pair = struct { fst : I64, snd : I64 };"),
+        ReplInput::Global("type Pair = struct { fst : I64, snd : I64 };".to_string())
+    );
+    // An expression with a type annotation or a comparison is not a definition.
+    assert_eq!(
+        ReplInput::parse("x : I64"),
+        ReplInput::Expr("x : I64".to_string())
+    );
+    assert_eq!(
+        ReplInput::parse("x == 1;"),
+        ReplInput::Expr("x == 1".to_string())
+    );
+    assert_eq!(
+        ReplInput::parse("[1, 2,\n 3].to_iter.sum\n"),
+        ReplInput::Expr("[1, 2,\n 3].to_iter.sum".to_string())
+    );
+}
+
+#[test]
+pub fn test_check_values_not_used_from_main() {
+    // Values which are not used from `main` are also type-checked by `fix check`.
     let source = r##"
         module Main;
 
-        get_release_func_of_codom : (a -> b) -> Ptr;
-        get_release_func_of_codom = |f| (
-            let lazy_b = |_| f(undefined());
-            lazy_b.unsafe_get_release_function_of_boxed_value
-        );
-
-        get_retain_func_of_dom : (a -> b) -> Ptr;
-        get_retain_func_of_dom = |f| (
-            let lazy_a = |_| let x = undefined(); let _ = f(x); x;
-            lazy_a.unsafe_get_release_function_of_boxed_value
-        );
+        unused : I64;
+        unused = "Hello";
 
-        main: IO ();
-        main = (
-            let release = get_release_func_of_codom(|_ : I64| [42]);
-            let retain = get_retain_func_of_dom(|_ : Array I64| 42);
-            pure()
-        );
+        main : IO ();
+        main = pure();
     "##;
-    test_source(&source, Configuration::develop_compiler_mode());
+    let source_hash = format!("{:x}", md5::compute(source));
+    save_temporary_source(source, "main_check", &source_hash);
+    let mut config = Configuration::release_mode(SubCommand::Diagnostics);
+    config
+        .source_files
+        .push(temporary_source_path("main_check", &source_hash));
+    let res = check_file(&mut config);
+    assert!(res.is_err());
 }
 
 #[test]
-pub fn test_unsafe_get_release_function_of_boxed_value_error() {
+pub fn test_coverage_report() {
     let source = r##"
         module Main;
 
-        main: IO ();
+        fact : I64 -> I64;
+        fact = |n| if n == 0 { 1 } else { n * fact(n - 1) };
+
+        unused : I64 -> I64;
+        unused = |n| n + 1;
+
+        main : IO ();
         main = (
-            let release = (|_| undefined() : I64).unsafe_get_release_function_of_boxed_value;
+            eval assert_eq(|_|"", fact(3), 6);
+            eval assert_eq(|_|"", if fact(1) == 1 { 0 } else { unused(0) }, 0);
             pure()
         );
     "##;
-    test_source_fail(&source, Configuration::develop_compiler_mode(), "");
+    let mut config = Configuration::release_mode(SubCommand::Run);
+    config.coverage = true;
+    config.coverage_html = true;
+    test_source(&source, config);
+
+    let lcov = fs::read_to_string(PathBuf::from(COVERAGE_REPORT_PATH).join("lcov.info")).unwrap();
+    // Both branches of the `if` expression in `fact` are taken.
+    assert!(lcov.contains("BRDA:5,0,0,2\n"));
+    assert!(lcov.contains("BRDA:5,0,1,4\n"));
+    // The body of `unused` is not executed.
+    assert!(lcov.contains("DA:8,0\n"));
+    assert!(PathBuf::from(COVERAGE_REPORT_PATH)
+        .join("index.html")
+        .exists());
 }
 
 #[test]
-pub fn test_unsafe_get_retain_function_of_boxed_value_error() {
+pub fn test_profile_report() {
     let source = r##"
         module Main;
 
-        main: IO ();
+        fib : I64 -> I64;
+        fib = |n| if n <= 1 { n } else { fib(n - 1) + fib(n - 2) };
+
+        main : IO ();
         main = (
-            let retain = (|_| undefined() : I64).unsafe_get_retain_function_of_boxed_value;
+            let xs = Array::from_map(10, |i| fib(i));
+            eval assert_eq(|_|"", xs.@(9), 34);
             pure()
         );
     "##;
-    test_source_fail(&source, Configuration::develop_compiler_mode(), "");
-}
+    let mut config = Configuration::release_mode(SubCommand::Run);
+    config.profile = true;
+    test_source(&source, config);
 
-#[test]
-pub fn test_external_projects() {
-    test_external_project("https://github.com/tttmmmyyyy/fixlang-math.git");
-    test_external_project("https://github.com/tttmmmyyyy/fixlang-hashmap.git");
-    test_external_project("https://github.com/tttmmmyyyy/fixlang-hashset.git");
-    test_external_project("https://github.com/tttmmmyyyy/fixlang-random.git");
-    test_external_project("https://github.com/tttmmmyyyy/fixlang-time.git");
-    test_external_project("https://github.com/tttmmmyyyy/fixlang-character.git");
-    test_external_project("https://github.com/tttmmmyyyy/fixlang-subprocess.git");
-    test_external_project("https://github.com/tttmmmyyyy/fixlang-regexp.git");
-    test_external_project("https://github.com/tttmmmyyyy/fixlang-asynctask.git");
-    test_external_project("https://github.com/tttmmmyyyy/fixlang-gmp.git");
+    let summary =
+        fs::read_to_string(PathBuf::from(PROFILE_REPORT_PATH).join("summary.txt")).unwrap();
+    assert!(summary.contains("Main::fib : "));
+    assert!(summary.contains("Main::main::{lambda@"));
+    let folded =
+        fs::read_to_string(PathBuf::from(PROFILE_REPORT_PATH).join("stacks.folded")).unwrap();
+    assert!(folded.lines().any(|line| line.contains(";Main::fib ")));
 }
 
-pub fn test_external_project(url: &str) {
-    println!("Testing external project: {}", url);
-
-    // Run `cargo install --locked --path .`.
+#[test]
+pub fn test_profile_report_multi_threaded() {
+    // Run `cargo install --locked --path .` to run `fix` with the dependency on `asynctask`.
     let _ = Command::new("cargo")
         .arg("install")
         .arg("--locked")
@@ -6404,39 +6528,1381 @@ pub fn test_external_project(url: &str) {
         .output()
         .expect("Failed to run cargo install.");
 
-    // Recreate working directory for this test.
-    let work_dir = PathBuf::from(format!(
-        "{}/{}",
-        COMPILER_TEST_WORKING_PATH,
-        function_name!()
-    ));
+    let work_dir = PathBuf::from(COMPILER_TEST_WORKING_PATH).join(function_name!());
     let _ = fs::remove_dir_all(&work_dir);
-    let _ = fs::create_dir_all(&work_dir);
+    fs::create_dir_all(&work_dir).unwrap();
+    fs::write(
+        work_dir.join("fixproj.toml"),
+        r##"
+[general]
+name = "profile-multi-threaded"
+version = "0.1.0"
+
+[build]
+files = ["main.fix"]
+threaded = true
+
+[[dependencies]]
+name = "asynctask"
+git = { url = "https://github.com/tttmmmyyyy/fixlang-asynctask.git" }
+"##,
+    )
+    .unwrap();
+    fs::write(
+        work_dir.join("main.fix"),
+        r##"
+module Main;
+import AsyncTask;
+
+fib : I64 -> I64;
+fib = |n| if n <= 1 { n } else { fib(n - 1) + fib(n - 2) };
+
+count : I64 -> I64;
+count = |n| if n == 0 { 0 } else { 1 + count(n - 1) };
+
+main : IO ();
+main = (
+    // `fib` is called only in the thread of the task.
+    let task = Task::make(|_| fib(20));
+    eval assert_eq(|_|"", count(1000), 1000);
+    eval assert_eq(|_|"", task.get, 6765);
+    pure()
+);
+"##,
+    )
+    .unwrap();
 
-    // Run `git clone {url}`.
-    let _ = Command::new("git")
-        .arg("clone")
-        .arg(url)
+    let output = Command::new("fix")
+        .arg("run")
+        .arg("--profile")
         .current_dir(&work_dir)
         .output()
-        .expect("Failed to run git clone.");
+        .expect("Failed to run fix run.");
+    assert_eq!(
+        output.status.code(),
+        Some(0),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
 
-    // Get the created directory name.
-    let dir_name = url
-        .split("/")
-        .last()
+    // Both of the main thread and the thread of the task are profiled.
+    let report_dir = work_dir.join(PROFILE_REPORT_PATH);
+    let summary = fs::read_to_string(report_dir.join("summary.txt")).unwrap();
+    assert!(summary.contains("Main::count : "));
+    assert!(summary.contains("Main::fib : "));
+    let folded = fs::read_to_string(report_dir.join("stacks.folded")).unwrap();
+    assert!(folded.lines().any(|line| line.contains("Main::count ")));
+    assert!(folded
+        .lines()
+        .any(|line| line.contains("Main::main::{lambda@") && line.contains(";Main::fib ")));
+}
+
+#[test]
+pub fn test_pgo() {
+    let source = r##"
+        module Main;
+
+        fib : I64 -> I64;
+        fib = |n| if n <= 1 { n } else { fib(n - 1) + fib(n - 2) };
+
+        main : IO ();
+        main = (
+            eval assert_eq(|_|"", fib(20), 6765);
+            pure()
+        );
+    "##;
+    let source_hash = format!("{:x}", md5::compute(source));
+    save_temporary_source(source, "main_pgo", &source_hash);
+    let source_path = temporary_source_path("main_pgo", &source_hash);
+    let dir = tempfile::tempdir().unwrap();
+    let profile_path = dir.path().join(PGO_DEFAULT_PROFILE_PATH);
+
+    // Write a profile by an instrumented program.
+    let mut config = Configuration::release_mode(SubCommand::Build);
+    config.source_files.push(source_path.clone());
+    config.out_file_path = Some(dir.path().join("app_instrumented"));
+    config.pgo_generate = true;
+    build_file(&mut config).ok().unwrap();
+    let status = Command::new(dir.path().join("app_instrumented"))
+        .env("FIX_PGO_PROFILE", &profile_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let profile = fs::read_to_string(&profile_path).unwrap();
+    // The "then" branch of the `if` expression in `fib` is taken `fib(21)` times.
+    assert!(profile
+        .lines()
+        .any(|line| line.starts_with("10946\tif ") && line.ends_with(" 0")));
+    assert!(profile
+        .lines()
+        .any(|line| line.contains("\tfn ") && line.contains("fib") && !line.starts_with("0\t")));
+
+    // Build the program using the profile and a custom pass pipeline.
+    let mut config = Configuration::release_mode(SubCommand::Build);
+    config.source_files.push(source_path);
+    config.out_file_path = Some(dir.path().join("app"));
+    config.emit_llvm = true;
+    config.pgo_profile = Some(Arc::new(PgoProfile::load(&[profile_path]).ok().unwrap()));
+    config.llvm_passes = Some(parse_llvm_pass_names(
+        "function_inlining, default,loop_vectorize",
+    ));
+    build_file(&mut config).ok().unwrap();
+    let status = Command::new(dir.path().join("app")).status().unwrap();
+    assert!(status.success());
+
+    // The counts are passed to LLVM as branch weights of the `if` expression and entry counts of functions.
+    let ir = fs::read_dir(dir.path())
         .unwrap()
-        .to_string()
-        .replace(".git", "");
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().map_or(false, |ext| ext == "ll"))
+        .filter(|path| !path.to_string_lossy().ends_with("_optimized.ll"))
+        .map(|path| fs::read_to_string(path).unwrap())
+        .collect::<Vec<_>>()
+        .join("\n");
+    assert!(ir.contains("!{!\"branch_weights\", i32 10946, i32 10945}"));
+    assert!(ir.contains("!{!\"function_entry_count\", i64 21891}"));
+
+    // An LLVM profile is rejected.
+    let llvm_profile_path = dir.path().join("default.profdata");
+    fs::write(&llvm_profile_path, b"\x81iforpl\xff\x00\x00").unwrap();
+    assert!(PgoProfile::load(&[llvm_profile_path]).is_err());
+}
+
+#[test]
+pub fn test_unknown_llvm_pass() {
+    let passes = parse_llvm_pass_names("default,no_such_pass");
+    assert!(validate_llvm_pass_names(&passes).is_err());
+    let passes = parse_llvm_pass_names("sccp,scalar_repl_aggregates_ssa,jump_threading");
+    assert!(validate_llvm_pass_names(&passes).is_ok());
+
+    // An unknown pass in the project file is reported at its position.
+    let dir = PathBuf::from(COMPILER_TEST_WORKING_PATH).join(function_name!());
+    let _ = fs::create_dir_all(&dir);
+    let path = dir.join("fixproj.toml");
+    let content = r##"
+[general]
+name = "myproject"
+version = "0.1.0"
+
+[build]
+files = []
+llvm_passes = ["default", "no_such_pass"]
+"##;
+    fs::write(&path, content).unwrap();
+    let errs = ProjectFile::read_file(&path)
+        .err()
+        .unwrap()
+        .organize_by_path();
+    let span = &errs[0].1[0].srcs[0];
+    assert_eq!(&content[span.start..span.end], "\"no_such_pass\"");
 
-    // Run `fix test`.
-    let output = Command::new("fix")
-        .arg("test")
-        .current_dir(work_dir.join(dir_name))
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+pub fn test_pie_executable() {
+    let source = r##"
+        module Main;
+
+        main : IO ();
+        main = (
+            let arr = Array::from_map(10, |i| i * i);
+            eval assert_eq(|_|"", arr.to_iter.fold(0, add), 285);
+            pure()
+        );
+    "##;
+    let mut config = Configuration::develop_compiler_mode();
+    config.pie = true;
+    config.link_flags.push("-Wl,--as-needed".to_string());
+    test_source(&source, config);
+}
+
+#[test]
+pub fn test_c_compiler_command() {
+    // The C compiler can be given with a wrapper or arguments.
+    let mut config = Configuration::develop_compiler_mode();
+    config.c_compiler = "ccache gcc -m64".to_string();
+    let com = config.c_compiler_command().ok().unwrap();
+    assert_eq!(com.get_program(), "ccache");
+    assert_eq!(com.get_args().collect::<Vec<_>>(), vec!["gcc", "-m64"]);
+    config.c_compiler = " ".to_string();
+    assert!(config.c_compiler_command().is_err());
+
+    // Unused sections are removed only by linkers known to support it.
+    config.linker = Some("lld".to_string());
+    assert!(config.gc_sections_flag().is_some());
+    config.linker = Some("my-linker".to_string());
+    assert!(config.gc_sections_flag().is_none());
+
+    // A failure of the C compiler is reported as an error.
+    let mut config = Configuration::develop_compiler_mode();
+    config.c_compiler = "no-such-c-compiler".to_string();
+    config
+        .source_files
+        .push(PathBuf::from("examples/hello_world.fix"));
+    let errs = build_file(&mut config).err().unwrap();
+    assert!(errs.to_string().contains("no-such-c-compiler"));
+}
+
+#[test]
+pub fn test_c_type_sizes_of_host() {
+    let sizes = Configuration::develop_compiler_mode().c_type_sizes;
+    assert_eq!(sizes.char, 8);
+    assert_eq!(sizes.short, 16);
+    assert_eq!(sizes.int, 32);
+    assert_eq!(sizes.long_long, 64);
+    assert_eq!(sizes.float, 32);
+    assert_eq!(sizes.double, 64);
+    assert_eq!(sizes.size_t, usize::BITS as usize);
+    if std::env::consts::OS != "windows" {
+        assert_eq!(sizes.long, usize::BITS as usize);
+    }
+}
+
+#[test]
+pub fn test_needs_libdl() {
+    let config = Configuration::develop_compiler_mode();
+    assert!(!needs_libdl(&HashSet::new(), &config));
+    let called = HashSet::from(["fixruntime_dlopen".to_string()]);
+    assert!(needs_libdl(&called, &config));
+
+    // If the linker may not remove the unused wrappers in the runtime, libdl is always linked.
+    let mut config = Configuration::develop_compiler_mode();
+    config.linker = Some("unknown-linker".to_string());
+    assert!(needs_libdl(&HashSet::new(), &config));
+}
+
+#[test]
+pub fn test_thin_lto() {
+    let source = r##"
+        module Main;
+
+        table : Array I64;
+        table = Array::from_map(10, |i| i * i);
+
+        square : I64 -> I64;
+        square = |x| table.@(x);
+
+        add_offset : I64 -> I64 -> I64;
+        add_offset = |offset, x| x + offset;
+
+        main : IO ();
+        main = (
+            let f = add_offset(3);
+            let sum = Iterator::range(0, 10).fold(0, |s, i| s + f(square(i)));
+            eval assert_eq(|_|"", sum, 315);
+            println("Hello, " + "ThinLTO!")
+        );
+    "##;
+    // The second run uses cached bitcode files and object files.
+    for _ in 0..2 {
+        let mut config = Configuration::develop_compiler_mode();
+        config.set_fix_opt_level(FixOptimizationLevel::ThinLTO);
+        test_source(&source, config);
+    }
+    let mut config = Configuration::develop_compiler_mode();
+    config.set_fix_opt_level(FixOptimizationLevel::ThinLTO);
+    config.set_threaded();
+    test_source(&source, config);
+}
+
+#[test]
+pub fn test_ffi_call_struct_by_value() {
+    let source = r##"
+        module Main;
+
+        type IntPair = unbox struct { a : I32, b : I32 };
+        type Mixed = unbox struct { i : I64, f : F64 };
+        type Vec3f = unbox struct { x : F32, y : F32, z : F32 };
+        type Vec2 = unbox struct { x : F64, y : F64 };
+        type Rect = unbox struct { min : Vec2, max : Vec2 };
+        type Tagged = unbox struct { tag : U8, s : I16, p : Ptr };
+        type Big = unbox struct { a : I64, b : I64, c : I64 };
+        type DivT = unbox struct { quot : CInt, rem : CInt };
+
+        main : IO ();
+        main = (
+            let p = FFI_CALL[IntPair swap_pair(IntPair), IntPair { a : 1_I32, b : 2_I32 }];
+            eval assert_eq(|_|"swap_pair a", p.@a, 2_I32);
+            eval assert_eq(|_|"swap_pair b", p.@b, 1_I32);
+
+            let m = FFI_CALL[Mixed scale_mixed(Mixed, I64), Mixed { i : 3, f : 1.5 }, 2];
+            eval assert_eq(|_|"scale_mixed i", m.@i, 6);
+            eval assert_eq(|_|"scale_mixed f", m.@f, 3.0);
+
+            let v = FFI_CALL[Vec3f add_vec3f(Vec3f, Vec3f), Vec3f { x : 1.0_F32, y : 2.0_F32, z : 3.0_F32 }, Vec3f { x : 0.5_F32, y : 0.25_F32, z : 0.125_F32 }];
+            eval assert_eq(|_|"add_vec3f x", v.@x, 1.5_F32);
+            eval assert_eq(|_|"add_vec3f y", v.@y, 2.25_F32);
+            eval assert_eq(|_|"add_vec3f z", v.@z, 3.125_F32);
+
+            let r = FFI_CALL[Rect make_rect(Vec2, Vec2), Vec2 { x : 1.0, y : 2.0 }, Vec2 { x : 4.0, y : 6.0 }];
+            eval assert_eq(|_|"make_rect", r.@max.@y, 6.0);
+            let area = FFI_CALL[F64 rect_area(Rect), r];
+            eval assert_eq(|_|"rect_area", area, 12.0);
+
+            let t = "Fix".borrow_c_str(|ptr| FFI_CALL[I64 tagged_value(Tagged), Tagged { tag : 7_U8, s : -3_I16, p : ptr }]);
+            eval assert_eq(|_|"tagged_value", t, 6997);
+
+            let b = FFI_CALL[Big sum_big(Big, Big), Big { a : 1, b : 2, c : 3 }, Big { a : 10, b : 20, c : 30 }];
+            eval assert_eq(|_|"sum_big", (b.@a, b.@b, b.@c), (11, 22, 33));
+
+            // The struct is passed on the stack since general purpose registers are used up.
+            let n = FFI_CALL[I64 many_args(I64, I64, I64, I64, I64, IntPair), 1, 2, 3, 4, 5, IntPair { a : 6_I32, b : 7_I32 }];
+            eval assert_eq(|_|"many_args", n, 82);
+
+            let d = FFI_CALL[DivT div(CInt, CInt), 17.to_CInt, 5.to_CInt];
+            eval assert_eq(|_|"div quot", d.@quot, 3.to_CInt);
+            eval assert_eq(|_|"div rem", d.@rem, 2.to_CInt);
+
+            let x = FFI_CALL[CLongLong llabs(CLongLong), -42.to_CLongLong];
+            eval assert_eq(|_|"llabs", x, 42.to_CLongLong);
+
+            pure()
+        );
+    "##;
+    let c_source = r##"
+        #include <stdint.h>
+        #include <stddef.h>
+
+        typedef struct { int32_t a; int32_t b; } IntPair;
+        typedef struct { int64_t i; double f; } Mixed;
+        typedef struct { float x; float y; float z; } Vec3f;
+        typedef struct { double x; double y; } Vec2;
+        typedef struct { Vec2 min; Vec2 max; } Rect;
+        typedef struct { uint8_t tag; int16_t s; void* p; } Tagged;
+        typedef struct { int64_t a; int64_t b; int64_t c; } Big;
+
+        IntPair swap_pair(IntPair p) {
+            IntPair r = { p.b, p.a };
+            return r;
+        }
+
+        Mixed scale_mixed(Mixed m, int64_t k) {
+            m.i *= k;
+            m.f *= k;
+            return m;
+        }
+
+        Vec3f add_vec3f(Vec3f a, Vec3f b) {
+            Vec3f r = { a.x + b.x, a.y + b.y, a.z + b.z };
+            return r;
+        }
+
+        Rect make_rect(Vec2 min, Vec2 max) {
+            Rect r = { min, max };
+            return r;
+        }
+
+        double rect_area(Rect r) {
+            return (r.max.x - r.min.x) * (r.max.y - r.min.y);
+        }
+
+        int64_t tagged_value(Tagged t) {
+            if (t.p == NULL) {
+                return -1;
+            }
+            return t.tag * 1000 + t.s;
+        }
+
+        Big sum_big(Big x, Big y) {
+            Big r = { x.a + y.a, x.b + y.b, x.c + y.c };
+            return r;
+        }
+
+        int64_t many_args(int64_t a, int64_t b, int64_t c, int64_t d, int64_t e, IntPair p) {
+            return a + b + c + d + e + p.a * 10 + p.b;
+        }
+    "##;
+
+    // Create a working directory.
+    let _ = fs::create_dir_all(COMPILER_TEST_WORKING_PATH);
+
+    // Save `c_source` to a file.
+    let c_file = format!("{}/{}.c", COMPILER_TEST_WORKING_PATH, function_name!());
+    let mut file = File::create(&c_file).unwrap();
+    file.write_all(c_source.as_bytes()).unwrap();
+
+    // Build `c_source` into a shared library.
+    let lib_name = function_name!();
+    let so_file_path = format!("lib{}.so", lib_name);
+    let mut com = Command::new("gcc");
+    let output = com
+        .arg("-shared")
+        .arg("-fPIC")
+        .arg("-o")
+        .arg(so_file_path.clone())
+        .arg(&c_file)
         .output()
-        .expect("Failed to run fix test.");
+        .expect("Failed to run gcc.");
+    if output.stderr.len() > 0 {
+        eprintln!(
+            "{}",
+            String::from_utf8(output.stderr)
+                .unwrap_or("(failed to parse stderr from gcc as UTF8.)".to_string())
+        );
+    }
 
-    // Check the result.
+    // Link the shared library to the Fix program.
+    let mut config = Configuration::develop_compiler_mode();
+    config.add_dyanmic_library(lib_name);
+    config.library_search_paths.push(PathBuf::from("."));
+    test_source(&source, config);
+
+    let _ = fs::remove_file(so_file_path);
+}
+
+#[test]
+pub fn test_ffi_call_struct_not_representable() {
+    let source = r##"
+        module Main;
+
+        type Boxed = box struct { x : I64 };
+
+        main : IO ();
+        main = (
+            let _ = FFI_CALL[() use_boxed(Boxed), Boxed { x : 0 }];
+            pure()
+        );
+    "##;
+    test_source_fail(
+        &source,
+        Configuration::develop_compiler_mode(),
+        "Cannot use type `Main::Boxed` in the signature of FFI_CALL: `Main::Boxed` is a boxed struct.",
+    );
+
+    let source = r##"
+        module Main;
+
+        type Wrapper = unbox struct { s : String };
+
+        main : IO ();
+        main = (
+            let _ = FFI_CALL[() use_wrapper(Wrapper), Wrapper { s : "" }];
+            pure()
+        );
+    "##;
+    test_source_fail(
+        &source,
+        Configuration::develop_compiler_mode(),
+        "the type of field `_data` of `Std::String` is",
+    );
+}
+
+#[test]
+pub fn test_ffi_make_c_callback() {
+    let source = r##"
+        module Main;
+
+        main : IO ();
+        main = (
+            // A closure capturing a boxed value.
+            let offsets = [10, 20, 30];
+            let f : I64 -> I64 = |x| x + offsets.@(1);
+            let (fun, ctx, release) = f.unsafe_make_c_callback;
+            let res = FFI_CALL[I64 apply_twice(Ptr, Ptr, Ptr, I64), fun, ctx, release, 1];
+            eval assert_eq(|_|"apply_twice", res, 41);
+
+            // A closure with multiple parameters.
+            let scale = 3.0;
+            let f : CInt -> F64 -> F64 = |x, y| x.to_F64 * scale + y;
+            let (fun, ctx, release) = f.unsafe_make_c_callback;
+            let res = FFI_CALL[F64 fold_range(Ptr, Ptr, Ptr, CInt), fun, ctx, release, 4.to_CInt];
+            eval assert_eq(|_|"fold_range", res, 18.0);
+
+            // A closure which takes `()` and returns an I/O action.
+            let f : () -> IO I64 = |_| pure $ offsets.get_size;
+            let (fun, ctx, release) = f.unsafe_make_c_callback;
+            let res = FFI_CALL[I64 call_thunk(Ptr, Ptr, Ptr), fun, ctx, release];
+            eval assert_eq(|_|"call_thunk", res, 3);
+
+            // A closure which returns `()`.
+            let f : U32 -> () = |x| FFI_CALL[() record(U32), x + 1_U32];
+            let (fun, ctx, release) = f.unsafe_make_c_callback;
+            eval FFI_CALL[() call_each(Ptr, Ptr, Ptr, U32), fun, ctx, release, 5_U32];
+            eval assert_eq(|_|"record", FFI_CALL[U32 recorded()], 15_U32);
+
+            pure()
+        );
+    "##;
+    let c_source = r##"
+        #include <stdint.h>
+
+        int64_t apply_twice(int64_t (*f)(int64_t, void*), void* ctx, void (*release)(void*), int64_t x) {
+            int64_t res = f(f(x, ctx), ctx);
+            release(ctx);
+            return res;
+        }
+
+        double fold_range(double (*f)(int, double, void*), void* ctx, void (*release)(void*), int n) {
+            double acc = 0.0;
+            for (int i = 0; i < n; i++) {
+                acc = f(i, acc, ctx);
+            }
+            release(ctx);
+            return acc;
+        }
+
+        int64_t call_thunk(int64_t (*f)(void*), void* ctx, void (*release)(void*)) {
+            int64_t res = f(ctx);
+            release(ctx);
+            return res;
+        }
+
+        static uint32_t sum = 0;
+
+        void record(uint32_t x) {
+            sum += x;
+        }
+
+        uint32_t recorded() {
+            return sum;
+        }
+
+        void call_each(void (*f)(uint32_t, void*), void* ctx, void (*release)(void*), uint32_t n) {
+            for (uint32_t i = 0; i < n; i++) {
+                f(i, ctx);
+            }
+            release(ctx);
+        }
+    "##;
+
+    // Create a working directory.
+    let _ = fs::create_dir_all(COMPILER_TEST_WORKING_PATH);
+
+    // Save `c_source` to a file.
+    let c_file = format!("{}/{}.c", COMPILER_TEST_WORKING_PATH, function_name!());
+    let mut file = File::create(&c_file).unwrap();
+    file.write_all(c_source.as_bytes()).unwrap();
+
+    // Build `c_source` into a shared library.
+    let lib_name = function_name!();
+    let so_file_path = format!("lib{}.so", lib_name);
+    let mut com = Command::new("gcc");
+    let output = com
+        .arg("-shared")
+        .arg("-fPIC")
+        .arg("-o")
+        .arg(so_file_path.clone())
+        .arg(&c_file)
+        .output()
+        .expect("Failed to run gcc.");
+    if output.stderr.len() > 0 {
+        eprintln!(
+            "{}",
+            String::from_utf8(output.stderr)
+                .unwrap_or("(failed to parse stderr from gcc as UTF8.)".to_string())
+        );
+    }
+
+    // Link the shared library to the Fix program.
+    let mut config = Configuration::develop_compiler_mode();
+    config.add_dyanmic_library(lib_name);
+    config.library_search_paths.push(PathBuf::from("."));
+    test_source(&source, config);
+
+    let _ = fs::remove_file(so_file_path);
+}
+
+#[test]
+pub fn test_ffi_dynamic_library() {
+    let source = r##"
+        module Main;
+
+        type Vec2 = unbox struct { x : F64, y : F64 };
+
+        main : IO ();
+        main = (
+            let lib = *DynamicLibrary::open("./libtest_ffi_dynamic_library.so").try(exit_with_msg(1));
+
+            let add = *lib.get_symbol("add_int").try(exit_with_msg(1));
+            let x = FFI_CALL_PTR[CInt(CInt, CInt), add, 1.to_CInt, 2.to_CInt];
+            eval assert_eq(|_|"add_int", x, 3.to_CInt);
+
+            let scale = *lib.get_symbol("scale_vec2").try(exit_with_msg(1));
+            let v = FFI_CALL_PTR[Vec2(Vec2, F64), scale, Vec2 { x : 1.5, y : -2.0 }, 2.0];
+            eval assert_eq(|_|"scale_vec2", (v.@x, v.@y), (3.0, -4.0));
+
+            let n = *lib.borrow_symbol("get_counter", |f| IO::from_func(|_| FFI_CALL_PTR[I64(), f])).try(exit_with_msg(1));
+            eval assert_eq(|_|"get_counter", n, 42);
+
+            let res = *lib.get_symbol("no_such_symbol").to_result;
+            eval assert(|_|"no_such_symbol", res.is_err);
+            eval assert(|_|"no_such_symbol message", res.as_err.find("Failed to find a symbol \"no_such_symbol\"", 0).is_some);
+
+            let res = *DynamicLibrary::open("./libno_such_library.so").to_result;
+            eval assert(|_|"no_such_library", res.is_err);
+            eval assert(|_|"no_such_library message", res.as_err.find("Failed to open a dynamic library \"./libno_such_library.so\"", 0).is_some);
+
+            pure()
+        );
+    "##;
+    let c_source = r##"
+        #include <stdint.h>
+
+        typedef struct { double x; double y; } Vec2;
+
+        int add_int(int a, int b) {
+            return a + b;
+        }
+
+        Vec2 scale_vec2(Vec2 v, double k) {
+            Vec2 r = { v.x * k, v.y * k };
+            return r;
+        }
+
+        int64_t get_counter() {
+            return 42;
+        }
+    "##;
+
+    // Create a working directory.
+    let _ = fs::create_dir_all(COMPILER_TEST_WORKING_PATH);
+
+    // Save `c_source` to a file.
+    let c_file = format!("{}/{}.c", COMPILER_TEST_WORKING_PATH, function_name!());
+    let mut file = File::create(&c_file).unwrap();
+    file.write_all(c_source.as_bytes()).unwrap();
+
+    // Build `c_source` into a shared library, which is loaded by the Fix program at runtime.
+    let so_file_path = format!("lib{}.so", function_name!());
+    let mut com = Command::new("gcc");
+    let output = com
+        .arg("-shared")
+        .arg("-fPIC")
+        .arg("-o")
+        .arg(so_file_path.clone())
+        .arg(&c_file)
+        .output()
+        .expect("Failed to run gcc.");
+    if output.stderr.len() > 0 {
+        eprintln!(
+            "{}",
+            String::from_utf8(output.stderr)
+                .unwrap_or("(failed to parse stderr from gcc as UTF8.)".to_string())
+        );
+    }
+
+    test_source(&source, Configuration::develop_compiler_mode());
+
+    let _ = fs::remove_file(so_file_path);
+}
+
+#[test]
+pub fn test_ffi_call_ptr_wrong_number_of_arguments() {
+    let source = r##"
+        module Main;
+
+        main : IO ();
+        main = (
+            let _ = FFI_CALL_PTR[CInt(CInt), nullptr];
+            pure()
+        );
+    "##;
+    test_source_fail(
+        &source,
+        Configuration::develop_compiler_mode(),
+        "Wrong number of arguments in FFI_CALL_PTR expression.",
+    );
+}
+
+#[test]
+pub fn test_bindgen() {
+    let header = r##"
+        #ifndef COUNTER_H
+        #define COUNTER_H
+
+        #include <stdint.h>
+        #include <stddef.h>
+
+        #define ANSWER 42
+        #define MASK (1u << 4)
+        #define NEG (-ANSWER)
+        #define RATIO 2.5
+        #define GREETING "hello"
+        #define SQUARE(x) ((x) * (x))
+
+        /* An opaque handle. */
+        typedef struct counter counter_t;
+        typedef unsigned int flags_t;
+        typedef struct { int32_t x; int32_t y; } point_t;
+        typedef enum { RED, GREEN = 5, BLUE } color_t;
+        typedef union { int i; float f; } value_t;
+        struct bits { unsigned a : 1; };
+
+        #ifdef __cplusplus
+        extern "C" {
+        #endif
+
+        counter_t* counter_new(int64_t init);
+        void counter_add(counter_t* c, flags_t n);
+        int64_t counter_get(const counter_t *c);
+        void counter_free(counter_t* c) __attribute__((nonnull));
+        point_t point_add(point_t a, point_t b);
+        int color_value(color_t c);
+        size_t sum_bytes(const uint8_t data[], size_t len);
+        int log_message(const char* fmt, ...);
+        value_t make_value(int i);
+        static inline int twice(int x) { return 2 * x; }
+
+        #ifdef __cplusplus
+        }
+        #endif
+
+        #endif
+    "##;
+    let bindings = bindgen::generate_bindings(header, "counter.h", "Counter")
+        .ok()
+        .unwrap();
+    let source = &bindings.source;
+    assert!(source.contains("module Counter;"));
+    assert!(source.contains("type FlagsT = CUnsignedInt;"));
+    assert!(source.contains("type PointT = unbox struct { x : I32, y : I32 };"));
+    assert!(source.contains("type ColorT = CInt;"));
+    assert!(source.contains("mask : U64;\nmask = 16_U64;"));
+    assert!(source.contains("blue : CInt;\nblue = 6.to_CInt;"));
+    assert!(source.contains("counter_add : Ptr -> FlagsT -> IO ();"));
+    assert!(source.contains(
+        "sum_bytes = |data, len| IO::from_func(|_| FFI_CALL[CSizeT sum_bytes(Ptr, CSizeT), data, len]);"
+    ));
+    let warnings = bindings.warnings.join("\n");
+    assert!(warnings.contains("counter.h:12: macro `GREETING`"));
+    assert!(warnings.contains("macro `SQUARE`: function-like macros are not supported"));
+    assert!(warnings.contains("typedef `value_t`: unions are not supported"));
+    assert!(warnings.contains("`struct bits`: bitfields are not supported"));
+    assert!(warnings.contains("function `log_message`: variadic functions are not supported"));
+    assert!(warnings.contains("function `make_value`: the return type: `value_t` is not supported"));
+    assert!(warnings.contains("function `twice`: function definitions are not supported"));
+    assert!(!warnings.contains("counter_t"));
+
+    // Use the generated bindings.
+    let source = r##"
+        module Main;
+        import Counter;
+
+        main : IO ();
+        main = (
+            let c = *counter_new(10);
+            eval *counter_add(c, 5.to_CUnsignedInt);
+            let v = *counter_get(c);
+            eval assert_eq(|_|"counter_get", v, 15);
+            eval *counter_free(c);
+
+            let p = *point_add(PointT { x : 1_I32, y : 2_I32 }, PointT { x : 10_I32, y : 20_I32 });
+            eval assert_eq(|_|"point_add", (p.@x, p.@y), (11_I32, 22_I32));
+
+            let c = *color_value(blue);
+            eval assert_eq(|_|"color_value", c, 60.to_CInt);
+
+            eval assert_eq(|_|"answer", answer, 42);
+            eval assert_eq(|_|"neg", neg, -42);
+            eval assert_eq(|_|"ratio", ratio, 2.5);
+            pure()
+        );
+    "##;
+    let c_source = r##"
+        #include <stdint.h>
+        #include <stdlib.h>
+
+        typedef struct counter { int64_t value; } counter_t;
+        typedef struct { int32_t x; int32_t y; } point_t;
+
+        counter_t* counter_new(int64_t init) {
+            counter_t* c = malloc(sizeof(counter_t));
+            c->value = init;
+            return c;
+        }
+
+        void counter_add(counter_t* c, unsigned int n) {
+            c->value += n;
+        }
+
+        int64_t counter_get(const counter_t* c) {
+            return c->value;
+        }
+
+        void counter_free(counter_t* c) {
+            free(c);
+        }
+
+        point_t point_add(point_t a, point_t b) {
+            point_t r = { a.x + b.x, a.y + b.y };
+            return r;
+        }
+
+        int color_value(int c) {
+            return c * 10;
+        }
+    "##;
+
+    // Create a working directory.
+    let _ = fs::create_dir_all(COMPILER_TEST_WORKING_PATH);
+
+    // Save the bindings and `c_source` to files.
+    let bindings_file = format!("{}/{}.fix", COMPILER_TEST_WORKING_PATH, function_name!());
+    fs::write(&bindings_file, &bindings.source).unwrap();
+    let c_file = format!("{}/{}.c", COMPILER_TEST_WORKING_PATH, function_name!());
+    fs::write(&c_file, c_source).unwrap();
+
+    // Build `c_source` into a shared library.
+    let lib_name = function_name!();
+    let so_file_path = format!("lib{}.so", lib_name);
+    let output = Command::new("gcc")
+        .arg("-shared")
+        .arg("-fPIC")
+        .arg("-o")
+        .arg(so_file_path.clone())
+        .arg(&c_file)
+        .output()
+        .expect("Failed to run gcc.");
+    if output.stderr.len() > 0 {
+        eprintln!(
+            "{}",
+            String::from_utf8(output.stderr)
+                .unwrap_or("(failed to parse stderr from gcc as UTF8.)".to_string())
+        );
+    }
+
+    // Link the shared library to the Fix program.
+    let mut config = Configuration::develop_compiler_mode();
+    config.add_dyanmic_library(lib_name);
+    config.library_search_paths.push(PathBuf::from("."));
+    config.source_files.push(PathBuf::from(bindings_file));
+    test_source(&source, config);
+
+    let _ = fs::remove_file(so_file_path);
+}
+
+#[test]
+pub fn test_reuse_destructured_boxed_struct() {
+    let source = r##"
+        module Main;
+
+        type Pair = box struct { fst : Array I64, snd : Array I64 };
+        type Point = box struct { x : I64, y : I64 };
+
+        swap : Pair -> Pair;
+        swap = |p| (
+            let Pair { fst : x, snd : y } = p;
+            Pair { fst : y, snd : x }
+        );
+
+        // Reuse only in one branch.
+        swap_if : Bool -> Pair -> Pair;
+        swap_if = |c, p| (
+            let Pair { fst : x, snd : y } = p;
+            if c { Pair { fst : y, snd : x } } else { Pair { fst : x.push_back(0), snd : y } }
+        );
+
+        // Reuse the memory of `Point` for another `Point`, and discard unused fields.
+        move : Point -> Point;
+        move = |p| (
+            let Point { x : x } = p;
+            Point { x : x + 1, y : 0 }
+        );
+
+        // Memory of a `Pair` can be reused for a `Point` since they have the same size.
+        sum : Pair -> Point;
+        sum = |p| (
+            let Pair { fst : x, snd : y } = p;
+            Point { x : x.get_size, y : y.get_size }
+        );
+
+        main : IO ();
+        main = (
+            // Unique case.
+            let p = swap(Pair { fst : [1, 2], snd : [3] });
+            eval assert_eq(|_|"", p.@fst, [3]);
+            eval assert_eq(|_|"", p.@snd, [1, 2]);
+
+            // Shared case.
+            let q = Pair { fst : [1, 2], snd : [3] };
+            let r = swap(q);
+            eval assert_eq(|_|"", q.@fst, [1, 2]);
+            eval assert_eq(|_|"", r.@fst, [3]);
+
+            let p = swap_if(true, p);
+            eval assert_eq(|_|"", p.@fst, [1, 2]);
+            let p = swap_if(false, p);
+            eval assert_eq(|_|"", p.@fst, [1, 2, 0]);
+
+            let pt = move(Point { x : 1, y : 2 });
+            eval assert_eq(|_|"", pt.@x, 2);
+            eval assert_eq(|_|"", pt.@y, 0);
+
+            let pt = sum(p);
+            eval assert_eq(|_|"", pt.@x, 3);
+            eval assert_eq(|_|"", pt.@y, 1);
+            pure()
+        );
+    "##;
+    test_source(&source, Configuration::develop_compiler_mode());
+    let mut config = Configuration::develop_compiler_mode();
+    config.set_sanitize_memory();
+    test_source(&source, config);
+}
+
+#[test]
+pub fn test_stack_allocation_of_non_escaping_objects() {
+    let source = r##"
+        module Main;
+
+        type Pair = box struct { fst : Array I64, snd : I64 };
+
+        // A local closure which captures a boxed value and does not escape.
+        sum_with : Array I64 -> I64 -> I64;
+        sum_with = |arr, n| (
+            let add = |i| arr.@(0) + i;
+            add(n) + add(n + 1)
+        );
+
+        // Local closures which do not escape and are called in tail position.
+        add_one : I64 -> I64;
+        add_one = |n| (
+            let g = |x| x + n;
+            g(1)
+        );
+        add_first : Array I64 -> I64 -> I64;
+        add_first = |arr, n| (
+            let g = |x| arr.@(0) + x;
+            g(n)
+        );
+
+        // A boxed struct which does not escape.
+        use_pair : Array I64 -> I64;
+        use_pair = |arr| (
+            let p = Pair { fst : arr, snd : 10 };
+            let Pair { fst : xs } = p;
+            p.@snd + xs.get_size + p.@fst.@(0)
+        );
+
+        // A boxed struct which does not escape and is passed to a getter in tail position.
+        snd_of_pair : I64 -> I64;
+        snd_of_pair = |n| (
+            let p = Pair { fst : [n], snd : n };
+            p.@snd
+        );
+
+        // Objects which escape.
+        make_adder : Array I64 -> I64 -> I64;
+        make_adder = |arr| (
+            let add = |i| arr.@(0) + i;
+            add
+        );
+        make_pair : Array I64 -> Pair;
+        make_pair = |arr| (
+            let p = Pair { fst : arr, snd : 10 };
+            p
+        );
+
+        main : IO ();
+        main = (
+            let arr = [1, 2, 3];
+            eval assert_eq(|_|"", sum_with(arr, 5), 13);
+            eval assert_eq(|_|"", add_one(5), 6);
+            eval assert_eq(|_|"", add_first(arr, 5), 6);
+            eval assert_eq(|_|"", use_pair(arr), 14);
+            eval assert_eq(|_|"", snd_of_pair(7), 7);
+            eval assert_eq(|_|"", make_adder(arr)(5), 6);
+            eval assert_eq(|_|"", make_pair(arr).@snd, 10);
+            eval assert_eq(|_|"", Iterator::range(0, 10).fold(0, |s, i| (
+                let p = Pair { fst : [i], snd : i };
+                let f = |j| j + p.@snd;
+                s + f(p.@fst.@(0))
+            )), 90);
+            pure()
+        );
+    "##;
+    test_source(&source, Configuration::develop_compiler_mode());
+    test_source(&source, Configuration::release_mode(SubCommand::Run));
+    let mut config = Configuration::develop_compiler_mode();
+    config.set_sanitize_memory();
+    test_source(&source, config);
+
+    // Calls which take an object allocated on the stack of the caller are not marked as `tail`.
+    let source_hash = format!("{:x}", md5::compute(source));
+    save_temporary_source(source, "main_stack_allocation", &source_hash);
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = Configuration::release_mode(SubCommand::Build);
+    config
+        .source_files
+        .push(temporary_source_path("main_stack_allocation", &source_hash));
+    config.out_file_path = Some(dir.path().join("app"));
+    config.emit_llvm = true;
+    build_file(&mut config).ok().unwrap();
+    let ir = fs::read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().map_or(false, |ext| ext == "ll"))
+        .filter(|path| !path.to_string_lossy().ends_with("_optimized.ll"))
+        .map(|path| fs::read_to_string(path).unwrap())
+        .collect::<Vec<_>>()
+        .join("\n");
+    let body_lines = ir
+        .lines()
+        .skip_while(|line| !(line.starts_with("define ") && line.contains("@\"Main::snd_of_pair")))
+        .take_while(|line| *line != "}")
+        .collect::<Vec<_>>();
+    assert!(body_lines
+        .iter()
+        .any(|line| line.contains("call ") && line.contains("@snd")));
+    assert!(!body_lines.iter().any(|line| line.contains("tail call ")));
+}
+
+#[test]
+pub fn test_inline_optimization() {
+    let source = r##"
+        module Main;
+
+        type Vec2 = box struct { x : I64, y : I64 };
+
+        trait a : Norm {
+            norm : a -> I64;
+        }
+        impl Vec2 : Norm {
+            norm = |v| v.@x * v.@x + v.@y * v.@y;
+        }
+        INLINE[Norm::norm];
+
+        // Small functions, which are inlined.
+        sub : I64 -> I64 -> I64;
+        sub = |x, y| x - y;
+
+        add_vec : Vec2 -> Vec2 -> Vec2;
+        add_vec = |u, v| Vec2 { x : u.@x + v.@x, y : u.@y + v.@y };
+
+        apply_twice : (I64 -> I64) -> I64 -> I64;
+        apply_twice = |f, x| f(f(x));
+
+        // Mutually recursive functions.
+        is_even : I64 -> Bool;
+        is_even = |n| if n == 0 { true } else { is_odd(n - 1) };
+
+        is_odd : I64 -> Bool;
+        is_odd = |n| if n == 0 { false } else { is_even(n - 1) };
+
+        // A recursive function.
+        sum_to : I64 -> I64;
+        sum_to = |n| if n == 0 { 0 } else { n + sum_to(n - 1) };
+
+        namespace Large {
+            // A large function annotated to be inlined.
+            poly : I64 -> I64;
+            poly = |x| (
+                let a = x * x * x * x + 2 * x * x * x;
+                let b = 3 * x * x + 4 * x + 5;
+                let c = [a, b, x].to_iter.fold(0, |s, y| s + y);
+                a + b + c
+            );
+            INLINE[poly];
+
+            // A small function annotated not to be inlined.
+            inc : I64 -> I64;
+            inc = |x| x + 1;
+        }
+        NOINLINE[Large::inc];
+
+        main : IO ();
+        main = (
+            // The argument `x` has the same name as the first parameter of `sub`.
+            let y = 1;
+            let x = 10;
+            eval assert_eq(|_|"", sub(y, x), -9);
+            eval assert_eq(|_|"", sub(x, y), 9);
+
+            let u = Vec2 { x : 1, y : 2 };
+            let w = add_vec(u, add_vec(u, u));
+            eval assert_eq(|_|"", w.@x + w.@y, 9);
+            eval assert_eq(|_|"", w.norm, 45);
+            eval assert_eq(|_|"", apply_twice(|z| z * 3, 2), 18);
+            eval assert_eq(|_|"", apply_twice(Large::inc, 2), 4);
+            eval assert_eq(|_|"", Large::inc(1), 2);
+            eval assert_eq(|_|"", is_even(10), true);
+            eval assert_eq(|_|"", is_odd(7), true);
+            eval assert_eq(|_|"", sum_to(100), 5050);
+            eval assert_eq(|_|"", Large::poly(2), 116);
+            eval assert_eq(|_|"", Iterator::range(0, 10).fold(0, |s, i| s + sub(i, 1)), 35);
+            pure()
+        );
+    "##;
+    test_source(&source, Configuration::develop_compiler_mode());
+    let mut config = Configuration::develop_compiler_mode();
+    config.set_sanitize_memory();
+    test_source(&source, config);
+
+    // Check the LLVM-IR before LLVM's optimization, where only inlining by Fix has been done.
+    let source_hash = format!("{:x}", md5::compute(source));
+    save_temporary_source(source, "main_inline", &source_hash);
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = Configuration::release_mode(SubCommand::Build);
+    config
+        .source_files
+        .push(temporary_source_path("main_inline", &source_hash));
+    config.out_file_path = Some(dir.path().join("app"));
+    config.emit_llvm = true;
+    build_file(&mut config).ok().unwrap();
+    let ir = fs::read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().map_or(false, |ext| ext == "ll"))
+        .filter(|path| !path.to_string_lossy().ends_with("_optimized.ll"))
+        .map(|path| fs::read_to_string(path).unwrap())
+        .collect::<Vec<_>>()
+        .join("\n");
+    // A global function is called directly, or through its accessor `Get#...` if it is not uncurried.
+    let is_called = |name: &str| {
+        ir.lines().any(|line| {
+            line.contains("call ")
+                && (line.contains(&format!("@\"{}", name))
+                    || line.contains(&format!("@\"Get#{}", name)))
+        })
+    };
+    // Small functions and functions annotated by `INLINE` are inlined at all call sites.
+    assert!(!is_called("Main::sub"));
+    assert!(!is_called("Main::add_vec"));
+    assert!(!is_called("Main::Norm::norm"));
+    assert!(!is_called("Main::Large::poly"));
+    // A function annotated by `NOINLINE` is not inlined.
+    assert!(is_called("Main::Large::inc"));
+    // A recursive function is not inlined into itself.
+    assert!(is_called("Main::sum_to"));
+}
+
+#[test]
+pub fn test_inline_statement_unknown_value() {
+    let source = r##"
+        module Main;
+
+        f : I64 -> I64;
+        f = |x| x + 1;
+        INLINE[g];
+
+        main : IO ();
+        main = pure();
+    "##;
+    test_source_fail(
+        &source,
+        Configuration::develop_compiler_mode(),
+        "Unknown value `Main::g` in `INLINE` statement.",
+    );
+}
+
+#[test]
+pub fn test_inline_statement_conflict() {
+    let source = r##"
+        module Main;
+
+        f : I64 -> I64;
+        f = |x| x + 1;
+        INLINE[f];
+        NOINLINE[f];
+
+        main : IO ();
+        main = pure();
+    "##;
+    test_source_fail(
+        &source,
+        Configuration::develop_compiler_mode(),
+        "Both `INLINE` and `NOINLINE` are specified for `Main::f`.",
+    );
+}
+
+#[test]
+pub fn test_stream() {
+    let source = r##"
+        module Main;
+
+        main : IO ();
+        main = (
+            let n = 100;
+            let expected = Iterator::range(0, n).map(|i| 3 * i + 1).filter(|x| x % 2 == 0).fold(0, |s, x| s + x);
+            let actual = Stream::from_range(0, n).map(|i| 3 * i + 1).filter(|x| x % 2 == 0).fold(0, |s, x| s + x);
+            eval assert_eq(|_|"", actual, expected);
+
+            eval assert_eq(|_|"", Stream::from_range(0, 5).to_array, [0, 1, 2, 3, 4]);
+            eval assert_eq(|_|"", Stream::from_range(3, 3).to_array, []);
+            eval assert_eq(|_|"", Stream::from_range(0, 10).take(3).to_array, [0, 1, 2]);
+            eval assert_eq(|_|"", Stream::from_range(0, 2).take(3).to_array, [0, 1]);
+            eval assert_eq(|_|"", Stream::from_range(0, 10).filter(|i| i % 3 == 0).get_size, 4);
+            eval assert_eq(|_|"", Stream::from_range(1, 5).sum, 10);
+            eval assert_eq(|_|"", ["a", "b", "c"].to_stream.map(|s| s + s).to_array, ["aa", "bb", "cc"]);
+            eval assert_eq(|_|"", ["a", "b"].to_stream.zip(Stream::from_range(0, 3)).to_array, [("a", 0), ("b", 1)]);
+            eval assert_eq(|_|"", Iterator::range(0, 3).to_stream.map(|i| i * 2).to_array, [0, 2, 4]);
+            eval assert_eq(|_|"", Stream::from_range(0, 3).to_iter.to_array, [0, 1, 2]);
+            pure()
+        );
+    "##;
+    test_source(&source, Configuration::develop_compiler_mode());
+
+    // Check that a stream does not allocate objects for each element, by the number of allocations counted by the sanitizer.
+    let source = r##"
+        module Main;
+
+        by_stream : I64 -> I64;
+        by_stream = |n| Stream::from_range(0, n).map(|i| 3 * i + 1).filter(|x| x % 2 == 0).fold(0, |s, x| s + x);
+
+        by_iterator : I64 -> I64;
+        by_iterator = |n| Iterator::range(0, n).map(|i| 3 * i + 1).filter(|x| x % 2 == 0).fold(0, |s, x| s + x);
+
+        main : IO ();
+        main = (
+            let c0 = FFI_CALL[I64 get_allocation_count()];
+            let s10 = by_stream(10);
+            let c1 = FFI_CALL[I64 get_allocation_count()];
+            let s1000 = by_stream(1000);
+            let c2 = FFI_CALL[I64 get_allocation_count()];
+            let i10 = by_iterator(10);
+            let c3 = FFI_CALL[I64 get_allocation_count()];
+            let i1000 = by_iterator(1000);
+            let c4 = FFI_CALL[I64 get_allocation_count()];
+
+            eval assert_eq(|_|"", s10, i10);
+            eval assert_eq(|_|"", s1000, i1000);
+            // The number of allocations does not depend on the number of elements.
+            eval assert_eq(|_|"", c2 - c1, c1 - c0);
+            // The counter really counts allocations: an iterator allocates for each element.
+            eval assert(|_|"", c4 - c3 > c3 - c2);
+            pure()
+        );
+    "##;
+    let mut config = Configuration::develop_compiler_mode();
+    config.set_sanitize_memory();
+    test_source(&source, config);
+}
+
+#[test]
+pub fn test_unsafe_get_release_retain_function_of_boxed_value_decltype_technique_1() {
+    // Actual usage of `unsafe_get_release_function_of_boxed_value` is tested in asynctask.fix.
+    let source = r##"
+        module Main;
+
+        type VoidType = box struct {};
+        // No constructor for `VoidType` is provided.
+
+        main: IO ();
+        main = (
+            let release = (|_| undefined() : VoidType).unsafe_get_release_function_of_boxed_value;
+            let retain = (|_| undefined() : VoidType).unsafe_get_retain_function_of_boxed_value;
+            pure()
+        );
+    "##;
+    test_source(&source, Configuration::develop_compiler_mode());
+}
+
+#[test]
+pub fn test_unsafe_get_release_retain_function_of_boxed_value_decltype_technique_2() {
+    // Actual usage of `unsafe_get_release_function_of_boxed_value` is tested in asynctask.fix.
+    let source = r##"
+        module Main;
+
+        get_release_func_of_codom : (a -> b) -> Ptr;
+        get_release_func_of_codom = |f| (
+            let lazy_b = |_| f(undefined());
+            lazy_b.unsafe_get_release_function_of_boxed_value
+        );
+
+        get_retain_func_of_dom : (a -> b) -> Ptr;
+        get_retain_func_of_dom = |f| (
+            let lazy_a = |_| let x = undefined(); let _ = f(x); x;
+            lazy_a.unsafe_get_release_function_of_boxed_value
+        );
+
+        main: IO ();
+        main = (
+            let release = get_release_func_of_codom(|_ : I64| [42]);
+            let retain = get_retain_func_of_dom(|_ : Array I64| 42);
+            pure()
+        );
+    "##;
+    test_source(&source, Configuration::develop_compiler_mode());
+}
+
+#[test]
+pub fn test_unsafe_get_release_function_of_boxed_value_error() {
+    let source = r##"
+        module Main;
+
+        main: IO ();
+        main = (
+            let release = (|_| undefined() : I64).unsafe_get_release_function_of_boxed_value;
+            pure()
+        );
+    "##;
+    test_source_fail(&source, Configuration::develop_compiler_mode(), "");
+}
+
+#[test]
+pub fn test_unsafe_get_retain_function_of_boxed_value_error() {
+    let source = r##"
+        module Main;
+
+        main: IO ();
+        main = (
+            let retain = (|_| undefined() : I64).unsafe_get_retain_function_of_boxed_value;
+            pure()
+        );
+    "##;
+    test_source_fail(&source, Configuration::develop_compiler_mode(), "");
+}
+
+#[test]
+pub fn test_external_projects() {
+    test_external_project("https://github.com/tttmmmyyyy/fixlang-math.git");
+    test_external_project("https://github.com/tttmmmyyyy/fixlang-hashmap.git");
+    test_external_project("https://github.com/tttmmmyyyy/fixlang-hashset.git");
+    test_external_project("https://github.com/tttmmmyyyy/fixlang-random.git");
+    test_external_project("https://github.com/tttmmmyyyy/fixlang-time.git");
+    test_external_project("https://github.com/tttmmmyyyy/fixlang-character.git");
+    test_external_project("https://github.com/tttmmmyyyy/fixlang-subprocess.git");
+    test_external_project("https://github.com/tttmmmyyyy/fixlang-regexp.git");
+    test_external_project("https://github.com/tttmmmyyyy/fixlang-asynctask.git");
+    test_external_project("https://github.com/tttmmmyyyy/fixlang-gmp.git");
+}
+
+pub fn test_external_project(url: &str) {
+    println!("Testing external project: {}", url);
+
+    // Run `cargo install --locked --path .`.
+    let _ = Command::new("cargo")
+        .arg("install")
+        .arg("--locked")
+        .arg("--path")
+        .arg(".")
+        .output()
+        .expect("Failed to run cargo install.");
+
+    // Recreate working directory for this test.
+    let work_dir = PathBuf::from(format!(
+        "{}/{}",
+        COMPILER_TEST_WORKING_PATH,
+        function_name!()
+    ));
+    let _ = fs::remove_dir_all(&work_dir);
+    let _ = fs::create_dir_all(&work_dir);
+
+    // Run `git clone {url}`.
+    let _ = Command::new("git")
+        .arg("clone")
+        .arg(url)
+        .current_dir(&work_dir)
+        .output()
+        .expect("Failed to run git clone.");
+
+    // Get the created directory name.
+    let dir_name = url
+        .split("/")
+        .last()
+        .unwrap()
+        .to_string()
+        .replace(".git", "");
+
+    // Run `fix test`.
+    let output = Command::new("fix")
+        .arg("test")
+        .current_dir(work_dir.join(dir_name))
+        .output()
+        .expect("Failed to run fix test.");
+
+    // Check the result.
     assert_eq!(
         output.status.code(),
         Some(0),
@@ -6444,3 +7910,842 @@ pub fn test_external_project(url: &str) {
         url
     );
 }
+
+#[test]
+pub fn test_project_file_error_spans() {
+    // Returns the text highlighted by the first span of the error in the project file `content`.
+    fn error_text(dir: &Path, content: &str) -> String {
+        let _ = fs::create_dir_all(dir);
+        let path = dir.join("fixproj.toml");
+        fs::write(&path, content).unwrap();
+        let errs = match ProjectFile::read_file(&path) {
+            Ok(_) => panic!("The project file should be invalid."),
+            Err(errs) => errs.organize_by_path(),
+        };
+        assert_eq!(errs.len(), 1);
+        assert_eq!(errs[0].0, path);
+        let span = &errs[0].1[0].srcs[0];
+        content[span.start..span.end].to_string()
+    }
+
+    let dir = PathBuf::from(COMPILER_TEST_WORKING_PATH).join(function_name!());
+    let general = "[general]\nname = \"myproject\"\nversion = \"0.1.0\"\n\n[build]\nfiles = [\"main.fix\"]\n";
+
+    let content = "[general]\nname = \"my project\"\nversion = \"0.1.0\"\n\n[build]\nfiles = []\n";
+    assert_eq!(error_text(&dir, content), "\"my project\"");
+
+    let content = "[general]\nname = \"myproject\"\nversion = \"0.1\"\n\n[build]\nfiles = []\n";
+    assert_eq!(error_text(&dir, content), "\"0.1\"");
+
+    let content = general.to_string()
+        + "\n[[dependencies]]\nname = \"dep-a\"\npath = \"a\"\n"
+        + "\n[[dependencies]]\nname = \"dep-b\"\npath = \"b\"\n"
+        + "\n[[dependencies]]\nname = \"dep-a\"\npath = \"c\"\n";
+    let text = error_text(&dir, &content);
+    assert_eq!(text, "\"dep-a\"");
+    // The span points at the second occurrence.
+    let errs = ProjectFile::read_file(&dir.join("fixproj.toml"))
+        .err()
+        .unwrap()
+        .organize_by_path();
+    assert_eq!(errs[0].1[0].srcs[0].start, content.rfind("\"dep-a\"").unwrap());
+
+    let content = general.to_string()
+        + "\n[[dependencies]]\nname = \"dep-a\"\npath = \"a\"\ngit = { url = \"https://example.com/a.git\" }\n";
+    assert_eq!(error_text(&dir, &content), "\"a\"");
+
+    let content = general.to_string() + "\n[[dependencies]]\nname = \"dep-a\"\n";
+    assert_eq!(error_text(&dir, &content), "\"dep-a\"");
+
+    let content =
+        general.to_string() + "\n[[dependencies]]\nname = \"dep-a\"\npath = \"a\"\nversion = \"x.y\"\n";
+    assert_eq!(error_text(&dir, &content), "\"x.y\"");
+
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+pub fn test_project_file_profiles() {
+    let dir = PathBuf::from(COMPILER_TEST_WORKING_PATH).join(function_name!());
+    let _ = fs::create_dir_all(&dir);
+    let path = dir.join("fixproj.toml");
+    let content = r##"
+[general]
+name = "myproject"
+version = "0.1.0"
+
+[build]
+files = []
+opt_level = "separated"
+
+[profile.release]
+opt_level = "default"
+link_flags = ["-s"]
+
+[profile.fast-compile]
+opt_level = "minimum"
+threaded = true
+max_cu_size = 16
+
+[profile.single-threaded]
+threaded = false
+"##;
+    fs::write(&path, content).unwrap();
+    let proj_file = ProjectFile::read_file(&path).ok().unwrap();
+
+    // Defaults of the built-in profiles.
+    let mut config = Configuration::new(SubCommand::Run).ok().unwrap();
+    config.set_debug_info();
+    config.set_build_profile_defaults();
+    assert!(config.fix_opt_level == FixOptimizationLevel::Minimum);
+    assert!(!config.debug_info);
+    let mut config = Configuration::new(SubCommand::Build).ok().unwrap();
+    config.fix_opt_level = FixOptimizationLevel::None;
+    config.set_build_profile_defaults();
+    assert!(config.fix_opt_level == FixOptimizationLevel::Default);
+    assert!(!config.debug_info);
+
+    // `fix build` uses the release profile by default, which overrides `[build]` section.
+    let mut config = Configuration::new(SubCommand::Build).ok().unwrap();
+    config.set_build_profile_defaults();
+    proj_file.set_config(&mut config, false).ok().unwrap();
+    assert!(config.fix_opt_level == FixOptimizationLevel::Default);
+    assert_eq!(config.link_flags, vec!["-s".to_string()]);
+
+    // The dev profile is not defined, so `[build]` section overrides the defaults of the dev profile.
+    let mut config = Configuration::new(SubCommand::Run).ok().unwrap();
+    config.set_build_profile_defaults();
+    proj_file.set_config(&mut config, false).ok().unwrap();
+    assert!(config.fix_opt_level == FixOptimizationLevel::Separated);
+    assert!(config.link_flags.is_empty());
+
+    // A custom profile.
+    let mut config = Configuration::new(SubCommand::Build).ok().unwrap();
+    config.build_profile = "fast-compile".to_string();
+    proj_file.set_config(&mut config, false).ok().unwrap();
+    assert!(config.fix_opt_level == FixOptimizationLevel::Minimum);
+    assert!(config.threaded);
+    assert_eq!(config.max_cu_size, 16);
+
+    // A profile can turn off the threaded mode.
+    let mut config = Configuration::new(SubCommand::Build).ok().unwrap();
+    config.set_threaded();
+    config.build_profile = "single-threaded".to_string();
+    proj_file.set_config(&mut config, false).ok().unwrap();
+    assert!(!config.threaded);
+
+    // Profiles are distinguished by the hash of object files.
+    let mut release = Configuration::new(SubCommand::Build).ok().unwrap();
+    let mut dev = release.clone();
+    dev.build_profile = "dev".to_string();
+    release.build_profile = "release".to_string();
+    assert_ne!(
+        release.object_generation_hash(),
+        dev.object_generation_hash()
+    );
+
+    // Errors.
+    let mut config = Configuration::new(SubCommand::Build).ok().unwrap();
+    config.build_profile = "no-such-profile".to_string();
+    assert!(proj_file.set_config(&mut config, false).is_err());
+
+    // An invalid profile is an error even if it is not selected.
+    let content = content.to_string() + "\n[profile.broken]\nopt_level = \"fastest\"\n";
+    fs::write(&path, &content).unwrap();
+    let errs = ProjectFile::read_file(&path)
+        .err()
+        .unwrap()
+        .organize_by_path();
+    let span = &errs[0].1[0].srcs[0];
+    assert_eq!(&content[span.start..span.end], "\"fastest\"");
+
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+pub fn test_project_file_features() {
+    let dir = PathBuf::from(COMPILER_TEST_WORKING_PATH).join(function_name!());
+    let write_project = |sub_dir: &str, content: &str| {
+        let proj_dir = dir.join(sub_dir);
+        let _ = fs::create_dir_all(&proj_dir);
+        fs::write(proj_dir.join("fixproj.toml"), content).unwrap();
+        proj_dir
+    };
+    let root_dir = write_project(
+        "root",
+        r##"
+[general]
+name = "root"
+version = "0.1.0"
+
+[build]
+files = []
+
+[features]
+default = ["json"]
+json = ["dep-json"]
+fast = ["dep-lib/threaded"]
+
+[[dependencies]]
+name = "dep-lib"
+path = "../dep-lib"
+
+[[dependencies]]
+name = "dep-json"
+path = "../dep-json"
+optional = true
+
+[[dependencies]]
+name = "dep-unused"
+path = "../dep-unused"
+optional = true
+"##,
+    );
+    let lib_dir = write_project(
+        "dep-lib",
+        r##"
+[general]
+name = "dep-lib"
+version = "0.1.0"
+
+[build]
+files = ["lib.fix"]
+
+[features]
+default = ["log"]
+log = []
+threaded = []
+"##,
+    );
+    let json_dir = write_project(
+        "dep-json",
+        r##"
+[general]
+name = "dep-json"
+version = "0.1.0"
+
+[build]
+files = ["json.fix"]
+
+[[dependencies]]
+name = "dep-lib"
+path = "../dep-lib"
+default_features = false
+"##,
+    );
+    let unused_dir = write_project(
+        "dep-unused",
+        r##"
+[general]
+name = "dep-unused"
+version = "0.1.0"
+
+[build]
+files = ["unused.fix"]
+"##,
+    );
+    let root = ProjectFile::read_file(&root_dir.join("fixproj.toml"))
+        .ok()
+        .unwrap();
+    let mut lock_file = "proj_file_hash = \"\"\n".to_string();
+    for (name, path) in [
+        ("dep-lib", &lib_dir),
+        ("dep-json", &json_dir),
+        ("dep-unused", &unused_dir),
+    ] {
+        lock_file += &format!(
+            "\n[[dependencies]]\nname = \"{}\"\nversion = \"0.1.0\"\npath = \"{}\"\n",
+            name,
+            path.to_string_lossy()
+        );
+    }
+    let lock_file = toml::from_str::<DependecyLockFile>(&lock_file).unwrap();
+
+    // The default feature enables the optional dependency `dep-json`, and `--features=fast` enables a feature of `dep-lib`.
+    let mut config = Configuration::new(SubCommand::Build).ok().unwrap();
+    config.features = vec!["fast".to_string()];
+    root.set_config(&mut config, false).ok().unwrap();
+    lock_file.set_config(&root, &mut config).ok().unwrap();
+    let root_features = config.project_features(&root_dir.join("main.fix")).unwrap();
+    for feature in ["default", "json", "fast"] {
+        assert!(root_features.enabled.iter().any(|f| f == feature));
+    }
+    let lib_features = config.project_features(&lib_dir.join("lib.fix")).unwrap();
+    for feature in ["default", "log", "threaded"] {
+        assert!(lib_features.enabled.iter().any(|f| f == feature));
+    }
+    assert!(config.source_files.contains(&json_dir.join("json.fix")));
+    assert!(!config.source_files.contains(&unused_dir.join("unused.fix")));
+
+    // Unknown feature.
+    let mut config = Configuration::new(SubCommand::Build).ok().unwrap();
+    config.features = vec!["no-such-feature".to_string()];
+    assert!(root.set_config(&mut config, false).is_err());
+
+    // A feature should refer to a feature, an optional dependency or a feature of a dependency.
+    let content = r##"
+[general]
+name = "root"
+version = "0.1.0"
+
+[build]
+files = []
+
+[features]
+json = ["dep-json"]
+"##;
+    let path = write_project("broken", content).join("fixproj.toml");
+    let errs = ProjectFile::read_file(&path)
+        .err()
+        .unwrap()
+        .organize_by_path();
+    let span = &errs[0].1[0].srcs[0];
+    assert_eq!(&content[span.start..span.end], "[\"dep-json\"]");
+
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+pub fn test_cfg_attribute() {
+    let source = r##"
+        module Main;
+
+        CFG[!foo]
+        import NoSuchModule;
+
+        CFG[foo]
+        value : I64;
+        CFG[foo]
+        value = 1;
+        CFG[!foo]
+        value : I64;
+        CFG[!foo]
+        value = 2;
+
+        type Wrapper = box struct { data : I64 };
+
+        CFG[foo]
+        impl Wrapper : ToString {
+            to_string = |_| "foo";
+        }
+
+        namespace Sub {
+            CFG[ bar ] value : I64;
+            CFG[ bar ] value = 3;
+            CFG[ !bar ] value : I64;
+            CFG[ !bar ] value = 4;
+        }
+
+        main : IO ();
+        main = (
+            eval assert_eq(|_|"case 1", value, 1);
+            eval assert_eq(|_|"case 2", Sub::value, 4);
+            eval assert_eq(|_|"case 3", Wrapper { data : 0 }.to_string, "foo");
+            pure()
+        );
+    "##;
+
+    // Source files which do not belong to any project use features given by `--features` option.
+    let mut config = Configuration::develop_compiler_mode();
+    config.features = vec!["foo".to_string()];
+    test_source(&source, config);
+
+    // Features of the project which the source file belongs to.
+    let mut config = Configuration::develop_compiler_mode();
+    config.feature_sets.push(ProjectFeatures {
+        dir: std::env::current_dir().unwrap(),
+        declared: vec!["foo".to_string(), "bar".to_string()],
+        enabled: vec!["foo".to_string()],
+    });
+    test_source(&source, config);
+
+    // Features not declared in the project file.
+    let mut config = Configuration::develop_compiler_mode();
+    config.feature_sets.push(ProjectFeatures {
+        dir: std::env::current_dir().unwrap(),
+        declared: vec!["foo".to_string()],
+        enabled: vec!["foo".to_string()],
+    });
+    test_source_fail(&source, config, "Unknown feature `bar`.");
+
+    // An error in a namespace does not stop checking the following definitions.
+    let source = r##"
+        module Main;
+
+        namespace Sub {
+            CFG[baz] a : I64;
+            CFG[qux] b : I64;
+        }
+
+        CFG[quux] c : I64;
+
+        main : IO ();
+        main = pure();
+    "##;
+    for feature in ["baz", "qux", "quux"] {
+        let mut config = Configuration::develop_compiler_mode();
+        config.feature_sets.push(ProjectFeatures {
+            dir: std::env::current_dir().unwrap(),
+            declared: vec!["foo".to_string()],
+            enabled: vec![],
+        });
+        test_source_fail(&source, config, &format!("Unknown feature `{}`.", feature));
+    }
+}
+
+#[test]
+pub fn test_git_dependency_pinned() {
+    let dir = to_absolute_path(&PathBuf::from(COMPILER_TEST_WORKING_PATH).join(function_name!()));
+    let _ = fs::remove_dir_all(&dir);
+
+    // Create a git repository of a library: version 0.1.0 is tagged as "v0.1.0", and version 0.2.0 is committed on branch "dev".
+    let lib_dir = dir.join("lib");
+    fs::create_dir_all(&lib_dir).unwrap();
+    let repo = git2::Repository::init(&lib_dir).unwrap();
+    let sig = git2::Signature::now("test", "test@example.com").unwrap();
+    let commit_version = |version: &str, parent: Option<git2::Oid>| {
+        let content = format!(
+            "[general]\nname = \"lib\"\nversion = \"{}\"\n\n[build]\nfiles = []\n",
+            version
+        );
+        fs::write(lib_dir.join("fixproj.toml"), content).unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("fixproj.toml")).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let parents = parent.map(|oid| repo.find_commit(oid).unwrap());
+        let parents = parents.iter().collect::<Vec<_>>();
+        repo.commit(Some("HEAD"), &sig, &sig, version, &tree, &parents)
+            .unwrap()
+    };
+    let commit_1 = commit_version("0.1.0", None);
+    repo.tag_lightweight("v0.1.0", &repo.find_object(commit_1, None).unwrap(), false)
+        .unwrap();
+    let commit_2 = commit_version("0.2.0", Some(commit_1));
+    repo.branch("dev", &repo.find_commit(commit_2).unwrap(), false)
+        .unwrap();
+    // Move the default branch back to version 0.1.0.
+    repo.reference(
+        repo.head().unwrap().name().unwrap(),
+        commit_1,
+        true,
+        "reset",
+    )
+    .unwrap();
+
+    // Create the lock file of a project depending on the library with the given `git` field.
+    let root_dir = dir.join("root");
+    fs::create_dir_all(&root_dir).unwrap();
+    let create_lock_file = |version: &str, pin: &str| {
+        let content = format!(
+            "[general]\nname = \"root\"\nversion = \"0.1.0\"\n\n[build]\nfiles = []\n\n[[dependencies]]\nname = \"lib\"\nversion = \"{}\"\ngit = {{ url = \"{}\", {} }}\n",
+            version,
+            lib_dir.to_string_lossy(),
+            pin
+        );
+        let path = root_dir.join("fixproj.toml");
+        fs::write(&path, content).unwrap();
+        let proj_file = ProjectFile::read_file(&path).ok().unwrap();
+        DependecyLockFile::create(&proj_file, false, Path::new(VENDOR_PATH))
+            .map(|lock_file| toml::to_string(&lock_file).unwrap())
+    };
+
+    let lock_file = create_lock_file("0.2.0", "branch = \"dev\"").ok().unwrap();
+    assert!(lock_file.contains(&format!("rev = \"{}\"", commit_2)));
+    assert!(lock_file.contains("branch = \"dev\""));
+
+    let lock_file = create_lock_file("0.1.0", "tag = \"v0.1.0\"").ok().unwrap();
+    assert!(lock_file.contains(&format!("rev = \"{}\"", commit_1)));
+    assert!(lock_file.contains("tag = \"v0.1.0\""));
+
+    let rev = commit_2.to_string();
+    let lock_file = create_lock_file("*", &format!("rev = \"{}\"", &rev[..10]))
+        .ok()
+        .unwrap();
+    assert!(lock_file.contains(&format!("rev = \"{}\"", commit_2)));
+
+    // The pinned commit does not satisfy the version requirement.
+    let errs = create_lock_file("0.1.0", "branch = \"dev\"").err().unwrap();
+    let msg = errs.to_string();
+    assert!(msg.contains("branch \"dev\""));
+    assert!(msg.contains("is at version \"0.2.0\""));
+
+    // At most one of `rev`, `branch` and `tag`.
+    let content = format!(
+        "[general]\nname = \"root\"\nversion = \"0.1.0\"\n\n[build]\nfiles = []\n\n[[dependencies]]\nname = \"lib\"\ngit = {{ url = \"{}\", branch = \"dev\", tag = \"v0.1.0\" }}\n",
+        lib_dir.to_string_lossy(),
+    );
+    let path = root_dir.join("fixproj.toml");
+    fs::write(&path, content).unwrap();
+    assert!(ProjectFile::read_file(&path).is_err());
+
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+pub fn test_deps_vendor_offline() {
+    let dir = to_absolute_path(&PathBuf::from(COMPILER_TEST_WORKING_PATH).join(function_name!()));
+    let _ = fs::remove_dir_all(&dir);
+
+    // Create a git repository of a library.
+    let lib_dir = dir.join("lib");
+    fs::create_dir_all(&lib_dir).unwrap();
+    let repo = git2::Repository::init(&lib_dir).unwrap();
+    let sig = git2::Signature::now("test", "test@example.com").unwrap();
+    fs::write(
+        lib_dir.join("fixproj.toml"),
+        "[general]\nname = \"test-vendor-lib\"\nversion = \"0.1.0\"\n\n[build]\nfiles = [\"lib.fix\"]\n",
+    )
+    .unwrap();
+    let mut index = repo.index().unwrap();
+    index.add_path(Path::new("fixproj.toml")).unwrap();
+    index.write().unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    repo.commit(Some("HEAD"), &sig, &sig, "init", &tree, &[])
+        .unwrap();
+
+    // Create a project depending on the library.
+    let root_dir = dir.join("root");
+    fs::create_dir_all(&root_dir).unwrap();
+    let path = root_dir.join("fixproj.toml");
+    fs::write(
+        &path,
+        format!(
+            "[general]\nname = \"root\"\nversion = \"0.1.0\"\n\n[build]\nfiles = []\n\n[[dependencies]]\nname = \"test-vendor-lib\"\ngit = {{ url = \"{}\" }}\n",
+            lib_dir.to_string_lossy()
+        ),
+    )
+    .unwrap();
+    let proj_file = ProjectFile::read_file(&path).ok().unwrap();
+    let vendor_root = root_dir.join(VENDOR_PATH);
+
+    // In offline mode, the repository cannot be cloned to resolve the dependency.
+    let errs = DependecyLockFile::create(&proj_file, true, &vendor_root)
+        .err()
+        .unwrap();
+    assert!(errs.to_string().contains("in offline mode"));
+
+    // Resolve the dependency and install it into the test directory.
+    let created = DependecyLockFile::create(&proj_file, false, &vendor_root)
+        .ok()
+        .unwrap();
+    let created = toml::to_string(&created).unwrap();
+    let installed = dir.join("installed");
+    let lock_file = created.replace(EXTERNAL_PROJ_INSTALL_PATH, &installed.to_string_lossy());
+    let mut lock_file = toml::from_str::<DependecyLockFile>(&lock_file).unwrap();
+    lock_file.vendor_root = vendor_root.clone();
+    lock_file.install(false).ok().unwrap();
+
+    // The installed dependency can be used in offline mode.
+    lock_file.install(true).ok().unwrap();
+
+    // Vendor the dependency.
+    lock_file.vendor().ok().unwrap();
+    let vendored = vendor_root.join("test-vendor-lib");
+    assert!(vendored.join("fixproj.toml").exists());
+    assert!(!vendored.join(".git").exists());
+
+    // The vendored copy is used even if the installed one is removed.
+    fs::remove_dir_all(&installed).unwrap();
+    lock_file.install(true).ok().unwrap();
+    let mut config = Configuration::new(SubCommand::Build).ok().unwrap();
+    lock_file.set_config(&proj_file, &mut config).ok().unwrap();
+    assert!(config.source_files.contains(&vendored.join("lib.fix")));
+
+    // The dependency can be resolved from the vendored copy in offline mode.
+    let resolved = DependecyLockFile::create(&proj_file, true, &vendor_root)
+        .ok()
+        .unwrap();
+    assert_eq!(toml::to_string(&resolved).unwrap(), created);
+
+    // The vendored copy is not used for a dependency pinned to a tag, since it was not vendored for the tag.
+    let pinned_dir = dir.join("pinned");
+    fs::create_dir_all(&pinned_dir).unwrap();
+    let pinned_path = pinned_dir.join("fixproj.toml");
+    fs::write(
+        &pinned_path,
+        format!(
+            "[general]\nname = \"pinned\"\nversion = \"0.1.0\"\n\n[build]\nfiles = []\n\n[[dependencies]]\nname = \"test-vendor-lib\"\ngit = {{ url = \"{}\", tag = \"v0.1.0\" }}\n",
+            lib_dir.to_string_lossy()
+        ),
+    )
+    .unwrap();
+    let pinned = ProjectFile::read_file(&pinned_path).ok().unwrap();
+    let errs = DependecyLockFile::create(&pinned, true, &vendor_root)
+        .err()
+        .unwrap();
+    assert!(errs.to_string().contains("at tag \"v0.1.0\""));
+
+    // Neither vendored nor installed.
+    fs::remove_dir_all(&vendored).unwrap();
+    let errs = lock_file.install(true).err().unwrap();
+    assert!(errs.to_string().contains("in offline mode"));
+
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+pub fn test_infer_type_signature() {
+    // The type signature suggested by the language server includes constraints of the inferred scheme.
+    let source = r##"
+        module Main;
+
+        twice : a;
+        twice = |x| x + x;
+
+        exclaim : a;
+        exclaim = |x| x.to_string + "!";
+
+        main : IO ();
+        main = pure();
+    "##;
+    let source_hash = format!("{:x}", md5::compute(source));
+    save_temporary_source(source, "main_infer", &source_hash);
+    let mut config = Configuration::release_mode(SubCommand::Diagnostics);
+    config
+        .source_files
+        .push(temporary_source_path("main_infer", &source_hash));
+    let mut program = load_source_files(&mut config).ok().unwrap();
+    let tc = create_typechecker(&mut program, &config).ok().unwrap();
+    let import_stmts = program.mod_to_import_stmts[&"Main".to_string()].clone();
+    let tycon_names = program.tycon_names_with_aliases();
+    let trait_names = program.trait_names_with_aliases();
+    let infer = |name: &str| {
+        let name = FullName::from_strs(&["Main"], name);
+        let scm = program
+            .infer_scheme_of_global_value(&tc, &name)
+            .ok()
+            .unwrap();
+        lsp::language_server::shorten_scheme_for_display(
+            &scm,
+            &import_stmts,
+            &tycon_names,
+            &trait_names,
+        )
+    };
+    assert_eq!(infer("twice"), "[a : Add] a -> a");
+    assert_eq!(infer("exclaim"), "[a : ToString] a -> String");
+}
+
+#[test]
+pub fn test_lsp_signature_help_helpers() {
+    use lsp::language_server::{find_function_application_at, position_to_bytes, utf16_len};
+
+    // Find the function application enclosing the cursor, and the index of the parameter at the cursor.
+    assert_eq!(
+        find_function_application_at("    let x = f("),
+        Some(("f".to_string(), 0))
+    );
+    assert_eq!(
+        find_function_application_at("Std::Array::fill(3, "),
+        Some(("Std::Array::fill".to_string(), 1))
+    );
+    assert_eq!(
+        find_function_application_at("x.foo(g(1, 2), [3, 4], "),
+        Some(("foo".to_string(), 2))
+    );
+    assert_eq!(
+        find_function_application_at("f(g(1, "),
+        Some(("g".to_string(), 1))
+    );
+    // Closed applications, array literals and blocks are not function applications.
+    assert_eq!(find_function_application_at("f(1)"), None);
+    assert_eq!(find_function_application_at("f([1, "), None);
+    assert_eq!(find_function_application_at("(1, "), None);
+    assert_eq!(find_function_application_at("f(1); g"), None);
+
+    // Positions in LSP are counted in lines and characters.
+    let text = "ab\ncd\n";
+    let pos = |line, character| lsp_types::Position { line, character };
+    assert_eq!(position_to_bytes(text, pos(0, 0)), 0);
+    assert_eq!(position_to_bytes(text, pos(0, 1)), 1);
+    assert_eq!(position_to_bytes(text, pos(1, 0)), 3);
+    assert_eq!(position_to_bytes(text, pos(1, 2)), 5);
+    assert_eq!(position_to_bytes(text, pos(2, 0)), 6);
+    assert_eq!(utf16_len("abc"), 3);
+    assert_eq!(utf16_len("αβ"), 2);
+    assert_eq!(utf16_len("😀"), 2);
+
+    // Byte ranges of parameter types in a type signature.
+    let a = type_tyvar_star("a");
+    let b = type_tyvar_star("b");
+    let to_string = TraitId::from_fullname(FullName::from_strs(&[STD_NAME], "ToString"));
+    let scm = Scheme::generalize(
+        &[],
+        vec![Predicate::make(to_string, a.clone())],
+        vec![],
+        type_fun(
+            a.clone(),
+            type_fun(type_fun(a.clone(), b.clone()), b.clone()),
+        ),
+    );
+    let (str, ranges) = scm.to_string_normalize_with_param_ranges();
+    assert_eq!(str, "[a : Std::ToString] a -> (a -> b) -> b");
+    let params = ranges
+        .iter()
+        .map(|(start, end)| &str[*start..*end])
+        .collect::<Vec<_>>();
+    assert_eq!(params, vec!["a", "(a -> b)"]);
+}
+
+#[test]
+pub fn test_lsp_inlay_hint_helpers() {
+    use lsp::language_server::shorten_type_for_display;
+
+    // Names are shortened as far as they are unambiguous among accessible names.
+    let main = "Main".to_string();
+    let stmts = vec![
+        ImportStatement::implicit_self_import(main.clone()),
+        ImportStatement::implicit_std_import(main.clone()),
+    ];
+    let std_i64 = FullName::from_strs(&[STD_NAME], "I64");
+    let main_i64 = FullName::from_strs(&["Main"], "I64");
+    let std_array = FullName::from_strs(&[STD_NAME], "Array");
+    let other_array = FullName::from_strs(&["Other"], "Array");
+    let names = HashSet::from([
+        std_i64.clone(),
+        main_i64.clone(),
+        std_array.clone(),
+        other_array.clone(),
+    ]);
+    let shorten = |name: &FullName| ast::import::shortest_accessible_name(&stmts, name, &names);
+    assert_eq!(shorten(&std_i64).to_string(), "Std::I64");
+    assert_eq!(shorten(&main_i64).to_string(), "Main::I64");
+    assert_eq!(shorten(&std_array).to_string(), "Array");
+    assert_eq!(shorten(&other_array).to_string(), "Other::Array");
+
+    // Collect inferred types of `let` bindings, lambda parameters and monadic binds.
+    let source = r##"
+        module Main;
+
+        main : IO ();
+        main = (
+            let xs = [1, 2, 3];
+            let show = |x| x.to_string;
+            let line = *pure(show(xs.@(0)));
+            println(line)
+        );
+    "##;
+    let source_hash = format!("{:x}", md5::compute(source));
+    save_temporary_source(source, "main_inlay_hint", &source_hash);
+    let path = temporary_source_path("main_inlay_hint", &source_hash);
+    let mut config = Configuration::release_mode(SubCommand::Diagnostics);
+    config.source_files.push(path.clone());
+    let mut program = load_source_files(&mut config).ok().unwrap();
+    let tc = create_typechecker(&mut program, &config).ok().unwrap();
+    let main_name = FullName::from_strs(&["Main"], "main");
+    program
+        .resolve_namespace_and_check_type(&tc, &main_name, |_| Ok(true))
+        .ok()
+        .unwrap();
+    let import_stmts = program.mod_to_import_stmts[&main].clone();
+    let tycon_names = program.tycon_names_with_aliases();
+    let hints = program
+        .collect_binder_types(&path)
+        .into_iter()
+        .map(|(span, ty)| {
+            (
+                source[span.start..span.end].to_string(),
+                shorten_type_for_display(&ty, &import_stmts, &tycon_names),
+            )
+        })
+        .collect::<Vec<_>>();
+    let hint_of = |binder: &str| {
+        hints
+            .iter()
+            .find(|(text, _)| text == binder)
+            .map(|(_, ty)| ty.clone())
+    };
+    assert_eq!(hint_of("xs"), Some("Array I64".to_string()));
+    assert_eq!(hint_of("show"), Some("I64 -> String".to_string()));
+    assert_eq!(hint_of("x"), Some("I64".to_string()));
+    assert_eq!(hint_of("line"), Some("String".to_string()));
+    assert!(hints
+        .iter()
+        .any(|(text, ty)| text.ends_with("pure(show(xs.@(0)))") && ty == "String"));
+}
+
+#[test]
+pub fn test_lsp_code_action_helpers() {
+    use lsp::language_server::{
+        bytes_to_position, code_action_missing_field, code_action_type_signature,
+        import_statement_edits, position_to_bytes,
+    };
+    let pos = |line, character| lsp_types::Position { line, character };
+    let diag = |start, end| lsp_types::Diagnostic {
+        range: lsp_types::Range { start, end },
+        ..Default::default()
+    };
+
+    // Conversion between byte positions and LSP positions.
+    let text = "ab\ncd\n";
+    for bytes in 0..=text.len() {
+        assert_eq!(
+            position_to_bytes(text, bytes_to_position(text, bytes)),
+            bytes
+        );
+    }
+    assert_eq!(bytes_to_position(text, 4), pos(1, 1));
+
+    // Error codes are sent to the client as the data of diagnostics.
+    let code = error::ErrorCode::MissingField {
+        field: "y".to_string(),
+    };
+    let value = serde_json::to_value(&code).unwrap();
+    assert_eq!(
+        value,
+        serde_json::json!({ "kind": "MissingField", "field": "y" })
+    );
+    assert_eq!(
+        serde_json::from_value::<error::ErrorCode>(value).unwrap(),
+        code
+    );
+
+    // Import statements are inserted after the last import statement.
+    let content = "module Main;\nimport Std;\n\nmain : IO ();\n";
+    let entities = [
+        FullName::from_strs(&["Character"], "is_digit"),
+        FullName::from_strs(&[STD_NAME, "Iterator"], "fold"),
+    ];
+    let edits = import_statement_edits(&entities, content);
+    let titles = edits
+        .iter()
+        .map(|(title, _)| title.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        titles,
+        vec![
+            "Add `import Character::{is_digit};`",
+            "Add `import Std::{Iterator::fold};`"
+        ]
+    );
+    assert_eq!(edits[0].1.range.start, pos(2, 0));
+    assert_eq!(edits[0].1.new_text, "import Character::{is_digit};\n");
+
+    // A type signature is inserted above the definition keeping its indentation.
+    let content = "module Main;\nnamespace Foo {\n    twice = |x| x + x;\n}\n";
+    let edits = code_action_type_signature(
+        "twice : [a : Add] a -> a;",
+        &diag(pos(2, 4), pos(2, 5)),
+        content,
+    );
+    assert_eq!(edits.len(), 1);
+    assert_eq!(edits[0].0, "Add type signature `twice : [a : Add] a -> a;`");
+    assert_eq!(edits[0].1.range.start, pos(2, 0));
+    assert_eq!(edits[0].1.new_text, "    twice : [a : Add] a -> a;\n");
+
+    // A missing field is added before the closing brace of the struct literal.
+    let content = "let p = Point { x : 1 };";
+    let edits = code_action_missing_field("y", &diag(pos(0, 8), pos(0, 23)), content)
+        .ok()
+        .unwrap();
+    assert_eq!(edits[0].0, "Add field `y: undefined()`");
+    assert_eq!(edits[0].1.range.start, pos(0, 21));
+    assert_eq!(edits[0].1.new_text, ", y: undefined()");
+    let content = "let p = Point {};";
+    let edits = code_action_missing_field("y", &diag(pos(0, 8), pos(0, 16)), content)
+        .ok()
+        .unwrap();
+    assert_eq!(edits[0].1.range.start, pos(0, 15));
+    assert_eq!(edits[0].1.new_text, "y: undefined()");
+}
@@ -0,0 +1,343 @@
+// Link-time optimization across compilation units (`FixOptimizationLevel::ThinLTO`).
+//
+// This works like LLVM's ThinLTO:
+// 1. Each compilation unit is compiled and optimized separately, and written as an LLVM bitcode file together with a summary.
+//    The summary records the sizes of functions defined in the unit and the names of functions it refers to.
+//    Both files are cached by the hash of the compilation unit, as object files are in `FixOptimizationLevel::Separated`.
+// 2. At link time, using summaries of all units, we decide which functions each unit imports from other units:
+//    a function is imported if the unit refers to it and it has at most `THIN_LTO_IMPORT_SIZE_THRESHOLD` instructions.
+//    Imported functions are linked into the module of the unit as `available_externally` definitions, so that LLVM can inline them,
+//    while other functions of the source module are turned into declarations.
+//    Then the module is optimized again and written as an object file.
+//    The object file is cached by the contents of the unit and the imported units, so only units affected by changes are optimized again.
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use inkwell::{
+    context::Context,
+    module::{Linkage, Module},
+    passes::PassManager,
+    values::{BasicValue, FunctionValue},
+    OptimizationLevel,
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    configuration::Configuration,
+    constants::{COMPILATION_UNITS_PATH, THIN_LTO_IMPORT_SIZE_THRESHOLD},
+    error::Errors,
+    runner::{add_optimization_passes, get_target_machine, write_to_object_file},
+};
+
+// The summary of a compilation unit.
+#[derive(Serialize, Deserialize)]
+pub struct ThinLtoSummary {
+    // Hash of the LLVM IR of the unit.
+    // Unlike the hash of the compilation unit, this does not change when the main unit is regenerated without any changes.
+    content_hash: String,
+    // Functions with external linkage defined in the unit, and their numbers of instructions.
+    defined: BTreeMap<String, usize>,
+    // Functions declared in the unit and used.
+    referenced: Vec<String>,
+}
+
+pub fn bitcode_file_path(unit_hash: &str) -> PathBuf {
+    PathBuf::from(COMPILATION_UNITS_PATH).join(format!("{}.bc", unit_hash))
+}
+
+pub fn summary_file_path(unit_hash: &str) -> PathBuf {
+    PathBuf::from(COMPILATION_UNITS_PATH).join(format!("{}.summary.json", unit_hash))
+}
+
+fn lto_object_file_path(lto_hash: &str) -> PathBuf {
+    PathBuf::from(COMPILATION_UNITS_PATH).join(format!("{}.lto.o", lto_hash))
+}
+
+// Write the bitcode and the summary of an optimized module of a compilation unit.
+pub fn write_unit_bitcode<'c>(module: &Module<'c>, unit_hash: &str) -> Result<(), Errors> {
+    let summary = ThinLtoSummary::create(module);
+    let summary = serde_json::to_string(&summary).unwrap();
+
+    // Write to temporary files and rename them, so that other processes do not see incomplete files.
+    // The summary is written after the bitcode, since the existence of the summary means that the unit is cached.
+    let bc_path = bitcode_file_path(unit_hash);
+    let tmp_path = temporary_path(&bc_path);
+    fs::create_dir_all(COMPILATION_UNITS_PATH).map_err(|e| {
+        Errors::from_msg(format!(
+            "Failed to create directory \"{}\": {}",
+            COMPILATION_UNITS_PATH, e
+        ))
+    })?;
+    if !module.write_bitcode_to_path(&tmp_path) {
+        return Err(Errors::from_msg(format!(
+            "Failed to write bitcode to \"{}\".",
+            bc_path.to_string_lossy()
+        )));
+    }
+    rename(&tmp_path, &bc_path)?;
+
+    let summary_path = summary_file_path(unit_hash);
+    let tmp_path = temporary_path(&summary_path);
+    fs::write(&tmp_path, summary).map_err(|e| {
+        Errors::from_msg(format!(
+            "Failed to write to file \"{}\": {}",
+            summary_path.to_string_lossy(),
+            e
+        ))
+    })?;
+    rename(&tmp_path, &summary_path)?;
+    Ok(())
+}
+
+fn temporary_path(path: &Path) -> PathBuf {
+    path.with_extension(rand::thread_rng().gen::<u64>().to_string() + ".tmp")
+}
+
+fn rename(from: &Path, to: &Path) -> Result<(), Errors> {
+    fs::rename(from, to).map_err(|e| {
+        Errors::from_msg(format!(
+            "Failed to rename \"{}\" to \"{}\": {}",
+            from.to_string_lossy(),
+            to.to_string_lossy(),
+            e
+        ))
+    })
+}
+
+impl ThinLtoSummary {
+    fn create<'c>(module: &Module<'c>) -> ThinLtoSummary {
+        let mut defined = BTreeMap::new();
+        let mut referenced = vec![];
+        for func in module.get_functions() {
+            let name = func.get_name().to_str().unwrap().to_string();
+            if func.count_basic_blocks() == 0 {
+                let used = func
+                    .as_global_value()
+                    .as_pointer_value()
+                    .get_first_use()
+                    .is_some();
+                if used {
+                    referenced.push(name);
+                }
+                continue;
+            }
+            if is_local_linkage(func.get_linkage()) {
+                continue;
+            }
+            defined.insert(name, function_size(func));
+        }
+
+        // The module identifier contains the hash of the compilation unit, so we exclude it.
+        let ir = module.print_to_string().to_string();
+        let ir = ir
+            .lines()
+            .filter(|line| !line.starts_with("; ModuleID") && !line.starts_with("source_filename"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        ThinLtoSummary {
+            content_hash: format!("{:x}", md5::compute(ir)),
+            defined,
+            referenced,
+        }
+    }
+
+    fn load(unit_hash: &str) -> Result<ThinLtoSummary, Errors> {
+        let path = summary_file_path(unit_hash);
+        let content = fs::read_to_string(&path).map_err(|e| {
+            Errors::from_msg(format!(
+                "Failed to read \"{}\": {}",
+                path.to_string_lossy(),
+                e
+            ))
+        })?;
+        serde_json::from_str(&content).map_err(|e| {
+            Errors::from_msg(format!(
+                "Failed to parse \"{}\": {}",
+                path.to_string_lossy(),
+                e
+            ))
+        })
+    }
+}
+
+fn is_local_linkage(linkage: Linkage) -> bool {
+    linkage == Linkage::Internal || linkage == Linkage::Private
+}
+
+// The number of instructions in a function.
+fn function_size(func: FunctionValue) -> usize {
+    let mut size = 0;
+    for bb in func.get_basic_blocks() {
+        let mut inst = bb.get_first_instruction();
+        while let Some(i) = inst {
+            size += 1;
+            inst = i.get_next_instruction();
+        }
+    }
+    size
+}
+
+// Perform link-time optimization of compilation units, and return paths of object files to be linked.
+// `unit_hashes` are the hashes of all compilation units, whose bitcode files and summaries have been written.
+pub fn thin_lto_link_units(
+    unit_hashes: &[String],
+    config: &Configuration,
+) -> Result<Vec<PathBuf>, Errors> {
+    let summaries = unit_hashes
+        .iter()
+        .map(|hash| ThinLtoSummary::load(hash))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Map each function to the unit defining it.
+    let mut definitions: BTreeMap<&str, usize> = BTreeMap::new();
+    for (i, summary) in summaries.iter().enumerate() {
+        for name in summary.defined.keys() {
+            definitions.insert(name, i);
+        }
+    }
+
+    let mut obj_paths = vec![];
+    let mut threads = vec![];
+    for (i, summary) in summaries.iter().enumerate() {
+        // Decide functions to be imported from other units.
+        let mut imports: BTreeMap<usize, BTreeSet<String>> = BTreeMap::new();
+        for name in &summary.referenced {
+            let src = match definitions.get(name.as_str()) {
+                Some(src) if *src != i => *src,
+                _ => continue,
+            };
+            if summaries[src].defined[name] <= THIN_LTO_IMPORT_SIZE_THRESHOLD {
+                imports.entry(src).or_default().insert(name.clone());
+            }
+        }
+
+        // The object file depends on the contents of this unit and the imported functions.
+        let mut data = vec![summary.content_hash.clone()];
+        for (src, names) in &imports {
+            data.push(summaries[*src].content_hash.clone());
+            data.extend(names.iter().cloned());
+        }
+        let lto_hash = format!("{:x}", md5::compute(data.join(", ")));
+        let obj_path = lto_object_file_path(&lto_hash);
+        obj_paths.push(obj_path.clone());
+        if obj_path.exists() {
+            if config.verbose {
+                eprintln!(
+                    "Skipping link-time optimization of unit {}.",
+                    unit_hashes[i]
+                );
+            }
+            continue;
+        }
+        if config.verbose {
+            eprintln!(
+                "Link-time optimization of unit {}: importing {} functions from {} units.",
+                unit_hashes[i],
+                imports.values().map(|names| names.len()).sum::<usize>(),
+                imports.len()
+            );
+        }
+
+        let unit_hash = unit_hashes[i].clone();
+        let imports = imports
+            .into_iter()
+            .map(|(src, names)| (unit_hashes[src].clone(), names))
+            .collect::<Vec<_>>();
+        let config = config.clone();
+        threads.push(std::thread::spawn(move || {
+            optimize_unit_with_imports(&unit_hash, &imports, &obj_path, &config)
+        }));
+    }
+    for t in threads {
+        match t.join() {
+            Err(e) => std::panic::panic_any(e),
+            Ok(res) => res?,
+        }
+    }
+    Ok(obj_paths)
+}
+
+// Link imported functions into the module of a unit, optimize it and write it to an object file.
+fn optimize_unit_with_imports(
+    unit_hash: &str,
+    imports: &[(String, BTreeSet<String>)],
+    obj_path: &Path,
+    config: &Configuration,
+) -> Result<(), Errors> {
+    let context = Context::create();
+    let module = parse_bitcode(unit_hash, &context)?;
+    for (src_hash, names) in imports {
+        let src = parse_bitcode(src_hash, &context)?;
+        prepare_imported_module(&src, names);
+        module.link_in_module(src).map_err(|e| {
+            Errors::from_msg(format!(
+                "Failed to link unit {} into unit {}: {}",
+                src_hash,
+                unit_hash,
+                e.to_string()
+            ))
+        })?;
+    }
+
+    // Optimize the module again, and remove functions which are no longer used.
+    let passmgr = PassManager::create(());
+    passmgr.add_verifier_pass();
+    add_optimization_passes(&passmgr, config);
+    passmgr.add_global_dce_pass();
+    passmgr.add_verifier_pass();
+    passmgr.run_on(&module);
+
+    let target_machine = get_target_machine(OptimizationLevel::Default, config);
+    write_to_object_file(&module, &target_machine, obj_path);
+    Ok(())
+}
+
+fn parse_bitcode<'c>(unit_hash: &str, context: &'c Context) -> Result<Module<'c>, Errors> {
+    let path = bitcode_file_path(unit_hash);
+    Module::parse_bitcode_from_path(&path, context).map_err(|e| {
+        Errors::from_msg(format!(
+            "Failed to read bitcode \"{}\": {}",
+            path.to_string_lossy(),
+            e.to_string()
+        ))
+    })
+}
+
+// Edit a module so that linking it into another module only imports the given functions.
+// - Imported functions become `available_externally`, i.e., they can be inlined but are not emitted.
+// - Other functions and global variables with external linkage become declarations (or `available_externally` for global variables).
+// - Functions and constants with local linkage (e.g., closures and string literals) are kept, since imported functions may use them.
+//   They are copied into the importing module, and removed after optimization if they are no longer used.
+fn prepare_imported_module<'c>(module: &Module<'c>, imported: &BTreeSet<String>) {
+    let funcs = module.get_functions().collect::<Vec<_>>();
+    for func in funcs {
+        if func.count_basic_blocks() == 0 || is_local_linkage(func.get_linkage()) {
+            continue;
+        }
+        let name = func.get_name().to_str().unwrap().to_string();
+        if imported.contains(&name) {
+            func.set_linkage(Linkage::AvailableExternally);
+            continue;
+        }
+        // Replace the function with a declaration.
+        let decl = module.add_function("", func.get_type(), Some(Linkage::External));
+        decl.set_call_conventions(func.get_call_conventions());
+        func.replace_all_uses_with(decl);
+        unsafe {
+            func.delete();
+        }
+        decl.as_global_value().set_name(&name);
+    }
+
+    let mut global = module.get_first_global();
+    while let Some(gv) = global {
+        if !gv.is_declaration() && !is_local_linkage(gv.get_linkage()) {
+            gv.set_linkage(Linkage::AvailableExternally);
+        }
+        global = gv.get_next_global();
+    }
+}
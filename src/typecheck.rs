@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use crate::error::Errors;
+use crate::error::{ErrorCode, Errors};
 use serde::{Deserialize, Serialize};
 
 use self::ast::import;
@@ -80,7 +80,7 @@ where
     }
 
     // Get candidates list for overload resolution.
-    fn overloaded_candidates(
+    pub fn overloaded_candidates(
         &self,
         name: &FullName,
         import_stmts: &[ImportStatement],
@@ -569,7 +569,10 @@ impl TypeCheckContext {
                     return Err(Errors::from_msg_srcs(
                         format!("No value `{}` is found.", var.name.to_string()),
                         &[&ei.source],
-                    ));
+                    )
+                    .set_code(ErrorCode::UnknownValue {
+                        name: var.name.to_string(),
+                    }));
                 }
                 let mut overload_res: Vec<Result<_, _>> = vec![];
                 for (ns, scm) in &candidates {
@@ -789,7 +792,8 @@ impl TypeCheckContext {
                         return Err(Errors::from_msg_srcs(
                             format!("Missing field `{}` of struct `{}`.", f, tc.to_string()),
                             &[&ei.source],
-                        ));
+                        )
+                        .set_code(ErrorCode::MissingField { field: f.to_string() }));
                     }
                 }
                 for f in &field_names_in_expression {
@@ -849,7 +853,11 @@ impl TypeCheckContext {
                 }
                 Ok(ei)
             }
-            Expr::FFICall(_, ret_ty, param_tys, args) => {
+            Expr::FFICall(fun_name, ret_ty, param_tys, args) => {
+                check_ffi_type(ret_ty, true, &self.type_env, &ei.source)?;
+                for param_ty in param_tys {
+                    check_ffi_type(param_ty, false, &self.type_env, &ei.source)?;
+                }
                 let ret_ty = type_tycon(ret_ty);
                 if let Err(_) = UnifOrOtherErr::extract_others(self.unify(&ty, &ret_ty))? {
                     return Err(Errors::from_msg_srcs(
@@ -861,10 +869,14 @@ impl TypeCheckContext {
                         &[&ei.source],
                     ));
                 }
-                let param_tys = param_tys
+                let mut param_tys = param_tys
                     .iter()
                     .map(|tc| type_tycon(tc))
                     .collect::<Vec<_>>();
+                if fun_name.is_none() {
+                    // The first argument of `FFI_CALL_PTR` is the function pointer.
+                    param_tys.insert(0, make_ptr_ty());
+                }
                 let mut ei = ei.clone();
                 for (i, e) in args.iter().enumerate() {
                     assert!(i < param_tys.len());
@@ -926,6 +938,38 @@ impl TypeCheckContext {
         Ok(expr)
     }
 
+    // Infer the most general scheme of an expression.
+    // Predicates and equalities which remain after reduction are put into the scheme as its constraints.
+    pub fn infer_scheme(&mut self, expr: Arc<ExprNode>) -> Result<Arc<Scheme>, Errors> {
+        // This function should be called when TypeCheckContext is "fresh".
+        assert!(self.substitution.is_empty());
+        assert!(self.predicates.is_empty());
+        assert!(self.equalities.is_empty());
+
+        let ty = type_tyvar_star(&self.new_tyvar());
+        let expr = self.unify_type_of_expr(&expr, ty.clone())?;
+        let reduction_res = UnifOrOtherErr::extract_others(self.reduce_predicates())?;
+        if let Err(e) = reduction_res {
+            return Err(Errors::from_msg_srcs(
+                format!(
+                    "`{}` is required in the type inference of this expression but cannot be deduced from assumptions.",
+                    e.to_constraint_string()
+                ), &[&expr.source]
+            ));
+        }
+        let ty = self.substitute_type(&ty);
+        let ty = self.reduce_type_by_equality(ty)?;
+        let mut preds = std::mem::replace(&mut self.predicates, vec![]);
+        for pred in &mut preds {
+            self.substitute_predicate(pred);
+        }
+        let mut eqs = std::mem::replace(&mut self.equalities, vec![]);
+        for eq in &mut eqs {
+            self.substitute_equality(eq);
+        }
+        Ok(Scheme::generalize(&[], preds, eqs, ty))
+    }
+
     fn add_substitution(&mut self, subst: &Substitution) -> Result<(), UnifOrOtherErr> {
         self.substitution.add_substitution(subst);
         let eqs = std::mem::replace(&mut self.equalities, vec![]);
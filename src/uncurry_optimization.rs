@@ -107,7 +107,7 @@ fn funptr_lambda(
 }
 
 // Decompose expression |x, y| z to ([x, y], z).
-fn collect_abs(expr: &Arc<ExprNode>, vars_limit: usize) -> (Vec<Arc<Var>>, Arc<ExprNode>) {
+pub fn collect_abs(expr: &Arc<ExprNode>, vars_limit: usize) -> (Vec<Arc<Var>>, Arc<ExprNode>) {
     fn collect_abs_inner(
         expr: &Arc<ExprNode>,
         vars: &mut Vec<Arc<Var>>,